@@ -1,16 +1,20 @@
+pub mod animation;
 pub mod camera;
 pub mod egui_renderer;
 pub mod gui_state;
 pub mod input;
+mod profiler;
 mod renderer;
-mod scene;
+pub mod scene;
 
 use crate::app::gui_state::GuiState;
 use crate::app::input::PressRecord;
 use crate::app::renderer::{Renderer, RendererParameters};
 use crate::app::scene::Scene;
 use crate::rendering::mesh::Mesh;
-use crate::rendering::wgpu::{Wgpu, WgpuTexture, WgpuTextureBindingInstruction, WgpuTextureBindingType};
+use crate::rendering::wgpu::Wgpu;
+#[cfg(not(target_arch = "wasm32"))]
+use crate::rendering::SamplerType;
 use crate::time;
 use camera::{Camera, CameraUpdateParameters};
 use cfg_if::cfg_if;
@@ -19,8 +23,11 @@ use log::info;
 use nalgebra::{Point4, Vector2, Vector3};
 use std::cell::{Ref, RefCell, RefMut};
 use std::collections::HashMap;
+#[cfg(not(target_arch = "wasm32"))]
+use std::ops::Range;
+#[cfg(not(target_arch = "wasm32"))]
+use std::path::Path;
 use std::sync::Arc;
-use wgpu::{ShaderStages, TextureSampleType};
 use winit::dpi::PhysicalSize;
 use winit::event::{DeviceEvent, DeviceId, ElementState, MouseButton, StartCause, WindowEvent};
 use winit::event_loop::ActiveEventLoop;
@@ -32,6 +39,112 @@ cfg_if! {
     }
 }
 
+#[cfg(not(target_arch = "wasm32"))]
+struct CpuRenderArguments {
+    output: String,
+    width: u32,
+    height: u32,
+    samples_per_pixel: u32,
+    max_ray_bounces: u32,
+    seed: u32,
+    sampler_type: SamplerType,
+    frames: Option<Range<u32>>, // 按场景动画渲染的帧，None 时只渲染静态场景
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl CpuRenderArguments {
+    // 没有 --cpu 参数时返回 None
+    fn parse(mut args: impl Iterator<Item = String>) -> Option<Self> {
+        let mut arguments = Self {
+            output: String::new(),
+            width: 400,
+            height: 400,
+            samples_per_pixel: 64,
+            max_ray_bounces: 32,
+            seed: 0,
+            sampler_type: SamplerType::Independent,
+            frames: None,
+        };
+        let mut cpu = false;
+
+        while let Some(arg) = args.next() {
+            let mut value = || args.next().unwrap_or_default();
+            match arg.as_str() {
+                "--cpu" => {
+                    cpu = true;
+                    arguments.output = value();
+                }
+                "--width" => arguments.width = value().parse().unwrap_or(arguments.width),
+                "--height" => arguments.height = value().parse().unwrap_or(arguments.height),
+                "--spp" => arguments.samples_per_pixel = value().parse().unwrap_or(arguments.samples_per_pixel),
+                "--bounces" => arguments.max_ray_bounces = value().parse().unwrap_or(arguments.max_ray_bounces),
+                "--seed" => arguments.seed = value().parse().unwrap_or(arguments.seed),
+                "--sampler" => {
+                    let name = value().to_lowercase();
+                    match SamplerType::ALL
+                        .into_iter()
+                        .find(|sampler_type| format!("{:?}", sampler_type).to_lowercase() == name)
+                    {
+                        Some(sampler_type) => arguments.sampler_type = sampler_type,
+                        None => log::warn!("Unknown sampler: {name}"),
+                    }
+                }
+                "--frames" => {
+                    let frames = value();
+                    match Self::parse_frames(&frames) {
+                        Some(frames) => arguments.frames = Some(frames),
+                        None => log::warn!("Invalid frame range: {frames}"),
+                    }
+                }
+                _ => log::warn!("Unknown argument: {arg}"),
+            }
+        }
+
+        if arguments.output.is_empty() {
+            arguments.output = match arguments.frames {
+                Some(_) => "frame_####.png".into(),
+                None => "cpu.png".into(),
+            };
+        }
+        cpu.then_some(arguments)
+    }
+
+    // "N..M" 不包含 M，"N..=M" 包含 M，单独的 "N" 只渲染一帧
+    fn parse_frames(frames: &str) -> Option<Range<u32>> {
+        if let Some((start, end)) = frames.split_once("..=") {
+            return Some(start.parse().ok()?..end.parse::<u32>().ok()?.checked_add(1)?);
+        }
+        if let Some((start, end)) = frames.split_once("..") {
+            return Some(start.parse().ok()?..end.parse().ok()?);
+        }
+        let frame: u32 = frames.parse().ok()?;
+        Some(frame..frame.checked_add(1)?)
+    }
+
+    // 输出路径中连续的 # 替换为补零的帧号，没有 # 时在扩展名之前加上 _0001 形式的帧号
+    fn frame_output(&self, frame: u32) -> String {
+        if let Some(start) = self.output.find('#') {
+            let width = self.output[start..].chars().take_while(|&c| c == '#').count();
+            return format!(
+                "{}{:0width$}{}",
+                &self.output[..start],
+                frame,
+                &self.output[start + width..]
+            );
+        }
+        let path = Path::new(&self.output);
+        match path.extension() {
+            Some(extension) => format!(
+                "{}_{:04}.{}",
+                path.with_extension("").display(),
+                frame,
+                extension.to_string_lossy()
+            ),
+            None => format!("{}_{:04}", self.output, frame),
+        }
+    }
+}
+
 #[derive(Default, Getters)]
 pub struct App {
     last_frame_time: Option<time::Instant>,
@@ -59,6 +172,13 @@ impl App {
                 console_log::init_with_level(log::Level::Info).expect("Couldn't initialize logger");
             } else {
                 log4rs::init_file("log4rs.yml", Default::default()).unwrap();
+
+                // renderer --cpu out.png [--width 400] [--height 400] [--spp 64] [--bounces 32] [--seed 0] [--sampler sobol]
+                // 加上 --frames 0..96 时按场景动画渲染序列帧，输出 .exr 时保存线性空间的浮点颜色
+                if let Some(arguments) = CpuRenderArguments::parse(std::env::args().skip(1)) {
+                    Self::render_cpu(&arguments);
+                    return;
+                }
             }
         }
 
@@ -114,6 +234,46 @@ impl App {
         event_loop.run_app(&mut app).expect("panic");
     }
 
+    // 不创建窗口和 GPU 设备，用 CPU 路径追踪器渲染一张图片，或者按场景动画渲染编号的序列帧
+    #[cfg(not(target_arch = "wasm32"))]
+    fn render_cpu(arguments: &CpuRenderArguments) {
+        let mut scene = Scene::scene_cornell_box();
+        let render = |scene: &mut Scene, output: &str| {
+            let start = time::Instant::now();
+            let image = scene.render_cpu(
+                arguments.width,
+                arguments.height,
+                arguments.samples_per_pixel,
+                arguments.max_ray_bounces,
+                arguments.sampler_type,
+                arguments.seed,
+            );
+            info!("cpu render {output}: {:?}", start.elapsed());
+
+            let result = if Path::new(output)
+                .extension()
+                .is_some_and(|extension| extension.eq_ignore_ascii_case("exr"))
+            {
+                image.to_rgb32f().save(output).map_err(|e| e.to_string())
+            } else {
+                image.to_rgba8().save(output).map_err(|e| e.to_string())
+            };
+            if let Err(e) = result {
+                log::error!("Failed to save {output}: {e}");
+            }
+        };
+
+        match &arguments.frames {
+            Some(frames) => {
+                for frame in frames.clone() {
+                    scene.animate(scene.animation.frame_time(frame));
+                    render(&mut scene, &arguments.frame_output(frame));
+                }
+            }
+            None => render(&mut scene, &arguments.output),
+        }
+    }
+
     fn resize(&mut self, size: &PhysicalSize<u32>) {
         if self.size == *size {
             return;
@@ -147,14 +307,47 @@ impl App {
 
         self.camera_mut().translate(translation);
 
+        let duration = self.scene().animation.duration();
+        self.gui_state_mut().advance_animation(delta_time, duration);
+        let animation_time = self.gui_state_mut().take_animation_time();
+        if let Some(time) = animation_time {
+            self.animate(time);
+        }
+
         self.camera_mut().on_update(self.gui_state().camera_update_parameters());
-        self.renderer_mut()
-            .on_update(window, self.wgpu(), delta_time, self.camera_mut(), self.gui_state_mut());
+        self.renderer_mut().on_update(
+            window,
+            self.wgpu(),
+            delta_time,
+            self.camera_mut(),
+            self.gui_state_mut(),
+            self.scene_mut(),
+        );
 
         // info!("camera position: {:?}", self.camera.position());
         // info!("camera rotation: {:?}", self.camera.rotation());
     }
 
+    // 把动画在 time 秒的状态应用到场景。有关键帧的相机属性跟随动画，视角和对焦距离经过界面的参数传给相机
+    fn animate(&self, time: f32) {
+        let mut scene = self.scene_mut();
+        scene.animate(time);
+
+        let animation = &scene.animation;
+        let parameters = &scene.camera_parameters;
+        if !animation.camera_position.is_empty() || !animation.camera_look_at.is_empty() {
+            self.camera_mut()
+                .look_at(&parameters.initial_position, &parameters.initial_look_at);
+        }
+        let mut gui_state = self.gui_state_mut();
+        if !animation.vfov.is_empty() {
+            gui_state.camera_update_parameters.vfov = parameters.vfov;
+        }
+        if !animation.focus_distance.is_empty() {
+            gui_state.camera_update_parameters.focus_distance = parameters.focus_distance;
+        }
+    }
+
     fn render(&mut self) {
         let (width, height): (u32, u32) = self.window().inner_size().into();
 
@@ -170,18 +363,7 @@ impl App {
             }
         };
 
-        let wgpu_surface_storage = WgpuTexture::new_from_texture(
-            "surface",
-            &surface_texture.texture,
-            WgpuTextureBindingInstruction {
-                visibility: ShaderStages::COMPUTE,
-                binding_type: WgpuTextureBindingType::StorageTexture,
-                storage_access: None,
-                sample_type: Some(TextureSampleType::Float { filterable: false }),
-            },
-        );
-
-        let status = self.renderer_mut().render(self.wgpu(), wgpu_surface_storage);
+        let status = self.renderer_mut().render(self.wgpu(), &surface_texture.texture);
         self.gui_state_mut().update(status);
 
         surface_texture.present();
@@ -420,6 +602,12 @@ impl App {
         drop(render_parameter);
         drop(scene);
 
+        // 渲染器已经上传了当前的场景，之后只有运行时的修改才需要重新上传
+        let mut scene = self.scene_mut();
+        scene.objects.take_changed();
+        scene.materials.take_changed();
+        drop(scene);
+
         self.renderer = Some(renderer);
     }
 
@@ -0,0 +1,332 @@
+use crate::app::camera::CameraParameters;
+use crate::rendering::material::{MaterialHandle, MaterialParameter, MaterialRegistry};
+use crate::rendering::mesh::scene_graph::{SceneGraph, Transform};
+use nalgebra::{Point3, Scale3, Translation3};
+
+// 从一个关键帧到下一个关键帧之间的插值方式，由前一个关键帧决定
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Interpolation {
+    Step, // 保持前一个关键帧的值，到下一个关键帧时跳变
+    Linear,
+    // 与 CSS 的 cubic-bezier 相同的缓动曲线，从 (0, 0) 到 (1, 1)，两个控制点的 x 在 [0, 1] 内
+    Bezier { x1: f32, y1: f32, x2: f32, y2: f32 },
+}
+
+impl Interpolation {
+    pub const EASE_IN_OUT: Interpolation = Interpolation::Bezier {
+        x1: 0.42,
+        y1: 0.0,
+        x2: 0.58,
+        y2: 1.0,
+    };
+
+    // 两个关键帧之间经过的时间比例 t 映射到值的插值比例
+    pub fn ease(&self, t: f32) -> f32 {
+        let t = t.clamp(0.0, 1.0);
+        match *self {
+            Interpolation::Step => 0.0,
+            Interpolation::Linear => t,
+            Interpolation::Bezier { x1, y1, x2, y2 } => {
+                // 控制点的 x 在 [0, 1] 内时 x(s) 单调，二分求出 x(s) = t 的参数 s
+                let (x1, x2) = (x1.clamp(0.0, 1.0), x2.clamp(0.0, 1.0));
+                let (mut low, mut high) = (0.0, 1.0);
+                for _ in 0..32 {
+                    let middle = 0.5 * (low + high);
+                    if cubic_bezier(x1, x2, middle) < t {
+                        low = middle;
+                    } else {
+                        high = middle;
+                    }
+                }
+                cubic_bezier(y1, y2, 0.5 * (low + high))
+            }
+        }
+    }
+}
+
+// 端点为 0 和 1 的一维三次贝塞尔曲线
+fn cubic_bezier(p1: f32, p2: f32, s: f32) -> f32 {
+    let r = 1.0 - s;
+    3.0 * r * r * s * p1 + 3.0 * r * s * s * p2 + s * s * s
+}
+
+// 可以在两个关键帧之间插值的值
+pub trait Animatable: Clone {
+    fn interpolate(&self, other: &Self, t: f32) -> Self;
+}
+
+impl Animatable for f32 {
+    fn interpolate(&self, other: &Self, t: f32) -> Self {
+        self + (other - self) * t
+    }
+}
+
+impl Animatable for Point3<f32> {
+    fn interpolate(&self, other: &Self, t: f32) -> Self {
+        self.lerp(other, t)
+    }
+}
+
+// 平移和缩放线性插值，旋转球面插值
+impl Animatable for Transform {
+    fn interpolate(&self, other: &Self, t: f32) -> Self {
+        Transform {
+            translation: Translation3::from(self.translation.vector.lerp(&other.translation.vector, t)),
+            rotation: self.rotation.slerp(&other.rotation, t),
+            scale: Scale3::from(self.scale.vector.lerp(&other.scale.vector, t)),
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct Keyframe<T> {
+    pub time: f32, // 秒
+    pub value: T,
+    pub interpolation: Interpolation, // 到下一个关键帧的插值方式
+}
+
+// 一个属性的关键帧，按时间排序，同一时间只有一个关键帧
+#[derive(Clone, Debug)]
+pub struct Track<T> {
+    keyframes: Vec<Keyframe<T>>,
+}
+
+impl<T> Default for Track<T> {
+    fn default() -> Self {
+        Self { keyframes: Vec::new() }
+    }
+}
+
+impl<T: Animatable> Track<T> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // 已经有同一时间的关键帧时替换它
+    pub fn insert(&mut self, time: f32, value: T, interpolation: Interpolation) -> &mut Self {
+        let keyframe = Keyframe {
+            time,
+            value,
+            interpolation,
+        };
+        let index = self.keyframes.partition_point(|keyframe| keyframe.time < time);
+        match self.keyframes.get_mut(index) {
+            Some(existing) if existing.time == time => *existing = keyframe,
+            _ => self.keyframes.insert(index, keyframe),
+        }
+        self
+    }
+
+    pub fn keyframes(&self) -> &[Keyframe<T>] {
+        &self.keyframes
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.keyframes.is_empty()
+    }
+
+    pub fn end_time(&self) -> f32 {
+        self.keyframes.last().map_or(0.0, |keyframe| keyframe.time)
+    }
+
+    // 第一个关键帧之前和最后一个关键帧之后保持端点的值，没有关键帧时返回 None
+    pub fn sample(&self, time: f32) -> Option<T> {
+        let next = self.keyframes.partition_point(|keyframe| keyframe.time <= time);
+        if next == 0 {
+            return self.keyframes.first().map(|keyframe| keyframe.value.clone());
+        }
+        // 正好落在关键帧上时直接返回它的值，不受插值误差影响
+        let previous = &self.keyframes[next - 1];
+        let Some(next) = self.keyframes.get(next).filter(|_| time != previous.time) else {
+            return Some(previous.value.clone());
+        };
+        let t = (time - previous.time) / (next.time - previous.time);
+        Some(previous.value.interpolate(&next.value, previous.interpolation.ease(t)))
+    }
+}
+
+// 按名字对应 GpuMaterial::parameters 中的参数，类型也要一致
+enum MaterialTrack {
+    Scalar(Track<f32>),
+    Color(Track<Point3<f32>>),
+}
+
+// 场景的关键帧动画：相机的位置、注视点、视角和对焦距离，按路径查找的节点变换，以及材质参数。
+// 没有关键帧的属性不受动画影响
+pub struct Animation {
+    pub frame_rate: f32, // 渲染序列帧时每秒的帧数
+    pub camera_position: Track<Point3<f32>>,
+    pub camera_look_at: Track<Point3<f32>>,
+    pub vfov: Track<f32>,
+    pub focus_distance: Track<f32>,
+    nodes: Vec<(String, Track<Transform>)>,
+    materials: Vec<(MaterialHandle, &'static str, MaterialTrack)>,
+}
+
+impl Default for Animation {
+    fn default() -> Self {
+        Self {
+            frame_rate: 24.0,
+            camera_position: Track::new(),
+            camera_look_at: Track::new(),
+            vfov: Track::new(),
+            focus_distance: Track::new(),
+            nodes: Vec::new(),
+            materials: Vec::new(),
+        }
+    }
+}
+
+impl Animation {
+    pub fn new(frame_rate: f32) -> Self {
+        Self {
+            frame_rate,
+            ..Default::default()
+        }
+    }
+
+    // 路径与 SceneGraph::find 相同，没有这条轨道时新建
+    pub fn node(&mut self, path: &str) -> &mut Track<Transform> {
+        let index = match self.nodes.iter().position(|(other, _)| other == path) {
+            Some(index) => index,
+            None => {
+                self.nodes.push((path.to_string(), Track::new()));
+                self.nodes.len() - 1
+            }
+        };
+        &mut self.nodes[index].1
+    }
+
+    // 这个参数已经有颜色轨道时替换为新的标量轨道，原来的关键帧被丢弃
+    pub fn material_scalar(&mut self, handle: MaterialHandle, parameter: &'static str) -> &mut Track<f32> {
+        let track = self.material(handle, parameter);
+        if !matches!(track, MaterialTrack::Scalar(_)) {
+            *track = MaterialTrack::Scalar(Track::new());
+        }
+        match track {
+            MaterialTrack::Scalar(track) => track,
+            MaterialTrack::Color(_) => unreachable!(),
+        }
+    }
+
+    // 这个参数已经有标量轨道时替换为新的颜色轨道，原来的关键帧被丢弃
+    pub fn material_color(&mut self, handle: MaterialHandle, parameter: &'static str) -> &mut Track<Point3<f32>> {
+        let track = self.material(handle, parameter);
+        if !matches!(track, MaterialTrack::Color(_)) {
+            *track = MaterialTrack::Color(Track::new());
+        }
+        match track {
+            MaterialTrack::Color(track) => track,
+            MaterialTrack::Scalar(_) => unreachable!(),
+        }
+    }
+
+    // 没有这条轨道时新建一条空的标量轨道，由调用者换成需要的类型
+    fn material(&mut self, handle: MaterialHandle, parameter: &'static str) -> &mut MaterialTrack {
+        let index = match self
+            .materials
+            .iter()
+            .position(|(other, name, _)| other.material_id == handle.material_id && *name == parameter)
+        {
+            Some(index) => index,
+            None => {
+                self.materials
+                    .push((handle, parameter, MaterialTrack::Scalar(Track::new())));
+                self.materials.len() - 1
+            }
+        };
+        &mut self.materials[index].2
+    }
+
+    // 最后一个关键帧的时间，没有关键帧时为 0
+    pub fn duration(&self) -> f32 {
+        let camera = [&self.camera_position, &self.camera_look_at]
+            .map(Track::end_time)
+            .into_iter()
+            .chain([&self.vfov, &self.focus_distance].map(Track::end_time));
+        let nodes = self.nodes.iter().map(|(_, track)| track.end_time());
+        let materials = self.materials.iter().map(|(_, _, track)| match track {
+            MaterialTrack::Scalar(track) => track.end_time(),
+            MaterialTrack::Color(track) => track.end_time(),
+        });
+        camera.chain(nodes).chain(materials).fold(0.0, f32::max)
+    }
+
+    pub fn frame_time(&self, frame: u32) -> f32 {
+        frame as f32 / self.frame_rate
+    }
+
+    // 离 time 最近的帧
+    pub fn frame_at(&self, time: f32) -> u32 {
+        (time * self.frame_rate).round() as u32
+    }
+
+    // 把时间 time 的值写入相机参数、场景图和材质。值没有变化的节点和材质不会被标记为改变，
+    // 不存在的节点（例如已经在界面上删除）和材质参数被忽略
+    pub fn apply(
+        &self,
+        time: f32,
+        camera: &mut CameraParameters,
+        objects: &mut SceneGraph,
+        materials: &mut MaterialRegistry,
+    ) {
+        if let Some(position) = self.camera_position.sample(time) {
+            camera.initial_position = position;
+        }
+        if let Some(look_at) = self.camera_look_at.sample(time) {
+            camera.initial_look_at = look_at;
+        }
+        if let Some(vfov) = self.vfov.sample(time) {
+            camera.vfov = vfov;
+        }
+        if let Some(focus_distance) = self.focus_distance.sample(time) {
+            camera.focus_distance = focus_distance;
+        }
+
+        for (path, track) in &self.nodes {
+            let (Some(id), Some(transform)) = (objects.find(path), track.sample(time)) else {
+                continue;
+            };
+            if objects.node(id).transform() != transform {
+                objects.set_transform(id, transform);
+            }
+        }
+
+        let mut materials_changed = false;
+        for (handle, material) in materials.iter_mut() {
+            let tracks = self
+                .materials
+                .iter()
+                .filter(|(other, _, _)| other.material_id == handle.material_id);
+            for (_, name, track) in tracks {
+                let parameter = material.parameters().into_iter().find(|parameter| match parameter {
+                    MaterialParameter::Scalar { name: other, .. }
+                    | MaterialParameter::Color { name: other, .. }
+                    | MaterialParameter::Toggle { name: other, .. } => other == name,
+                });
+                match (parameter, track) {
+                    (Some(MaterialParameter::Scalar { value, .. }), MaterialTrack::Scalar(track)) => {
+                        materials_changed |= assign(value, track.sample(time));
+                    }
+                    (Some(MaterialParameter::Color { value, .. }), MaterialTrack::Color(track)) => {
+                        materials_changed |= assign(value, track.sample(time));
+                    }
+                    _ => {}
+                }
+            }
+        }
+        if materials_changed {
+            materials.mark_changed();
+        }
+    }
+}
+
+fn assign<T: PartialEq>(value: &mut T, sampled: Option<T>) -> bool {
+    match sampled {
+        Some(sampled) if *value != sampled => {
+            *value = sampled;
+            true
+        }
+        _ => false,
+    }
+}
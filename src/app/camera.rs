@@ -22,6 +22,13 @@ pub struct Camera {
     movement_speed: f32,
     rotation_scale: f32,
 
+    #[getset(get_copy = "pub")]
+    shutter_open: f32,
+    #[getset(get_copy = "pub")]
+    shutter_close: f32,
+    #[getset(get = "pub")]
+    motion: Vector3<f32>, // 时间 0 到 1 之间相机的位移
+
     // Camera frame basis vectors
     #[getset(get = "pub")]
     u: UnitVector3<f32>, // 相机朝向的右侧
@@ -44,6 +51,9 @@ impl Default for Camera {
             defocus_angle: 0.0,
             movement_speed: 0.0,
             rotation_scale: Default::default(),
+            shutter_open: 0.0,
+            shutter_close: 0.0,
+            motion: Vector3::zeros(),
             u: Vector3::x_axis(),
             v: Vector3::y_axis(),
             w: Vector3::z_axis(),
@@ -61,6 +71,10 @@ pub struct CameraParameters {
     pub defocus_angle: f32,
     pub movement_speed: f32,
     pub rotation_scale: f32,
+    // 快门在 [0, 1] 中打开和关闭的时间，相等时不产生运动模糊
+    pub shutter_open: f32,
+    pub shutter_close: f32,
+    pub motion: Vector3<f32>,
 }
 
 #[derive(Default)]
@@ -83,18 +97,18 @@ impl Default for CameraParameters {
             defocus_angle: 0.0,
             movement_speed: 0.0,
             rotation_scale: Default::default(),
+            shutter_open: 0.0,
+            shutter_close: 0.0,
+            motion: Vector3::zeros(),
         }
     }
 }
 
 impl Camera {
     pub fn new(parameters: &CameraParameters) -> Self {
-        let rotation = UnitQuaternion::rotation_between(
-            &Vector3::z_axis(),
-            &(parameters.initial_position - parameters.initial_look_at),
-        )
-        // rotation_between 在两个方向共线且方向相反时会返回 None ，因为此时的旋转不唯一
-        .unwrap_or(UnitQuaternion::from_axis_angle(&Vector3::y_axis(), PI));
+        let rotation = Self::rotation_towards(&parameters.initial_position, &parameters.initial_look_at);
+        // 运动的包围盒只覆盖 [0, 1]
+        let shutter_open = parameters.shutter_open.clamp(0.0, 1.0);
         let mut camera = Camera {
             position: parameters.initial_position,
             rotation,
@@ -104,6 +118,9 @@ impl Camera {
             defocus_angle: parameters.defocus_angle,
             movement_speed: parameters.movement_speed,
             rotation_scale: parameters.rotation_scale,
+            shutter_open,
+            shutter_close: parameters.shutter_close.clamp(shutter_open, 1.0),
+            motion: parameters.motion,
             u: Vector3::x_axis(),
             v: Vector3::y_axis(),
             w: Vector3::z_axis(),
@@ -147,6 +164,19 @@ impl Camera {
         }
     }
 
+    // 动画驱动相机时直接设置位置和注视点
+    pub fn look_at(&mut self, position: &Point3<f32>, look_at: &Point3<f32>) {
+        let rotation = Self::rotation_towards(position, look_at);
+        if self.position == *position && self.rotation == rotation {
+            return;
+        }
+
+        self.position = *position;
+        self.rotation = rotation;
+        self.update_camera_frame();
+        self.should_rerender = true;
+    }
+
     pub fn take_rerender(&mut self) -> bool {
         if self.should_rerender {
             self.should_rerender = false;
@@ -155,6 +185,12 @@ impl Camera {
         false
     }
 
+    fn rotation_towards(position: &Point3<f32>, look_at: &Point3<f32>) -> UnitQuaternion<f32> {
+        UnitQuaternion::rotation_between(&Vector3::z_axis(), &(position - look_at))
+            // rotation_between 在两个方向共线且方向相反时会返回 None ，因为此时的旋转不唯一
+            .unwrap_or(UnitQuaternion::from_axis_angle(&Vector3::y_axis(), PI))
+    }
+
     fn try_rotate(&mut self, rotation: &UnitQuaternion<f32>) -> bool {
         let new_rotation = rotation * self.rotation;
         if !self.nearly_up(&UnitVector3::new_unchecked(
@@ -1,4 +1,5 @@
 use crate::app::gui_state::GuiState;
+use crate::app::scene::Scene;
 use crate::rendering::wgpu::Wgpu;
 use crate::{time, FONT_SOURCE_HANS_SANS_CN_MEDIUM, FONT_SOURCE_HANS_SANS_CN_MEDIUM_NAME};
 use egui::{ClippedPrimitive, FontData, FontDefinitions, FontFamily, TexturesDelta};
@@ -70,7 +71,13 @@ impl EguiRenderer {
         self.egui_state.on_window_event(window, event)
     }
 
-    pub fn update(&mut self, window: &Window, _delta_time: time::Duration, gui_state: &mut GuiState) {
+    pub fn update(
+        &mut self,
+        window: &Window,
+        _delta_time: time::Duration,
+        gui_state: &mut GuiState,
+        scene: &mut Scene,
+    ) {
         let gui_input = self.egui_state.take_egui_input(window);
         self.egui_state.egui_ctx().begin_pass(gui_input);
 
@@ -84,6 +91,27 @@ impl EguiRenderer {
         let gui_window = egui::Window::new("Ray Tracer").default_width(288.0);
         gui_window.show(self.egui_state.egui_ctx(), |ui| gui_state.create_ui(ui));
 
+        let material_window = egui::Window::new("Materials")
+            .default_width(288.0)
+            .default_open(false)
+            .vscroll(true);
+        material_window.show(self.egui_state.egui_ctx(), |ui| {
+            gui_state.create_material_ui(ui, &mut scene.materials)
+        });
+
+        let scene_window = egui::Window::new("Scene")
+            .default_width(288.0)
+            .default_open(false)
+            .vscroll(true);
+        scene_window.show(self.egui_state.egui_ctx(), |ui| {
+            gui_state.create_scene_ui(ui, &mut scene.objects)
+        });
+
+        let timeline_window = egui::Window::new("Timeline").default_width(288.0).default_open(false);
+        timeline_window.show(self.egui_state.egui_ctx(), |ui| {
+            gui_state.create_timeline_ui(ui, &scene.animation)
+        });
+
         let egui::FullOutput {
             textures_delta,
             shapes,
@@ -1,8 +1,17 @@
+use crate::app::animation::Animation;
 use crate::app::camera::CameraUpdateParameters;
-use egui::{Color32, RichText, Ui};
+use crate::math::{degree_to_radian, radian_to_degree};
+use crate::rendering::bvh::BvhRefitMode;
+use crate::rendering::material::{MaterialParameter, MaterialRegistry};
+use crate::rendering::mesh::scene_graph::{NodeId, SceneGraph};
+use crate::rendering::{clamp_adaptive_min_samples, AdaptiveSamplingMode, SampleView, SamplerType};
+use egui::{Color32, DragValue, RichText, Ui};
 use getset::{CopyGetters, Getters};
+use nalgebra::UnitQuaternion;
+use std::time::Duration;
 
-use super::renderer::RenderStatue;
+use super::profiler::{milliseconds, ProfilerHistory, ProfilerSample};
+use super::renderer::{RenderStatue, SampleBatch};
 use egui::special_emojis::GITHUB;
 
 #[derive(Default, Getters, CopyGetters)]
@@ -11,10 +20,34 @@ pub struct GuiState {
     pub samples_per_pixel: u32,
     #[getset(get_copy = "pub")]
     pub max_ray_bounces: u32,
+    #[getset(get_copy = "pub")]
+    pub samples_per_dispatch: u32,
+    pub frame_time_budget: bool,
+    pub target_frame_time_ms: f32,
+    #[getset(get_copy = "pub")]
+    pub adaptive_sampling_mode: AdaptiveSamplingMode,
+    #[getset(get_copy = "pub")]
+    pub convergence_threshold: f32,
+    pub adaptive_min_samples: u32,
+    #[getset(get_copy = "pub")]
+    pub sample_view: SampleView,
+    #[getset(get_copy = "pub")]
+    pub sampler_type: SamplerType,
+    #[getset(get_copy = "pub")]
+    pub seed: u32,
+    #[getset(get_copy = "pub")]
+    pub bvh_refit_mode: BvhRefitMode,
     #[getset(get = "pub")]
     pub camera_update_parameters: CameraUpdateParameters,
     pub render_status: RenderStatue,
     pub progress: f32,
+    pub profiler_history: ProfilerHistory,
+    #[getset(get_copy = "pub")]
+    pub animation_time: f32,
+    pub animation_playing: bool,
+    reload_shader: bool,
+    materials_changed: bool,
+    animation_time_changed: bool,
 }
 
 impl GuiState {
@@ -22,9 +55,25 @@ impl GuiState {
         Self {
             samples_per_pixel,
             max_ray_bounces,
+            samples_per_dispatch: 1,
+            frame_time_budget: false,
+            target_frame_time_ms: 33.0,
+            adaptive_sampling_mode: AdaptiveSamplingMode::Off,
+            convergence_threshold: 0.01,
+            adaptive_min_samples: 16,
+            sample_view: SampleView::Color,
+            sampler_type: SamplerType::Independent,
+            seed: 0,
+            bvh_refit_mode: BvhRefitMode::Cpu,
             camera_update_parameters,
             render_status: Default::default(),
             progress: 0.0,
+            profiler_history: ProfilerHistory::default(),
+            animation_time: 0.0,
+            animation_playing: false,
+            reload_shader: false,
+            materials_changed: false,
+            animation_time_changed: false,
         }
     }
 
@@ -65,8 +114,43 @@ impl GuiState {
                 .small(),
             );
             ui.end_row();
+
+            let timings = &self.render_status.frame_timings;
+            ui.label("Frame Time");
+            ui.label(format!("{:.2} ms", timings.frame.as_secs_f64() * 1000.0));
+            ui.end_row();
+
+            for (label, duration) in [
+                ("    Prepare", timings.prepare),
+                ("    Encode", timings.encode),
+                ("    Submit", timings.submit),
+            ] {
+                ui.label(label);
+                ui.label(RichText::new(format!("{:.3} ms", duration.as_secs_f64() * 1000.0)).small());
+                ui.end_row();
+            }
+
+            ui.label("    Pipeline");
+            ui.label(RichText::new(if timings.pipeline_rebuilt { "rebuilt" } else { "cached" }).small());
+            ui.end_row();
+
+            if self.render_status.adaptive_sampling {
+                ui.label("Converged Pixels");
+                ui.label(
+                    RichText::new(format!(
+                        "{} / {}",
+                        self.render_status.converged_pixels, self.render_status.total_pixels
+                    ))
+                    .small(),
+                );
+                ui.end_row();
+            }
         });
 
+        ui.label(RichText::new("Profiler").strong());
+        ui.separator();
+        self.create_profiler_ui(ui);
+
         ui.label(RichText::new("Camera").strong());
         ui.separator();
 
@@ -112,8 +196,94 @@ impl GuiState {
             ui.label("Max Ray Bounces");
             ui.add(egui::Slider::new(&mut self.max_ray_bounces, 0..=128));
             ui.end_row();
+
+            ui.label("Frame Time Budget");
+            ui.checkbox(&mut self.frame_time_budget, "");
+            ui.end_row();
+
+            if self.frame_time_budget {
+                ui.label("Target Frame Time (ms)");
+                ui.add(egui::Slider::new(&mut self.target_frame_time_ms, 8.0..=200.0));
+                ui.end_row();
+
+                ui.label("Samples Per Dispatch");
+                ui.label(format!("{}", self.render_status.samples_per_dispatch));
+                ui.end_row();
+            } else {
+                ui.label("Samples Per Dispatch");
+                ui.add(egui::Slider::new(
+                    &mut self.samples_per_dispatch,
+                    1..=SampleBatch::MAX_SAMPLES_PER_DISPATCH,
+                ));
+                ui.end_row();
+            }
+
+            ui.label("Sampler");
+            egui::ComboBox::from_id_salt("sampler")
+                .selected_text(format!("{:?}", self.sampler_type))
+                .show_ui(ui, |ui| {
+                    for sampler_type in SamplerType::ALL {
+                        ui.selectable_value(&mut self.sampler_type, sampler_type, format!("{:?}", sampler_type));
+                    }
+                });
+            ui.end_row();
+
+            ui.label("Seed");
+            ui.add(egui::DragValue::new(&mut self.seed));
+            ui.end_row();
+
+            ui.label("Adaptive Sampling");
+            egui::ComboBox::from_id_salt("adaptive sampling")
+                .selected_text(format!("{:?}", self.adaptive_sampling_mode))
+                .show_ui(ui, |ui| {
+                    for mode in [
+                        AdaptiveSamplingMode::Off,
+                        AdaptiveSamplingMode::StopConverged,
+                        AdaptiveSamplingMode::Redistribute,
+                    ] {
+                        ui.selectable_value(&mut self.adaptive_sampling_mode, mode, format!("{:?}", mode));
+                    }
+                });
+            ui.end_row();
+
+            if self.adaptive_sampling_mode != AdaptiveSamplingMode::Off {
+                ui.label("Convergence Threshold");
+                ui.add(egui::Slider::new(&mut self.convergence_threshold, 0.001..=0.1).logarithmic(true));
+                ui.end_row();
+
+                ui.label("Min Samples");
+                let max_min_samples = self.samples_per_pixel.clamp(2, 256);
+                ui.add(egui::Slider::new(&mut self.adaptive_min_samples, 2..=max_min_samples));
+                ui.end_row();
+            }
+
+            ui.label("Sample Count Heatmap");
+            let mut heatmap = self.sample_view == SampleView::SampleCountHeatmap;
+            if ui.checkbox(&mut heatmap, "").changed() {
+                self.sample_view = if heatmap {
+                    SampleView::SampleCountHeatmap
+                } else {
+                    SampleView::Color
+                };
+            }
+            ui.end_row();
+
+            ui.label("BVH Refit");
+            egui::ComboBox::from_id_salt("bvh refit")
+                .selected_text(format!("{:?}", self.bvh_refit_mode))
+                .show_ui(ui, |ui| {
+                    for mode in BvhRefitMode::ALL {
+                        ui.selectable_value(&mut self.bvh_refit_mode, mode, format!("{:?}", mode));
+                    }
+                });
+            ui.end_row();
         });
 
+        #[cfg(not(target_arch = "wasm32"))]
+        if ui.button("Reload Shader").clicked() {
+            self.reload_shader = true;
+        }
+
         ui.label(RichText::new("About").strong());
         ui.separator();
 
@@ -125,8 +295,295 @@ impl GuiState {
         ui.add_space(1.0);
     }
 
+    // 每个材质一个折叠栏，列出 GpuMaterial::parameters
+    pub fn create_material_ui(&mut self, ui: &mut Ui, materials: &mut MaterialRegistry) {
+        let kind_names = materials.kind_names();
+        for (i, (handle, material)) in materials.iter_mut().enumerate() {
+            let parameters = material.parameters();
+            let title = format!("#{} {}", i, kind_names[handle.material_type as usize]);
+            ui.add_enabled_ui(!parameters.is_empty(), |ui| {
+                egui::CollapsingHeader::new(title).id_salt(i).show(ui, |ui| {
+                    egui::Grid::new(("material", i)).min_col_width(120.0).show(ui, |ui| {
+                        for parameter in parameters {
+                            let changed = match parameter {
+                                MaterialParameter::Scalar { name, value, range } => {
+                                    ui.label(name);
+                                    ui.add(egui::Slider::new(value, range)).changed()
+                                }
+                                MaterialParameter::Color { name, value } => {
+                                    ui.label(name);
+                                    let mut rgb = [value.x, value.y, value.z];
+                                    let changed = ui.color_edit_button_rgb(&mut rgb).changed();
+                                    value.coords = rgb.into();
+                                    changed
+                                }
+                                MaterialParameter::Toggle { name, value } => {
+                                    ui.label(name);
+                                    ui.checkbox(value, "").changed()
+                                }
+                            };
+                            self.materials_changed |= changed;
+                            ui.end_row();
+                        }
+                    });
+                });
+            });
+        }
+    }
+
+    // 场景图的层级，每个节点一个折叠栏，可以编辑相对父节点的变换或删除节点
+    pub fn create_scene_ui(&self, ui: &mut Ui, graph: &mut SceneGraph) {
+        let mut removed = None;
+        for root in graph.roots().to_vec() {
+            Self::create_node_ui(ui, graph, root, &mut removed);
+        }
+        // 遍历结束后再删除，遍历时子节点列表不变
+        if let Some(id) = removed {
+            graph.remove(id);
+        }
+    }
+
+    fn create_node_ui(ui: &mut Ui, graph: &mut SceneGraph, id: NodeId, removed: &mut Option<NodeId>) {
+        let node = graph.node(id);
+        let path = graph.path(id);
+        let title = if node.has_mesh() {
+            format!("{} (mesh)", node.name())
+        } else {
+            node.name().clone()
+        };
+        let children = node.children().clone();
+        let mut transform = node.transform();
+
+        egui::CollapsingHeader::new(title).id_salt(&path).show(ui, |ui| {
+            let (roll, pitch, yaw) = transform.rotation.euler_angles();
+            let mut rotation = [roll, pitch, yaw].map(radian_to_degree);
+            let mut changed = false;
+            let mut rotation_changed = false;
+            egui::Grid::new(("node", &path)).min_col_width(120.0).show(ui, |ui| {
+                ui.label("Path");
+                ui.label(&path);
+                ui.end_row();
+
+                ui.label("Translation");
+                ui.horizontal(|ui| {
+                    for value in transform.translation.vector.iter_mut() {
+                        changed |= ui.add(DragValue::new(value).speed(0.01)).changed();
+                    }
+                });
+                ui.end_row();
+
+                ui.label("Rotation");
+                ui.horizontal(|ui| {
+                    for value in rotation.iter_mut() {
+                        rotation_changed |= ui.add(DragValue::new(value).speed(0.5).suffix("°")).changed();
+                    }
+                });
+                ui.end_row();
+
+                ui.label("Scale");
+                ui.horizontal(|ui| {
+                    for value in transform.scale.vector.iter_mut() {
+                        changed |= ui
+                            .add(DragValue::new(value).speed(0.01).range(0.001..=f32::MAX))
+                            .changed();
+                    }
+                });
+                ui.end_row();
+            });
+
+            // 只在旋转被编辑时才从欧拉角重建，避免来回转换累积误差
+            if rotation_changed {
+                let [roll, pitch, yaw] = rotation.map(degree_to_radian);
+                transform.rotation = UnitQuaternion::from_euler_angles(roll, pitch, yaw);
+            }
+            if changed || rotation_changed {
+                graph.set_transform(id, transform);
+            }
+            if ui.button("Remove").clicked() {
+                *removed = Some(id);
+            }
+
+            for child in children {
+                Self::create_node_ui(ui, graph, child, removed);
+            }
+        });
+    }
+
+    // 播放、暂停和拖动时间轴，拖动时暂停播放
+    pub fn create_timeline_ui(&mut self, ui: &mut Ui, animation: &Animation) {
+        let duration = animation.duration();
+        if duration <= 0.0 {
+            ui.label("The scene has no keyframes.");
+            return;
+        }
+
+        ui.horizontal(|ui| {
+            if ui
+                .button(if self.animation_playing { "Pause" } else { "Play" })
+                .clicked()
+            {
+                self.animation_playing = !self.animation_playing;
+            }
+            if ui.button("Rewind").clicked() {
+                self.animation_time = 0.0;
+                self.animation_time_changed = true;
+            }
+            ui.label(format!(
+                "Frame {} / {}",
+                animation.frame_at(self.animation_time),
+                animation.frame_at(duration)
+            ));
+        });
+
+        let slider = egui::Slider::new(&mut self.animation_time, 0.0..=duration).suffix(" s");
+        if ui.add(slider).changed() {
+            self.animation_playing = false;
+            self.animation_time_changed = true;
+        }
+    }
+
+    fn create_profiler_ui(&mut self, ui: &mut Ui) {
+        const FRAME_COLOR: Color32 = Color32::from_gray(200);
+        const CPU_COLOR: Color32 = Color32::from_rgb(230, 200, 60);
+        const RAY_TRACING_COLOR: Color32 = Color32::from_rgb(230, 110, 50);
+        const EGUI_COLOR: Color32 = Color32::from_rgb(80, 180, 230);
+
+        let format_gpu = |duration: Option<Duration>| match duration {
+            Some(duration) => format!("{:.3} ms", milliseconds(duration)),
+            None => "unsupported".to_owned(),
+        };
+
+        let startup = self.render_status.startup_timings;
+        let gpu = self.render_status.gpu_timings;
+        egui::Grid::new("profiler").min_col_width(160.0).show(ui, |ui| {
+            ui.label("BVH Build");
+            ui.label(RichText::new(format!("{:.3} ms", milliseconds(startup.bvh_build))).small());
+            ui.end_row();
+
+            ui.label("Buffer Upload");
+            ui.label(RichText::new(format!("{:.3} ms", milliseconds(startup.buffer_upload))).small());
+            ui.end_row();
+
+            ui.label(RichText::new("GPU Ray Tracing").color(RAY_TRACING_COLOR));
+            ui.label(RichText::new(format_gpu(gpu.ray_tracing)).small());
+            ui.end_row();
+
+            ui.label(RichText::new("GPU Egui").color(EGUI_COLOR));
+            ui.label(RichText::new(format_gpu(gpu.egui)).small());
+            ui.end_row();
+        });
+
+        // 每帧依次为：帧时间、CPU 耗时、GPU 光线追踪、GPU egui
+        let series = |sample: &ProfilerSample| {
+            let cpu = &sample.cpu;
+            [
+                Some(milliseconds(cpu.frame)),
+                Some(milliseconds(cpu.prepare + cpu.encode + cpu.submit)),
+                sample.gpu.ray_tracing.map(milliseconds),
+                sample.gpu.egui.map(milliseconds),
+            ]
+        };
+        let colors = [FRAME_COLOR, CPU_COLOR, RAY_TRACING_COLOR, EGUI_COLOR];
+
+        let samples = self.profiler_history.samples();
+        let max = samples
+            .iter()
+            .flat_map(|sample| series(sample).into_iter().flatten())
+            .fold(1.0f64, f64::max);
+
+        let (response, painter) = ui.allocate_painter(egui::vec2(ui.available_width(), 80.0), egui::Sense::hover());
+        let rect = response.rect;
+        painter.rect_filled(rect, 2.0, Color32::from_black_alpha(96));
+
+        for (n, color) in colors.iter().enumerate() {
+            let points: Vec<egui::Pos2> = samples
+                .iter()
+                .enumerate()
+                .filter_map(|(i, sample)| {
+                    series(sample)[n].map(|value| {
+                        egui::pos2(
+                            rect.left() + rect.width() * i as f32 / (ProfilerHistory::CAPACITY - 1) as f32,
+                            rect.bottom() - rect.height() * (value / max) as f32,
+                        )
+                    })
+                })
+                .collect();
+            painter.add(egui::Shape::line(points, egui::Stroke::new(1.0, *color)));
+        }
+        painter.text(
+            rect.left_top() + egui::vec2(4.0, 2.0),
+            egui::Align2::LEFT_TOP,
+            format!("{:.1} ms", max),
+            egui::FontId::monospace(10.0),
+            FRAME_COLOR,
+        );
+
+        ui.horizontal(|ui| {
+            ui.label(RichText::new("Frame").color(FRAME_COLOR).small());
+            ui.label(RichText::new("CPU").color(CPU_COLOR).small());
+            ui.label(RichText::new("Ray Tracing").color(RAY_TRACING_COLOR).small());
+            ui.label(RichText::new("Egui").color(EGUI_COLOR).small());
+        });
+
+        #[cfg(not(target_arch = "wasm32"))]
+        ui.horizontal(|ui| {
+            if ui.button("Export CSV").clicked() {
+                Self::export("profile.csv", self.profiler_history.to_csv());
+            }
+            if ui.button("Export Chrome Trace").clicked() {
+                Self::export("profile.json", self.profiler_history.to_chrome_trace());
+            }
+        });
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    fn export(path: &str, content: String) {
+        match std::fs::write(path, content) {
+            Ok(()) => log::info!("Profile exported to {path}"),
+            Err(error) => log::error!("Failed to export profile to {path}: {error}"),
+        }
+    }
+
+    pub fn target_frame_time(&self) -> Option<Duration> {
+        self.frame_time_budget
+            .then(|| Duration::from_secs_f32(self.target_frame_time_ms / 1000.0))
+    }
+
+    // 与 RenderContext 中的值一致，不超过每个像素的采样数
+    pub fn adaptive_min_samples(&self) -> u32 {
+        clamp_adaptive_min_samples(self.adaptive_min_samples, self.samples_per_pixel)
+    }
+
+    pub fn take_reload_shader(&mut self) -> bool {
+        std::mem::take(&mut self.reload_shader)
+    }
+
+    pub fn take_materials_changed(&mut self) -> bool {
+        std::mem::take(&mut self.materials_changed)
+    }
+
+    // 播放时按真实时间推进，到结尾后从头循环
+    pub fn advance_animation(&mut self, delta_time: Duration, duration: f32) {
+        if self.animation_playing && duration > 0.0 {
+            self.animation_time = (self.animation_time + delta_time.as_secs_f32()) % duration;
+            self.animation_time_changed = true;
+        }
+    }
+
+    // 时间在播放或拖动后改变时返回新的时间
+    pub fn take_animation_time(&mut self) -> Option<f32> {
+        std::mem::take(&mut self.animation_time_changed).then_some(self.animation_time)
+    }
+
     pub fn update(&mut self, render_status: RenderStatue) {
+        self.profiler_history.startup = render_status.startup_timings;
+        self.profiler_history
+            .push(render_status.frame_timings, render_status.gpu_timings);
         self.render_status = render_status;
-        self.progress = self.render_status.sampled_count as f32 / self.render_status.total_sample as f32;
+        self.progress = if self.render_status.adaptive_sampling {
+            self.render_status.converged_pixels as f32 / self.render_status.total_pixels.max(1) as f32
+        } else {
+            self.render_status.sampled_count as f32 / self.render_status.total_sample as f32
+        };
     }
 }
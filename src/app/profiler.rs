@@ -0,0 +1,259 @@
+use crate::app::renderer::FrameTimings;
+use crate::rendering::wgpu::{Wgpu, WgpuReadbackBuffer};
+use crate::time;
+use std::collections::VecDeque;
+use std::fmt::Write;
+use std::time::Duration;
+use wgpu::*;
+
+const RAY_TRACING_BEGIN: u32 = 0;
+const RAY_TRACING_END: u32 = 1;
+const EGUI_BEGIN: u32 = 2;
+const EGUI_END: u32 = 3;
+const QUERY_COUNT: u32 = 4;
+
+#[derive(Default, Copy, Clone)]
+pub struct GpuTimings {
+    pub ray_tracing: Option<Duration>,
+    pub egui: Option<Duration>,
+}
+
+// Renderer::new 中各阶段的 CPU 耗时
+#[derive(Default, Copy, Clone)]
+pub struct StartupTimings {
+    pub bvh_build: Duration,
+    pub buffer_upload: Duration,
+}
+
+// 用 wgpu 时间戳查询测量光线追踪计算通道和 egui 渲染通道的 GPU 耗时。
+// egui 通道由 egui_wgpu_backend 创建，只能在 encoder 上写入时间戳，所以还需要 TIMESTAMP_QUERY_INSIDE_ENCODERS
+pub struct GpuTimer {
+    query_set: QuerySet,
+    resolve_buffer: Buffer,
+    readback_buffer: WgpuReadbackBuffer,
+    inside_encoders: bool,
+    period: f32, // 每个时间戳单位对应的纳秒数
+    resolved: bool,
+    timings: GpuTimings,
+}
+
+impl GpuTimer {
+    // 适配器不支持时间戳查询时返回 None
+    pub fn new(wgpu: &Wgpu) -> Option<Self> {
+        let features = wgpu.device.features();
+        if !features.contains(Features::TIMESTAMP_QUERY) {
+            return None;
+        }
+
+        let query_set = wgpu.device.create_query_set(&QuerySetDescriptor {
+            label: Some("gpu timer query set"),
+            ty: QueryType::Timestamp,
+            count: QUERY_COUNT,
+        });
+
+        let size = (size_of::<u64>() as u32 * QUERY_COUNT) as BufferAddress;
+        let resolve_buffer = wgpu.device.create_buffer(&BufferDescriptor {
+            label: Some("gpu timer resolve buffer"),
+            size,
+            usage: BufferUsages::QUERY_RESOLVE | BufferUsages::COPY_SRC,
+            mapped_at_creation: false,
+        });
+
+        Some(Self {
+            query_set,
+            resolve_buffer,
+            readback_buffer: WgpuReadbackBuffer::new(wgpu, "gpu timer", size),
+            inside_encoders: features.contains(Features::TIMESTAMP_QUERY_INSIDE_ENCODERS),
+            period: wgpu.queue.get_timestamp_period(),
+            resolved: false,
+            timings: GpuTimings::default(),
+        })
+    }
+
+    pub fn ray_tracing_timestamp_writes(&self) -> ComputePassTimestampWrites<'_> {
+        ComputePassTimestampWrites {
+            query_set: &self.query_set,
+            beginning_of_pass_write_index: Some(RAY_TRACING_BEGIN),
+            end_of_pass_write_index: Some(RAY_TRACING_END),
+        }
+    }
+
+    pub fn begin_egui(&self, encoder: &mut CommandEncoder) {
+        if self.inside_encoders {
+            encoder.write_timestamp(&self.query_set, EGUI_BEGIN);
+        }
+    }
+
+    pub fn end_egui(&self, encoder: &mut CommandEncoder) {
+        if self.inside_encoders {
+            encoder.write_timestamp(&self.query_set, EGUI_END);
+        }
+    }
+
+    // 在所有时间戳写入之后录制
+    pub fn resolve(&mut self, encoder: &mut CommandEncoder) {
+        if !self.readback_buffer.is_idle() {
+            return;
+        }
+
+        let count = if self.inside_encoders { QUERY_COUNT } else { EGUI_BEGIN };
+        encoder.resolve_query_set(&self.query_set, 0..count, &self.resolve_buffer, 0);
+        self.resolved = self.readback_buffer.copy_from(encoder, &self.resolve_buffer, 0);
+    }
+
+    // 在提交之后调用
+    pub fn map(&mut self) {
+        if self.resolved {
+            self.readback_buffer.map();
+            self.resolved = false;
+        }
+    }
+
+    // 返回最近一次读回的结果
+    pub fn poll(&mut self, wgpu: &Wgpu) -> GpuTimings {
+        if let Some(data) = self.readback_buffer.try_read(wgpu) {
+            let timestamps: Vec<u64> = data
+                .chunks_exact(size_of::<u64>())
+                .map(bytemuck::pod_read_unaligned)
+                .collect();
+
+            self.timings.ray_tracing = Some(self.elapsed(
+                timestamps[RAY_TRACING_BEGIN as usize],
+                timestamps[RAY_TRACING_END as usize],
+            ));
+            if self.inside_encoders {
+                self.timings.egui = Some(self.elapsed(timestamps[EGUI_BEGIN as usize], timestamps[EGUI_END as usize]));
+            }
+        }
+
+        self.timings
+    }
+
+    fn elapsed(&self, begin: u64, end: u64) -> Duration {
+        Duration::from_nanos((end.saturating_sub(begin) as f64 * self.period as f64) as u64)
+    }
+}
+
+#[derive(Copy, Clone)]
+pub struct ProfilerSample {
+    pub time: Duration, // 相对于 ProfilerHistory 创建时刻
+    pub cpu: FrameTimings,
+    pub gpu: GpuTimings,
+}
+
+// 最近若干帧的耗时，用于绘制曲线和导出
+pub struct ProfilerHistory {
+    start: time::Instant,
+    samples: VecDeque<ProfilerSample>,
+    pub startup: StartupTimings,
+}
+
+impl Default for ProfilerHistory {
+    fn default() -> Self {
+        Self {
+            start: time::Instant::now(),
+            samples: VecDeque::with_capacity(Self::CAPACITY),
+            startup: StartupTimings::default(),
+        }
+    }
+}
+
+impl ProfilerHistory {
+    pub const CAPACITY: usize = 240;
+
+    pub fn push(&mut self, cpu: FrameTimings, gpu: GpuTimings) {
+        if self.samples.len() == Self::CAPACITY {
+            self.samples.pop_front();
+        }
+
+        self.samples.push_back(ProfilerSample {
+            time: self.start.elapsed(),
+            cpu,
+            gpu,
+        });
+    }
+
+    pub fn samples(&self) -> &VecDeque<ProfilerSample> {
+        &self.samples
+    }
+
+    pub fn latest(&self) -> Option<&ProfilerSample> {
+        self.samples.back()
+    }
+
+    pub fn to_csv(&self) -> String {
+        let mut csv = String::from("time_ms,frame_ms,prepare_ms,encode_ms,submit_ms,gpu_ray_tracing_ms,gpu_egui_ms\n");
+        for sample in &self.samples {
+            let _ = writeln!(
+                csv,
+                "{:.3},{:.3},{:.3},{:.3},{:.3},{},{}",
+                milliseconds(sample.time),
+                milliseconds(sample.cpu.frame),
+                milliseconds(sample.cpu.prepare),
+                milliseconds(sample.cpu.encode),
+                milliseconds(sample.cpu.submit),
+                sample
+                    .gpu
+                    .ray_tracing
+                    .map(|d| format!("{:.3}", milliseconds(d)))
+                    .unwrap_or_default(),
+                sample
+                    .gpu
+                    .egui
+                    .map(|d| format!("{:.3}", milliseconds(d)))
+                    .unwrap_or_default(),
+            );
+        }
+        csv
+    }
+
+    // Chrome Trace Event 格式，可以在 chrome://tracing 或 Perfetto 中打开。
+    // GPU 时间戳与 CPU 时钟不同步，GPU 事件放在提交时刻之后
+    pub fn to_chrome_trace(&self) -> String {
+        const CPU: u32 = 1;
+        const GPU: u32 = 2;
+
+        let mut events = Vec::new();
+        let mut event = |name: &str, tid: u32, ts: Duration, dur: Duration| {
+            events.push(format!(
+                r#"{{"name":"{name}","cat":"{}","ph":"X","pid":1,"tid":{tid},"ts":{:.3},"dur":{:.3}}}"#,
+                if tid == CPU { "cpu" } else { "gpu" },
+                microseconds(ts),
+                microseconds(dur),
+            ));
+        };
+
+        event("bvh build", CPU, Duration::ZERO, self.startup.bvh_build);
+        event("buffer upload", CPU, self.startup.bvh_build, self.startup.buffer_upload);
+
+        for sample in &self.samples {
+            let prepare = sample.time;
+            let encode = prepare + sample.cpu.prepare;
+            let submit = encode + sample.cpu.encode;
+            let submitted = submit + sample.cpu.submit;
+
+            event("prepare", CPU, prepare, sample.cpu.prepare);
+            event("encode", CPU, encode, sample.cpu.encode);
+            event("submit", CPU, submit, sample.cpu.submit);
+
+            let mut gpu = submitted;
+            if let Some(ray_tracing) = sample.gpu.ray_tracing {
+                event("ray tracing", GPU, gpu, ray_tracing);
+                gpu += ray_tracing;
+            }
+            if let Some(egui) = sample.gpu.egui {
+                event("egui", GPU, gpu, egui);
+            }
+        }
+
+        format!(r#"{{"traceEvents":[{}],"displayTimeUnit":"ms"}}"#, events.join(","))
+    }
+}
+
+pub fn milliseconds(duration: Duration) -> f64 {
+    duration.as_secs_f64() * 1000.0
+}
+
+fn microseconds(duration: Duration) -> f64 {
+    duration.as_secs_f64() * 1_000_000.0
+}
@@ -1,16 +1,21 @@
 use crate::app::camera::Camera;
 use crate::app::egui_renderer::EguiRenderer;
 use crate::app::gui_state::GuiState;
-use crate::rendering::bvh::build_bvh_tree;
-use crate::rendering::bvh::BvhBuildingEntry;
-use crate::rendering::bvh::BvhNode;
-use crate::rendering::material::*;
-use crate::rendering::primitive::sphere::SphereData;
+use crate::app::profiler::{GpuTimer, GpuTimings, StartupTimings};
+use crate::app::scene::Scene;
+use crate::rendering::bvh::{
+    bvh_refit_leaves, BvhNode, BvhRefitContext, BvhRefitLeaf, BvhRefitMode, BVH_REFIT_BINDINGS,
+    BVH_REFIT_WORKGROUP_SIZE,
+};
+use crate::rendering::layout::RAY_TRACING_BINDINGS;
+use crate::rendering::material::MaterialRegistry;
+use crate::rendering::mesh::Mesh;
 use crate::rendering::primitive::*;
+use crate::rendering::scene_data::{BvhUpdate, SceneData};
 use crate::rendering::wgpu::*;
-use crate::rendering::RenderContext;
+use crate::rendering::{AdaptiveSamplingCounters, AdaptiveSamplingMode, RenderContext};
 use crate::time;
-use crate::RAY_TRACING_SHADER;
+use crate::{BVH_REFIT_SHADER, RAY_TRACING_SHADER};
 use egui_winit::EventResponse;
 use log::info;
 use nalgebra::Point4;
@@ -28,19 +33,52 @@ use winit::dpi::PhysicalSize;
 pub struct Renderer {
     render_context: RenderContext,
     render_context_uniform_buffer: WgpuBindBuffer,
-    bvh_storage_buffer: WgpuBindBuffer,
-    important_indices_storage_buffer: WgpuBindBuffer,
-    quads_storage_buffer: WgpuBindBuffer,
-    spheres_storage_buffer: WgpuBindBuffer,
-    lambertian_materials_storage_buffer: WgpuBindBuffer,
-    diffuse_light_materials_storage_buffer: WgpuBindBuffer,
-    dielectric_materials_storage_buffer: WgpuBindBuffer,
+    bvh_storage_buffer: WgpuMirroredBuffer,
+    important_indices_storage_buffer: WgpuMirroredBuffer,
+    primitives_storage_buffer: WgpuMirroredBuffer,
+    materials_storage_buffer: WgpuMirroredBuffer,
     pixel_color_storage_buffer: WgpuBindBuffer,
+    pixel_statistics_storage_buffer: WgpuBindBuffer,
+    adaptive_counters_storage_buffer: WgpuBindBuffer,
+    adaptive_counters_readback_buffer: WgpuReadbackBuffer,
+    bvh_refit_context_uniform_buffer: WgpuBindBuffer,
+    bvh_refit_leaves_storage_buffer: WgpuMirroredBuffer,
+    bvh_refit_bounds_storage_buffer: WgpuBindBuffer,
+    converged_pixels: u32,
+    freed_samples: u64, // 本次渲染中 Redistribute 模式省下和额外使用的采样数，由读回的计数器累加
+    extra_samples: u64,
+    renders: u32,                  // 重新渲染的次数，丢弃上一次渲染中发出的读回
+    adaptive_counters_render: u32, // 正在读回的计数器属于哪一次渲染
+    output_texture: WgpuTexture<'static>,
+    ray_tracing_shader: ShaderModule,
+    material_shader: String,
+    ray_tracing_pipeline: Option<RayTracingPipeline>,
+    bvh_refit_shader: ShaderModule,
+    bvh_refit_pipeline: Option<BvhRefitPipeline>,
+    bvh_refit_mode: BvhRefitMode,
+    scene_data: SceneData, // 上一次上传的场景，几何体改变后在它的基础上重新拟合 BVH
     egui_renderer: EguiRenderer,
     should_rerender: bool,
     frames_time: Option<time::Instant>,
     frames_count: u32,
     frames_per_second: u32,
+    last_frame_start: Option<time::Instant>,
+    frame_timings: FrameTimings,
+    sample_batch: SampleBatch,
+    gpu_timer: Option<GpuTimer>,
+    startup_timings: StartupTimings,
+}
+
+struct RayTracingPipeline {
+    bind_group: WgpuBindGroup,
+    compute_pass: WgpuComputePass,
+}
+
+struct BvhRefitPipeline {
+    context: BvhRefitContext, // 调度的线程组数量由叶节点和节点的数量决定
+    bind_group: WgpuBindGroup,
+    propagate_pass: WgpuComputePass,
+    write_pass: WgpuComputePass,
 }
 
 pub struct RendererParameters<'a> {
@@ -54,7 +92,7 @@ pub struct RendererParameters<'a> {
     pub camera: Ref<'a, Camera>,
     pub primitives: &'a [Rc<PrimitiveData>],
     pub important_indices: &'a [u32],
-    pub materials: &'a MaterialList,
+    pub materials: &'a MaterialRegistry,
 }
 
 impl RendererParameters<'_> {
@@ -68,162 +106,150 @@ pub struct RenderStatue {
     pub sampled_count: u32,
     pub total_sample: u32,
     pub frames_per_second: u32,
+    pub adaptive_sampling: bool,
+    pub converged_pixels: u32,
+    pub total_pixels: u32,
+    pub frame_timings: FrameTimings,
+    pub samples_per_dispatch: u32,
+    pub gpu_timings: GpuTimings,
+    pub startup_timings: StartupTimings,
 }
 
-impl Renderer {
-    pub fn new(wgpu: Ref<Wgpu>, parameters: &RendererParameters) -> Self {
-        let mut primitives_indices = Vec::new();
-        let mut quads_data = Vec::new();
-        let mut spheres_data = Vec::new();
-        let mut bvh_building = Vec::new();
-        let mut importance = Vec::new();
-
-        for primitive in parameters.primitives.iter().map(Rc::clone) {
-            match primitive.as_ref() {
-                PrimitiveData::Quad(quad) => {
-                    bvh_building.push(BvhBuildingEntry {
-                        primitive: Rc::clone(&primitive),
-                        primitive_type: (*primitive).into(),
-                        primitive_id: quads_data.len() as u32,
-                        bounding_box: primitive.bounding_box(),
-                    });
-                    primitives_indices.push(PrimitiveIndex {
-                        primitive_type: (*primitive).into(),
-                        primitive_id: quads_data.len() as u32,
-                    });
-                    quads_data.push(*quad);
-                }
-                PrimitiveData::Sphere(sphere) => {
-                    bvh_building.push(BvhBuildingEntry {
-                        primitive: Rc::clone(&primitive),
-                        primitive_type: (*primitive).into(),
-                        primitive_id: spheres_data.len() as u32,
-                        bounding_box: primitive.bounding_box(),
-                    });
-                    primitives_indices.push(PrimitiveIndex {
-                        primitive_type: (*primitive).into(),
-                        primitive_id: spheres_data.len() as u32,
-                    });
-                    spheres_data.push(*sphere);
-                }
-            }
+// 每次调度的采样数。设置了帧时间预算时，根据上一帧的耗时调整采样数使帧时间接近预算
+#[derive(Copy, Clone)]
+pub struct SampleBatch {
+    pub samples_per_dispatch: u32,
+    pub target_frame_time: Option<Duration>,
+    adaptive_samples: f32,
+}
+
+impl SampleBatch {
+    pub const MAX_SAMPLES_PER_DISPATCH: u32 = 64;
+
+    pub fn new(samples_per_dispatch: u32, target_frame_time: Option<Duration>) -> Self {
+        Self {
+            samples_per_dispatch,
+            target_frame_time,
+            adaptive_samples: samples_per_dispatch as f32,
         }
+    }
 
-        for important in parameters.important_indices {
-            importance.push(primitives_indices[*important as usize]);
+    fn samples_per_dispatch(&mut self, last_frame_time: Duration) -> u32 {
+        let Some(target_frame_time) = self.target_frame_time else {
+            return self.samples_per_dispatch.clamp(1, Self::MAX_SAMPLES_PER_DISPATCH);
+        };
+
+        if !last_frame_time.is_zero() {
+            // 每帧最多翻倍或减半，并做平滑，避免在垂直同步的边界上振荡
+            let ratio = (target_frame_time.as_secs_f32() / last_frame_time.as_secs_f32()).clamp(0.5, 2.0);
+            let target = self.adaptive_samples * ratio;
+            self.adaptive_samples += (target - self.adaptive_samples) * 0.5;
+            self.adaptive_samples = self.adaptive_samples.clamp(1.0, Self::MAX_SAMPLES_PER_DISPATCH as f32);
         }
 
-        let len = bvh_building.len();
-        let mut bvh_tree = Vec::new();
-        build_bvh_tree(&mut bvh_tree, &mut bvh_building, 0, len, 0);
+        self.adaptive_samples.round() as u32
+    }
+}
+
+// CPU 端每帧各阶段的耗时
+#[derive(Default, Copy, Clone)]
+pub struct FrameTimings {
+    pub prepare: Duration, // 更新 uniform ，必要时重建绑定组和管线
+    pub encode: Duration,
+    pub submit: Duration,
+    pub frame: Duration, // 与上一帧开始时刻的间隔
+    pub pipeline_rebuilt: bool,
+}
+
+impl Renderer {
+    pub fn new(wgpu: Ref<Wgpu>, parameters: &RendererParameters) -> Self {
+        let scene_data = SceneData::new(
+            parameters.primitives,
+            parameters.important_indices,
+            parameters.materials,
+        );
+        let bvh_tree = scene_data.bvh_tree();
+        let bvh_build = scene_data.bvh_build();
 
         for (i, node) in bvh_tree.iter().enumerate() {
             info!("{} = {:?}\n", i, node);
         }
-        let bvh_storage_buffer = WgpuBindBuffer::new(
+        let buffer_upload_start = time::Instant::now();
+        let bvh_storage_buffer = WgpuMirroredBuffer::new(
             &wgpu,
             "bvh storage",
-            (size_of::<BvhNode>() * cmp::max(bvh_tree.len(), 1)) as BufferAddress,
-            BufferUsages::STORAGE | BufferUsages::COPY_DST,
-            ShaderStages::COMPUTE,
-            true,
+            size_of::<BvhNode>(),
+            bytemuck::cast_slice(bvh_tree.as_slice()),
         );
-        bvh_storage_buffer.write(&wgpu, 0, bytemuck::cast_slice(bvh_tree.as_slice()));
-
-        let important_indices_storage_buffer = WgpuBindBuffer::new(
+        let important_indices_storage_buffer = WgpuMirroredBuffer::new(
             &wgpu,
             "important indices storage",
-            (size_of::<PrimitiveIndex>() * cmp::max(importance.len(), 1)) as BufferAddress,
-            BufferUsages::STORAGE | BufferUsages::COPY_DST,
-            ShaderStages::COMPUTE,
-            true,
+            size_of::<PrimitiveIndex>(),
+            bytemuck::cast_slice(scene_data.importance().as_slice()),
         );
-        important_indices_storage_buffer.write(&wgpu, 0, bytemuck::cast_slice(importance.as_slice()));
-
-        let quads_storage_buffer = WgpuBindBuffer::new(
+        let primitives_storage_buffer = WgpuMirroredBuffer::new(
             &wgpu,
-            "quad storage",
-            (size_of::<QuadData>() * cmp::max(quads_data.len(), 1)) as BufferAddress,
-            BufferUsages::STORAGE | BufferUsages::COPY_DST,
-            ShaderStages::COMPUTE,
-            true,
+            "primitives storage",
+            size_of::<u32>(),
+            bytemuck::cast_slice(scene_data.primitives()),
+        );
+        let materials_storage_buffer = WgpuMirroredBuffer::new(
+            &wgpu,
+            "materials storage",
+            size_of::<u32>(),
+            bytemuck::cast_slice(scene_data.materials()),
         );
-        quads_storage_buffer.write(&wgpu, 0, bytemuck::cast_slice(quads_data.as_slice()));
+        let buffer_upload = buffer_upload_start.elapsed();
+        info!("bvh build: {:?}, buffer upload: {:?}", bvh_build, buffer_upload);
 
-        let spheres_storage_buffer = WgpuBindBuffer::new(
+        let pixel_color_storage_buffer = WgpuBindBuffer::new(
             &wgpu,
-            "sphere storage",
-            (size_of::<SphereData>() * cmp::max(spheres_data.len(), 1)) as BufferAddress,
-            BufferUsages::STORAGE | BufferUsages::COPY_DST,
-            ShaderStages::COMPUTE,
-            true,
+            "pixel color storage",
+            ((size_of::<f32>() * 3) as u32 * parameters.max_pixels()) as BufferAddress,
+            BufferUsages::STORAGE,
+            ShaderStages::COMPUTE | ShaderStages::FRAGMENT,
+            false,
         );
-        spheres_storage_buffer.write(&wgpu, 0, bytemuck::cast_slice(spheres_data.as_slice()));
-
-        let mut lambertian_materials = Vec::new();
-        let mut diffuse_light_materials = Vec::new();
-        let mut dielectric_materials = Vec::new();
-        for (material_type, materials) in parameters.materials.map() {
-            match material_type {
-                MaterialType::DebugNormal => (),
-                MaterialType::Lambertian => lambertian_materials.append(
-                    &mut materials
-                        .iter()
-                        .map(|material| *material.as_any().downcast_ref::<Lambertian>().unwrap())
-                        .collect(),
-                ),
-                MaterialType::DiffuseLight => diffuse_light_materials.append(
-                    &mut materials
-                        .iter()
-                        .map(|material| *material.as_any().downcast_ref::<DiffuseLight>().unwrap())
-                        .collect(),
-                ),
-                MaterialType::Dielectric => dielectric_materials.append(
-                    &mut materials
-                        .iter()
-                        .map(|material| *material.as_any().downcast_ref::<Dielectric>().unwrap())
-                        .collect(),
-                ),
-            }
-        }
-        let lambertian_materials_storage_buffer = WgpuBindBuffer::new(
+
+        // 每个像素的亮度和、亮度平方和、采样数和是否收敛，见 ray_tracing.wgsl 中的 PixelStatistics
+        let pixel_statistics_storage_buffer = WgpuBindBuffer::new(
             &wgpu,
-            "lambertian materials storage",
-            (size_of::<SphereData>() * cmp::max(lambertian_materials.len(), 1)) as BufferAddress,
-            BufferUsages::STORAGE | BufferUsages::COPY_DST,
+            "pixel statistics storage",
+            ((size_of::<u32>() * 4) as u32 * parameters.max_pixels()) as BufferAddress,
+            BufferUsages::STORAGE,
             ShaderStages::COMPUTE,
-            true,
+            false,
         );
-        lambertian_materials_storage_buffer.write(&wgpu, 0, bytemuck::cast_slice(&lambertian_materials));
 
-        let diffuse_light_materials_storage_buffer = WgpuBindBuffer::new(
+        let adaptive_counters_storage_buffer = WgpuBindBuffer::new(
             &wgpu,
-            "diffuse light materials storage",
-            (size_of::<SphereData>() * cmp::max(diffuse_light_materials.len(), 1)) as BufferAddress,
-            BufferUsages::STORAGE | BufferUsages::COPY_DST,
+            "adaptive sampling counters storage",
+            size_of::<AdaptiveSamplingCounters>() as BufferAddress,
+            BufferUsages::STORAGE | BufferUsages::COPY_DST | BufferUsages::COPY_SRC,
             ShaderStages::COMPUTE,
-            true,
+            false,
+        );
+        let adaptive_counters_readback_buffer = WgpuReadbackBuffer::new(
+            &wgpu,
+            "adaptive sampling counters",
+            size_of::<AdaptiveSamplingCounters>() as BufferAddress,
         );
-        diffuse_light_materials_storage_buffer.write(&wgpu, 0, bytemuck::cast_slice(&diffuse_light_materials));
 
-        let dielectric_materials_storage_buffer = WgpuBindBuffer::new(
+        let bvh_refit_context_uniform_buffer = WgpuBindBuffer::new(
             &wgpu,
-            "dielectric materials storage",
-            (size_of::<SphereData>() * cmp::max(dielectric_materials.len(), 1)) as BufferAddress,
-            BufferUsages::STORAGE | BufferUsages::COPY_DST,
+            "bvh refit context",
+            size_of::<BvhRefitContext>() as BufferAddress,
+            BufferUsages::UNIFORM | BufferUsages::COPY_DST,
             ShaderStages::COMPUTE,
             true,
         );
-        dielectric_materials_storage_buffer.write(&wgpu, 0, bytemuck::cast_slice(&dielectric_materials));
-
-        let pixel_color_storage_buffer = WgpuBindBuffer::new(
+        let bvh_refit_leaves_storage_buffer = WgpuMirroredBuffer::new(
             &wgpu,
-            "pixel color storage",
-            ((size_of::<f32>() * 3) as u32 * parameters.max_pixels()) as BufferAddress,
-            BufferUsages::STORAGE,
-            ShaderStages::COMPUTE | ShaderStages::FRAGMENT,
-            false,
+            "bvh refit leaves storage",
+            size_of::<BvhRefitLeaf>(),
+            bytemuck::cast_slice(&bvh_refit_leaves(scene_data.bvh_tree())),
         );
+        let bvh_refit_bounds_storage_buffer = Self::create_bvh_refit_bounds_buffer(&wgpu, scene_data.bvh_tree().len());
 
         let egui_renderer = EguiRenderer::new(&parameters.window, &wgpu.device, wgpu.surface_configuration.format);
 
@@ -247,87 +273,206 @@ impl Renderer {
         );
         render_context_uniform_buffer.write(&wgpu, 0, bytemuck::bytes_of(&render_context));
 
+        let output_texture = Self::create_output_texture(&wgpu, width, height);
+        let material_shader = parameters.materials.wgsl();
+        let ray_tracing_shader = Self::create_shader_module(&wgpu, &RAY_TRACING_SHADER, &material_shader);
+        let bvh_refit_shader = wgpu.device.create_shader_module(ShaderModuleDescriptor {
+            label: Some("bvh refit shader"),
+            source: ShaderSource::Wgsl(Cow::Borrowed(&BVH_REFIT_SHADER)),
+        });
+
         Self {
             render_context,
             render_context_uniform_buffer,
             bvh_storage_buffer,
             important_indices_storage_buffer,
-            quads_storage_buffer,
-            spheres_storage_buffer,
-            lambertian_materials_storage_buffer,
-            diffuse_light_materials_storage_buffer,
-            dielectric_materials_storage_buffer,
+            primitives_storage_buffer,
+            materials_storage_buffer,
             pixel_color_storage_buffer,
+            pixel_statistics_storage_buffer,
+            adaptive_counters_storage_buffer,
+            adaptive_counters_readback_buffer,
+            bvh_refit_context_uniform_buffer,
+            bvh_refit_leaves_storage_buffer,
+            bvh_refit_bounds_storage_buffer,
+            converged_pixels: 0,
+            freed_samples: 0,
+            extra_samples: 0,
+            renders: 0,
+            adaptive_counters_render: 0,
+            output_texture,
+            ray_tracing_shader,
+            material_shader,
+            ray_tracing_pipeline: None,
+            bvh_refit_shader,
+            bvh_refit_pipeline: None,
+            bvh_refit_mode: BvhRefitMode::Cpu,
+            scene_data,
             egui_renderer,
             should_rerender: false,
             frames_time: None,
             frames_count: 0,
-            frames_per_second: 0
+            frames_per_second: 0,
+            last_frame_start: None,
+            frame_timings: FrameTimings::default(),
+            sample_batch: SampleBatch::new(1, None),
+            gpu_timer: GpuTimer::new(&wgpu),
+            startup_timings: StartupTimings {
+                bvh_build,
+                buffer_upload,
+            },
         }
     }
 
-    pub fn render(&mut self, wgpu: Ref<Wgpu>, surface: WgpuTexture) -> RenderStatue {
+    pub fn render(&mut self, wgpu: Ref<Wgpu>, surface: &Texture) -> RenderStatue {
+        let frame_start = time::Instant::now();
         self.frames_count += 1;
 
         let mut encoder = wgpu.device.create_command_encoder(&CommandEncoderDescriptor {
             label: Some("Render Encoder"),
         });
 
+        if let Some(data) = self.adaptive_counters_readback_buffer.try_read(&wgpu) {
+            let counters = bytemuck::pod_read_unaligned::<AdaptiveSamplingCounters>(&data);
+            if self.adaptive_counters_render == self.renders {
+                self.converged_pixels = counters.converged_pixels;
+                self.freed_samples += counters.freed_samples as u64;
+                self.extra_samples += counters.extra_samples as u64;
+            }
+        }
+
+        let gpu_timings = self
+            .gpu_timer
+            .as_mut()
+            .map(|gpu_timer| gpu_timer.poll(&wgpu))
+            .unwrap_or_default();
+
         if self.take_rerender() {
             self.render_context.reset_sample_id();
             // info!("{:?}", self.render_context.sample_id);
+            self.converged_pixels = 0;
+            self.freed_samples = 0;
+            self.extra_samples = 0;
+            self.renders = self.renders.wrapping_add(1);
             self.frames_count = 0;
             self.frames_time = Some(time::Instant::now());
         } else if self.render_context.sample_id < self.render_context.samples_per_pixel {
+            // 按上一次调度的采样数前进，所以必须在更新 samples_per_dispatch 之前调用
             self.render_context.increment_sample_id();
             // info!("{:?}", self.render_context.sample_id);
         }
 
+        let samples_per_dispatch = self.sample_batch.samples_per_dispatch(self.frame_timings.frame);
+        self.render_context.set_samples_per_dispatch(samples_per_dispatch);
+        // 计数器晚几帧才读回，预算可能被超出几次调度的采样数
+        self.render_context
+            .set_sample_budget(self.freed_samples > self.extra_samples);
+
         self.render_context_uniform_buffer.write(
             &wgpu,
             mem::offset_of!(RenderContext, sample_id),
             bytemuck::bytes_of(&self.render_context.sample_id),
         );
-
-        let ray_tracing_bind_group = WgpuBindGroup::new(
+        self.render_context_uniform_buffer.write(
             &wgpu,
-            Option::from("ray tracing"),
+            mem::offset_of!(RenderContext, samples_per_dispatch),
+            bytemuck::bytes_of(&self.render_context.samples_per_dispatch),
+        );
+        self.render_context_uniform_buffer.write(
+            &wgpu,
+            mem::offset_of!(RenderContext, max_samples_per_pixel),
+            bytemuck::bytes_of(&self.render_context.max_samples_per_pixel),
+        );
+        // 重新渲染时清零所有计数器，否则只清零每帧重新统计的 converged_pixels
+        if self.render_context.sample_id == 0 {
+            self.adaptive_counters_storage_buffer.write(
+                &wgpu,
+                0,
+                bytemuck::bytes_of(&AdaptiveSamplingCounters::default()),
+            );
+        } else {
+            self.adaptive_counters_storage_buffer
+                .write(&wgpu, 0, bytemuck::bytes_of(&0u32));
+        }
+
+        let pipeline_rebuilt = self.ray_tracing_pipeline.is_none();
+        if pipeline_rebuilt {
+            self.ray_tracing_pipeline = Some(self.create_ray_tracing_pipeline(&wgpu));
+        }
+        let ray_tracing_pipeline = self.ray_tracing_pipeline.as_ref().unwrap();
+
+        let prepared = time::Instant::now();
+
+        ray_tracing_pipeline.compute_pass.render(
+            &mut encoder,
+            Some(&[&ray_tracing_pipeline.bind_group]),
+            self.gpu_timer
+                .as_ref()
+                .map(|gpu_timer| gpu_timer.ray_tracing_timestamp_writes()),
+        );
+
+        let adaptive_counters_copied = self.adaptive_counters_readback_buffer.copy_from(
+            &mut encoder,
+            self.adaptive_counters_storage_buffer.buffer(),
             0,
-            &[
-                &self.render_context_uniform_buffer,
-                &self.pixel_color_storage_buffer,
-                &self.bvh_storage_buffer,
-                &self.important_indices_storage_buffer,
-                &self.quads_storage_buffer,
-                &self.spheres_storage_buffer,
-                &self.lambertian_materials_storage_buffer,
-                &self.diffuse_light_materials_storage_buffer,
-                &self.dielectric_materials_storage_buffer,
-                &surface,
-            ],
         );
+        if adaptive_counters_copied {
+            // 读回之后清零累加的采样数，没有读回的帧继续累加
+            let offset = mem::offset_of!(AdaptiveSamplingCounters, freed_samples) as BufferAddress;
+            encoder.clear_buffer(self.adaptive_counters_storage_buffer.buffer(), offset, None);
+            self.adaptive_counters_render = self.renders;
+        }
 
-        let ray_tracing_compute_pass = WgpuComputePass::new(
-            &wgpu,
-            "ray tracing",
-            Some(&[ray_tracing_bind_group.bind_group_layout()]),
-            &wgpu.device.create_shader_module(ShaderModuleDescriptor {
-                label: Some("ray tracing shader"),
-                source: ShaderSource::Wgsl(Cow::Borrowed(*RAY_TRACING_SHADER)),
-            }),
-            [
-                (self.render_context.width as f32 / 16f32).ceil() as u32,
-                (self.render_context.height as f32 / 16f32).ceil() as u32,
-                1,
-            ],
+        // 交换链中的纹理每帧都可能不同，所以光线追踪的结果写入固定的输出纹理，再复制到交换链纹理中
+        let output = self.output_texture.texture();
+        encoder.copy_texture_to_texture(
+            output.as_image_copy(),
+            surface.as_image_copy(),
+            Extent3d {
+                width: cmp::min(output.width(), surface.width()),
+                height: cmp::min(output.height(), surface.height()),
+                depth_or_array_layers: 1,
+            },
         );
-        ray_tracing_compute_pass.render(&mut encoder, Some(&[&ray_tracing_bind_group]));
 
-        self.egui_renderer
-            .render(&wgpu, &mut encoder, surface.texture_view(), None);
+        let surface_view = surface.create_view(&TextureViewDescriptor {
+            label: Some("surface texture view"),
+            ..Default::default()
+        });
+        if let Some(gpu_timer) = &self.gpu_timer {
+            gpu_timer.begin_egui(&mut encoder);
+        }
+        self.egui_renderer.render(&wgpu, &mut encoder, &surface_view, None);
+        if let Some(gpu_timer) = &mut self.gpu_timer {
+            gpu_timer.end_egui(&mut encoder);
+            gpu_timer.resolve(&mut encoder);
+        }
+
+        let encoded = time::Instant::now();
 
         wgpu.queue.submit(Some(encoder.finish()));
 
+        if adaptive_counters_copied {
+            self.adaptive_counters_readback_buffer.map();
+        }
+        if let Some(gpu_timer) = &mut self.gpu_timer {
+            gpu_timer.map();
+        }
+
+        let submitted = time::Instant::now();
+
+        self.frame_timings = FrameTimings {
+            prepare: prepared - frame_start,
+            encode: encoded - prepared,
+            submit: submitted - encoded,
+            frame: self
+                .last_frame_start
+                .map(|last_frame_start| frame_start - last_frame_start)
+                .unwrap_or_default(),
+            pipeline_rebuilt,
+        };
+        self.last_frame_start = Some(frame_start);
+
         if self.frames_time.is_none() {
             self.frames_time = Some(time::Instant::now());
         } else {
@@ -338,11 +483,135 @@ impl Renderer {
                 self.frames_count = 0;
             }
         }
-        
+
         RenderStatue {
             sampled_count: self.render_context.sample_id,
             total_sample: self.render_context.samples_per_pixel,
             frames_per_second: self.frames_per_second,
+            adaptive_sampling: self.render_context.adaptive_sampling_mode() != AdaptiveSamplingMode::Off,
+            converged_pixels: self.converged_pixels,
+            total_pixels: self.render_context.pixels(),
+            frame_timings: self.frame_timings,
+            samples_per_dispatch: self.render_context.samples_per_dispatch,
+            gpu_timings,
+            startup_timings: self.startup_timings,
+        }
+    }
+
+    // 绑定的缓冲区被重新分配、输出纹理被重新创建或着色器被重新加载后，需要调用此方法
+    fn invalidate_ray_tracing_pipeline(&mut self) {
+        self.ray_tracing_pipeline = None;
+    }
+
+    fn create_ray_tracing_pipeline(&self, wgpu: &Wgpu) -> RayTracingPipeline {
+        info!("Creating ray tracing pipeline");
+
+        let bind_group = WgpuBindGroup::new(
+            wgpu,
+            Option::from("ray tracing"),
+            0,
+            &RAY_TRACING_BINDINGS.map(|name| self.ray_tracing_bindable(name)),
+        );
+
+        let compute_pass = WgpuComputePass::new(
+            wgpu,
+            "ray tracing",
+            Some(&[bind_group.bind_group_layout()]),
+            &self.ray_tracing_shader,
+            [
+                (self.render_context.width as f32 / 16f32).ceil() as u32,
+                (self.render_context.height as f32 / 16f32).ceil() as u32,
+                1,
+            ],
+        );
+
+        RayTracingPipeline {
+            bind_group,
+            compute_pass,
+        }
+    }
+
+    // 绑定顺序由 RAY_TRACING_BINDINGS 决定，名字与 ray_tracing.wgsl 中的变量名一致
+    fn ray_tracing_bindable(&self, name: &str) -> &dyn WgpuBindable<'_> {
+        match name {
+            "context" => &self.render_context_uniform_buffer,
+            "pixel_color" => &self.pixel_color_storage_buffer,
+            "pixel_statistics" => &self.pixel_statistics_storage_buffer,
+            "adaptive_counters" => &self.adaptive_counters_storage_buffer,
+            "bvh_tree" => &self.bvh_storage_buffer,
+            "importance" => &self.important_indices_storage_buffer,
+            "primitives" => &self.primitives_storage_buffer,
+            "materials" => &self.materials_storage_buffer,
+            "surface" => &self.output_texture,
+            _ => panic!("unknown ray tracing binding {}", name),
+        }
+    }
+
+    fn create_output_texture(wgpu: &Wgpu, width: u32, height: u32) -> WgpuTexture<'static> {
+        WgpuTexture::new(
+            wgpu,
+            &TextureDescriptor {
+                label: Some("ray tracing output"),
+                size: Extent3d {
+                    width: cmp::max(width, 1),
+                    height: cmp::max(height, 1),
+                    depth_or_array_layers: 1,
+                },
+                mip_level_count: 1,
+                sample_count: 1,
+                dimension: TextureDimension::D2,
+                format: wgpu.surface_configuration.format,
+                usage: TextureUsages::STORAGE_BINDING | TextureUsages::COPY_SRC,
+                view_formats: &[],
+            },
+            WgpuTextureBindingInstruction {
+                visibility: ShaderStages::COMPUTE,
+                binding_type: WgpuTextureBindingType::StorageTexture,
+                storage_access: None,
+                sample_type: None,
+            },
+        )
+    }
+
+    // 着色器编译失败时保留原来的管线，返回 false
+    pub fn reload_shader(&mut self, wgpu: &Wgpu, source: &str) -> bool {
+        let shader = match Self::compile_shader_module(wgpu, source, &self.material_shader) {
+            Ok(shader) => shader,
+            Err(error) => {
+                log::error!("Failed to reload shader: {error}");
+                return false;
+            }
+        };
+
+        info!("Shader reloaded");
+        self.ray_tracing_shader = shader;
+        self.invalidate_ray_tracing_pipeline();
+        self.should_rerender = true;
+        true
+    }
+
+    // 材质的 WGSL 代码由 MaterialRegistry 生成，拼接在 ray_tracing.wgsl 之后
+    fn create_shader_module(wgpu: &Wgpu, source: &str, material_shader: &str) -> ShaderModule {
+        wgpu.device.create_shader_module(ShaderModuleDescriptor {
+            label: Some("ray tracing shader"),
+            source: ShaderSource::Wgsl(Cow::Owned(format!("{}\n{}", source, material_shader))),
+        })
+    }
+
+    // 在错误作用域中创建着色器模块，检查编译错误。
+    // wasm 上不能阻塞等待 pop_error_scope，直接创建，编译错误由未捕获错误的回调报告
+    fn compile_shader_module(wgpu: &Wgpu, source: &str, material_shader: &str) -> Result<ShaderModule, Error> {
+        cfg_if::cfg_if! {
+            if #[cfg(target_arch = "wasm32")] {
+                Ok(Self::create_shader_module(wgpu, source, material_shader))
+            } else {
+                wgpu.device.push_error_scope(ErrorFilter::Validation);
+                let shader = Self::create_shader_module(wgpu, source, material_shader);
+                match futures::executor::block_on(wgpu.device.pop_error_scope()) {
+                    Some(error) => Err(error),
+                    None => Ok(shader),
+                }
+            }
         }
     }
 
@@ -350,6 +619,8 @@ impl Renderer {
         self.render_context.update(&camera, size.width, size.height);
         self.render_context_uniform_buffer
             .write(&wgpu, 0, bytemuck::bytes_of(&self.render_context));
+        self.output_texture = Self::create_output_texture(&wgpu, size.width, size.height);
+        self.invalidate_ray_tracing_pipeline();
         self.should_rerender = true;
     }
 
@@ -360,6 +631,7 @@ impl Renderer {
         delta_time: Duration,
         mut camera: RefMut<Camera>,
         mut gui_state: RefMut<GuiState>,
+        mut scene: RefMut<Scene>,
     ) {
         if self.render_context.max_ray_bounces != gui_state.max_ray_bounces() {
             self.render_context.max_ray_bounces = gui_state.max_ray_bounces();
@@ -371,6 +643,41 @@ impl Renderer {
             self.should_rerender = true;
         }
 
+        if self.render_context.adaptive_sampling_mode() != gui_state.adaptive_sampling_mode()
+            || self.render_context.convergence_threshold != gui_state.convergence_threshold()
+            || self.render_context.adaptive_min_samples != gui_state.adaptive_min_samples()
+        {
+            self.render_context.set_adaptive_sampling(
+                gui_state.adaptive_sampling_mode(),
+                gui_state.convergence_threshold(),
+                gui_state.adaptive_min_samples(),
+            );
+            self.should_rerender = true;
+        }
+
+        if self.render_context.sampler_type() != gui_state.sampler_type()
+            || self.render_context.seed != gui_state.seed()
+        {
+            self.render_context
+                .set_sampler(gui_state.sampler_type(), gui_state.seed());
+            self.should_rerender = true;
+        }
+
+        self.render_context.set_sample_view(gui_state.sample_view());
+
+        self.sample_batch.samples_per_dispatch = gui_state.samples_per_dispatch();
+        self.sample_batch.target_frame_time = gui_state.target_frame_time();
+        self.bvh_refit_mode = gui_state.bvh_refit_mode();
+
+        if gui_state.take_reload_shader() {
+            match Self::read_shader_source() {
+                Some(source) => {
+                    self.reload_shader(&wgpu, &source);
+                }
+                None => log::warn!("Shader source is not available for reloading"),
+            }
+        }
+
         if camera.take_rerender() {
             self.should_rerender = true;
         }
@@ -380,7 +687,184 @@ impl Renderer {
         self.render_context_uniform_buffer
             .write(&wgpu, 0, bytemuck::bytes_of(&self.render_context));
 
-        self.egui_renderer.update(&window, delta_time, gui_state.deref_mut())
+        self.egui_renderer
+            .update(&window, delta_time, gui_state.deref_mut(), scene.deref_mut());
+
+        if gui_state.take_materials_changed() {
+            scene.materials.mark_changed();
+        }
+        self.update_scene(&wgpu, scene.deref_mut());
+    }
+
+    // 场景图或材质在上一帧之后有变化时，重新生成图元，重新拟合或构建 BVH，只上传与上一次不同的部分。
+    // 缓冲区需要变大时重新分配并重建管线，新的材质类型需要重新编译着色器。有变化时从头累积采样
+    pub fn update_scene(&mut self, wgpu: &Wgpu, scene: &mut Scene) {
+        let geometry_changed = scene.objects.take_changed();
+        let materials_changed = scene.materials.take_changed();
+        if !geometry_changed && !materials_changed {
+            return;
+        }
+
+        let mut updates = Vec::new();
+        if geometry_changed {
+            let mut primitives = Vec::new();
+            let mut important_indices = Vec::new();
+            scene.primitives(&mut primitives, &mut important_indices);
+            let bvh_update =
+                self.scene_data
+                    .update(&primitives, &important_indices, &scene.materials, self.bvh_refit_mode);
+            info!("BVH {:?}: {:?}", bvh_update, self.scene_data.bvh_build());
+
+            let scene_data = &self.scene_data;
+            updates.extend([
+                self.important_indices_storage_buffer
+                    .update(wgpu, bytemuck::cast_slice(scene_data.importance().as_slice())),
+                self.primitives_storage_buffer
+                    .update(wgpu, bytemuck::cast_slice(scene_data.primitives())),
+            ]);
+            self.render_context.important_index_len = scene_data.importance().len() as u32;
+
+            if bvh_update == BvhUpdate::RefitLeaves {
+                updates.push(self.refit_bvh_on_gpu(wgpu));
+            } else {
+                updates.push(
+                    self.bvh_storage_buffer
+                        .update(wgpu, bytemuck::cast_slice(self.scene_data.bvh_tree().as_slice())),
+                );
+            }
+            self.render_context_uniform_buffer
+                .write(wgpu, 0, bytemuck::bytes_of(&self.render_context));
+        }
+
+        if materials_changed {
+            updates.push(
+                self.materials_storage_buffer
+                    .update(wgpu, bytemuck::cast_slice(&scene.materials.pack())),
+            );
+            let material_shader = scene.materials.wgsl();
+            if material_shader != self.material_shader {
+                // 与 reload_shader 相同，编译失败时保留原来的着色器和管线
+                match Self::compile_shader_module(wgpu, &RAY_TRACING_SHADER, &material_shader) {
+                    Ok(shader) => {
+                        self.ray_tracing_shader = shader;
+                        self.material_shader = material_shader;
+                        self.invalidate_ray_tracing_pipeline();
+                    }
+                    Err(error) => log::error!("Failed to compile material shader: {error}"),
+                }
+            }
+        }
+
+        if updates.iter().any(|update| update.reallocated) {
+            self.invalidate_ray_tracing_pipeline();
+            self.bvh_refit_pipeline = None;
+        }
+        let uploaded: usize = updates.iter().map(|update| update.uploaded).sum();
+        info!("Scene updated, {} bytes uploaded", uploaded);
+        self.should_rerender = true;
+    }
+
+    // 上传叶节点的包围盒，在 GPU 上合并到内部节点。CPU 上的 BVH 只有叶节点是最新的
+    fn refit_bvh_on_gpu(&mut self, wgpu: &Wgpu) -> WgpuBufferUpdate {
+        let bvh_tree = self.scene_data.bvh_tree();
+        let leaves = bvh_refit_leaves(bvh_tree);
+        let context = BvhRefitContext {
+            leaf_count: leaves.len() as u32,
+            node_count: bvh_tree.len() as u32,
+        };
+
+        let mut update = self
+            .bvh_refit_leaves_storage_buffer
+            .update(wgpu, bytemuck::cast_slice(&leaves));
+        let bounds_size = Self::bvh_refit_bounds_size(bvh_tree.len());
+        if self.bvh_refit_bounds_storage_buffer.buffer().size() < bounds_size {
+            self.bvh_refit_bounds_storage_buffer = Self::create_bvh_refit_bounds_buffer(wgpu, bvh_tree.len());
+            update.reallocated = true;
+        }
+        self.bvh_refit_context_uniform_buffer
+            .write(wgpu, 0, bytemuck::bytes_of(&context));
+
+        if update.reallocated
+            || self
+                .bvh_refit_pipeline
+                .as_ref()
+                .is_none_or(|pipeline| pipeline.context != context)
+        {
+            self.bvh_refit_pipeline = Some(self.create_bvh_refit_pipeline(wgpu, context));
+        }
+        let pipeline = self.bvh_refit_pipeline.as_ref().unwrap();
+
+        let mut encoder = wgpu.device.create_command_encoder(&CommandEncoderDescriptor {
+            label: Some("bvh refit encoder"),
+        });
+        encoder.clear_buffer(self.bvh_refit_bounds_storage_buffer.buffer(), 0, None);
+        pipeline
+            .propagate_pass
+            .render(&mut encoder, Some(&[&pipeline.bind_group]), None);
+        pipeline
+            .write_pass
+            .render(&mut encoder, Some(&[&pipeline.bind_group]), None);
+        wgpu.queue.submit([encoder.finish()]);
+
+        // 内存中的副本与 GPU 上的内容不再一致，下一次从 CPU 更新时整体上传
+        self.bvh_storage_buffer.invalidate();
+        update
+    }
+
+    fn create_bvh_refit_pipeline(&self, wgpu: &Wgpu, context: BvhRefitContext) -> BvhRefitPipeline {
+        info!("Creating bvh refit pipeline");
+
+        let bvh_tree = self.bvh_storage_buffer.writable();
+        let bind_group = WgpuBindGroup::new(
+            wgpu,
+            Some("bvh refit"),
+            0,
+            &BVH_REFIT_BINDINGS.map(|name| -> &dyn WgpuBindable<'_> {
+                match name {
+                    "refit" => &self.bvh_refit_context_uniform_buffer,
+                    "refit_leaves" => &self.bvh_refit_leaves_storage_buffer,
+                    "refit_bounds" => &self.bvh_refit_bounds_storage_buffer,
+                    "bvh_tree" => &bvh_tree,
+                    _ => panic!("unknown bvh refit binding {}", name),
+                }
+            }),
+        );
+
+        let pass = |entry_point: &str, count: u32| {
+            WgpuComputePass::with_entry_point(
+                wgpu,
+                entry_point,
+                Some(&[bind_group.bind_group_layout()]),
+                &self.bvh_refit_shader,
+                entry_point,
+                [count.div_ceil(BVH_REFIT_WORKGROUP_SIZE), 1, 1],
+            )
+        };
+        let propagate_pass = pass("BvhRefit_propagate", context.leaf_count);
+        let write_pass = pass("BvhRefit_write", context.node_count);
+
+        BvhRefitPipeline {
+            context,
+            bind_group,
+            propagate_pass,
+            write_pass,
+        }
+    }
+
+    // 每个节点 6 个字，见 bvh_refit.wgsl 中的 refit_bounds
+    fn bvh_refit_bounds_size(node_count: usize) -> BufferAddress {
+        (size_of::<u32>() * 6 * cmp::max(node_count, 1)) as BufferAddress
+    }
+
+    fn create_bvh_refit_bounds_buffer(wgpu: &Wgpu, node_count: usize) -> WgpuBindBuffer {
+        WgpuBindBuffer::new(
+            wgpu,
+            "bvh refit bounds storage",
+            Self::bvh_refit_bounds_size(node_count),
+            BufferUsages::STORAGE | BufferUsages::COPY_DST,
+            ShaderStages::COMPUTE,
+            false,
+        )
     }
 
     pub fn on_window_event(
@@ -391,6 +875,16 @@ impl Renderer {
         self.egui_renderer.on_window_event(&window, event)
     }
 
+    fn read_shader_source() -> Option<String> {
+        cfg_if::cfg_if! {
+            if #[cfg(target_arch = "wasm32")] {
+                None
+            } else {
+                std::fs::read_to_string(concat!(env!("CARGO_MANIFEST_DIR"), "/src/shader/ray_tracing.wgsl")).ok()
+            }
+        }
+    }
+
     fn take_rerender(&mut self) -> bool {
         if self.should_rerender {
             self.should_rerender = false;
@@ -1,27 +1,102 @@
 use crate::math::degree_to_radian;
+use crate::app::camera::Camera;
+use crate::rendering::cpu::{hash_u32, u32_to_unit_float, CpuImage, CpuPathTracer};
 use crate::rendering::primitive::Transformable;
-use crate::rendering::material::{DebugNormal, Dielectric, DiffuseLight, Lambertian, MaterialList};
+use crate::rendering::material::{
+    AlphaMask, AlphaMode, DebugNormal, Dielectric, DiffuseLight, Hair, Lambertian, MaterialRegistry, NormalMap,
+    Principled, SurfaceMaps, Terrain, TerrainLayer, Texture,
+};
 use crate::rendering::mesh::mesh_list::TransformableMeshList;
+use crate::rendering::mesh::moving::Moving;
+use crate::rendering::mesh::scene_graph::{SceneGraph, Transform};
 use crate::rendering::mesh::Mesh;
 use crate::rendering::primitive::sphere::Sphere;
-use crate::rendering::primitive::{PrimitiveData, Quad};
+use crate::rendering::primitive::{
+    parse_curves, Csg, CsgNode, Curve, CurveKind, Heightfield, Motion, PrimitiveData, Quad, Sdf, SdfNode, Shape,
+    ShapeKind,
+};
+use crate::rendering::scene_data::SceneData;
+use crate::rendering::{RenderContext, SamplerType};
+use image::{DynamicImage, ImageBuffer, Luma, Rgba, RgbaImage};
 use log::info;
-use nalgebra::{Point3, Translation3, UnitQuaternion, Vector3};
+use nalgebra::{Point3, Scale3, Translation3, UnitQuaternion, Vector2, Vector3};
 use std::rc::Rc;
 
+use super::animation::{Animation, Interpolation};
 use super::camera::CameraParameters;
 
+pub type SceneConstructor = fn() -> Scene;
+
 #[derive(Default)]
 pub struct Scene {
     pub camera_parameters: CameraParameters,
-    pub objects: TransformableMeshList,
-    pub materials: MaterialList,
+    pub objects: SceneGraph,
+    pub materials: MaterialRegistry,
+    pub animation: Animation,
 }
 
 impl Scene {
+    // 所有内置场景及其名称
+    pub const BUILT_IN: [(&'static str, SceneConstructor); 14] = [
+        ("quad", Scene::scene_quad),
+        ("primitives", Scene::scene_primitives),
+        ("light", Scene::scene_light),
+        ("light_huge", Scene::scene_light_huge),
+        ("cornell_box", Scene::scene_cornell_box),
+        ("principled", Scene::scene_principled),
+        ("normal_map", Scene::scene_normal_map),
+        ("alpha_mask", Scene::scene_alpha_mask),
+        ("bouncing_spheres", Scene::scene_bouncing_spheres),
+        ("shapes", Scene::scene_shapes),
+        ("sdf", Scene::scene_sdf),
+        ("csg", Scene::scene_csg),
+        ("curves", Scene::scene_curves),
+        ("terrain", Scene::scene_terrain),
+    ];
+
+    // 从场景的初始相机位置用 CPU 路径追踪器渲染
+    pub fn render_cpu(
+        &mut self,
+        width: u32,
+        height: u32,
+        samples_per_pixel: u32,
+        max_ray_bounces: u32,
+        sampler_type: SamplerType,
+        seed: u32,
+    ) -> CpuImage {
+        let camera = Camera::new(&self.camera_parameters);
+
+        let mut primitives = Vec::new();
+        let mut important_indices = Vec::new();
+        self.primitives(&mut primitives, &mut important_indices);
+        let scene_data = SceneData::new(&primitives, &important_indices, &self.materials);
+
+        let mut render_context = RenderContext::new(
+            &camera,
+            width,
+            height,
+            samples_per_pixel,
+            max_ray_bounces,
+            important_indices.len() as u32,
+        );
+        render_context.set_sampler(sampler_type, seed);
+
+        CpuPathTracer::new(&scene_data, render_context).render()
+    }
+
+    // 把动画在 time 秒的状态写入相机参数、场景图和材质
+    pub fn animate(&mut self, time: f32) {
+        self.animation.apply(
+            time,
+            &mut self.camera_parameters,
+            &mut self.objects,
+            &mut self.materials,
+        );
+    }
+
     #[allow(unused)]
     pub fn scene_quad() -> Self {
-        let mut materials = MaterialList::default();
+        let mut materials = MaterialRegistry::default();
         let debug_normal = materials.add(Box::new(DebugNormal {}));
 
         let mut objects = TransformableMeshList::new();
@@ -43,18 +118,20 @@ impl Scene {
             defocus_angle: 0.0,
             movement_speed: 1.0,
             rotation_scale: 0.2,
+            ..Default::default()
         };
 
         Self {
             camera_parameters,
-            objects,
+            objects: SceneGraph::from_mesh("quad", objects),
             materials,
+            ..Default::default()
         }
     }
 
     #[allow(unused)]
     pub fn scene_primitives() -> Self {
-        let mut materials = MaterialList::default();
+        let mut materials = MaterialRegistry::default();
         let debug_normal = materials.add(Box::new(DebugNormal {}));
 
         let mut objects = TransformableMeshList::new();
@@ -68,6 +145,16 @@ impl Scene {
         objects.add(cube);
         objects.add(Sphere::new(Point3::new(1.5, 0.0, 0.0), 0.5, debug_normal, false));
 
+        // 压扁再倾斜的椭球
+        let mut ellipsoid = Sphere::new(Point3::origin(), 0.5, debug_normal, false);
+        ellipsoid.scale(Scale3::new(1.6, 0.5, 0.8));
+        ellipsoid.rotate(UnitQuaternion::from_axis_angle(
+            &Vector3::z_axis(),
+            degree_to_radian(20.0),
+        ));
+        ellipsoid.translate(Translation3::new(0.75, -1.1, 0.0));
+        objects.add(ellipsoid);
+
         let camera_parameters = CameraParameters {
             initial_position: Point3::new(0.75, 0.0, 4.0),
             initial_look_at: Point3::new(0.75, 0.0, 0.0),
@@ -77,18 +164,20 @@ impl Scene {
             defocus_angle: 0.0,
             movement_speed: 1.2,
             rotation_scale: 0.2,
+            ..Default::default()
         };
 
         Self {
             camera_parameters,
-            objects,
+            objects: SceneGraph::from_mesh("primitives", objects),
             materials,
+            ..Default::default()
         }
     }
 
     #[allow(unused)]
     pub fn scene_light() -> Self {
-        let mut materials = MaterialList::default();
+        let mut materials = MaterialRegistry::default();
         let lambertian_red = materials.add(Box::new(Lambertian::new(Point3::new(0.65, 0.05, 0.05))));
         info!("{:?}", lambertian_red);
         let lambertian_white = materials.add(Box::new(Lambertian::new(Point3::new(0.73, 0.73, 0.73))));
@@ -134,18 +223,20 @@ impl Scene {
             defocus_angle: 0.0,
             movement_speed: 1.0,
             rotation_scale: 0.2,
+            ..Default::default()
         };
 
         Self {
             camera_parameters,
-            objects,
+            objects: SceneGraph::from_mesh("light", objects),
             materials,
+            ..Default::default()
         }
     }
 
     #[allow(unused)]
     pub fn scene_light_huge() -> Self {
-        let mut materials = MaterialList::default();
+        let mut materials = MaterialRegistry::default();
         let lambertian_red = materials.add(Box::new(Lambertian::new(Point3::new(0.65, 0.05, 0.05))));
         info!("{:?}", lambertian_red);
         let lambertian_white = materials.add(Box::new(Lambertian::new(Point3::new(0.73, 0.73, 0.73))));
@@ -200,18 +291,20 @@ impl Scene {
             defocus_angle: 0.0,
             movement_speed: 100.0,
             rotation_scale: 0.2,
+            ..Default::default()
         };
 
         Self {
             camera_parameters,
-            objects,
+            objects: SceneGraph::from_mesh("light_huge", objects),
             materials,
+            ..Default::default()
         }
     }
 
     #[allow(unused)]
     pub fn scene_cornell_box() -> Self {
-        let mut materials = MaterialList::default();
+        let mut materials = MaterialRegistry::default();
 
         let lambertian_red = materials.add(Box::new(Lambertian::new(Point3::new(0.65, 0.05, 0.05))));
         let lambertian_white = materials.add(Box::new(Lambertian::new(Point3::new(0.73, 0.73, 0.73))));
@@ -219,7 +312,7 @@ impl Scene {
         let light = materials.add(Box::new(DiffuseLight::new(Point3::new(15.0, 15.0, 15.0))));
         let dielectric = materials.add(Box::new(Dielectric::new(1.5)));
 
-        let mut objects = TransformableMeshList::new();
+        let mut walls = TransformableMeshList::new();
 
         // Light
         // objects.add(Quad::new(
@@ -231,35 +324,35 @@ impl Scene {
         // ));
 
         // Cornell box sides
-        objects.add(Quad::new(
+        walls.add(Quad::new(
             Point3::new(5.550, 2.775, 2.775),
             Vector3::new(0.0, 0.0, 5.550),
             Vector3::new(0.0, 5.550, 0.0),
             lambertian_green,
             false,
         ));
-        objects.add(Quad::new(
+        walls.add(Quad::new(
             Point3::new(0.0, 2.775, 2.775),
             Vector3::new(0.0, 0.0, -5.550),
             Vector3::new(0.0, 5.550, 0.0),
             lambertian_red,
             false,
         ));
-        objects.add(Quad::new(
+        walls.add(Quad::new(
             Point3::new(2.775, 5.550, 2.775),
             Vector3::new(5.550, 0.0, 0.0),
             Vector3::new(0.0, 0.0, 5.550),
             lambertian_white,
             false,
         ));
-        objects.add(Quad::new(
+        walls.add(Quad::new(
             Point3::new(2.775, 0.0, 2.775),
             Vector3::new(5.550, 0.0, 0.0),
             Vector3::new(0.0, 0.0, -5.550),
             lambertian_white,
             false,
         ));
-        objects.add(Quad::new(
+        walls.add(Quad::new(
             Point3::new(2.775, 2.775, 5.550),
             Vector3::new(-5.550, 0.0, 0.0),
             Vector3::new(0.0, 5.550, 0.0),
@@ -267,48 +360,944 @@ impl Scene {
             false,
         ));
 
-        objects.add(Sphere::new(Point3::new(1.900, 0.900, 1.900), 0.900, dielectric, true));
+        let mut objects = SceneGraph::new();
+        let cornell = objects.add_node(None, "cornell", Transform::identity());
+        objects.add_mesh(Some(cornell), "walls", walls);
+        objects.add_mesh(
+            Some(cornell),
+            "glass_sphere",
+            Sphere::new(Point3::new(1.900, 0.900, 1.900), 0.900, dielectric, true),
+        );
+        objects.add_mesh(
+            Some(cornell),
+            "light",
+            Quad::new(
+                Point3::new(2.780, 5.540, 2.795),
+                Vector3::new(1.300, 0.0, 0.0),
+                Vector3::new(0.0, 0.0, 1.050),
+                light,
+                true,
+            ),
+        );
+
+        // 方块的一个底角在节点的原点
+        let tall_box = objects.add_node(
+            Some(cornell),
+            "tall_box",
+            Transform {
+                translation: Translation3::new(2.650, 0.0, 2.950),
+                rotation: UnitQuaternion::from_axis_angle(&Vector3::y_axis(), degree_to_radian(15.0)),
+                ..Transform::identity()
+            },
+        );
+        objects.set_mesh(
+            tall_box,
+            TransformableMeshList::cube(
+                Point3::new(0.825, 1.650, 0.825),
+                1.650,
+                3.300,
+                1.650,
+                lambertian_white,
+                false,
+            ),
+        );
+
+        let camera_parameters = CameraParameters {
+            initial_position: Point3::new(2.780, 2.780, -8.000),
+            initial_look_at: Point3::new(2.780, 2.780, 0.0),
+            vfov: 40.0,
+            up: Vector3::y_axis(),
+            focus_distance: 1.0,
+            defocus_angle: 0.0,
+            movement_speed: 2.0,
+            rotation_scale: 0.2,
+            ..Default::default()
+        };
+
+        // 4 秒的演示动画：相机缓慢推近，高方块来回转动，灯光在中间突然变暖。第 0 秒与静态场景相同
+        let mut animation = Animation::default();
+        animation
+            .camera_position
+            .insert(0.0, camera_parameters.initial_position, Interpolation::EASE_IN_OUT)
+            .insert(4.0, Point3::new(3.400, 3.200, -6.500), Interpolation::Linear);
+        let tall_box_transform = objects.node(tall_box).transform();
+        animation
+            .node("cornell/tall_box")
+            .insert(0.0, tall_box_transform, Interpolation::EASE_IN_OUT)
+            .insert(
+                2.0,
+                Transform {
+                    rotation: UnitQuaternion::from_axis_angle(&Vector3::y_axis(), degree_to_radian(-30.0)),
+                    ..tall_box_transform
+                },
+                Interpolation::EASE_IN_OUT,
+            )
+            .insert(4.0, tall_box_transform, Interpolation::Linear);
+        animation
+            .material_color(light, "Color")
+            .insert(0.0, Point3::new(1.0, 1.0, 1.0), Interpolation::Step)
+            .insert(2.0, Point3::new(1.0, 0.72, 0.5), Interpolation::Step);
+
+        Self {
+            camera_parameters,
+            objects,
+            materials,
+            animation,
+        }
+    }
+
+    // 一排 Principled 材质的球：清漆塑料、粗糙金、玻璃和带 sheen 的布料
+    #[allow(unused)]
+    pub fn scene_principled() -> Self {
+        let mut materials = MaterialRegistry::default();
+        let floor = materials.add(Box::new(Lambertian::new(Point3::new(0.5, 0.5, 0.5))));
+        let light = materials.add(Box::new(DiffuseLight::new(Point3::new(8.0, 8.0, 8.0))));
+
+        let plastic = materials.add(Box::new(Principled {
+            clearcoat: 1.0,
+            ..Principled::new(Point3::new(0.7, 0.1, 0.1), 0.0, 0.4)
+        }));
+        let gold = materials.add(Box::new(Principled::new(Point3::new(1.0, 0.71, 0.29), 1.0, 0.3)));
+        let glass = materials.add(Box::new(Principled {
+            transmission: 1.0,
+            ..Principled::new(Point3::new(0.9, 0.95, 1.0), 0.0, 0.0)
+        }));
+        let fabric = materials.add(Box::new(Principled {
+            sheen: 1.0,
+            ..Principled::new(Point3::new(0.1, 0.2, 0.6), 0.0, 0.9)
+        }));
+
+        let mut objects = TransformableMeshList::new();
 
-        // Light
         objects.add(Quad::new(
-            Point3::new(2.780, 5.540, 2.795),
-            Vector3::new(1.300, 0.0, 0.0),
-            Vector3::new(0.0, 0.0, 1.050),
+            Point3::new(0.0, 0.0, 0.0),
+            Vector3::new(8.0, 0.0, 0.0),
+            Vector3::new(0.0, 0.0, -8.0),
+            floor,
+            false,
+        ));
+
+        objects.add(Quad::new(
+            Point3::new(0.0, 3.0, 0.0),
+            Vector3::new(2.0, 0.0, 0.0),
+            Vector3::new(0.0, 0.0, 2.0),
             light,
             true,
         ));
 
-        let mut cube = TransformableMeshList::cube(
-            Point3::new(0.825, 1.650, 0.825),
-            1.650,
-            3.300,
-            1.650,
-            lambertian_white,
+        for (i, material) in [plastic, gold, glass, fabric].into_iter().enumerate() {
+            let x = -1.5 + i as f32;
+            objects.add(Sphere::new(Point3::new(x, 0.45, 0.0), 0.45, material, false));
+        }
+
+        let camera_parameters = CameraParameters {
+            initial_position: Point3::new(0.0, 1.2, 4.0),
+            initial_look_at: Point3::new(0.0, 0.45, 0.0),
+            vfov: 40.0,
+            up: Vector3::y_axis(),
+            focus_distance: 1.0,
+            defocus_angle: 0.0,
+            movement_speed: 1.0,
+            rotation_scale: 0.2,
+            ..Default::default()
+        };
+
+        Self {
+            camera_parameters,
+            objects: SceneGraph::from_mesh("principled", objects),
+            materials,
+            ..Default::default()
+        }
+    }
+
+    // 地面是带有波纹高度图的 Lambertian，球是带有切线空间法线贴图的 Principled，贴图都是程序生成的
+    #[allow(unused)]
+    pub fn scene_normal_map() -> Self {
+        use std::f32::consts::TAU;
+
+        const SIZE: u32 = 128;
+        let ripples = RgbaImage::from_fn(SIZE, SIZE, |x, y| {
+            let (u, v) = (x as f32 / SIZE as f32 - 0.5, y as f32 / SIZE as f32 - 0.5);
+            let h = 0.5 + 0.5 * ((u * u + v * v).sqrt() * TAU * 8.0).cos();
+            let h = (h * 255.0) as u8;
+            Rgba([h, h, h, 255])
+        });
+        // h = sin(2π·6u) sin(2π·6v) / (2π·6) 的法线
+        let dimples = RgbaImage::from_fn(SIZE, SIZE, |x, y| {
+            let (u, v) = (x as f32 / SIZE as f32 * TAU * 6.0, y as f32 / SIZE as f32 * TAU * 6.0);
+            let n = Vector3::new(-0.5 * u.cos() * v.sin(), -0.5 * u.sin() * v.cos(), 1.0).normalize();
+            let encode = |c: f32| ((c * 0.5 + 0.5) * 255.0).round() as u8;
+            Rgba([encode(n.x), encode(n.y), encode(n.z), 255])
+        });
+
+        let mut materials = MaterialRegistry::default();
+        let floor = materials.add_with_normal_map(
+            Box::new(Lambertian::new(Point3::new(0.6, 0.6, 0.6))),
+            NormalMap::Bump {
+                texture: Texture::from_image(&ripples),
+                scale: 0.01,
+            },
+        );
+        let ball = materials.add_with_normal_map(
+            Box::new(Principled::new(Point3::new(0.8, 0.3, 0.1), 0.0, 0.3)),
+            NormalMap::TangentSpace {
+                texture: Texture::from_image(&dimples),
+                strength: 1.0,
+            },
+        );
+        let light = materials.add(Box::new(DiffuseLight::new(Point3::new(8.0, 8.0, 8.0))));
+
+        let mut objects = TransformableMeshList::new();
+
+        objects.add(Quad::new(
+            Point3::new(0.0, 0.0, 0.0),
+            Vector3::new(4.0, 0.0, 0.0),
+            Vector3::new(0.0, 0.0, -4.0),
+            floor,
             false,
+        ));
+
+        objects.add(Quad::new(
+            Point3::new(-1.0, 2.5, 1.0),
+            Vector3::new(1.0, 0.0, 0.0),
+            Vector3::new(0.0, 0.0, 1.0),
+            light,
+            true,
+        ));
+
+        objects.add(Sphere::new(Point3::new(0.0, 0.6, 0.0), 0.6, ball, false));
+
+        let camera_parameters = CameraParameters {
+            initial_position: Point3::new(0.0, 1.5, 3.0),
+            initial_look_at: Point3::new(0.0, 0.4, 0.0),
+            vfov: 40.0,
+            up: Vector3::y_axis(),
+            focus_distance: 1.0,
+            defocus_angle: 0.0,
+            movement_speed: 1.0,
+            rotation_scale: 0.2,
+            ..Default::default()
+        };
+
+        Self {
+            camera_parameters,
+            objects: SceneGraph::from_mesh("normal_map", objects),
+            materials,
+            ..Default::default()
+        }
+    }
+
+    // 铁丝网和树叶用阈值镂空，右边的方块的透明度从左到右由 0 渐变到 1，用随机透明表现
+    #[allow(unused)]
+    pub fn scene_alpha_mask() -> Self {
+        const SIZE: u32 = 128;
+        let chain_link = RgbaImage::from_fn(SIZE, SIZE, |x, y| {
+            // 两组斜线组成的菱形网格
+            let (u, v) = (x as f32 / SIZE as f32 * 12.0, y as f32 / SIZE as f32 * 8.0);
+            let distance = |t: f32| (t - t.round()).abs();
+            let wire = distance(u + v) < 0.08 || distance(u - v) < 0.08;
+            Rgba([128, 128, 128, if wire { 255 } else { 0 }])
+        });
+        let leaf = RgbaImage::from_fn(SIZE, SIZE, |x, y| {
+            let (u, v) = (x as f32 / SIZE as f32, y as f32 / SIZE as f32);
+            let half_width = 0.35 * (v * std::f32::consts::PI).sin();
+            let opaque = (u - 0.5).abs() < half_width && (u - 0.5).abs() > 0.01;
+            Rgba([64, 160, 64, if opaque { 255 } else { 0 }])
+        });
+        let gradient = RgbaImage::from_fn(SIZE, 1, |x, _| Rgba([255, 255, 255, (x * 255 / (SIZE - 1)) as u8]));
+
+        let mut materials = MaterialRegistry::default();
+        let floor = materials.add(Box::new(Lambertian::new(Point3::new(0.7, 0.7, 0.7))));
+        let fence = materials.add_with_maps(
+            Box::new(Lambertian::new(Point3::new(0.5, 0.5, 0.5))),
+            SurfaceMaps {
+                alpha_mask: Some(AlphaMask {
+                    texture: Texture::from_image(&chain_link),
+                    mode: AlphaMode::Cutoff(0.5),
+                }),
+                ..Default::default()
+            },
         );
-        cube.rotate(UnitQuaternion::from_axis_angle(
+        let leaf = materials.add_with_maps(
+            Box::new(Lambertian::new(Point3::new(0.2, 0.5, 0.15))),
+            SurfaceMaps {
+                alpha_mask: Some(AlphaMask {
+                    texture: Texture::from_image(&leaf),
+                    mode: AlphaMode::Cutoff(0.5),
+                }),
+                ..Default::default()
+            },
+        );
+        let translucent = materials.add_with_maps(
+            Box::new(Lambertian::new(Point3::new(0.1, 0.2, 0.7))),
+            SurfaceMaps {
+                alpha_mask: Some(AlphaMask {
+                    texture: Texture::from_image(&gradient),
+                    mode: AlphaMode::Stochastic,
+                }),
+                ..Default::default()
+            },
+        );
+        let red = materials.add(Box::new(Lambertian::new(Point3::new(0.7, 0.15, 0.1))));
+        let light = materials.add(Box::new(DiffuseLight::new(Point3::new(10.0, 10.0, 10.0))));
+
+        let mut objects = TransformableMeshList::new();
+
+        objects.add(Quad::new(
+            Point3::new(0.0, 0.0, 0.0),
+            Vector3::new(4.0, 0.0, 0.0),
+            Vector3::new(0.0, 0.0, -4.0),
+            floor,
+            false,
+        ));
+
+        objects.add(Quad::new(
+            Point3::new(0.0, 3.0, 0.0),
+            Vector3::new(1.0, 0.0, 0.0),
+            Vector3::new(0.0, 0.0, 1.0),
+            light,
+            true,
+        ));
+
+        objects.add(Quad::new(
+            Point3::new(0.0, 0.5, 0.6),
+            Vector3::new(2.4, 0.0, 0.0),
+            Vector3::new(0.0, 1.0, 0.0),
+            fence,
+            false,
+        ));
+
+        objects.add(Quad::new(
+            Point3::new(-0.3, 1.5, -0.3),
+            Vector3::new(0.6, 0.0, 0.0),
+            Vector3::new(0.0, 0.3, -0.9),
+            leaf,
+            false,
+        ));
+
+        objects.add(Quad::new(
+            Point3::new(0.6, 0.4, -0.4),
+            Vector3::new(0.8, 0.0, 0.0),
+            Vector3::new(0.0, 0.8, 0.0),
+            translucent,
+            false,
+        ));
+
+        objects.add(Sphere::new(Point3::new(-0.6, 0.4, -0.5), 0.4, red, false));
+
+        let camera_parameters = CameraParameters {
+            initial_position: Point3::new(0.0, 1.3, 3.5),
+            initial_look_at: Point3::new(0.0, 0.6, 0.0),
+            vfov: 40.0,
+            up: Vector3::y_axis(),
+            focus_distance: 1.0,
+            defocus_angle: 0.0,
+            movement_speed: 1.0,
+            rotation_scale: 0.2,
+            ..Default::default()
+        };
+
+        Self {
+            camera_parameters,
+            objects: SceneGraph::from_mesh("alpha_mask", objects),
+            materials,
+            ..Default::default()
+        }
+    }
+    // 《Ray Tracing: The Next Week》第 2 章的弹跳小球，漫反射小球在快门打开期间向上运动
+    #[allow(unused)]
+    pub fn scene_bouncing_spheres() -> Self {
+        // 固定种子的伪随机数，保证每次构造出的场景相同
+        let mut state = 0u32;
+        let mut random = || {
+            state += 1;
+            u32_to_unit_float(hash_u32(state))
+        };
+
+        let mut materials = MaterialRegistry::default();
+        let ground = materials.add(Box::new(Lambertian::new(Point3::new(0.5, 0.5, 0.5))));
+        let sky = materials.add(Box::new(DiffuseLight::new(Point3::new(0.9, 1.0, 1.2))));
+
+        let mut objects = TransformableMeshList::new();
+
+        objects.add(Quad::new(
+            Point3::new(0.0, 0.0, 0.0),
+            Vector3::new(100.0, 0.0, 0.0),
+            Vector3::new(0.0, 0.0, -100.0),
+            ground,
+            false,
+        ));
+
+        // 场景中没有天空，用头顶的大面积光源代替
+        objects.add(Quad::new(
+            Point3::new(0.0, 12.0, 0.0),
+            Vector3::new(40.0, 0.0, 0.0),
+            Vector3::new(0.0, 0.0, 40.0),
+            sky,
+            true,
+        ));
+
+        for a in -11..11 {
+            for b in -11..11 {
+                let choose = random();
+                let center = Point3::new(a as f32 + 0.9 * random(), 0.2, b as f32 + 0.9 * random());
+                if (center - Point3::new(4.0, 0.2, 0.0)).norm() <= 0.9 {
+                    continue;
+                }
+
+                if choose < 0.8 {
+                    let albedo = Point3::new(random() * random(), random() * random(), random() * random());
+                    let material = materials.add(Box::new(Lambertian::new(albedo)));
+                    let bounce = Translation3::new(0.0, 0.5 * random(), 0.0);
+                    objects.add(Moving::new(
+                        Sphere::new(center, 0.2, material, false),
+                        Motion::translation(bounce),
+                    ));
+                } else if choose < 0.95 {
+                    let albedo = Point3::new(0.5 + 0.5 * random(), 0.5 + 0.5 * random(), 0.5 + 0.5 * random());
+                    let roughness = (0.5 * random()).max(0.05);
+                    let material = materials.add(Box::new(Principled::new(albedo, 1.0, roughness)));
+                    objects.add(Sphere::new(center, 0.2, material, false));
+                } else {
+                    let material = materials.add(Box::new(Dielectric::new(1.5)));
+                    objects.add(Sphere::new(center, 0.2, material, false));
+                }
+            }
+        }
+
+        let glass = materials.add(Box::new(Dielectric::new(1.5)));
+        objects.add(Sphere::new(Point3::new(0.0, 1.0, 0.0), 1.0, glass, false));
+
+        let diffuse = materials.add(Box::new(Lambertian::new(Point3::new(0.4, 0.2, 0.1))));
+        objects.add(Sphere::new(Point3::new(-4.0, 1.0, 0.0), 1.0, diffuse, false));
+
+        let metal = materials.add(Box::new(Principled::new(Point3::new(0.7, 0.6, 0.5), 1.0, 0.05)));
+        objects.add(Sphere::new(Point3::new(4.0, 1.0, 0.0), 1.0, metal, false));
+
+        let camera_parameters = CameraParameters {
+            initial_position: Point3::new(13.0, 2.0, 3.0),
+            initial_look_at: Point3::new(0.0, 0.0, 0.0),
+            vfov: 20.0,
+            up: Vector3::y_axis(),
+            focus_distance: 10.0,
+            defocus_angle: 0.6,
+            movement_speed: 1.0,
+            rotation_scale: 0.2,
+            shutter_open: 0.0,
+            shutter_close: 1.0,
+            ..Default::default()
+        };
+
+        Self {
+            camera_parameters,
+            objects: SceneGraph::from_mesh("bouncing_spheres", objects),
+            materials,
+            ..Default::default()
+        }
+    }
+    // 圆盘光源照亮的各种解析形状
+    #[allow(unused)]
+    pub fn scene_shapes() -> Self {
+        let mut materials = MaterialRegistry::default();
+        let floor = materials.add(Box::new(Lambertian::new(Point3::new(0.5, 0.5, 0.5))));
+        let light = materials.add(Box::new(DiffuseLight::new(Point3::new(10.0, 10.0, 10.0))));
+        let red = materials.add(Box::new(Lambertian::new(Point3::new(0.65, 0.05, 0.05))));
+        let gold = materials.add(Box::new(Principled::new(Point3::new(1.0, 0.71, 0.29), 1.0, 0.3)));
+        let blue = materials.add(Box::new(Principled {
+            clearcoat: 1.0,
+            ..Principled::new(Point3::new(0.1, 0.2, 0.6), 0.0, 0.4)
+        }));
+        let glass = materials.add(Box::new(Dielectric::new(1.5)));
+        let green = materials.add(Box::new(Lambertian::new(Point3::new(0.12, 0.45, 0.15))));
+
+        let mut objects = TransformableMeshList::new();
+
+        objects.add(Quad::new(
+            Point3::new(0.0, 0.0, 0.0),
+            Vector3::new(8.0, 0.0, 0.0),
+            Vector3::new(0.0, 0.0, -8.0),
+            floor,
+            false,
+        ));
+
+        // 圆盘的法线默认朝上，翻转后朝向地面
+        let mut disk = Shape::new(ShapeKind::Disk { radius: 0.8 }, Point3::origin(), light, true);
+        disk.rotate(UnitQuaternion::from_axis_angle(
+            &Vector3::x_axis(),
+            degree_to_radian(180.0),
+        ));
+        disk.translate(Translation3::new(0.0, 3.0, 0.0));
+        objects.add(disk);
+
+        objects.add(Shape::new(
+            ShapeKind::Cylinder {
+                radius: 0.35,
+                height: 0.9,
+            },
+            Point3::new(-1.6, 0.45, 0.0),
+            red,
+            false,
+        ));
+
+        objects.add(Shape::new(
+            ShapeKind::Cone {
+                radius: 0.4,
+                height: 0.9,
+            },
+            Point3::new(-0.55, 0.45, 0.3),
+            gold,
+            false,
+        ));
+
+        let mut torus = Shape::new(
+            ShapeKind::Torus {
+                major_radius: 0.35,
+                minor_radius: 0.12,
+            },
+            Point3::origin(),
+            blue,
+            false,
+        );
+        torus.rotate(UnitQuaternion::from_axis_angle(
+            &Vector3::x_axis(),
+            degree_to_radian(60.0),
+        ));
+        torus.translate(Translation3::new(0.5, 0.45, 0.0));
+        objects.add(torus);
+
+        let mut glass_box = Shape::new(
+            ShapeKind::Box {
+                size: Vector3::new(0.6, 0.6, 0.6),
+            },
+            Point3::origin(),
+            glass,
+            false,
+        );
+        glass_box.rotate(UnitQuaternion::from_axis_angle(
             &Vector3::y_axis(),
-            degree_to_radian(15.0),
+            degree_to_radian(30.0),
         ));
-        cube.translate(Translation3::new(2.650, 0.0, 2.950));
+        glass_box.translate(Translation3::new(1.6, 0.3, 0.2));
+        objects.add(glass_box);
 
-        objects.add(cube);
+        let mut green_box = Shape::new(
+            ShapeKind::Box {
+                size: Vector3::new(0.4, 0.2, 0.8),
+            },
+            Point3::origin(),
+            green,
+            false,
+        );
+        green_box.rotate(UnitQuaternion::from_axis_angle(
+            &Vector3::y_axis(),
+            degree_to_radian(-20.0),
+        ));
+        green_box.translate(Translation3::new(0.9, 0.1, 1.0));
+        objects.add(green_box);
 
         let camera_parameters = CameraParameters {
-            initial_position: Point3::new(2.780, 2.780, -8.000),
-            initial_look_at: Point3::new(2.780, 2.780, 0.0),
+            initial_position: Point3::new(0.0, 1.6, 4.5),
+            initial_look_at: Point3::new(0.0, 0.4, 0.0),
             vfov: 40.0,
             up: Vector3::y_axis(),
             focus_distance: 1.0,
             defocus_angle: 0.0,
-            movement_speed: 2.0,
+            movement_speed: 1.0,
             rotation_scale: 0.2,
+            ..Default::default()
         };
 
         Self {
             camera_parameters,
-            objects,
+            objects: SceneGraph::from_mesh("shapes", objects),
+            materials,
+            ..Default::default()
+        }
+    }
+    // 用球面追踪渲染的有符号距离场，包括平滑布尔运算
+    #[allow(unused)]
+    pub fn scene_sdf() -> Self {
+        let mut materials = MaterialRegistry::default();
+        let floor = materials.add(Box::new(Lambertian::new(Point3::new(0.5, 0.5, 0.5))));
+        let light = materials.add(Box::new(DiffuseLight::new(Point3::new(8.0, 8.0, 8.0))));
+        let red = materials.add(Box::new(Lambertian::new(Point3::new(0.65, 0.05, 0.05))));
+        let gold = materials.add(Box::new(Principled::new(Point3::new(1.0, 0.71, 0.29), 1.0, 0.3)));
+        let blue = materials.add(Box::new(Principled {
+            clearcoat: 1.0,
+            ..Principled::new(Point3::new(0.1, 0.2, 0.6), 0.0, 0.4)
+        }));
+        let green = materials.add(Box::new(Lambertian::new(Point3::new(0.12, 0.45, 0.15))));
+
+        let mut objects = TransformableMeshList::new();
+
+        objects.add(Quad::new(
+            Point3::new(0.0, 0.0, 0.0),
+            Vector3::new(8.0, 0.0, 0.0),
+            Vector3::new(0.0, 0.0, -8.0),
+            floor,
+            false,
+        ));
+
+        objects.add(Quad::new(
+            Point3::new(0.0, 3.0, 0.0),
+            Vector3::new(2.0, 0.0, 0.0),
+            Vector3::new(0.0, 0.0, 2.0),
+            light,
+            true,
+        ));
+
+        // 长方体与球的交集挖去三个方向的圆柱，构造实体几何的经典例子
+        let quarter = |axis| UnitQuaternion::from_axis_angle(&axis, degree_to_radian(90.0));
+        let drill = || SdfNode::Capsule {
+            radius: 0.18,
+            height: 1.0,
+        };
+        let csg = SdfNode::Box {
+            size: Vector3::new(0.6, 0.6, 0.6),
+        }
+        .intersect(SdfNode::Sphere { radius: 0.4 }, 0.0)
+        .subtract(
+            drill()
+                .union(drill().rotated(quarter(Vector3::x_axis())), 0.0)
+                .union(drill().rotated(quarter(Vector3::z_axis())), 0.0),
+            0.0,
+        );
+        objects.add(Sdf::new(&csg, Point3::new(-1.5, 0.3, 0.0), red));
+
+        // 平滑并集把几个球融合在一起
+        let blob = SdfNode::Sphere { radius: 0.3 }
+            .union(
+                SdfNode::Sphere { radius: 0.2 }.translated(Translation3::new(0.3, 0.25, 0.0)),
+                0.25,
+            )
+            .union(
+                SdfNode::Sphere { radius: 0.15 }.translated(Translation3::new(-0.2, 0.3, 0.15)),
+                0.25,
+            );
+        objects.add(Sdf::new(&blob, Point3::new(-0.45, 0.3, 0.3), gold));
+
+        // 圆角长方体平滑地挖去一个球
+        let carved = SdfNode::RoundedBox {
+            size: Vector3::new(0.6, 0.6, 0.6),
+            radius: 0.08,
+        }
+        .subtract(
+            SdfNode::Sphere { radius: 0.3 }.translated(Translation3::new(0.0, 0.3, 0.0)),
+            0.05,
+        );
+        let mut carved = Sdf::new(&carved, Point3::origin(), blue);
+        carved.rotate(UnitQuaternion::from_axis_angle(
+            &Vector3::y_axis(),
+            degree_to_radian(30.0),
+        ));
+        carved.translate(Translation3::new(0.6, 0.3, 0.0));
+        objects.add(carved);
+
+        // 竖立的环面平滑地连接在一个小球上，再在水平方向上拉宽
+        let torus = SdfNode::Torus {
+            major_radius: 0.25,
+            minor_radius: 0.08,
+        }
+        .rotated(quarter(Vector3::x_axis()))
+        .translated(Translation3::new(0.0, 0.33, 0.0))
+        .union(SdfNode::Sphere { radius: 0.08 }, 0.1);
+        let mut torus = Sdf::new(&torus, Point3::origin(), green);
+        torus.scale(Scale3::new(1.5, 1.0, 1.5));
+        torus.translate(Translation3::new(1.6, 0.08, 0.3));
+        objects.add(torus);
+
+        let camera_parameters = CameraParameters {
+            initial_position: Point3::new(0.0, 1.6, 4.5),
+            initial_look_at: Point3::new(0.0, 0.4, 0.0),
+            vfov: 40.0,
+            up: Vector3::y_axis(),
+            focus_distance: 1.0,
+            defocus_angle: 0.0,
+            movement_speed: 1.0,
+            rotation_scale: 0.2,
+            ..Default::default()
+        };
+
+        Self {
+            camera_parameters,
+            objects: SceneGraph::from_mesh("sdf", objects),
+            materials,
+            ..Default::default()
+        }
+    }
+
+    #[allow(unused)]
+    pub fn scene_csg() -> Self {
+        let mut materials = MaterialRegistry::default();
+        let floor = materials.add(Box::new(Lambertian::new(Point3::new(0.5, 0.5, 0.5))));
+        let light = materials.add(Box::new(DiffuseLight::new(Point3::new(8.0, 8.0, 8.0))));
+        let glass = materials.add(Box::new(Dielectric::new(1.5)));
+        let red = materials.add(Box::new(Lambertian::new(Point3::new(0.65, 0.05, 0.05))));
+        let gold = materials.add(Box::new(Principled::new(Point3::new(1.0, 0.71, 0.29), 1.0, 0.3)));
+
+        let mut objects = TransformableMeshList::new();
+
+        objects.add(Quad::new(
+            Point3::new(0.0, 0.0, 0.0),
+            Vector3::new(8.0, 0.0, 0.0),
+            Vector3::new(0.0, 0.0, -8.0),
+            floor,
+            false,
+        ));
+
+        objects.add(Quad::new(
+            Point3::new(0.0, 3.0, 0.0),
+            Vector3::new(2.0, 0.0, 0.0),
+            Vector3::new(0.0, 0.0, 2.0),
+            light,
+            true,
+        ));
+
+        // 两个球的交集是双凸透镜，侧过来露出轮廓
+        let lens = CsgNode::Sphere { radius: 0.8 }
+            .translated(Translation3::new(0.0, 0.0, -0.6))
+            .intersect(CsgNode::Sphere { radius: 0.8 }.translated(Translation3::new(0.0, 0.0, 0.6)));
+        let mut lens = Csg::new(lens, Point3::origin(), glass);
+        lens.rotate(UnitQuaternion::from_axis_angle(
+            &Vector3::y_axis(),
+            degree_to_radian(60.0),
+        ));
+        lens.translate(Translation3::new(-1.3, 0.55, 0.0));
+        objects.add(lens);
+
+        // 长方体的一角被球咬去一口
+        let bitten = CsgNode::Box {
+            size: Vector3::new(0.8, 0.8, 0.8),
+        }
+        .subtract(CsgNode::Sphere { radius: 0.45 }.translated(Translation3::new(0.4, 0.4, 0.4)));
+        let mut bitten = Csg::new(bitten, Point3::origin(), red);
+        bitten.rotate(UnitQuaternion::from_axis_angle(
+            &Vector3::y_axis(),
+            degree_to_radian(-20.0),
+        ));
+        bitten.translate(Translation3::new(0.1, 0.4, 0.0));
+        objects.add(bitten);
+
+        // 圆柱挖去一个细圆柱成为管子，再与一个压扁的球合并成带法兰的接头
+        let pipe = CsgNode::Cylinder {
+            radius: 0.3,
+            height: 0.7,
+        }
+        .union(CsgNode::Sphere { radius: 0.45 }.scaled(Scale3::new(1.0, 0.2, 1.0)))
+        .subtract(CsgNode::Cylinder {
+            radius: 0.18,
+            height: 0.8,
+        });
+        let mut pipe = Csg::new(pipe, Point3::origin(), gold);
+        pipe.rotate(UnitQuaternion::from_axis_angle(
+            &Vector3::x_axis(),
+            degree_to_radian(70.0),
+        ));
+        pipe.translate(Translation3::new(1.4, 0.45, 0.0));
+        objects.add(pipe);
+
+        let camera_parameters = CameraParameters {
+            initial_position: Point3::new(0.0, 1.6, 4.5),
+            initial_look_at: Point3::new(0.0, 0.4, 0.0),
+            vfov: 40.0,
+            up: Vector3::y_axis(),
+            focus_distance: 1.0,
+            defocus_angle: 0.0,
+            movement_speed: 1.0,
+            rotation_scale: 0.2,
+            ..Default::default()
+        };
+
+        Self {
+            camera_parameters,
+            objects: SceneGraph::from_mesh("csg", objects),
+            materials,
+            ..Default::default()
+        }
+    }
+
+    #[allow(unused)]
+    pub fn scene_curves() -> Self {
+        // 一缕头发，从原点向上弯出再垂下，按曲线文件的格式写出
+        const LOCK: &str = "
+            tube
+            0.0  0.0   0.0 0.014
+            0.02 0.15  0.0 0.013
+            0.08 0.3   0.0 0.012
+            0.18 0.38  0.0 0.011 # 与前后两个控制点共线，保证曲线光滑
+            0.28 0.46  0.0 0.010
+            0.4  0.44  0.0 0.009
+            0.5  0.36  0.0 0.008
+            0.6  0.28  0.0 0.007
+            0.66 0.05  0.0 0.005
+            0.68 -0.2  0.0 0.003
+        ";
+
+        let mut state = 0;
+        let mut random = || {
+            state += 1;
+            u32_to_unit_float(hash_u32(state))
+        };
+
+        let mut materials = MaterialRegistry::default();
+        let floor = materials.add(Box::new(Lambertian::new(Point3::new(0.35, 0.28, 0.2))));
+        let light = materials.add(Box::new(DiffuseLight::new(Point3::new(8.0, 8.0, 8.0))));
+        let grass = materials.add(Box::new(Lambertian::new(Point3::new(0.2, 0.5, 0.12))));
+        let ball = materials.add(Box::new(Lambertian::new(Point3::new(0.7, 0.6, 0.5))));
+        let hair = materials.add(Box::new(Hair::new(Point3::new(0.45, 0.25, 0.1), 0.35, 0.25)));
+
+        let mut objects = TransformableMeshList::new();
+
+        objects.add(Quad::new(
+            Point3::new(0.0, 0.0, 0.0),
+            Vector3::new(8.0, 0.0, 0.0),
+            Vector3::new(0.0, 0.0, -8.0),
+            floor,
+            false,
+        ));
+
+        objects.add(Quad::new(
+            Point3::new(0.0, 3.0, 0.0),
+            Vector3::new(2.0, 0.0, 0.0),
+            Vector3::new(0.0, 0.0, 2.0),
+            light,
+            true,
+        ));
+
+        // 一片草地，每片草叶是一段向随机方向弯曲、逐渐变尖的条带
+        for _ in 0..250 {
+            let base = Point3::new(-2.2 + 1.9 * random(), 0.0, -1.2 + 2.0 * random());
+            let height = 0.3 + 0.3 * random();
+            let angle = 2.0 * std::f32::consts::PI * random();
+            let lean = (0.1 + 0.2 * random()) * height * Vector3::new(angle.cos(), 0.0, angle.sin());
+            let control_points = vec![
+                base,
+                base + Vector3::new(0.0, height / 3.0, 0.0),
+                base + 0.4 * lean + Vector3::new(0.0, 2.0 * height / 3.0, 0.0),
+                base + lean + Vector3::new(0.0, 0.95 * height, 0.0),
+            ];
+            objects.add(Curve::new(
+                CurveKind::Ribbon,
+                control_points,
+                vec![0.05, 0.04, 0.025, 0.0],
+                grass,
+            ));
+        }
+
+        // 球顶上长出的一簇头发，每一缕是同一条曲线绕竖直轴随机旋转、缩放后的副本
+        objects.add(Sphere::new(Point3::new(1.0, 0.3, 0.0), 0.3, ball, false));
+        for _ in 0..200 {
+            let angle = 2.0 * std::f32::consts::PI * random();
+            let size = 0.7 + 0.4 * random();
+            let offset = 0.08 * random().sqrt();
+            for mut lock in parse_curves(LOCK, hair) {
+                lock.scale(Scale3::new(size, size, size));
+                lock.rotate(UnitQuaternion::from_axis_angle(&Vector3::y_axis(), angle));
+                lock.translate(Translation3::new(
+                    1.0 + offset * angle.cos(),
+                    0.59,
+                    -offset * angle.sin(),
+                ));
+                objects.add(lock);
+            }
+        }
+
+        let camera_parameters = CameraParameters {
+            initial_position: Point3::new(0.0, 1.6, 4.5),
+            initial_look_at: Point3::new(0.0, 0.4, 0.0),
+            vfov: 40.0,
+            up: Vector3::y_axis(),
+            focus_distance: 1.0,
+            defocus_angle: 0.0,
+            movement_speed: 1.0,
+            rotation_scale: 0.2,
+            ..Default::default()
+        };
+
+        Self {
+            camera_parameters,
+            objects: SceneGraph::from_mesh("curves", objects),
+            materials,
+            ..Default::default()
+        }
+    }
+    #[allow(unused)]
+    pub fn scene_terrain() -> Self {
+        const SAMPLES: u32 = 129;
+
+        // 几道起伏叠加一座山峰，量化为 16 位灰度图，和从 PNG 读入的高度图一样
+        let heightmap = ImageBuffer::from_fn(SAMPLES, SAMPLES, |x, y| {
+            let u = x as f32 / (SAMPLES - 1) as f32 * 6.0 - 3.0;
+            let v = y as f32 / (SAMPLES - 1) as f32 * 6.0 - 3.0;
+            let hills = 0.5 * (1.3 * u + 0.4).sin() * (1.1 * v).cos()
+                + 0.25 * (3.1 * u + 2.0 * v).sin()
+                + 0.1 * (7.0 * u - 5.0 * v + 1.0).sin();
+            let peak = 2.2 * (-((u - 0.6).powi(2) + (v + 0.8).powi(2)) / 1.2).exp();
+            let height = ((hills + peak + 0.9) / 3.6).clamp(0.0, 1.0);
+            Luma([(height * u16::MAX as f32) as u16])
+        });
+
+        let mut state = 0;
+        let speckle = RgbaImage::from_fn(64, 64, |_, _| {
+            state += 1;
+            let c = (180.0 + 75.0 * u32_to_unit_float(hash_u32(state))) as u8;
+            Rgba([c, c, c, 255])
+        });
+
+        let mut materials = MaterialRegistry::default();
+        let light = materials.add(Box::new(DiffuseLight::new(Point3::new(6.0, 6.0, 6.0))));
+        let terrain = materials.add(Box::new(Terrain::new(vec![
+            // 陡峭处露出的岩石
+            TerrainLayer::new(Point3::new(0.3, 0.26, 0.22)),
+            // 低处平缓的地方是草地，带一点斑驳的纹理
+            TerrainLayer::new(Point3::new(0.18, 0.36, 0.08))
+                .heights(f32::MIN, 0.9)
+                .slopes(f32::MIN, 30.0)
+                .texture(Texture::from_image(&speckle), 12.0),
+            // 谷底的沙地
+            TerrainLayer::new(Point3::new(0.6, 0.52, 0.35))
+                .heights(f32::MIN, 0.25)
+                .slopes(f32::MIN, 20.0),
+            // 高处不太陡的地方积雪
+            TerrainLayer::new(Point3::new(0.9, 0.9, 0.95))
+                .heights(1.15, f32::MAX)
+                .slopes(f32::MIN, 45.0),
+        ])));
+
+        let mut objects = TransformableMeshList::new();
+
+        objects.add(Heightfield::from_image(
+            &DynamicImage::ImageLuma16(heightmap),
+            Vector2::new(6.0, 6.0),
+            1.6,
+            terrain,
+        ));
+
+        objects.add(Quad::new(
+            Point3::new(0.0, 4.0, 0.0),
+            Vector3::new(4.0, 0.0, 0.0),
+            Vector3::new(0.0, 0.0, 4.0),
+            light,
+            true,
+        ));
+
+        let camera_parameters = CameraParameters {
+            initial_position: Point3::new(0.0, 2.8, 5.5),
+            initial_look_at: Point3::new(0.0, 0.4, 0.0),
+            vfov: 45.0,
+            up: Vector3::y_axis(),
+            focus_distance: 1.0,
+            defocus_angle: 0.0,
+            movement_speed: 1.0,
+            rotation_scale: 0.2,
+            ..Default::default()
+        };
+
+        Self {
+            camera_parameters,
+            objects: SceneGraph::from_mesh("terrain", objects),
             materials,
+            ..Default::default()
         }
     }
 }
@@ -18,4 +18,5 @@ lazy_static! {
         include_bytes!("../asset/font/SourceHanSansCN-Medium.otf");
     static ref FONT_SOURCE_HANS_SANS_CN_MEDIUM_NAME: &'static str = "SourceHanSansCN-Medium";
     static ref RAY_TRACING_SHADER: &'static str = include_str!("shader/ray_tracing.wgsl");
+    static ref BVH_REFIT_SHADER: &'static str = include_str!("shader/bvh_refit.wgsl");
 }
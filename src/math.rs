@@ -8,7 +8,6 @@ pub fn degree_to_radian(degree: f32) -> f32 {
     degree * std::f32::consts::PI / 180f32
 }
 
-#[allow(unused)]
 pub fn radian_to_degree(radian: f32) -> f32 {
     radian * 180f32 / std::f32::consts::PI
 }
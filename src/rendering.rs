@@ -1,11 +1,14 @@
 pub mod bounding_box;
 pub mod bvh;
 pub mod configuration;
+pub mod cpu;
 pub mod wgpu;
-mod interval;
+pub mod interval;
+pub mod layout;
 pub mod material;
 pub mod mesh;
 pub mod primitive;
+pub mod scene_data;
 mod vertex;
 
 #[allow(unused)]
@@ -1,4 +1,5 @@
 use crate::rendering::interval::Interval;
+use crate::rendering::layout::wgsl_layout;
 use bytemuck::{Pod, Zeroable};
 use nalgebra::Point3;
 
@@ -8,6 +9,8 @@ pub struct BoundingBox {
     xyz: [Interval; 3],
 }
 
+wgsl_layout!(BoundingBox, "BoundingBox", [xyz]);
+
 impl BoundingBox {
     pub const fn empty() -> Self {
         const EMPTY: BoundingBox = BoundingBox {
@@ -90,6 +93,11 @@ impl BoundingBox {
         }
     }
 
+    pub fn surface_area(&self) -> f32 {
+        let (x, y, z) = (self.x().size(), self.y().size(), self.z().size());
+        2.0 * (x * y + y * z + z * x)
+    }
+
     pub fn axis(&self, n: i32) -> &Interval {
         if n == 1 {
             self.y()
@@ -1,3 +1,4 @@
+use crate::rendering::layout::wgsl_layout;
 use crate::rendering::primitive::PrimitiveData;
 use bytemuck::{Pod, Zeroable};
 use std::rc::Rc;
@@ -14,6 +15,18 @@ pub struct BvhNode {
     pub bounding_box: BoundingBox,
 }
 
+wgsl_layout!(
+    BvhNode,
+    "BvhNode",
+    [
+        left_or_primitive_type,
+        right_or_primitive_id,
+        parent,
+        is_leaf,
+        bounding_box as "box",
+    ]
+);
+
 pub struct BvhBuildingEntry {
     pub primitive: Rc<PrimitiveData>,
     pub primitive_type: u32,
@@ -79,3 +92,125 @@ pub fn build_bvh_tree(
 
     id as u32
 }
+
+// 表面积启发式中一次遍历和一次求交的相对代价
+const SAH_TRAVERSAL_COST: f32 = 1.0;
+const SAH_INTERSECTION_COST: f32 = 1.0;
+
+// 重新拟合后的 SAH 代价超过完整构建时的这个倍数，就重新构建
+pub const BVH_REFIT_MAX_COST_RATIO: f32 = 1.5;
+
+// 光线击中节点的概率与节点包围盒的表面积成正比，按根节点的表面积归一化
+pub fn bvh_sah_cost(tree: &[BvhNode]) -> f32 {
+    let Some(root) = tree.first() else {
+        return 0.0;
+    };
+    let root_area = root.bounding_box.surface_area();
+    if root_area <= 0.0 {
+        return 0.0;
+    }
+
+    let cost: f32 = tree
+        .iter()
+        .map(|node| {
+            let cost = if node.is_leaf == 1 {
+                SAH_INTERSECTION_COST
+            } else {
+                SAH_TRAVERSAL_COST
+            };
+            cost * node.bounding_box.surface_area()
+        })
+        .sum();
+    cost / root_area
+}
+
+// 在 GPU 上重新拟合时，内部节点不在 CPU 上计算，无法评估 SAH 代价。每隔这么多次更新在 CPU 上完整拟合一次，
+// 决定是否重新构建
+pub const BVH_GPU_REFIT_CHECK_INTERVAL: u32 = 8;
+
+// 只更新叶节点的包围盒，内部节点由 bvh_refit.wgsl 在 GPU 上合并
+pub fn update_bvh_leaves(tree: &mut [BvhNode], leaf_box: impl Fn(u32, u32) -> BoundingBox) {
+    for node in tree.iter_mut().filter(|node| node.is_leaf == 1) {
+        node.bounding_box = leaf_box(node.left_or_primitive_type, node.right_or_primitive_id);
+    }
+}
+
+// 图元移动或变形但拓扑不变时，从叶节点沿 parent 自底向上重新计算包围盒。
+// 内部节点等所有子节点都到达后才合并，与 GPU 上每个叶节点一个线程的做法相同
+pub fn refit_bvh_tree(tree: &mut [BvhNode], leaf_box: impl Fn(u32, u32) -> BoundingBox) {
+    let mut arrivals = vec![0u8; tree.len()];
+    for leaf in 0..tree.len() {
+        let node = tree[leaf];
+        if node.is_leaf != 1 {
+            continue;
+        }
+        tree[leaf].bounding_box = leaf_box(node.left_or_primitive_type, node.right_or_primitive_id);
+
+        // 根节点的 parent 是它自己
+        let mut child = leaf;
+        while child != 0 {
+            let parent = tree[child].parent as usize;
+            let BvhNode {
+                left_or_primitive_type: left,
+                right_or_primitive_id: right,
+                ..
+            } = tree[parent];
+            arrivals[parent] += 1;
+            if arrivals[parent] < if left == right { 1 } else { 2 } {
+                break;
+            }
+            tree[parent].bounding_box =
+                BoundingBox::new_from_boxes(&tree[left as usize].bounding_box, &tree[right as usize].bounding_box);
+            child = parent;
+        }
+    }
+}
+
+#[derive(Copy, Clone, Default, Debug, PartialEq, Eq)]
+pub enum BvhRefitMode {
+    // 在 CPU 上重新拟合，上传有变化的节点
+    #[default]
+    Cpu,
+    // 只上传叶节点的包围盒，由 bvh_refit.wgsl 在 GPU 上向上传播
+    Gpu,
+}
+
+impl BvhRefitMode {
+    pub const ALL: [BvhRefitMode; 2] = [BvhRefitMode::Cpu, BvhRefitMode::Gpu];
+}
+
+// bvh_refit.wgsl 中 @group(0) 各个绑定的变量名，下标就是 @binding 的值
+pub const BVH_REFIT_BINDINGS: [&str; 4] = ["refit", "refit_leaves", "refit_bounds", "bvh_tree"];
+
+// 与 bvh_refit.wgsl 中的 WORKGROUP_SIZE 一致
+pub const BVH_REFIT_WORKGROUP_SIZE: u32 = 64;
+
+#[repr(C)]
+#[derive(Copy, Clone, Zeroable, Pod, Default, Debug, PartialEq, Eq)]
+pub struct BvhRefitContext {
+    pub leaf_count: u32,
+    pub node_count: u32,
+}
+
+wgsl_layout!(BvhRefitContext, "BvhRefitContext", [leaf_count, node_count]);
+
+#[repr(C)]
+#[derive(Copy, Clone, Zeroable, Pod, Debug)]
+pub struct BvhRefitLeaf {
+    pub node: u32,
+    pub bounding_box: BoundingBox,
+}
+
+wgsl_layout!(BvhRefitLeaf, "BvhRefitLeaf", [node, bounding_box as "box"]);
+
+// GPU 重新拟合的输入，每个叶节点一项
+pub fn bvh_refit_leaves(tree: &[BvhNode]) -> Vec<BvhRefitLeaf> {
+    tree.iter()
+        .enumerate()
+        .filter(|(_, node)| node.is_leaf == 1)
+        .map(|(i, node)| BvhRefitLeaf {
+            node: i as u32,
+            bounding_box: node.bounding_box,
+        })
+        .collect()
+}
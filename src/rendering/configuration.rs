@@ -1,15 +1,81 @@
 use crate::app::camera::Camera;
 use crate::math::degree_to_radian;
+use crate::rendering::layout::wgsl_layout;
 use bytemuck::{Pod, Zeroable};
 use log::info;
-use nalgebra::{Point2, Point3, Vector3};
+use nalgebra::{Point3, Vector3};
+
+#[repr(u32)]
+#[derive(Copy, Clone, Default, Debug, PartialEq, Eq)]
+pub enum AdaptiveSamplingMode {
+    // 每个像素都采样 samples_per_pixel 次
+    #[default]
+    Off,
+    // 已收敛的像素不再采样
+    StopConverged,
+    // 已收敛的像素不再采样，省下的采样数作为全局预算分给未收敛的像素，
+    // 它们可以超过 samples_per_pixel，最多到 REDISTRIBUTED_MAX_SAMPLES_RATIO 倍
+    Redistribute,
+}
+
+// Redistribute 模式下一个像素的采样数最多是 samples_per_pixel 的这个倍数
+pub const REDISTRIBUTED_MAX_SAMPLES_RATIO: u32 = 4;
+
+// ray_tracing.wgsl 中自适应采样的计数器。converged_pixels 每帧清零后重新统计，
+// 另外两个在每次读回后清零，读回之间的调度累加在一起
+#[repr(C)]
+#[derive(Copy, Clone, Zeroable, Pod, Default, Debug)]
+pub struct AdaptiveSamplingCounters {
+    pub converged_pixels: u32,
+    pub freed_samples: u32, // 像素在 samples_per_pixel 之前收敛时省下的采样数
+    pub extra_samples: u32, // 像素超过 samples_per_pixel 的采样数
+}
+
+wgsl_layout!(
+    AdaptiveSamplingCounters,
+    "AdaptiveSamplingCounters",
+    [converged_pixels, freed_samples, extra_samples]
+);
+
+#[repr(u32)]
+#[derive(Copy, Clone, Default, Debug, PartialEq, Eq)]
+pub enum SampleView {
+    #[default]
+    Color,
+    SampleCountHeatmap,
+}
+
+// 路径上每个维度的样本来源
+#[repr(u32)]
+#[derive(Copy, Clone, Default, Debug, PartialEq, Eq)]
+pub enum SamplerType {
+    // Tausworthe 伪随机数，像素内使用 sqrt 网格分层
+    #[default]
+    Independent,
+    // Owen 扰乱的 Sobol 序列
+    Sobol,
+    // 随机旋转的 Halton 序列
+    Halton,
+    // 按维度打乱索引的 rank-1 格点，每个像素的随机旋转取自蓝噪声瓦片，误差在屏幕上呈蓝噪声分布
+    Rank1Lattice,
+}
+
+impl SamplerType {
+    pub const ALL: [SamplerType; 4] = [
+        SamplerType::Independent,
+        SamplerType::Sobol,
+        SamplerType::Halton,
+        SamplerType::Rank1Lattice,
+    ];
+}
 
 #[repr(C)]
 #[derive(Copy, Clone, Zeroable, Pod, Default, Debug)]
 pub struct RenderContext {
     pub width: u32,
     pub height: u32,
-    pub sample_position: Point2<u32>,
+    pub sample_grid_per_dimension: u32,
+    pub adaptive_sampling_mode: u32,
     pub pixel_origin: Point3<f32>, // Location of pixel 0, 0
     pub samples_per_pixel: u32,
     pub pixel_delta_u: Vector3<f32>, // Offset to pixel to the right
@@ -23,9 +89,54 @@ pub struct RenderContext {
     pub camera_position: Point3<f32>,
     pub max_ray_bounces: u32,
     pub important_index_len: u32,
-    _padding: [u32; 3],
+    pub convergence_threshold: f32, // 像素亮度均值的相对标准误差低于该值时视为收敛
+    pub adaptive_min_samples: u32,  // 判断收敛前至少需要的采样数
+    pub sample_view: u32,
+    pub samples_per_dispatch: u32, // 每次调度中每个像素的采样数
+    pub seed: u32,                 // 全局随机种子，相同的种子和设置得到相同的图像
+    pub sampler_type: u32,
+    pub shutter_open: f32,
+    pub shutter_close: f32,
+    pub max_samples_per_pixel: u32, // 一个像素最多的采样数，见 set_sample_budget
+    _padding: [u32; 2],
+    pub camera_motion: Vector3<f32>, // 时间 0 到 1 之间相机的位移
+    _padding2: u32,
 }
 
+wgsl_layout!(
+    RenderContext,
+    "RenderContext",
+    [
+        width,
+        height,
+        sample_grid_per_dimension,
+        adaptive_sampling_mode,
+        pixel_origin,
+        samples_per_pixel,
+        pixel_delta_u,
+        sample_grid_num,
+        pixel_delta_v,
+        defocus_angle,
+        defocus_disk_u,
+        sample_grid_len,
+        defocus_disk_v,
+        sample_id,
+        camera_position,
+        max_ray_bounces as "ray_bounces",
+        important_index_len,
+        convergence_threshold,
+        adaptive_min_samples,
+        sample_view,
+        samples_per_dispatch,
+        seed,
+        sampler_type,
+        shutter_open,
+        shutter_close,
+        max_samples_per_pixel,
+        camera_motion,
+    ]
+);
+
 impl RenderContext {
     pub fn new(
         camera: &Camera,
@@ -38,6 +149,7 @@ impl RenderContext {
         let mut configuration = Self {
             max_ray_bounces,
             important_index_len,
+            samples_per_dispatch: 1,
             ..Default::default()
         };
 
@@ -55,32 +167,75 @@ impl RenderContext {
         }
 
         self.samples_per_pixel = samples_per_pixel;
-        let sample_grid_per_dimension = self.sample_grid_per_dimension();
-        self.sample_grid_num = sample_grid_per_dimension.pow(2);
-        self.sample_grid_len = 1.0 / sample_grid_per_dimension as f32;
+        self.max_samples_per_pixel = samples_per_pixel;
+        self.adaptive_min_samples = clamp_adaptive_min_samples(self.adaptive_min_samples, samples_per_pixel);
+        self.sample_grid_per_dimension = (samples_per_pixel as f32).sqrt().floor() as u32;
+        self.sample_grid_num = self.sample_grid_per_dimension.pow(2);
+        self.sample_grid_len = 1.0 / self.sample_grid_per_dimension as f32;
     }
 
     pub fn set_sample_id(&mut self, sample_id: u32) {
         self.sample_id = sample_id;
-        let sample_grid_per_dimension = self.sample_grid_per_dimension();
-        if sample_id < self.sample_grid_num {
-            self.sample_position = Point2::new(
-                sample_id % sample_grid_per_dimension,
-                sample_id / sample_grid_per_dimension,
-            );
-        }
     }
 
     pub fn reset_sample_id(&mut self) {
         self.set_sample_id(0);
     }
 
+    // 前进一次调度的采样数
     pub fn increment_sample_id(&mut self) {
-        self.set_sample_id(self.sample_id + 1);
+        self.set_sample_id((self.sample_id + self.samples_per_dispatch).min(self.samples_per_pixel));
+    }
+
+    pub fn set_samples_per_dispatch(&mut self, samples_per_dispatch: u32) {
+        self.samples_per_dispatch = samples_per_dispatch.max(1);
+    }
+
+    pub fn set_adaptive_sampling(
+        &mut self,
+        mode: AdaptiveSamplingMode,
+        convergence_threshold: f32,
+        adaptive_min_samples: u32,
+    ) {
+        self.adaptive_sampling_mode = mode as u32;
+        self.convergence_threshold = convergence_threshold;
+        self.adaptive_min_samples = clamp_adaptive_min_samples(adaptive_min_samples, self.samples_per_pixel);
+    }
+
+    // Redistribute 模式下还有省下的采样数时，未收敛的像素可以超过 samples_per_pixel
+    pub fn set_sample_budget(&mut self, available: bool) {
+        self.max_samples_per_pixel = if available && self.adaptive_sampling_mode() == AdaptiveSamplingMode::Redistribute
+        {
+            self.samples_per_pixel.saturating_mul(REDISTRIBUTED_MAX_SAMPLES_RATIO)
+        } else {
+            self.samples_per_pixel
+        };
+    }
+
+    pub fn adaptive_sampling_mode(&self) -> AdaptiveSamplingMode {
+        match self.adaptive_sampling_mode {
+            1 => AdaptiveSamplingMode::StopConverged,
+            2 => AdaptiveSamplingMode::Redistribute,
+            _ => AdaptiveSamplingMode::Off,
+        }
     }
 
-    fn sample_grid_per_dimension(&self) -> u32 {
-        (self.samples_per_pixel as f32).sqrt().floor() as u32
+    pub fn set_sampler(&mut self, sampler_type: SamplerType, seed: u32) {
+        self.sampler_type = sampler_type as u32;
+        self.seed = seed;
+    }
+
+    pub fn sampler_type(&self) -> SamplerType {
+        match self.sampler_type {
+            1 => SamplerType::Sobol,
+            2 => SamplerType::Halton,
+            3 => SamplerType::Rank1Lattice,
+            _ => SamplerType::Independent,
+        }
+    }
+
+    pub fn set_sample_view(&mut self, sample_view: SampleView) {
+        self.sample_view = sample_view as u32;
     }
 
     pub fn update(&mut self, camera: &Camera, width: u32, height: u32) {
@@ -114,6 +269,10 @@ impl RenderContext {
         self.defocus_disk_v = camera.v().scale(defocus_radius);
 
         self.camera_position = *camera.position();
+
+        self.shutter_open = camera.shutter_open();
+        self.shutter_close = camera.shutter_close();
+        self.camera_motion = *camera.motion();
     }
 
     fn aspect_ratio(&self) -> f32 {
@@ -123,3 +282,8 @@ impl RenderContext {
         self.width * self.height
     }
 }
+
+// 判断收敛前至少需要 2 个采样才能估计方差，最多不超过每个像素的采样数，否则没有像素能收敛
+pub fn clamp_adaptive_min_samples(adaptive_min_samples: u32, samples_per_pixel: u32) -> u32 {
+    adaptive_min_samples.clamp(2, samples_per_pixel.max(2))
+}
@@ -0,0 +1,12 @@
+// ray_tracing.wgsl 的 CPU 实现，用于没有 GPU 的环境和回归测试。
+// 函数与 WGSL 中的同名函数一一对应，修改其中一边时需要同步修改另一边
+
+mod hair;
+pub mod hit;
+pub mod path_tracer;
+mod principled;
+pub mod sampler;
+
+pub use hit::*;
+pub use path_tracer::*;
+pub use sampler::*;
@@ -0,0 +1,92 @@
+// 与 src/shader/material/hair.wgsl 一一对应
+use crate::rendering::cpu::hit::{rotation_matrix, HitRecord, Ray};
+use crate::rendering::cpu::path_tracer::{random_cosine_direction, ScatterRecord};
+use crate::rendering::material::Hair;
+use nalgebra::{Vector2, Vector3};
+use std::f32::consts::{FRAC_PI_2, PI};
+
+pub(super) fn hair_scatter(hair: &Hair) -> ScatterRecord {
+    ScatterRecord {
+        attenuation: hair.color.coords,
+        skip_pdf_ray: None,
+    }
+}
+
+pub(super) fn hair_eval(hair: &Hair, ray: &Ray, hit_record: &HitRecord) -> Vector3<f32> {
+    let l = ray.direction.normalize();
+    let nl = hit_record.normal.dot(&l);
+    if nl <= 0.0 {
+        return Vector3::zeros();
+    }
+    let diffuse = (1.0 - hair.specular) * hair.color.coords * nl / PI;
+    diffuse + Vector3::repeat(hair.specular * specular_pdf(hair, hit_record, &l))
+}
+
+pub(super) fn hair_pdf_value(hair: &Hair, ray: &Ray, hit_record: &HitRecord) -> f32 {
+    let l = ray.direction.normalize();
+    let nl = hit_record.normal.dot(&l);
+    if nl <= 0.0 {
+        return 0.0;
+    }
+    (1.0 - hair.specular) * nl / PI + hair.specular * specular_pdf(hair, hit_record, &l)
+}
+
+pub(super) fn hair_random(hair: &Hair, hit_record: &HitRecord, u: Vector2<f32>) -> Vector3<f32> {
+    let n = hit_record.normal;
+
+    if u.x >= hair.specular {
+        let x = (u.x - hair.specular) / (1.0 - hair.specular);
+        return rotation_matrix(&Vector3::y(), &n) * random_cosine_direction(Vector2::new(x, u.y));
+    }
+
+    let peak = specular_peak(hair, hit_record);
+    let theta = peak + sample_trimmed_logistic(u.x / hair.specular, scale(hair), -FRAC_PI_2 - peak, FRAC_PI_2 - peak);
+    let phi = (u.y - 0.5) * PI;
+    let t = hit_record.tangent;
+    let b = t.cross(&n);
+    theta.sin() * t + theta.cos() * (phi.cos() * n + phi.sin() * b)
+}
+
+fn specular_pdf(hair: &Hair, hit_record: &HitRecord, l: &Vector3<f32>) -> f32 {
+    let sin_theta = l.dot(&hit_record.tangent).clamp(-1.0, 1.0);
+    let cos_theta = (1.0 - sin_theta * sin_theta).sqrt();
+    if cos_theta < 1e-4 {
+        return 0.0;
+    }
+    let peak = specular_peak(hair, hit_record);
+    let m = trimmed_logistic(
+        sin_theta.asin() - peak,
+        scale(hair),
+        -FRAC_PI_2 - peak,
+        FRAC_PI_2 - peak,
+    );
+    m / (PI * cos_theta)
+}
+
+fn specular_peak(hair: &Hair, hit_record: &HitRecord) -> f32 {
+    let sin_theta_o = (-hit_record.in_direction).dot(&hit_record.tangent).clamp(-1.0, 1.0);
+    -sin_theta_o.asin() + 2.0 * hair.tilt.to_radians()
+}
+
+fn scale(hair: &Hair) -> f32 {
+    hair.roughness.max(0.01) * 0.5
+}
+
+fn logistic(x: f32, s: f32) -> f32 {
+    let e = (-x.abs() / s).exp();
+    e / (s * (1.0 + e) * (1.0 + e))
+}
+
+fn logistic_cdf(x: f32, s: f32) -> f32 {
+    1.0 / (1.0 + (-x / s).min(80.0).exp())
+}
+
+fn trimmed_logistic(x: f32, s: f32, a: f32, b: f32) -> f32 {
+    logistic(x, s) / (logistic_cdf(b, s) - logistic_cdf(a, s))
+}
+
+fn sample_trimmed_logistic(u: f32, s: f32, a: f32, b: f32) -> f32 {
+    let cdf_a = logistic_cdf(a, s);
+    let c = (u * (logistic_cdf(b, s) - cdf_a) + cdf_a).clamp(1e-6, 1.0 - 1e-6);
+    (-s * (1.0 / c - 1.0).ln()).clamp(a, b)
+}
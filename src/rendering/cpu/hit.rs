@@ -0,0 +1,503 @@
+use crate::rendering::bounding_box::BoundingBox;
+use crate::rendering::bvh::BvhNode;
+use crate::rendering::cpu::sampler::{hash_u32, u32_to_unit_float};
+use crate::rendering::interval::Interval;
+use crate::rendering::material::{texture_sample, unpack_f32, AlphaMask};
+use crate::rendering::primitive::sphere::SphereData;
+use crate::rendering::primitive::{CsgData, CurveData, HeightfieldData, MotionData, QuadData, SdfData, ShapeData};
+use crate::rendering::scene_data::SceneData;
+use nalgebra::{Isometry3, Matrix3, Point3, Vector2, Vector3};
+use std::f32::consts::PI;
+
+mod csg;
+mod curve;
+mod heightfield;
+mod sdf;
+mod shape;
+
+pub use shape::*;
+
+const ZERO_TOLERANCE: f32 = 1e-8;
+
+#[derive(Copy, Clone, Debug)]
+pub struct Ray {
+    pub origin: Point3<f32>,
+    pub direction: Vector3<f32>,
+    pub time: f32,
+}
+
+impl Ray {
+    pub fn new(origin: Point3<f32>, direction: Vector3<f32>) -> Self {
+        Self::with_time(origin, direction, 0.0)
+    }
+
+    pub fn with_time(origin: Point3<f32>, direction: Vector3<f32>, time: f32) -> Self {
+        Self {
+            origin,
+            direction,
+            time,
+        }
+    }
+
+    pub fn at(&self, t: f32) -> Point3<f32> {
+        self.origin + t * self.direction
+    }
+}
+
+#[derive(Copy, Clone, Debug)]
+pub struct HitRecord {
+    pub position: Point3<f32>,
+    pub ray_t: f32,
+    pub normal: Vector3<f32>,
+    pub material_id: u32,
+    pub uv: Vector2<f32>,
+    pub material_type: u32,
+    pub is_front_face: bool,
+    pub in_direction: Vector3<f32>,     // 入射光线的单位方向
+    pub geometric_normal: Vector3<f32>, // 朝向入射光线一侧的几何法线，normal 是可能被法线贴图修改过的着色法线
+    pub tangent: Vector3<f32>,          // 与几何法线正交，沿 u 增加的方向
+    pub bitangent: Vector3<f32>,        // 与几何法线正交，沿 v 增加的方向
+}
+
+impl HitRecord {
+    fn new(ray: &Ray, ray_t: f32, outward_normal: Vector3<f32>, material_type: u32, material_id: u32) -> Self {
+        let is_front_face = ray.direction.dot(&outward_normal) < 0.0;
+        let normal = if is_front_face { outward_normal } else { -outward_normal };
+        Self {
+            position: ray.at(ray_t),
+            ray_t,
+            normal,
+            material_id,
+            uv: Vector2::zeros(),
+            material_type,
+            is_front_face,
+            in_direction: ray.direction.normalize(),
+            geometric_normal: normal,
+            tangent: Vector3::zeros(),
+            bitangent: Vector3::zeros(),
+        }
+    }
+}
+
+pub trait Hit {
+    fn hit(&self, ray: &Ray, interval: &Interval) -> Option<HitRecord>;
+}
+
+// 作为重要性采样的目标
+pub trait ImportanceSampling {
+    fn pdf_value(&self, ray: &Ray) -> f32;
+    fn random(&self, origin: &Point3<f32>, u: Vector2<f32>) -> Vector3<f32>;
+}
+
+/*------------------------------------------ Sphere ---------------------------------------------*/
+
+impl Hit for SphereData {
+    fn hit(&self, ray: &Ray, interval: &Interval) -> Option<HitRecord> {
+        // 在以 center 为原点、未经旋转缩放的局部坐标中与球面求交，t 在两个坐标系中相同
+        let inverse_transform = self.inverse_transform();
+        let origin = inverse_transform * (ray.origin - self.center());
+        let direction = inverse_transform * ray.direction;
+
+        let span = sphere_span(&Point3::from(origin), &direction, self.radius());
+
+        // Find the nearest root that lies in the acceptable range.
+        // 不相交时 span 为空区间，两个端点都不在 interval 中
+        let mut root = *span.min();
+        if !interval.surrounds(root) {
+            root = *span.max();
+            if !interval.surrounds(root) {
+                return None;
+            }
+        }
+
+        // 法线按逆转置变换到世界坐标
+        let local_normal = (origin + root * direction) / self.radius();
+        let outward_normal = (inverse_transform.transpose() * local_normal).normalize();
+        let mut hit_record = HitRecord::new(ray, root, outward_normal, self.material_type(), self.material_id());
+        hit_record.uv = sphere_uv(&local_normal);
+
+        let mut local_tangent = Vector3::new(local_normal.z, 0.0, -local_normal.x);
+        if local_tangent.norm_squared() < ZERO_TOLERANCE {
+            local_tangent = Vector3::x();
+        }
+        let transform = self.transform();
+        let tangent = transform * local_tangent;
+        hit_record.tangent = (tangent - outward_normal * outward_normal.dot(&tangent)).normalize();
+        hit_record.bitangent = outward_normal.cross(&hit_record.tangent);
+        // 镜像变换会翻转手性，副切线仍要沿 v 增加的方向
+        if hit_record
+            .bitangent
+            .dot(&(transform * local_normal.cross(&local_tangent)))
+            < 0.0
+        {
+            hit_record.bitangent = -hit_record.bitangent;
+        }
+        Some(hit_record)
+    }
+}
+
+// 光线与以原点为中心的球面的两个交点，即光线在球内的区间，不相交时返回空区间
+pub fn sphere_span(origin: &Point3<f32>, direction: &Vector3<f32>, radius: f32) -> Interval {
+    let oc = -origin.coords;
+    let a = direction.norm_squared();
+    let h = direction.dot(&oc);
+    let c = oc.norm_squared() - radius * radius;
+
+    let discriminant = h * h - a * c;
+    if discriminant < 0.0 {
+        return Interval::empty();
+    }
+
+    let sqrt_discriminant = discriminant.sqrt();
+    Interval::new((h - sqrt_discriminant) / a, (h + sqrt_discriminant) / a)
+}
+
+// 对包住椭球的球面所张的圆锥均匀采样，方向不一定击中椭球，pdf 也按圆锥计算
+impl ImportanceSampling for SphereData {
+    fn pdf_value(&self, ray: &Ray) -> f32 {
+        // This method only works for stationary spheres.
+        let oc = self.center() - ray.origin;
+        let a = ray.direction.norm_squared();
+        let h = ray.direction.dot(&oc);
+        let c = oc.norm_squared() - self.bounding_radius() * self.bounding_radius();
+        let discriminant = h * h - a * c;
+        if discriminant < 0.0 || (h + discriminant.sqrt()) / a <= 0.001 {
+            return 0.0;
+        }
+
+        let cos_theta_max = (1.0 - self.bounding_radius().powi(2) / oc.norm_squared()).sqrt();
+
+        let solid_angle = 2.0 * PI * (1.0 - cos_theta_max);
+        1.0 / solid_angle
+    }
+
+    fn random(&self, origin: &Point3<f32>, u: Vector2<f32>) -> Vector3<f32> {
+        let direction = self.center() - origin;
+        let distance_squared = direction.norm_squared();
+        rotation_matrix(&Vector3::y(), &direction.normalize())
+            * random_to_sphere(self.bounding_radius(), distance_squared, u)
+    }
+}
+
+pub fn sphere_uv(position: &Vector3<f32>) -> Vector2<f32> {
+    let theta = (-position.y).acos();
+    let phi = (-position.z).atan2(position.x) + PI;
+    Vector2::new(phi / (2.0 * PI), theta / PI)
+}
+
+// Ray Tracing: The Rest of Your Life, p80
+pub fn random_to_sphere(radius: f32, distance_squared: f32, u: Vector2<f32>) -> Vector3<f32> {
+    let cos_theta_max = (1.0 - radius * radius / distance_squared).sqrt();
+    let y = 1.0 + u.y * (cos_theta_max - 1.0);
+    let phi = 2.0 * PI * u.x;
+    let z = phi.cos() * (1.0 - y * y).sqrt();
+    let x = phi.sin() * (1.0 - y * y).sqrt();
+
+    Vector3::new(x, y, z)
+}
+
+/*------------------------------------------- Quad ----------------------------------------------*/
+
+impl Hit for QuadData {
+    fn hit(&self, ray: &Ray, interval: &Interval) -> Option<HitRecord> {
+        let nd = self.normal().dot(&ray.direction);
+
+        // No hit if the ray is parallel to the plane.
+        if nd.abs() < ZERO_TOLERANCE {
+            return None;
+        }
+
+        // Return None if the hit point parameter t is outside the ray interval.
+        let t = (self.d() - self.normal().dot(&ray.origin.coords)) / nd;
+        if !interval.contains(t) {
+            return None;
+        }
+
+        // Determine the hit point lies within the planar shape using its plane coordinates.
+        let planar_hit_vector = ray.at(t) - self.bottom_left();
+        let alpha = self.w().dot(&planar_hit_vector.cross(&self.up()));
+        let beta = self.w().dot(&self.right().cross(&planar_hit_vector));
+
+        let unit_interval = Interval::new(0.0, 1.0);
+        if !unit_interval.contains(alpha) || !unit_interval.contains(beta) {
+            return None;
+        }
+
+        let mut hit_record = HitRecord::new(ray, t, self.normal(), self.material_type(), self.material_id());
+        hit_record.uv = Vector2::new(alpha, beta);
+
+        let tangent = self.right().normalize();
+        hit_record.tangent = tangent;
+        hit_record.bitangent = (self.up() - self.up().dot(&tangent) * tangent).normalize();
+        Some(hit_record)
+    }
+}
+
+impl ImportanceSampling for QuadData {
+    fn pdf_value(&self, ray: &Ray) -> f32 {
+        let Some(hit_record) = self.hit(ray, &Interval::new(0.001, f32::MAX)) else {
+            return 0.0;
+        };
+
+        let distance_squared = hit_record.ray_t.powi(2) * ray.direction.norm_squared();
+        let cosine = (ray.direction.dot(&hit_record.normal) / ray.direction.norm()).abs();
+
+        distance_squared / (cosine * self.area())
+    }
+
+    fn random(&self, origin: &Point3<f32>, u: Vector2<f32>) -> Vector3<f32> {
+        let p = self.bottom_left() + u.x * self.up() + u.y * self.right();
+        (p - origin).normalize()
+    }
+}
+
+/*------------------------------------------ BVH ------------------------------------------------*/
+
+impl BoundingBox {
+    pub fn hit(&self, ray: &Ray, interval: &Interval) -> bool {
+        let mut min = *interval.min();
+        let mut max = *interval.max();
+
+        for axis in 0..3 {
+            let ad_inv = 1.0 / ray.direction[axis];
+            let t0 = (self.axis(axis as i32).min() - ray.origin[axis]) * ad_inv;
+            let t1 = (self.axis(axis as i32).max() - ray.origin[axis]) * ad_inv;
+
+            let (near, far) = if t0 < t1 { (t0, t1) } else { (t1, t0) };
+            if near > min {
+                min = near;
+            }
+            if far < max {
+                max = far;
+            }
+            if max <= min {
+                return false;
+            }
+        }
+
+        true
+    }
+}
+
+impl SceneData {
+    pub fn primitive(&self, primitive_type: u32, primitive_id: u32) -> Option<&dyn Primitive> {
+        match primitive_type {
+            0 => self
+                .quads()
+                .get(primitive_id as usize)
+                .map(|quad| quad as &dyn Primitive),
+            1 => self
+                .spheres()
+                .get(primitive_id as usize)
+                .map(|sphere| sphere as &dyn Primitive),
+            2 => self
+                .shapes()
+                .get(primitive_id as usize)
+                .map(|shape| shape as &dyn Primitive),
+            3 => self.sdfs().get(primitive_id as usize).map(|sdf| sdf as &dyn Primitive),
+            4 => self.csgs().get(primitive_id as usize).map(|csg| csg as &dyn Primitive),
+            5 => self
+                .curves()
+                .get(primitive_id as usize)
+                .map(|curve| curve as &dyn Primitive),
+            6 => self
+                .heightfields()
+                .get(primitive_id as usize)
+                .map(|heightfield| heightfield as &dyn Primitive),
+            _ => None,
+        }
+    }
+
+    // 返回最近的交点。与 WGSL 的无栈遍历顺序不同，但结果相同
+    pub fn hit(&self, ray: &Ray, interval: &Interval) -> Option<HitRecord> {
+        let tree = self.bvh_tree();
+        if tree.is_empty() {
+            return None;
+        }
+
+        let mut closest: Option<HitRecord> = None;
+        let mut stack = vec![0u32];
+        while let Some(id) = stack.pop() {
+            let node: &BvhNode = &tree[id as usize];
+            let current = Interval::new(*interval.min(), closest.map_or(*interval.max(), |hit| hit.ray_t));
+            if !node.bounding_box.hit(ray, &current) {
+                continue;
+            }
+
+            if node.is_leaf == 1 {
+                if let Some(hit_record) =
+                    self.primitive_hit(node.left_or_primitive_type, node.right_or_primitive_id, ray, &current)
+                {
+                    closest = Some(hit_record);
+                }
+            } else {
+                stack.push(node.right_or_primitive_id);
+                if node.left_or_primitive_type != node.right_or_primitive_id {
+                    stack.push(node.left_or_primitive_type);
+                }
+            }
+        }
+
+        closest
+    }
+
+    pub fn motion(&self, primitive_type: u32, primitive_id: u32) -> MotionData {
+        match primitive_type {
+            0 => self.quads().get(primitive_id as usize).map(QuadData::motion),
+            1 => self.spheres().get(primitive_id as usize).map(SphereData::motion),
+            2 => self.shapes().get(primitive_id as usize).map(ShapeData::motion),
+            3 => self.sdfs().get(primitive_id as usize).map(SdfData::motion),
+            4 => self.csgs().get(primitive_id as usize).map(CsgData::motion),
+            5 => self.curves().get(primitive_id as usize).map(CurveData::motion),
+            6 => self
+                .heightfields()
+                .get(primitive_id as usize)
+                .map(HeightfieldData::motion),
+            _ => None,
+        }
+        .unwrap_or_default()
+    }
+
+    // 与 Primitive_hit 一致，运动的图元在时间 0 时的坐标系中求交，跳过透明度测试失败的交点
+    fn primitive_hit(
+        &self,
+        primitive_type: u32,
+        primitive_id: u32,
+        ray: &Ray,
+        interval: &Interval,
+    ) -> Option<HitRecord> {
+        let primitive = self.primitive(primitive_type, primitive_id)?;
+        let motion = self.motion(primitive_type, primitive_id);
+        let transform = motion.at(ray.time);
+        let object_ray = if motion.is_moving() {
+            motion_ray_to_object(&transform, ray)
+        } else {
+            *ray
+        };
+
+        let mut interval = *interval;
+        for _ in 0..SHAPE_MAX_HITS {
+            let mut hit_record = primitive.hit(&object_ray, &interval)?;
+            if motion.is_moving() {
+                motion_hit_record_to_world(&transform, &mut hit_record);
+            }
+            if self.alpha_mask_passes(&hit_record) {
+                return Some(hit_record);
+            }
+            interval = Interval::new(hit_record.ray_t, *interval.max());
+        }
+        None
+    }
+
+    // 与 Primitive_pdf_value 一致
+    pub fn primitive_pdf_value(&self, primitive_type: u32, primitive_id: u32, ray: &Ray) -> f32 {
+        let Some(primitive) = self.primitive(primitive_type, primitive_id) else {
+            return 0.0;
+        };
+        let motion = self.motion(primitive_type, primitive_id);
+        if motion.is_moving() {
+            primitive.pdf_value(&motion_ray_to_object(&motion.at(ray.time), ray))
+        } else {
+            primitive.pdf_value(ray)
+        }
+    }
+
+    // 与 Primitive_random 一致
+    pub fn primitive_random(
+        &self,
+        primitive_type: u32,
+        primitive_id: u32,
+        origin: &Point3<f32>,
+        time: f32,
+        u: Vector2<f32>,
+    ) -> Vector3<f32> {
+        let Some(primitive) = self.primitive(primitive_type, primitive_id) else {
+            return Vector3::zeros();
+        };
+        let motion = self.motion(primitive_type, primitive_id);
+        if motion.is_moving() {
+            let transform = motion.at(time);
+            transform * primitive.random(&transform.inverse_transform_point(origin), u)
+        } else {
+            primitive.random(origin, u)
+        }
+    }
+
+    // 与 AlphaMask_passes 一致。没有注册材质的图元（例如直接构造的 PrimitiveData）总是不透明
+    fn alpha_mask_passes(&self, hit_record: &HitRecord) -> bool {
+        let data = self.materials();
+        let offset = (hit_record.material_id as usize)
+            .checked_sub(2)
+            .and_then(|header| data.get(header))
+            .map_or(0, |offset| *offset as usize);
+        if offset == 0 {
+            return true;
+        }
+
+        let alpha = texture_sample(&data[offset + 2..], hit_record.uv.x, hit_record.uv.y)[3];
+        if data[offset] == AlphaMask::MODE_STOCHASTIC {
+            let p = hit_record.position.map(f32::to_bits);
+            return alpha > u32_to_unit_float(hash_u32(p.x ^ hash_u32(p.y ^ hash_u32(p.z))));
+        }
+        alpha >= unpack_f32(data, offset + 1)
+    }
+}
+
+// 与 Motion_ray_to_object 一致，刚体变换不改变光线的参数 t
+fn motion_ray_to_object(transform: &Isometry3<f32>, ray: &Ray) -> Ray {
+    Ray::with_time(
+        transform.inverse_transform_point(&ray.origin),
+        transform.inverse_transform_vector(&ray.direction),
+        ray.time,
+    )
+}
+
+fn motion_hit_record_to_world(transform: &Isometry3<f32>, hit_record: &mut HitRecord) {
+    hit_record.position = transform * hit_record.position;
+    hit_record.normal = transform * hit_record.normal;
+    hit_record.geometric_normal = transform * hit_record.geometric_normal;
+    hit_record.tangent = transform * hit_record.tangent;
+    hit_record.bitangent = transform * hit_record.bitangent;
+}
+
+pub trait Primitive: Hit + ImportanceSampling + Sync {}
+impl<T: Hit + ImportanceSampling + Sync> Primitive for T {}
+
+/*---------------------------- a Matrix to Rotate One Vector to Another -------------------------*/
+
+// https://cs.brown.edu/people/jhughes/papers/Moller-EBA-1999/paper.pdf
+pub fn rotation_matrix(unit_from: &Vector3<f32>, unit_to: &Vector3<f32>) -> Matrix3<f32> {
+    let c = unit_from.dot(unit_to);
+    if c.abs() <= 0.99 {
+        let v = unit_from.cross(unit_to);
+        let h = 1.0 / (1.0 + c);
+
+        let hxx = h * v.x * v.x;
+        let hxy = h * v.x * v.y;
+        let hxz = h * v.x * v.z;
+        let hyy = h * v.y * v.y;
+        let hyz = h * v.y * v.z;
+        let hzz = h * v.z * v.z;
+
+        Matrix3::from_columns(&[
+            Vector3::new(c + hxx, hxy + v.z, hxz - v.y),
+            Vector3::new(hxy - v.z, c + hyy, hyz + v.x),
+            Vector3::new(hxz + v.y, hyz - v.x, c + hzz),
+        ])
+    } else {
+        let x_min = unit_from.x < unit_from.y && unit_from.x < unit_from.z;
+        let y_min = unit_from.y < unit_from.x && unit_from.y < unit_from.z;
+        let p = if x_min {
+            Vector3::x()
+        } else if y_min {
+            Vector3::y()
+        } else {
+            Vector3::z()
+        };
+        reflection_matrix(&(p - unit_to)) * reflection_matrix(&(p - unit_from))
+    }
+}
+
+fn reflection_matrix(u: &Vector3<f32>) -> Matrix3<f32> {
+    Matrix3::identity() - 2.0 / u.dot(u) * u * u.transpose()
+}
@@ -0,0 +1,183 @@
+use super::shape::{
+    azimuth, azimuth_tangent, box_face, box_face_axes, box_span, cap_hit, cylinder_span, tangent_frame, ShapeHit,
+};
+use super::{sphere_span, sphere_uv, Hit, HitRecord, ImportanceSampling, Ray};
+use crate::rendering::interval::Interval;
+use crate::rendering::primitive::{CsgData, CsgNode, CSG_MAX_NODES};
+use nalgebra::{Point3, Vector2, Vector3, Vector4};
+
+// 与 Csg_hit 一致
+impl Hit for CsgData {
+    fn hit(&self, ray: &Ray, interval: &Interval) -> Option<HitRecord> {
+        let nodes = self.nodes();
+
+        // 每个叶子的区间和 interval 起点处的内外状态，inside 的第 i 位对应第 i 个节点
+        let spans: Vec<Interval> = (0..nodes.len()).map(|i| self.leaf_span(i, ray)).collect();
+        let mut inside = 0u32;
+        for (i, span) in spans.iter().enumerate() {
+            if span.min() <= interval.min() && interval.min() < span.max() {
+                inside |= 1 << i;
+            }
+        }
+        let mut was_inside = self.evaluate(inside);
+
+        // 端点按 (t, key) 排序，key = 2i 是进入第 i 个叶子，2i + 1 是离开，t 相同时先进入后离开。
+        // 起点处的端点已经计入初始状态，从 key 最大的位置开始
+        let mut current = (*interval.min(), 2 * CSG_MAX_NODES);
+        for _ in 0..2 * nodes.len() {
+            let mut next = (f32::MAX, 2 * CSG_MAX_NODES);
+            for (i, span) in spans.iter().enumerate() {
+                if span.min() > span.max() {
+                    continue;
+                }
+                for (e, event_t) in [*span.min(), *span.max()].into_iter().enumerate() {
+                    let event = (event_t, 2 * i + e);
+                    if event > current && event < next {
+                        next = event;
+                    }
+                }
+            }
+            if next.0 >= *interval.max() {
+                return None;
+            }
+
+            current = next;
+            let (t, key) = next;
+            let leaf = key / 2;
+            let entering = key % 2 == 0;
+            if entering {
+                inside |= 1 << leaf;
+            } else {
+                inside &= !(1 << leaf);
+            }
+            let is_inside = self.evaluate(inside);
+            if is_inside != was_inside {
+                // 从被挖去的叶子中出来时进入整体，法线与叶子的外法线相反
+                return Some(self.hit_record(leaf, ray, t, entering != is_inside));
+            }
+            was_inside = is_inside;
+        }
+        None
+    }
+}
+
+// 布尔运算后的表面没有简单的参数化，CSG 不会出现在重要性采样的目标中
+impl ImportanceSampling for CsgData {
+    fn pdf_value(&self, _ray: &Ray) -> f32 {
+        0.0
+    }
+
+    fn random(&self, origin: &Point3<f32>, _u: Vector2<f32>) -> Vector3<f32> {
+        (Point3::from((self.bounds_min() + self.bounds_max()) / 2.0) - origin).normalize()
+    }
+}
+
+impl CsgData {
+    // 与 Csg_leaf_span 一致，内部节点返回空区间
+    pub fn leaf_span(&self, i: usize, ray: &Ray) -> Interval {
+        let node = &self.nodes()[i];
+        let inverse_transform = node.inverse_transform();
+        let origin = Point3::from(inverse_transform * (ray.origin - node.center()));
+        let direction = inverse_transform * ray.direction;
+        let parameters = node.parameters();
+        match node.kind() {
+            CsgNode::SPHERE => sphere_span(&origin, &direction, parameters.x),
+            CsgNode::BOX => box_span(&parameters.xyz(), &origin, &direction),
+            CsgNode::CYLINDER => cylinder_span(parameters.x, parameters.y, &origin, &direction),
+            _ => Interval::empty(),
+        }
+    }
+
+    // 与 Csg_evaluate 一致
+    pub fn evaluate(&self, inside: u32) -> bool {
+        let mut stack = [false; CSG_MAX_NODES];
+        let mut top = 0;
+        for (i, node) in self.nodes().iter().enumerate() {
+            if node.is_leaf() {
+                stack[top] = inside & (1 << i) != 0;
+                top += 1;
+            } else {
+                top -= 1;
+                let (a, b) = (stack[top - 1], stack[top]);
+                stack[top - 1] = match node.kind() {
+                    CsgNode::UNION => a || b,
+                    CsgNode::INTERSECTION => a && b,
+                    _ => a && !b,
+                };
+            }
+        }
+        top > 0 && stack[0]
+    }
+
+    fn hit_record(&self, leaf: usize, ray: &Ray, t: f32, flip: bool) -> HitRecord {
+        let node = &self.nodes()[leaf];
+
+        // 法线按逆转置变换到世界坐标
+        let inverse_transform = node.inverse_transform();
+        let p = Point3::from(inverse_transform * (ray.at(t) - node.center()));
+        let leaf_hit = leaf_hit(node.kind(), &node.parameters(), t, &p);
+        let mut outward_normal = (inverse_transform.transpose() * leaf_hit.normal).normalize();
+        if flip {
+            outward_normal = -outward_normal;
+        }
+        let mut hit_record = HitRecord::new(ray, t, outward_normal, self.material_type(), self.material_id());
+        hit_record.uv = leaf_hit.uv;
+
+        let transform = node.transform();
+        let (tangent, bitangent) = tangent_frame(
+            &outward_normal,
+            &(transform * leaf_hit.dpdu),
+            &(transform * leaf_hit.dpdv),
+        );
+        hit_record.tangent = tangent;
+        hit_record.bitangent = bitangent;
+        hit_record
+    }
+}
+
+// 与 Csg_leaf_hit 一致，叶子局部坐标中表面上的点 p 处的外法线和参数化
+fn leaf_hit(kind: u32, parameters: &Vector4<f32>, t: f32, p: &Point3<f32>) -> ShapeHit {
+    match kind {
+        CsgNode::SPHERE => {
+            let n = p.coords.normalize();
+            let dpdu = Vector3::new(n.z, 0.0, -n.x);
+            ShapeHit {
+                t,
+                position: *p,
+                normal: n,
+                uv: sphere_uv(&n),
+                dpdu,
+                dpdv: n.cross(&dpdu),
+            }
+        }
+        CsgNode::BOX => {
+            let half_size = parameters.xyz();
+            let axis = box_face(&half_size, p);
+            let (a, b) = box_face_axes(axis);
+            ShapeHit {
+                t,
+                position: *p,
+                normal: p[axis].signum() * Vector3::ith(axis, 1.0),
+                uv: Vector2::new(p[a] / half_size[a] + 1.0, p[b] / half_size[b] + 1.0) / 2.0,
+                dpdu: Vector3::ith(a, 1.0),
+                dpdv: Vector3::ith(b, 1.0),
+            }
+        }
+        _ => {
+            // 圆柱：取离 p 较近的侧面或盖子
+            let (radius, half_height) = (parameters.x, parameters.y);
+            if (p.y.abs() - half_height).abs() < (p.xz().coords.norm() - radius).abs() {
+                let side = p.y.signum();
+                return cap_hit(t, p, radius, side * half_height, side);
+            }
+            ShapeHit {
+                t,
+                position: *p,
+                normal: Vector3::new(p.x, 0.0, p.z).normalize(),
+                uv: Vector2::new(azimuth(p), (p.y + half_height) / (2.0 * half_height)),
+                dpdu: azimuth_tangent(p),
+                dpdv: Vector3::y(),
+            }
+        }
+    }
+}
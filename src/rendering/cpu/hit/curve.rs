@@ -0,0 +1,124 @@
+use super::shape::tangent_frame;
+use super::{Hit, HitRecord, ImportanceSampling, Ray, ZERO_TOLERANCE};
+use crate::rendering::interval::Interval;
+use crate::rendering::primitive::{CurveData, CurveKind};
+use nalgebra::{Point3, Vector2, Vector3, Vector4};
+use std::f32::consts::PI;
+
+// 与 Curve_hit 一致
+impl Hit for CurveData {
+    fn hit(&self, ray: &Ray, interval: &Interval) -> Option<HitRecord> {
+        let ray_length = ray.direction.norm();
+        if ray_length < ZERO_TOLERANCE {
+            return None;
+        }
+
+        // 光线坐标系，原点是光线起点，z 轴沿光线方向
+        let z = ray.direction / ray_length;
+        let x = if z.y.abs() > 0.9 {
+            Vector3::x().cross(&z)
+        } else {
+            Vector3::y().cross(&z)
+        }
+        .normalize();
+        let y = z.cross(&x);
+        let points = self.control_points().map(|control_point| {
+            let p = control_point.xyz() - ray.origin.coords;
+            Vector4::new(p.dot(&x), p.dot(&y), p.dot(&z), control_point.w)
+        });
+
+        // 每段折线上离光线最近的点。整条曲线的两端是平的，其他端点处取折线端点，相当于圆头
+        let pieces = self.pieces();
+        let is_start = self.u_min() <= 0.0;
+        let is_end = self.u_max() >= 1.0;
+        let mut closest: Option<(f32, f32)> = None;
+        let mut a = points[0];
+        for i in 1..=pieces {
+            let b = bezier(&points, i as f32 / pieces as f32);
+            let ab = b.xy() - a.xy();
+            let length2 = ab.norm_squared();
+            let s = if length2 > ZERO_TOLERANCE {
+                -a.xy().dot(&ab) / length2
+            } else {
+                0.0
+            };
+            if !((is_start && i == 1 && s < 0.0) || (is_end && i == pieces && s > 1.0)) {
+                let s = s.clamp(0.0, 1.0);
+                let p = a.lerp(&b, s);
+                let half_width = p.w / 2.0;
+                // 离起点不到半宽的交点是光线刚刚离开的那一处曲线，从曲线上散射出来的光线不再与它自己相交
+                if p.xy().norm_squared() <= half_width * half_width && p.z > half_width {
+                    let t = p.z / ray_length;
+                    let max = closest.map_or(*interval.max(), |(t, _)| t);
+                    if t > *interval.min() && t < max {
+                        closest = Some((t, ((i - 1) as f32 + s) / pieces as f32));
+                    }
+                }
+            }
+            a = b;
+        }
+
+        let (t, u) = closest?;
+        Some(self.hit_record(ray, &z, t, u))
+    }
+}
+
+// 曲线很细，作为光源时几乎不会被采样到
+impl ImportanceSampling for CurveData {
+    fn pdf_value(&self, _ray: &Ray) -> f32 {
+        0.0
+    }
+
+    fn random(&self, origin: &Point3<f32>, _u: Vector2<f32>) -> Vector3<f32> {
+        (Point3::from(self.point(0.5).xyz()) - origin).normalize()
+    }
+}
+
+impl CurveData {
+    // 与 Curve_set_hit_record 一致
+    fn hit_record(&self, ray: &Ray, direction: &Vector3<f32>, t: f32, u: f32) -> HitRecord {
+        let center = self.point(u);
+        let control_points = self.control_points();
+
+        let mut tangent = self.derivative(u).xyz();
+        if tangent.norm_squared() < ZERO_TOLERANCE {
+            tangent = (control_points[3] - control_points[0]).xyz();
+        }
+        let tangent = tangent.normalize();
+
+        // 条带的法线是光线反方向中与切线正交的部分，side 沿宽度方向，与光线和切线都正交
+        let mut normal = tangent * direction.dot(&tangent) - direction;
+        if normal.norm_squared() < ZERO_TOLERANCE {
+            let axis = if tangent.x.abs() > 0.9 {
+                Vector3::y()
+            } else {
+                Vector3::x()
+            };
+            normal = axis.cross(&tangent);
+        }
+        let mut normal = normal.normalize();
+        let side = tangent.cross(&normal);
+        let position = ray.at(t);
+        let v = (0.5 + (position.coords - center.xyz()).dot(&side) / center.w.max(f32::MIN_POSITIVE)).clamp(0.0, 1.0);
+        if self.kind() == CurveKind::TUBE {
+            let theta = (v - 0.5) * PI;
+            normal = theta.cos() * normal + theta.sin() * side;
+        }
+
+        // 曲线没有内外之分，法线总是朝向光线一侧
+        let mut hit_record = HitRecord::new(ray, t, -direction, self.material_type(), self.material_id());
+        hit_record.normal = normal;
+        hit_record.geometric_normal = normal;
+        hit_record.uv = Vector2::new(self.u_min() + (self.u_max() - self.u_min()) * u, v);
+        let (tangent, bitangent) = tangent_frame(&normal, &tangent, &side);
+        hit_record.tangent = tangent;
+        hit_record.bitangent = bitangent;
+        hit_record
+    }
+}
+
+// 与 Curve_bezier 一致，控制点在光线坐标系中
+fn bezier(p: &[Vector4<f32>; 4], u: f32) -> Vector4<f32> {
+    let v = 1.0 - u;
+    p[0] * (v * v * v) + p[1] * (3.0 * u * v * v) + p[2] * (3.0 * u * u * v) + p[3] * (u * u * u)
+}
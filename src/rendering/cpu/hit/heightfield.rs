@@ -0,0 +1,186 @@
+use super::shape::tangent_frame;
+use super::{Hit, HitRecord, ImportanceSampling, Ray, ZERO_TOLERANCE};
+use crate::rendering::interval::Interval;
+use crate::rendering::primitive::{HeightfieldData, HEIGHTFIELD_TILE_CELLS};
+use nalgebra::{Point3, Vector2, Vector3};
+
+// 与 ray_tracing.wgsl 中的 HEIGHTFIELD_EPSILON 一致
+const HEIGHTFIELD_EPSILON: f32 = 1e-5;
+
+// 与 Heightfield_hit 一致
+impl Hit for HeightfieldData {
+    fn hit(&self, ray: &Ray, interval: &Interval) -> Option<HitRecord> {
+        // 仿射变换不改变光线的参数 t
+        let inverse_transform = self.inverse_transform();
+        let origin = Point3::from(inverse_transform * (ray.origin - self.origin()));
+        let direction = inverse_transform * ray.direction;
+
+        let (near, far) = self.bounds_range(&origin, &direction)?;
+        let t_min = near.max(*interval.min());
+        let t_max = far.min(*interval.max());
+        if t_min > t_max {
+            return None;
+        }
+
+        // 光线进入包围盒时所在的格子，以及沿 x、z 走到下一条格子边界时的 t
+        let first = Vector2::new(self.bounds_min().x as i32, self.bounds_min().z as i32);
+        let last = Vector2::new(self.bounds_max().x as i32, self.bounds_max().z as i32).add_scalar(-1);
+        let start = origin.xz() + t_min * direction.xz();
+        let mut cell = Vector2::new(start.x.floor() as i32, start.y.floor() as i32).zip_zip_map(
+            &first,
+            &last,
+            |x, first, last| x.clamp(first, last),
+        );
+        let step = direction.xz().map(|d| if d >= 0.0 { 1 } else { -1 });
+        let mut t_next = Vector2::repeat(f32::MAX);
+        let mut t_delta = Vector2::repeat(f32::MAX);
+        for axis in 0..2 {
+            let d = direction.xz()[axis];
+            if d.abs() >= ZERO_TOLERANCE {
+                let boundary = (cell[axis] + step[axis].max(0)) as f32;
+                t_next[axis] = (boundary - origin.xz()[axis]) / d;
+                t_delta[axis] = (1.0 / d).abs();
+            }
+        }
+
+        // 光线在一块中最多经过 2 * HEIGHTFIELD_TILE_CELLS - 1 个格子
+        for _ in 0..2 * HEIGHTFIELD_TILE_CELLS {
+            if let Some(t) = self.cell_hit(cell, &origin, &direction, interval) {
+                return Some(self.hit_record(ray, t, cell, &(origin + t * direction)));
+            }
+            if t_next.x.min(t_next.y) >= t_max {
+                return None;
+            }
+
+            if t_next.x < t_next.y {
+                cell.x += step.x;
+                t_next.x += t_delta.x;
+            } else {
+                cell.y += step.y;
+                t_next.y += t_delta.y;
+            }
+            if cell.x < first.x || cell.y < first.y || cell.x > last.x || cell.y > last.y {
+                return None;
+            }
+        }
+        None
+    }
+}
+
+// 地形通常不发光，不会出现在重要性采样的目标中
+impl ImportanceSampling for HeightfieldData {
+    fn pdf_value(&self, _ray: &Ray) -> f32 {
+        0.0
+    }
+
+    fn random(&self, origin: &Point3<f32>, _u: Vector2<f32>) -> Vector3<f32> {
+        let middle = self.origin() + self.transform() * (self.bounds_min() + self.bounds_max()) / 2.0;
+        (middle - origin).normalize()
+    }
+}
+
+impl HeightfieldData {
+    // 与 Heightfield_cell_hit 一致
+    fn cell_hit(
+        &self,
+        cell: Vector2<i32>,
+        origin: &Point3<f32>,
+        direction: &Vector3<f32>,
+        interval: &Interval,
+    ) -> Option<f32> {
+        let f = origin.xz().coords - cell.cast::<f32>();
+        let mut closest = None;
+        for first in [true, false] {
+            // 三角形所在的平面 y = h00 + dot(slope, q)，q 是格子内的坐标
+            let slope = self.triangle_slope(cell, first);
+            let denominator = direction.y - slope.dot(&direction.xz());
+            if denominator.abs() < ZERO_TOLERANCE {
+                continue;
+            }
+            let t = (self.height(cell.x, cell.y) + slope.dot(&f) - origin.y) / denominator;
+            let q = f + t * direction.xz();
+
+            // 第一个三角形是 q.x >= q.y 的一半，交换坐标后第二个三角形满足同样的条件
+            let r = if first { q } else { q.yx() };
+            let inside =
+                r.x <= 1.0 + HEIGHTFIELD_EPSILON && r.y >= -HEIGHTFIELD_EPSILON && r.x - r.y >= -HEIGHTFIELD_EPSILON;
+            if inside && t > *interval.min() && t < closest.unwrap_or(*interval.max()) {
+                closest = Some(t);
+            }
+        }
+        closest
+    }
+
+    // 与 Heightfield_triangle_slope 一致
+    fn triangle_slope(&self, cell: Vector2<i32>, first: bool) -> Vector2<f32> {
+        let h00 = self.height(cell.x, cell.y);
+        let h10 = self.height(cell.x + 1, cell.y);
+        let h01 = self.height(cell.x, cell.y + 1);
+        let h11 = self.height(cell.x + 1, cell.y + 1);
+        if first {
+            Vector2::new(h10 - h00, h11 - h10)
+        } else {
+            Vector2::new(h11 - h01, h01 - h00)
+        }
+    }
+
+    // 与 Heightfield_gradient 一致
+    fn gradient(&self, x: i32, z: i32) -> Vector2<f32> {
+        Vector2::new(
+            self.height(x + 1, z) - self.height(x - 1, z),
+            self.height(x, z + 1) - self.height(x, z - 1),
+        ) / 2.0
+    }
+
+    // 与 Heightfield_set_hit_record 一致
+    fn hit_record(&self, ray: &Ray, t: f32, cell: Vector2<i32>, p: &Point3<f32>) -> HitRecord {
+        let f = (p.xz().coords - cell.cast::<f32>()).map(|x| x.clamp(0.0, 1.0));
+        let inverse_transpose = self.inverse_transform().transpose();
+        let slope = self.triangle_slope(cell, f.x >= f.y);
+        let outward_normal = (inverse_transpose * Vector3::new(-slope.x, 1.0, -slope.y)).normalize();
+        let mut hit_record = HitRecord::new(ray, t, outward_normal, self.material_type(), self.material_id());
+
+        let (x, z) = (cell.x, cell.y);
+        let gradient = self
+            .gradient(x, z)
+            .lerp(&self.gradient(x + 1, z), f.x)
+            .lerp(&self.gradient(x, z + 1).lerp(&self.gradient(x + 1, z + 1), f.x), f.y);
+        let shading_normal = (inverse_transpose * Vector3::new(-gradient.x, 1.0, -gradient.y)).normalize();
+        hit_record.normal = if hit_record.is_front_face {
+            shading_normal
+        } else {
+            -shading_normal
+        };
+
+        hit_record.uv = p.xz().coords.component_div(&self.grid_size());
+        let transform = self.transform();
+        let (tangent, bitangent) = tangent_frame(
+            &outward_normal,
+            &(transform * Vector3::new(1.0, gradient.x, 0.0)),
+            &(transform * Vector3::new(0.0, gradient.y, 1.0)),
+        );
+        hit_record.tangent = tangent;
+        hit_record.bitangent = bitangent;
+        hit_record
+    }
+
+    // 与 Heightfield_bounds_range 一致
+    fn bounds_range(&self, origin: &Point3<f32>, direction: &Vector3<f32>) -> Option<(f32, f32)> {
+        let (bounds_min, bounds_max) = (self.bounds_min(), self.bounds_max());
+        let mut near = f32::MIN;
+        let mut far = f32::MAX;
+        for axis in 0..3 {
+            if direction[axis].abs() < ZERO_TOLERANCE {
+                if origin[axis] < bounds_min[axis] || origin[axis] > bounds_max[axis] {
+                    return None;
+                }
+                continue;
+            }
+            let t0 = (bounds_min[axis] - origin[axis]) / direction[axis];
+            let t1 = (bounds_max[axis] - origin[axis]) / direction[axis];
+            near = near.max(t0.min(t1));
+            far = far.min(t0.max(t1));
+        }
+        Some((near, far))
+    }
+}
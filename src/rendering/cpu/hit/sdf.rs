@@ -0,0 +1,163 @@
+use super::shape::{azimuth_tangent, tangent_frame, torus_distance};
+use super::{sphere_uv, Hit, HitRecord, ImportanceSampling, Ray, ZERO_TOLERANCE};
+use crate::rendering::interval::Interval;
+use crate::rendering::primitive::{SdfData, SdfNode, SDF_MAX_NODES};
+use nalgebra::{Point3, Vector2, Vector3, Vector4};
+
+// 与 ray_tracing.wgsl 中的 SDF_STEPS 一致
+const SDF_STEPS: usize = 256;
+
+// 与 Sdf_hit 一致
+impl Hit for SdfData {
+    fn hit(&self, ray: &Ray, interval: &Interval) -> Option<HitRecord> {
+        let inverse_transform = self.inverse_transform();
+        let origin = Point3::from(inverse_transform * (ray.origin - self.center()));
+        let direction = inverse_transform * ray.direction;
+
+        // s 是沿单位方向的距离，s = t * scale
+        let scale = direction.norm();
+        if scale < ZERO_TOLERANCE {
+            return None;
+        }
+        let unit_direction = direction / scale;
+
+        // 只在光线穿过包围盒的一段中步进
+        let (near, far) = self.bounds_range(&origin, &unit_direction)?;
+        let start = interval.min() * scale;
+        let end = far.min(interval.max() * scale);
+
+        let epsilon = 1e-4 * (self.bounds_max() - self.bounds_min()).norm();
+        let mut s = near.max(start);
+        // 从区间起点出发时，起点所在的表面是上一次的交点
+        let mut left_surface = s > start || self.distance(&(origin + s * unit_direction)).abs() >= epsilon;
+        for _ in 0..SDF_STEPS {
+            if s > end {
+                return None;
+            }
+
+            let p = origin + s * unit_direction;
+            let distance = self.distance(&p).abs();
+            if distance >= epsilon {
+                left_surface = true;
+            } else if left_surface {
+                return Some(self.hit_record(ray, s / scale, &p, epsilon));
+            }
+            s += distance.max(epsilon);
+        }
+        None
+    }
+}
+
+// 没有办法在距离场的表面上均匀采样，SDF 不会出现在重要性采样的目标中
+impl ImportanceSampling for SdfData {
+    fn pdf_value(&self, _ray: &Ray) -> f32 {
+        0.0
+    }
+
+    fn random(&self, origin: &Point3<f32>, _u: Vector2<f32>) -> Vector3<f32> {
+        (self.center() - origin).normalize()
+    }
+}
+
+impl SdfData {
+    // 与 Sdf_distance 一致，p 是 SDF 坐标中的点
+    pub fn distance(&self, p: &Point3<f32>) -> f32 {
+        let mut stack = [0.0; SDF_MAX_NODES];
+        let mut top = 0;
+        for node in self.nodes() {
+            if node.kind() >= SdfNode::UNION {
+                top -= 1;
+                stack[top - 1] = combine(node.kind(), stack[top - 1], stack[top], node.smoothness());
+            } else {
+                let q = Point3::from(node.inverse_rotation() * (p - node.center()));
+                stack[top] = leaf_distance(node.kind(), &node.parameters(), &q);
+                top += 1;
+            }
+        }
+        if top == 0 {
+            return f32::MAX;
+        }
+        stack[0]
+    }
+
+    // 与 Sdf_normal 一致，中心差分求距离场的梯度
+    pub fn normal(&self, p: &Point3<f32>, h: f32) -> Vector3<f32> {
+        Vector3::from_fn(|axis, _| {
+            let offset = Vector3::ith(axis, h);
+            self.distance(&(p + offset)) - self.distance(&(p - offset))
+        })
+        .normalize()
+    }
+
+    fn hit_record(&self, ray: &Ray, t: f32, p: &Point3<f32>, epsilon: f32) -> HitRecord {
+        let outward_normal = (self.inverse_transform().transpose() * self.normal(p, epsilon)).normalize();
+        let mut hit_record = HitRecord::new(ray, t, outward_normal, self.material_type(), self.material_id());
+
+        // 距离场没有自然的参数化，以包围盒中心为球心做球面投影
+        let q = Point3::from(p - (self.bounds_min() + self.bounds_max()) / 2.0);
+        hit_record.uv = sphere_uv(&q.coords.normalize());
+
+        let transform = self.transform();
+        let (tangent, bitangent) = tangent_frame(
+            &outward_normal,
+            &(transform * azimuth_tangent(&q)),
+            &(transform * Vector3::y()),
+        );
+        hit_record.tangent = tangent;
+        hit_record.bitangent = bitangent;
+        hit_record
+    }
+
+    // 与 Sdf_bounds_range 一致
+    fn bounds_range(&self, origin: &Point3<f32>, direction: &Vector3<f32>) -> Option<(f32, f32)> {
+        let (bounds_min, bounds_max) = (self.bounds_min(), self.bounds_max());
+        let mut near = f32::MIN;
+        let mut far = f32::MAX;
+        for axis in 0..3 {
+            if direction[axis].abs() < ZERO_TOLERANCE {
+                if origin[axis] < bounds_min[axis] || origin[axis] > bounds_max[axis] {
+                    return None;
+                }
+                continue;
+            }
+            let t0 = (bounds_min[axis] - origin[axis]) / direction[axis];
+            let t1 = (bounds_max[axis] - origin[axis]) / direction[axis];
+            near = near.max(t0.min(t1));
+            far = far.min(t0.max(t1));
+        }
+        Some((near, far))
+    }
+}
+
+fn leaf_distance(kind: u32, parameters: &Vector4<f32>, p: &Point3<f32>) -> f32 {
+    match kind {
+        SdfNode::SPHERE => p.coords.norm() - parameters.x,
+        SdfNode::BOX => box_distance(p, &parameters.xyz()),
+        SdfNode::ROUNDED_BOX => box_distance(p, &parameters.xyz().add_scalar(-parameters.w)) - parameters.w,
+        SdfNode::TORUS => torus_distance(p, parameters.x, parameters.y),
+        SdfNode::CAPSULE => Vector3::new(p.x, p.y - p.y.clamp(-parameters.y, parameters.y), p.z).norm() - parameters.x,
+        _ => f32::MAX,
+    }
+}
+
+fn box_distance(p: &Point3<f32>, half_size: &Vector3<f32>) -> f32 {
+    let q = p.coords.abs() - half_size;
+    q.sup(&Vector3::zeros()).norm() + q.max().min(0.0)
+}
+
+fn combine(kind: u32, a: f32, b: f32, smoothness: f32) -> f32 {
+    match kind {
+        SdfNode::UNION => smooth_min(a, b, smoothness),
+        SdfNode::SUBTRACTION => -smooth_min(-a, b, smoothness),
+        _ => -smooth_min(-a, -b, smoothness),
+    }
+}
+
+// 与 Sdf_smooth_min 一致，比 min 最多小 smoothness / 4
+fn smooth_min(a: f32, b: f32, smoothness: f32) -> f32 {
+    if smoothness <= 0.0 {
+        return a.min(b);
+    }
+    let h = (smoothness - (a - b).abs()).max(0.0) / smoothness;
+    a.min(b) - h * h * smoothness / 4.0
+}
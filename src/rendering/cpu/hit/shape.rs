@@ -0,0 +1,539 @@
+use super::{Hit, HitRecord, ImportanceSampling, Ray, ZERO_TOLERANCE};
+use crate::rendering::interval::Interval;
+use crate::rendering::primitive::{ShapeData, ShapeKind};
+use nalgebra::{Point3, Vector2, Vector3, Vector4};
+use std::f32::consts::PI;
+
+// 与 ray_tracing.wgsl 中的 SHAPE_MAX_HITS、SHAPE_TORUS_STEPS 一致
+pub const SHAPE_MAX_HITS: usize = 4;
+const SHAPE_TORUS_STEPS: usize = 256;
+
+// 局部坐标中的交点，与 ShapeHit 一致
+#[derive(Copy, Clone, Debug)]
+pub(super) struct ShapeHit {
+    pub(super) t: f32,
+    pub(super) position: Point3<f32>,
+    pub(super) normal: Vector3<f32>, // 朝外的单位法线
+    pub(super) uv: Vector2<f32>,
+    pub(super) dpdu: Vector3<f32>,
+    pub(super) dpdv: Vector3<f32>,
+}
+
+impl Hit for ShapeData {
+    fn hit(&self, ray: &Ray, interval: &Interval) -> Option<HitRecord> {
+        let shape_hit = self.object_hit(ray, interval)?;
+
+        // 法线按逆转置变换到世界坐标
+        let outward_normal = (self.inverse_transform().transpose() * shape_hit.normal).normalize();
+        let mut hit_record = HitRecord::new(
+            ray,
+            shape_hit.t,
+            outward_normal,
+            self.material_type(),
+            self.material_id(),
+        );
+        hit_record.uv = shape_hit.uv;
+
+        let transform = self.transform();
+        let (tangent, bitangent) = tangent_frame(
+            &outward_normal,
+            &(transform * shape_hit.dpdu),
+            &(transform * shape_hit.dpdv),
+        );
+        hit_record.tangent = tangent;
+        hit_record.bitangent = bitangent;
+        Some(hit_record)
+    }
+}
+
+// 与 Shape_pdf_value、Shape_random 一致
+impl ImportanceSampling for ShapeData {
+    fn pdf_value(&self, ray: &Ray) -> f32 {
+        let inverse_transpose = self.inverse_transform().transpose();
+        let mut interval = Interval::new(0.001, f32::MAX);
+        let mut pdf = 0.0;
+        for _ in 0..SHAPE_MAX_HITS {
+            let Some(shape_hit) = self.object_hit(ray, &interval) else {
+                break;
+            };
+
+            let scaled_normal = inverse_transpose * shape_hit.normal;
+            let area_pdf = self.object_area_pdf(&shape_hit.position) / (self.determinant() * scaled_normal.norm());
+            let distance_squared = shape_hit.t.powi(2) * ray.direction.norm_squared();
+            let cosine = (ray.direction.dot(&scaled_normal.normalize()) / ray.direction.norm()).abs();
+            pdf += area_pdf * distance_squared / cosine;
+            interval = Interval::new(shape_hit.t, f32::MAX);
+        }
+        pdf
+    }
+
+    fn random(&self, origin: &Point3<f32>, u: Vector2<f32>) -> Vector3<f32> {
+        let p = self.center() + self.transform() * self.object_sample(u).coords;
+        (p - origin).normalize()
+    }
+}
+
+impl ShapeData {
+    fn object_hit(&self, ray: &Ray, interval: &Interval) -> Option<ShapeHit> {
+        let inverse_transform = self.inverse_transform();
+        let origin = Point3::from(inverse_transform * (ray.origin - self.center()));
+        let direction = inverse_transform * ray.direction;
+        let parameters = self.parameters();
+
+        match self.kind() {
+            ShapeKind::DISK => disk_hit(&parameters, &origin, &direction, interval),
+            ShapeKind::CYLINDER => cylinder_hit(&parameters, &origin, &direction, interval),
+            ShapeKind::CONE => cone_hit(&parameters, &origin, &direction, interval),
+            ShapeKind::TORUS => torus_hit(&parameters, &origin, &direction, interval),
+            ShapeKind::BOX => box_hit(&parameters, &origin, &direction, interval),
+            _ => None,
+        }
+    }
+
+    // 局部坐标中 p 处单位面积的概率密度
+    pub fn object_area_pdf(&self, p: &Point3<f32>) -> f32 {
+        if self.kind() == ShapeKind::TORUS {
+            // uv 均匀时密度与到 Y 轴的距离成反比
+            return 1.0 / (4.0 * PI * PI * self.parameters().y * p.xz().coords.norm());
+        }
+        1.0 / self.area()
+    }
+
+    // 与 Shape_object_sample 一致
+    pub fn object_sample(&self, u: Vector2<f32>) -> Point3<f32> {
+        let parameters = self.parameters();
+        let s = u.x * self.area();
+
+        match self.kind() {
+            ShapeKind::DISK => disk_sample(parameters.x, 0.0, u),
+            ShapeKind::CYLINDER => {
+                let (radius, half_height) = (parameters.x, parameters.y);
+                let side = 4.0 * PI * radius * half_height;
+                if s < side {
+                    let phi = 2.0 * PI * u.y;
+                    return Point3::new(
+                        radius * phi.cos(),
+                        half_height * (2.0 * s / side - 1.0),
+                        radius * phi.sin(),
+                    );
+                }
+                let cap = (s - side) / (PI * radius * radius);
+                let y = if cap >= 1.0 { half_height } else { -half_height };
+                disk_sample(radius, y, Vector2::new(cap.fract(), u.y))
+            }
+            ShapeKind::CONE => {
+                let (radius, half_height) = (parameters.x, parameters.y);
+                let side = PI * radius * (radius * radius + 4.0 * half_height * half_height).sqrt();
+                if s < side {
+                    // 到顶点的距离的概率密度与距离成正比
+                    let distance = (s / side).sqrt();
+                    let phi = 2.0 * PI * u.y;
+                    return Point3::new(
+                        radius * distance * phi.cos(),
+                        half_height * (1.0 - 2.0 * distance),
+                        radius * distance * phi.sin(),
+                    );
+                }
+                disk_sample(
+                    radius,
+                    -half_height,
+                    Vector2::new((s - side) / (PI * radius * radius), u.y),
+                )
+            }
+            ShapeKind::TORUS => {
+                let phi = 2.0 * PI * u.x;
+                let theta = 2.0 * PI * u.y;
+                let rho = parameters.x + parameters.y * theta.cos();
+                Point3::new(rho * phi.cos(), parameters.y * theta.sin(), rho * phi.sin())
+            }
+            ShapeKind::BOX => {
+                let half_size = parameters.xyz();
+                // 垂直于 X、Y、Z 的每个面的面积
+                let face_areas = 4.0
+                    * Vector3::new(
+                        half_size.y * half_size.z,
+                        half_size.x * half_size.z,
+                        half_size.x * half_size.y,
+                    );
+                let mut remaining = s;
+                let mut axis = 0;
+                while axis < 2 && remaining >= 2.0 * face_areas[axis] {
+                    remaining -= 2.0 * face_areas[axis];
+                    axis += 1;
+                }
+                let face = remaining / face_areas[axis];
+                let (a, b) = box_face_axes(axis);
+                let mut p = Point3::origin();
+                p[axis] = if face >= 1.0 { half_size[axis] } else { -half_size[axis] };
+                p[a] = (2.0 * face.fract() - 1.0) * half_size[a];
+                p[b] = (2.0 * u.y - 1.0) * half_size[b];
+                p
+            }
+            _ => Point3::origin(),
+        }
+    }
+}
+
+// 与 Shape_set_tangent_frame 一致
+pub(super) fn tangent_frame(
+    outward_normal: &Vector3<f32>,
+    dpdu: &Vector3<f32>,
+    dpdv: &Vector3<f32>,
+) -> (Vector3<f32>, Vector3<f32>) {
+    let mut tangent = dpdu - outward_normal * outward_normal.dot(dpdu);
+    if tangent.norm_squared() < ZERO_TOLERANCE {
+        tangent = dpdv.cross(outward_normal);
+    }
+    if tangent.norm_squared() < ZERO_TOLERANCE {
+        let axis = if outward_normal.x.abs() > 0.9 {
+            Vector3::y()
+        } else {
+            Vector3::x()
+        };
+        tangent = axis.cross(outward_normal);
+    }
+    let tangent = tangent.normalize();
+
+    let mut bitangent = outward_normal.cross(&tangent);
+    if bitangent.dot(dpdv) < 0.0 {
+        bitangent = -bitangent;
+    }
+    (tangent, bitangent)
+}
+
+pub(super) fn azimuth(p: &Point3<f32>) -> f32 {
+    ((-p.z).atan2(p.x) + PI) / (2.0 * PI)
+}
+
+pub(super) fn azimuth_tangent(p: &Point3<f32>) -> Vector3<f32> {
+    Vector3::new(p.z, 0.0, -p.x)
+}
+
+pub(super) fn cap_hit(t: f32, p: &Point3<f32>, radius: f32, y: f32, side: f32) -> ShapeHit {
+    let position = Point3::new(p.x, y, p.z);
+    let rho = position.xz().coords.norm();
+    let radial = if rho > ZERO_TOLERANCE {
+        Vector3::new(position.x, 0.0, position.z) / rho
+    } else {
+        Vector3::zeros()
+    };
+    ShapeHit {
+        t,
+        position,
+        normal: Vector3::new(0.0, side, 0.0),
+        uv: Vector2::new(azimuth(&position), rho / radius),
+        dpdu: azimuth_tangent(&position),
+        dpdv: radial,
+    }
+}
+
+fn disk_hit(
+    parameters: &Vector4<f32>,
+    origin: &Point3<f32>,
+    direction: &Vector3<f32>,
+    interval: &Interval,
+) -> Option<ShapeHit> {
+    let radius = parameters.x;
+    if direction.y.abs() < ZERO_TOLERANCE {
+        return None;
+    }
+
+    let t = -origin.y / direction.y;
+    let p = origin + t * direction;
+    if !interval.surrounds(t) || p.xz().coords.norm_squared() > radius * radius {
+        return None;
+    }
+    Some(cap_hit(t, &p, radius, 0.0, 1.0))
+}
+
+fn cylinder_hit(
+    parameters: &Vector4<f32>,
+    origin: &Point3<f32>,
+    direction: &Vector3<f32>,
+    interval: &Interval,
+) -> Option<ShapeHit> {
+    let (radius, half_height) = (parameters.x, parameters.y);
+    let mut closest: Option<ShapeHit> = None;
+    let is_closer =
+        |t: f32, closest: &Option<ShapeHit>| *interval.min() < t && t < closest.map_or(*interval.max(), |hit| hit.t);
+
+    // 侧面 x² + z² = r²
+    let a = direction.xz().norm_squared();
+    let h = -origin.xz().coords.dot(&direction.xz());
+    let c = origin.xz().coords.norm_squared() - radius * radius;
+    let discriminant = h * h - a * c;
+    if a > ZERO_TOLERANCE && discriminant >= 0.0 {
+        let sqrt_discriminant = discriminant.sqrt();
+        for t in [(h - sqrt_discriminant) / a, (h + sqrt_discriminant) / a] {
+            let p = origin + t * direction;
+            if is_closer(t, &closest) && p.y.abs() <= half_height {
+                closest = Some(ShapeHit {
+                    t,
+                    position: p,
+                    normal: Vector3::new(p.x, 0.0, p.z) / radius,
+                    uv: Vector2::new(azimuth(&p), (p.y + half_height) / (2.0 * half_height)),
+                    dpdu: azimuth_tangent(&p),
+                    dpdv: Vector3::y(),
+                });
+            }
+        }
+    }
+
+    // 上下两个盖子
+    if direction.y.abs() > ZERO_TOLERANCE {
+        for side in [-1.0, 1.0] {
+            let t = (side * half_height - origin.y) / direction.y;
+            let p = origin + t * direction;
+            if is_closer(t, &closest) && p.xz().coords.norm_squared() <= radius * radius {
+                closest = Some(cap_hit(t, &p, radius, side * half_height, side));
+            }
+        }
+    }
+
+    closest
+}
+
+fn cone_hit(
+    parameters: &Vector4<f32>,
+    origin: &Point3<f32>,
+    direction: &Vector3<f32>,
+    interval: &Interval,
+) -> Option<ShapeHit> {
+    let (radius, half_height) = (parameters.x, parameters.y);
+    let mut closest: Option<ShapeHit> = None;
+    let is_closer =
+        |t: f32, closest: &Option<ShapeHit>| *interval.min() < t && t < closest.map_or(*interval.max(), |hit| hit.t);
+
+    // 侧面 x² + z² = k²(h - y)²，k 是半径与高之比，顶点在 y = h
+    let k = radius / (2.0 * half_height);
+    let w = half_height - origin.y;
+    let a = direction.xz().norm_squared() - k * k * direction.y * direction.y;
+    let b = origin.xz().coords.dot(&direction.xz()) + k * k * w * direction.y;
+    let c = origin.xz().coords.norm_squared() - k * k * w * w;
+
+    // a t² + 2b t + c = 0，光线平行于母线时只有一个根
+    let mut roots = [f32::MAX; 2];
+    if a.abs() > ZERO_TOLERANCE {
+        let discriminant = b * b - a * c;
+        if discriminant >= 0.0 {
+            let sqrt_discriminant = discriminant.sqrt();
+            roots = [(-b - sqrt_discriminant) / a, (-b + sqrt_discriminant) / a];
+        }
+    } else if b.abs() > ZERO_TOLERANCE {
+        roots[0] = -c / (2.0 * b);
+    }
+
+    for t in roots {
+        let p = origin + t * direction;
+        // y 不超过顶点时排除另一半圆锥
+        if is_closer(t, &closest) && p.y.abs() <= half_height {
+            let rho = p.xz().coords.norm();
+            let (normal, dpdv) = if rho > ZERO_TOLERANCE {
+                (
+                    Vector3::new(p.x, k * rho, p.z).normalize(),
+                    Vector3::new(-k * p.x / rho, 1.0, -k * p.z / rho),
+                )
+            } else {
+                (Vector3::y(), Vector3::y())
+            };
+            closest = Some(ShapeHit {
+                t,
+                position: p,
+                normal,
+                uv: Vector2::new(azimuth(&p), (p.y + half_height) / (2.0 * half_height)),
+                dpdu: azimuth_tangent(&p),
+                dpdv,
+            });
+        }
+    }
+
+    // 底面
+    if direction.y.abs() > ZERO_TOLERANCE {
+        let t = (-half_height - origin.y) / direction.y;
+        let p = origin + t * direction;
+        if is_closer(t, &closest) && p.xz().coords.norm_squared() <= radius * radius {
+            closest = Some(cap_hit(t, &p, radius, -half_height, -1.0));
+        }
+    }
+
+    closest
+}
+
+pub(super) fn torus_distance(p: &Point3<f32>, major_radius: f32, minor_radius: f32) -> f32 {
+    Vector2::new(p.xz().coords.norm() - major_radius, p.y).norm() - minor_radius
+}
+
+// 与 Shape_torus_hit 一致，在包围球内用距离场步进求交
+fn torus_hit(
+    parameters: &Vector4<f32>,
+    origin: &Point3<f32>,
+    direction: &Vector3<f32>,
+    interval: &Interval,
+) -> Option<ShapeHit> {
+    let (major_radius, minor_radius) = (parameters.x, parameters.y);
+
+    // s 是沿单位方向的距离，s = t * scale
+    let scale = direction.norm();
+    if scale < ZERO_TOLERANCE {
+        return None;
+    }
+    let unit_direction = direction / scale;
+
+    let bound = major_radius + minor_radius;
+    let b = origin.coords.dot(&unit_direction);
+    let discriminant = b * b - (origin.coords.norm_squared() - bound * bound);
+    if discriminant < 0.0 {
+        return None;
+    }
+    let sqrt_discriminant = discriminant.sqrt();
+    let start = interval.min() * scale;
+    let end = (-b + sqrt_discriminant).min(interval.max() * scale);
+
+    let epsilon = 1e-4 * bound;
+    let mut s = (-b - sqrt_discriminant).max(start);
+    // 从区间起点出发时，起点所在的表面是上一次的交点；外赤道上的点也在包围球上，从包围球出发时不能跳过
+    let mut left_surface =
+        s > start || torus_distance(&(origin + s * unit_direction), major_radius, minor_radius).abs() >= epsilon;
+    for _ in 0..SHAPE_TORUS_STEPS {
+        if s > end {
+            return None;
+        }
+
+        let p = origin + s * unit_direction;
+        let distance = torus_distance(&p, major_radius, minor_radius).abs();
+        if distance >= epsilon {
+            left_surface = true;
+        } else if left_surface {
+            let rho = p.xz().coords.norm();
+            let radial = Vector3::new(p.x, 0.0, p.z) / rho;
+            let normal = (p.coords - radial * major_radius).normalize();
+            let cos_theta = normal.dot(&radial);
+            return Some(ShapeHit {
+                t: s / scale,
+                position: p,
+                normal,
+                uv: Vector2::new(azimuth(&p), (normal.y.atan2(cos_theta) + PI) / (2.0 * PI)),
+                dpdu: azimuth_tangent(&p),
+                dpdv: -normal.y * radial + cos_theta * Vector3::y(),
+            });
+        }
+        s += distance.max(epsilon);
+    }
+    None
+}
+
+// 垂直于 axis 的面上 u、v 对应的轴
+pub(super) fn box_face_axes(axis: usize) -> (usize, usize) {
+    match axis {
+        0 => (2, 1),
+        1 => (0, 2),
+        _ => (0, 1),
+    }
+}
+
+fn box_hit(
+    parameters: &Vector4<f32>,
+    origin: &Point3<f32>,
+    direction: &Vector3<f32>,
+    interval: &Interval,
+) -> Option<ShapeHit> {
+    let half_size = parameters.xyz();
+    let span = box_span(&half_size, origin, direction);
+
+    // 起点在长方体内时取离开的面
+    let t = if interval.surrounds(*span.min()) {
+        *span.min()
+    } else if interval.surrounds(*span.max()) {
+        *span.max()
+    } else {
+        return None;
+    };
+
+    let mut p = origin + t * direction;
+    let axis = box_face(&half_size, &p);
+    let side = p[axis].signum();
+    p[axis] = side * half_size[axis];
+    let (a, b) = box_face_axes(axis);
+    Some(ShapeHit {
+        t,
+        position: p,
+        normal: side * Vector3::ith(axis, 1.0),
+        uv: Vector2::new(p[a] / half_size[a] + 1.0, p[b] / half_size[b] + 1.0) / 2.0,
+        dpdu: Vector3::ith(a, 1.0),
+        dpdv: Vector3::ith(b, 1.0),
+    })
+}
+
+// slab 方法求光线在以原点为中心的长方体内的区间，不相交时返回空区间
+pub fn box_span(half_size: &Vector3<f32>, origin: &Point3<f32>, direction: &Vector3<f32>) -> Interval {
+    let (mut near, mut far) = (f32::MIN, f32::MAX);
+    for axis in 0..3 {
+        if direction[axis].abs() < ZERO_TOLERANCE {
+            if origin[axis].abs() > half_size[axis] {
+                return Interval::empty();
+            }
+            continue;
+        }
+        let t0 = (-half_size[axis] - origin[axis]) / direction[axis];
+        let t1 = (half_size[axis] - origin[axis]) / direction[axis];
+        near = near.max(t0.min(t1));
+        far = far.min(t0.max(t1));
+    }
+    if near > far {
+        return Interval::empty();
+    }
+    Interval::new(near, far)
+}
+
+// 长方体表面上的点所在的面：按半边长归一化后坐标绝对值最大的轴
+pub(super) fn box_face(half_size: &Vector3<f32>, p: &Point3<f32>) -> usize {
+    let q = p.coords.abs().component_div(half_size);
+    if q.x >= q.y && q.x >= q.z {
+        0
+    } else if q.y >= q.z {
+        1
+    } else {
+        2
+    }
+}
+
+// 光线在以原点为中心、轴沿 y 的实心圆柱内的区间，即无限长圆柱内的区间与两个盖子之间的区间的交集
+pub fn cylinder_span(radius: f32, half_height: f32, origin: &Point3<f32>, direction: &Vector3<f32>) -> Interval {
+    let (mut near, mut far) = (f32::MIN, f32::MAX);
+
+    let a = direction.xz().norm_squared();
+    let h = -origin.xz().coords.dot(&direction.xz());
+    let c = origin.xz().coords.norm_squared() - radius * radius;
+    if a > ZERO_TOLERANCE {
+        let discriminant = h * h - a * c;
+        if discriminant < 0.0 {
+            return Interval::empty();
+        }
+        let sqrt_discriminant = discriminant.sqrt();
+        near = (h - sqrt_discriminant) / a;
+        far = (h + sqrt_discriminant) / a;
+    } else if c > 0.0 {
+        return Interval::empty();
+    }
+
+    if direction.y.abs() > ZERO_TOLERANCE {
+        let t0 = (-half_height - origin.y) / direction.y;
+        let t1 = (half_height - origin.y) / direction.y;
+        near = near.max(t0.min(t1));
+        far = far.min(t0.max(t1));
+    } else if origin.y.abs() > half_height {
+        return Interval::empty();
+    }
+
+    if near > far {
+        return Interval::empty();
+    }
+    Interval::new(near, far)
+}
+
+// 高度为 y 的圆上按面积均匀采样
+fn disk_sample(radius: f32, y: f32, u: Vector2<f32>) -> Point3<f32> {
+    let rho = radius * u.x.sqrt();
+    let phi = 2.0 * PI * u.y;
+    Point3::new(rho * phi.cos(), y, rho * phi.sin())
+}
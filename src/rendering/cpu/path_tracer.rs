@@ -0,0 +1,591 @@
+use crate::rendering::cpu::hair::*;
+use crate::rendering::cpu::hit::{rotation_matrix, HitRecord, Ray};
+use crate::rendering::cpu::principled::*;
+use crate::rendering::cpu::sampler::Sampler;
+use crate::rendering::interval::Interval;
+use crate::rendering::material::{
+    angular_profile, texture_sample, unpack_f32, unpack_point3, DebugNormal, Dielectric, DiffuseLight, Hair,
+    Lambertian, NormalMap, Principled, Terrain,
+};
+use crate::rendering::scene_data::SceneData;
+use crate::rendering::{RenderContext, SamplerType};
+use getset::{CopyGetters, Getters};
+use image::{Rgb, Rgb32FImage, Rgba, RgbaImage};
+use nalgebra::{Point3, Vector2, Vector3};
+use std::f32::consts::PI;
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::thread;
+
+// 与 ray_tracing.wgsl 中的经验值一致
+const MATERIAL_WEIGHT: f32 = 0.6;
+
+enum RayColorEntry {
+    SkipPdf {
+        attenuation: Vector3<f32>,
+    },
+    Pdf {
+        color_from_emission: Vector3<f32>,
+        attenuation: Vector3<f32>, // BSDF 与余弦项的乘积
+        pdf_value: f32,
+    },
+}
+
+// 从 SceneData::materials 中按类型名解出的内置材质。其他材质只有 WGSL 实现，在 CPU 上既不发光也不散射。
+// DiffuseLight、Terrain 可能带有纹理，直接读取打包后的数据
+enum CpuMaterial<'a> {
+    DebugNormal,
+    Lambertian(Lambertian),
+    DiffuseLight(&'a [u32]),
+    Dielectric(Dielectric),
+    Principled(Principled),
+    Hair(Hair),
+    Terrain(&'a [u32]),
+    Unsupported,
+}
+
+pub(super) struct ScatterRecord {
+    pub(super) attenuation: Vector3<f32>,
+    pub(super) skip_pdf_ray: Option<Ray>,
+}
+
+// 线性空间的平均颜色，按行存储
+#[derive(Clone, Getters, CopyGetters)]
+pub struct CpuImage {
+    #[getset(get_copy = "pub")]
+    width: u32,
+    #[getset(get_copy = "pub")]
+    height: u32,
+    #[getset(get = "pub")]
+    pixels: Vec<Vector3<f32>>,
+}
+
+impl CpuImage {
+    pub fn new(width: u32, height: u32, pixels: Vec<Vector3<f32>>) -> Self {
+        assert_eq!(pixels.len(), (width * height) as usize);
+        Self { width, height, pixels }
+    }
+
+    pub fn pixel(&self, x: u32, y: u32) -> Vector3<f32> {
+        self.pixels[(x + y * self.width) as usize]
+    }
+
+    // 与 compute_main 写入 rgba8unorm 纹理的结果一致
+    pub fn to_rgba8(&self) -> RgbaImage {
+        RgbaImage::from_fn(self.width, self.height, |x, y| {
+            let color = linear_to_srgb(&self.pixel(x, y));
+            let to_u8 = |c: f32| (c.clamp(0.0, 1.0) * 255.0).round() as u8;
+            Rgba([to_u8(color.x), to_u8(color.y), to_u8(color.z), 255])
+        })
+    }
+
+    // 线性空间的浮点颜色，保留超过 1 的值，保存为 .exr 时使用
+    pub fn to_rgb32f(&self) -> Rgb32FImage {
+        Rgb32FImage::from_fn(self.width, self.height, |x, y| Rgb(self.pixel(x, y).into()))
+    }
+}
+
+// 多线程的 CPU 路径追踪器，估计量与 ray_tracing.wgsl 相同。
+// 总是对每个像素采样 samples_per_pixel 次，不支持自适应采样
+pub struct CpuPathTracer<'a> {
+    scene_data: &'a SceneData,
+    render_context: RenderContext,
+    threads: usize,
+}
+
+impl<'a> CpuPathTracer<'a> {
+    pub fn new(scene_data: &'a SceneData, render_context: RenderContext) -> Self {
+        Self {
+            scene_data,
+            render_context,
+            threads: thread::available_parallelism().map_or(1, |n| n.get()),
+        }
+    }
+
+    pub fn set_threads(&mut self, threads: usize) {
+        self.threads = threads.max(1);
+    }
+
+    pub fn render(&self) -> CpuImage {
+        let width = self.render_context.width;
+        let height = self.render_context.height;
+
+        let mut rows: Vec<(u32, Vec<Vector3<f32>>)> = if self.threads == 1 {
+            (0..height).map(|y| (y, self.render_row(y))).collect()
+        } else {
+            // 按行分配任务，每个线程取下一行
+            let next_row = AtomicU32::new(0);
+            thread::scope(|scope| {
+                let workers: Vec<_> = (0..self.threads)
+                    .map(|_| {
+                        scope.spawn(|| {
+                            let mut rows = Vec::new();
+                            loop {
+                                let y = next_row.fetch_add(1, Ordering::Relaxed);
+                                if y >= height {
+                                    break rows;
+                                }
+                                rows.push((y, self.render_row(y)));
+                            }
+                        })
+                    })
+                    .collect();
+
+                workers
+                    .into_iter()
+                    .flat_map(|worker| worker.join().expect("cpu path tracer worker panicked"))
+                    .collect()
+            })
+        };
+
+        rows.sort_unstable_by_key(|(y, _)| *y);
+        CpuImage::new(width, height, rows.into_iter().flat_map(|(_, row)| row).collect())
+    }
+
+    fn render_row(&self, y: u32) -> Vec<Vector3<f32>> {
+        let mut sampler = self.sampler();
+        (0..self.render_context.width)
+            .map(|x| self.render_pixel(&mut sampler, x, y))
+            .collect()
+    }
+
+    pub fn render_pixel(&self, sampler: &mut Sampler, x: u32, y: u32) -> Vector3<f32> {
+        let samples = self.render_context.samples_per_pixel;
+        let mut color = Vector3::zeros();
+        for sample_index in 0..samples {
+            color += self.sample(sampler, x, y, sample_index);
+        }
+        color / samples.max(1) as f32
+    }
+
+    pub fn sampler(&self) -> Sampler {
+        Sampler::new(
+            self.render_context.sampler_type(),
+            self.render_context.seed,
+            self.render_context.width,
+        )
+    }
+
+    // 对应 compute_main 中的一次采样
+    pub fn sample(&self, sampler: &mut Sampler, x: u32, y: u32, sample_index: u32) -> Vector3<f32> {
+        let pixel_index = x + y * self.render_context.width;
+        sampler.start(pixel_index, sample_index);
+
+        let ray = self.get_ray(sampler, Vector2::new(x as f32, y as f32), sample_index);
+        self.ray_color(sampler, ray)
+    }
+
+    fn get_ray(&self, sampler: &mut Sampler, pixel_position: Vector2<f32>, sample_index: u32) -> Ray {
+        let context = &self.render_context;
+
+        let offset = if sampler.sampler_type() == SamplerType::Independent {
+            self.sample_unit_square_stratified(sampler, sample_index)
+        } else {
+            sampler.sample_2d().add_scalar(-0.5)
+        };
+        let pixel_world_position = context.pixel_origin
+            + (pixel_position.x + offset.x) * context.pixel_delta_u
+            + (pixel_position.y + offset.y) * context.pixel_delta_v;
+
+        let ray_origin = if context.defocus_angle == 0.0 {
+            context.camera_position
+        } else {
+            let s = sample_unit_disk(sampler.sample_2d());
+            context.camera_position + s.x * context.defocus_disk_u + s.y * context.defocus_disk_v
+        };
+
+        let time = if context.shutter_close > context.shutter_open {
+            context.shutter_open + (context.shutter_close - context.shutter_open) * sampler.sample_1d()
+        } else {
+            context.shutter_open
+        };
+        let camera_offset = time * context.camera_motion;
+
+        Ray::with_time(
+            ray_origin + camera_offset,
+            (pixel_world_position - ray_origin).normalize(),
+            time,
+        )
+    }
+
+    fn sample_unit_square_stratified(&self, sampler: &mut Sampler, sample_index: u32) -> Vector2<f32> {
+        let context = &self.render_context;
+        if sample_index < context.sample_grid_num {
+            let n = context.sample_grid_per_dimension;
+            let x = (sample_index % n) as f32 + sampler.random();
+            let y = (sample_index / n) as f32 + sampler.random();
+            Vector2::new(x, y) * context.sample_grid_len - Vector2::repeat(0.5)
+        } else {
+            let x = sampler.random();
+            let y = sampler.random();
+            Vector2::new(x, y) - Vector2::repeat(0.5)
+        }
+    }
+
+    fn ray_color(&self, sampler: &mut Sampler, mut ray: Ray) -> Vector3<f32> {
+        let mut stack = Vec::new();
+
+        for _ in 0..=self.render_context.max_ray_bounces {
+            let Some(mut hit_record) = self.scene_data.hit(&ray, &Interval::new(0.001, f32::MAX)) else {
+                return resolve_ray_color(&stack, Vector3::zeros());
+            };
+            self.normal_map_apply(&mut hit_record);
+
+            let emitted_color = self.material_emit(&hit_record);
+
+            let Some(scatter_record) = self.material_scatter(sampler, &ray, &hit_record) else {
+                return resolve_ray_color(&stack, emitted_color);
+            };
+
+            if let Some(skip_pdf_ray) = scatter_record.skip_pdf_ray {
+                stack.push(RayColorEntry::SkipPdf {
+                    attenuation: scatter_record.attenuation,
+                });
+                ray = Ray::with_time(skip_pdf_ray.origin, skip_pdf_ray.direction, ray.time);
+                continue;
+            }
+
+            // 每次反弹固定消耗三个维度，与 WGSL 保持一致
+            let strategy_u = sampler.sample_1d();
+            let light_u = sampler.sample_1d();
+            let direction_u = sampler.sample_2d();
+
+            let origin = hit_record.position;
+            let direction = if strategy_u > MATERIAL_WEIGHT {
+                let direction = self.importance_random(&origin, ray.time, light_u, direction_u);
+                if self.importance_pdf_value(&Ray::with_time(origin, direction, ray.time)) == 0.0 {
+                    return Vector3::zeros();
+                }
+                direction
+            } else {
+                self.material_random(&hit_record, direction_u)
+            };
+            let scattered_ray = Ray::with_time(origin, direction, ray.time);
+
+            // 方向在几何表面之下时沿它继续追踪会漏光
+            if direction.dot(&hit_record.geometric_normal) <= 0.0 {
+                return resolve_ray_color(&stack, emitted_color);
+            }
+
+            let pdf_value = (1.0 - MATERIAL_WEIGHT) * self.importance_pdf_value(&scattered_ray)
+                + MATERIAL_WEIGHT * self.material_pdf_value(&scattered_ray, &hit_record);
+            if pdf_value <= 0.0 {
+                return resolve_ray_color(&stack, emitted_color);
+            }
+
+            stack.push(RayColorEntry::Pdf {
+                color_from_emission: emitted_color,
+                attenuation: self.material_eval(&scattered_ray, &hit_record, &scatter_record),
+                pdf_value,
+            });
+            ray = scattered_ray;
+        }
+
+        resolve_ray_color(&stack, Vector3::zeros())
+    }
+
+    fn importance_pdf_value(&self, ray: &Ray) -> f32 {
+        let importance = self.scene_data.importance();
+        if importance.is_empty() {
+            return 0.0;
+        }
+
+        let pdf: f32 = importance
+            .iter()
+            .map(|index| {
+                self.scene_data
+                    .primitive_pdf_value(index.primitive_type, index.primitive_id, ray)
+            })
+            .sum();
+        pdf / importance.len() as f32
+    }
+
+    fn importance_random(&self, origin: &Point3<f32>, time: f32, light_u: f32, u: Vector2<f32>) -> Vector3<f32> {
+        let importance = self.scene_data.importance();
+        if importance.is_empty() {
+            return Vector3::zeros();
+        }
+
+        let len = importance.len() as u32;
+        let index = importance[((light_u * len as f32) as u32).min(len - 1) as usize];
+        self.scene_data
+            .primitive_random(index.primitive_type, index.primitive_id, origin, time, u)
+    }
+
+    /*---------------------------------------- Materials --------------------------------------------*/
+
+    // 与 NormalMap_apply 一致
+    fn normal_map_apply(&self, hit_record: &mut HitRecord) {
+        let data = self.scene_data.materials();
+        let offset = data[hit_record.material_id as usize - 1] as usize;
+        if offset == 0 {
+            return;
+        }
+
+        let kind = data[offset];
+        let strength = unpack_f32(data, offset + 1);
+        let texture = &data[offset + 2..];
+        let (u, v) = (hit_record.uv.x, hit_record.uv.y);
+
+        let n = if kind == NormalMap::KIND_BUMP {
+            let (width, height) = (texture[0] as f32, texture[1] as f32);
+            let h = texture_sample(texture, u, v)[0];
+            let dhdu = (texture_sample(texture, u + 1.0 / width, v)[0] - h) * width;
+            let dhdv = (texture_sample(texture, u, v + 1.0 / height)[0] - h) * height;
+            Vector3::new(-strength * dhdu, -strength * dhdv, 1.0)
+        } else {
+            let [r, g, b, _] = texture_sample(texture, u, v).map(|c| c * 2.0 - 1.0);
+            Vector3::new(r * strength, g * strength, b)
+        };
+
+        let outward_normal = if hit_record.is_front_face {
+            hit_record.geometric_normal
+        } else {
+            -hit_record.geometric_normal
+        };
+        let mut normal = (n.x * hit_record.tangent + n.y * hit_record.bitangent + n.z * outward_normal).normalize();
+        if !hit_record.is_front_face {
+            normal = -normal;
+        }
+
+        let v = -hit_record.in_direction;
+        let nv = normal.dot(&v);
+        if nv < 0.01 {
+            normal = (normal + (0.01 - nv) * v).normalize();
+        }
+        hit_record.normal = normal;
+    }
+
+    fn material(&self, hit_record: &HitRecord) -> CpuMaterial<'_> {
+        let name = self.scene_data.material_names().get(hit_record.material_type as usize);
+        let data = &self.scene_data.materials()[hit_record.material_id as usize..];
+        match name.copied() {
+            Some(DebugNormal::WGSL_NAME) => CpuMaterial::DebugNormal,
+            Some(Lambertian::WGSL_NAME) => CpuMaterial::Lambertian(Lambertian::unpack(data)),
+            Some(DiffuseLight::WGSL_NAME) => CpuMaterial::DiffuseLight(data),
+            Some(Dielectric::WGSL_NAME) => CpuMaterial::Dielectric(Dielectric::unpack(data)),
+            Some(Principled::WGSL_NAME) => CpuMaterial::Principled(Principled::unpack(data)),
+            Some(Hair::WGSL_NAME) => CpuMaterial::Hair(Hair::unpack(data)),
+            Some(Terrain::WGSL_NAME) => CpuMaterial::Terrain(data),
+            _ => CpuMaterial::Unsupported,
+        }
+    }
+
+    fn material_emit(&self, hit_record: &HitRecord) -> Vector3<f32> {
+        match self.material(hit_record) {
+            CpuMaterial::DebugNormal => hit_record.normal * 0.5 + Vector3::repeat(0.5),
+            CpuMaterial::DiffuseLight(data) => diffuse_light_emit(data, hit_record),
+            _ => Vector3::zeros(),
+        }
+    }
+
+    fn material_scatter(&self, sampler: &mut Sampler, ray_in: &Ray, hit_record: &HitRecord) -> Option<ScatterRecord> {
+        match self.material(hit_record) {
+            CpuMaterial::Lambertian(lambertian) => Some(ScatterRecord {
+                attenuation: lambertian.albedo.coords,
+                skip_pdf_ray: None,
+            }),
+            CpuMaterial::Dielectric(dielectric) => Some(dielectric_scatter(&dielectric, sampler, ray_in, hit_record)),
+            CpuMaterial::Principled(principled) => Some(principled_scatter(&principled, sampler, hit_record)),
+            CpuMaterial::Hair(hair) => Some(hair_scatter(&hair)),
+            CpuMaterial::Terrain(data) => Some(ScatterRecord {
+                attenuation: terrain_albedo(data, hit_record),
+                skip_pdf_ray: None,
+            }),
+            _ => None,
+        }
+    }
+
+    fn material_eval(&self, ray: &Ray, hit_record: &HitRecord, scatter_record: &ScatterRecord) -> Vector3<f32> {
+        match self.material(hit_record) {
+            CpuMaterial::Principled(principled) => principled_eval(&principled, ray, hit_record),
+            CpuMaterial::Hair(hair) => hair_eval(&hair, ray, hit_record),
+            _ => scatter_record.attenuation * self.material_scattering_pdf_value(ray, hit_record),
+        }
+    }
+
+    fn material_pdf_value(&self, ray: &Ray, hit_record: &HitRecord) -> f32 {
+        match self.material(hit_record) {
+            CpuMaterial::Lambertian(_) | CpuMaterial::Terrain(_) => {
+                let cosine_theta = ray.direction.normalize().dot(&hit_record.normal);
+                (cosine_theta / PI).max(0.0)
+            }
+            CpuMaterial::Principled(principled) => principled_pdf_value(&principled, ray, hit_record),
+            CpuMaterial::Hair(hair) => hair_pdf_value(&hair, ray, hit_record),
+            _ => 0.0,
+        }
+    }
+
+    fn material_random(&self, hit_record: &HitRecord, u: Vector2<f32>) -> Vector3<f32> {
+        match self.material(hit_record) {
+            CpuMaterial::Lambertian(_) | CpuMaterial::Terrain(_) => {
+                rotation_matrix(&Vector3::y(), &hit_record.normal) * random_cosine_direction(u)
+            }
+            CpuMaterial::Principled(principled) => principled_random(&principled, hit_record, u),
+            CpuMaterial::Hair(hair) => hair_random(&hair, hit_record, u),
+            _ => Vector3::zeros(),
+        }
+    }
+
+    fn material_scattering_pdf_value(&self, ray: &Ray, hit_record: &HitRecord) -> f32 {
+        match self.material(hit_record) {
+            CpuMaterial::Lambertian(_) | CpuMaterial::Terrain(_) => {
+                let cos_theta = hit_record.normal.dot(&ray.direction.normalize());
+                if cos_theta < 0.0 {
+                    0.0
+                } else {
+                    cos_theta / PI
+                }
+            }
+            _ => 0.0,
+        }
+    }
+}
+
+fn diffuse_light_emit(data: &[u32], hit_record: &HitRecord) -> Vector3<f32> {
+    let two_sided = data[DiffuseLight::FLAGS] & DiffuseLight::FLAG_TWO_SIDED != 0;
+    if !hit_record.is_front_face && !two_sided {
+        return Vector3::zeros();
+    }
+
+    let profile_len = data[DiffuseLight::PROFILE_LEN] as usize;
+    let profile: &[f32] = bytemuck::cast_slice(&data[DiffuseLight::PROFILE..DiffuseLight::PROFILE + profile_len]);
+    let cos_theta = (-hit_record.in_direction).dot(&hit_record.normal);
+    let mut radiance = unpack_point3(data, DiffuseLight::RADIANCE).coords * angular_profile(profile, cos_theta);
+
+    if data[DiffuseLight::FLAGS] & DiffuseLight::FLAG_TEXTURE != 0 {
+        let texture = &data[DiffuseLight::PROFILE + profile_len..];
+        let [r, g, b, _] = texture_sample(texture, hit_record.uv.x, hit_record.uv.y);
+        radiance.component_mul_assign(&Vector3::new(r, g, b).map(srgb_to_linear));
+    }
+    radiance
+}
+
+// 与 Terrain_albedo 一致
+fn terrain_albedo(data: &[u32], hit_record: &HitRecord) -> Vector3<f32> {
+    let height = hit_record.position.y;
+    let slope = hit_record.normal.y.abs().clamp(0.0, 1.0).acos().to_degrees();
+    let height_blend = unpack_f32(data, Terrain::HEIGHT_BLEND);
+    let slope_blend = unpack_f32(data, Terrain::SLOPE_BLEND);
+
+    let mut albedo = Vector3::zeros();
+    for i in 0..data[Terrain::LAYER_COUNT] as usize {
+        let layer = Terrain::LAYERS + i * Terrain::LAYER_SIZE;
+        let mut color = unpack_point3(data, layer + Terrain::LAYER_COLOR).coords;
+        let texture = data[layer + Terrain::LAYER_TEXTURE] as usize;
+        if texture != 0 {
+            let uv = hit_record.uv * unpack_f32(data, layer + Terrain::LAYER_TEXTURE_SCALE);
+            let [r, g, b, _] = texture_sample(&data[texture..], uv.x, uv.y);
+            color.component_mul_assign(&Vector3::new(r, g, b).map(srgb_to_linear));
+        }
+
+        let weight = if i > 0 {
+            terrain_ramp(
+                height - unpack_f32(data, layer + Terrain::LAYER_MIN_HEIGHT),
+                height_blend,
+            ) * terrain_ramp(
+                unpack_f32(data, layer + Terrain::LAYER_MAX_HEIGHT) - height,
+                height_blend,
+            ) * terrain_ramp(slope - unpack_f32(data, layer + Terrain::LAYER_MIN_SLOPE), slope_blend)
+                * terrain_ramp(unpack_f32(data, layer + Terrain::LAYER_MAX_SLOPE) - slope, slope_blend)
+        } else {
+            1.0
+        };
+        albedo = albedo.lerp(&color, weight);
+    }
+    albedo
+}
+
+// 与 Terrain_ramp 一致
+fn terrain_ramp(x: f32, blend: f32) -> f32 {
+    let b = blend.max(1e-6);
+    let t = x.clamp(-b, b) / (2.0 * b) + 0.5;
+    t * t * (3.0 - 2.0 * t)
+}
+
+fn dielectric_scatter(
+    dielectric: &Dielectric,
+    sampler: &mut Sampler,
+    ray_in: &Ray,
+    hit_record: &HitRecord,
+) -> ScatterRecord {
+    let index = dielectric.reflection_index;
+    let refraction_index = if hit_record.is_front_face { 1.0 / index } else { index };
+
+    let in_direction = ray_in.direction.normalize();
+    let cos_theta = (-in_direction).dot(&hit_record.normal).min(1.0);
+    let sin_theta = (1.0 - cos_theta.powi(2)).sqrt();
+
+    let cannot_refract = refraction_index * sin_theta > 1.0;
+
+    let out_direction = if cannot_refract || reflectance(index, cos_theta) > sampler.sample_1d() {
+        reflect(&in_direction, &hit_record.normal)
+    } else {
+        refract(&in_direction, &hit_record.normal, refraction_index)
+    };
+
+    ScatterRecord {
+        attenuation: Vector3::repeat(1.0),
+        skip_pdf_ray: Some(Ray::new(hit_record.position, out_direction)),
+    }
+}
+
+fn resolve_ray_color(stack: &[RayColorEntry], last_color: Vector3<f32>) -> Vector3<f32> {
+    stack.iter().rev().fold(last_color, |color, entry| match entry {
+        RayColorEntry::SkipPdf { attenuation } => attenuation.component_mul(&color),
+        RayColorEntry::Pdf {
+            color_from_emission,
+            attenuation,
+            pdf_value,
+        } => color_from_emission + attenuation.component_mul(&color) / *pdf_value,
+    })
+}
+
+pub(super) fn random_cosine_direction(u: Vector2<f32>) -> Vector3<f32> {
+    let phi = 2.0 * PI * u.x;
+    let z = phi.cos() * u.y.sqrt();
+    let x = phi.sin() * u.y.sqrt();
+    let y = (1.0 - u.y).sqrt();
+
+    Vector3::new(x, y, z)
+}
+
+fn sample_unit_disk(u: Vector2<f32>) -> Vector2<f32> {
+    let phi = 2.0 * PI * u.x;
+    let r = u.y.sqrt();
+    Vector2::new(phi.cos(), phi.sin()) * r
+}
+
+// Schlick 近似，与 Dielectric_reflectance 一致
+fn reflectance(refraction_index: f32, cosine: f32) -> f32 {
+    let r0 = ((1.0 - refraction_index) / (1.0 + refraction_index)).powi(2);
+    r0 + (1.0 - r0) * (1.0 - cosine).powi(5)
+}
+
+pub(super) fn reflect(v: &Vector3<f32>, n: &Vector3<f32>) -> Vector3<f32> {
+    v - 2.0 * v.dot(n) * n
+}
+
+pub(super) fn refract(uv: &Vector3<f32>, n: &Vector3<f32>, etai_over_etat: f32) -> Vector3<f32> {
+    let cos_theta = (-uv).dot(n).min(1.0);
+    let r_out_perp = etai_over_etat * (uv + cos_theta * n);
+    let r_out_parallel = -(1.0 - r_out_perp.norm_squared()).abs().sqrt() * n;
+    r_out_perp + r_out_parallel
+}
+
+fn srgb_to_linear(c: f32) -> f32 {
+    if c < 0.04045 {
+        c / 12.92
+    } else {
+        ((c + 0.055) / 1.055).powf(2.4)
+    }
+}
+
+pub fn linear_to_srgb(color: &Vector3<f32>) -> Vector3<f32> {
+    color.map(|c| {
+        if c < 0.0031308 {
+            c * 12.92
+        } else {
+            1.055 * c.powf(1.0 / 2.4) - 0.055
+        }
+    })
+}
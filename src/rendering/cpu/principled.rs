@@ -0,0 +1,191 @@
+// 与 src/shader/material/principled.wgsl 一一对应
+use crate::rendering::cpu::hit::{rotation_matrix, HitRecord, Ray};
+use crate::rendering::cpu::path_tracer::{random_cosine_direction, reflect, refract, ScatterRecord};
+use crate::rendering::cpu::sampler::Sampler;
+use crate::rendering::material::Principled;
+use nalgebra::{Vector2, Vector3};
+use std::f32::consts::PI;
+
+pub(super) fn principled_scatter(
+    principled: &Principled,
+    sampler: &mut Sampler,
+    hit_record: &HitRecord,
+) -> ScatterRecord {
+    let base_color = principled.base_color.coords;
+
+    // 固定消耗一个维度，选中透射时重新映射到 [0, 1) 用于菲涅尔项
+    let u = sampler.sample_1d();
+    let transmission_weight = (1.0 - principled.metallic) * principled.transmission;
+    if u >= transmission_weight {
+        return ScatterRecord {
+            attenuation: base_color,
+            skip_pdf_ray: None,
+        };
+    }
+
+    let ior = principled.ior;
+    let refraction_index = if hit_record.is_front_face { 1.0 / ior } else { ior };
+
+    let in_direction = hit_record.in_direction;
+    let cos_theta = (-in_direction).dot(&hit_record.normal).min(1.0);
+    let sin_theta = (1.0 - cos_theta.powi(2)).sqrt();
+    let cannot_refract = refraction_index * sin_theta > 1.0;
+
+    let r0 = ((1.0 - ior) / (1.0 + ior)).powi(2);
+    let reflectance = r0 + (1.0 - r0) * schlick_weight(cos_theta);
+
+    let (out_direction, attenuation) = if cannot_refract || reflectance > u / transmission_weight {
+        (reflect(&in_direction, &hit_record.normal), Vector3::repeat(1.0))
+    } else {
+        // 只在进入物体时染色
+        let attenuation = if hit_record.is_front_face {
+            base_color
+        } else {
+            Vector3::repeat(1.0)
+        };
+        (
+            refract(&in_direction, &hit_record.normal, refraction_index),
+            attenuation,
+        )
+    };
+
+    ScatterRecord {
+        attenuation,
+        skip_pdf_ray: Some(Ray::new(hit_record.position, out_direction)),
+    }
+}
+
+pub(super) fn principled_eval(principled: &Principled, ray: &Ray, hit_record: &HitRecord) -> Vector3<f32> {
+    let n = hit_record.normal;
+    let v = -hit_record.in_direction;
+    let l = ray.direction.normalize();
+    let nl = n.dot(&l);
+    let nv = n.dot(&v);
+    if nl <= 0.0 || nv <= 0.0 {
+        return Vector3::zeros();
+    }
+    let h = (l + v).normalize();
+    let nh = n.dot(&h);
+    let lh = l.dot(&h);
+
+    let base_color = principled.base_color.coords;
+    let tint = tint(&base_color);
+    let white = Vector3::repeat(1.0);
+    let fh = schlick_weight(lh);
+
+    let fd90 = 0.5 + 2.0 * lh * lh * principled.roughness;
+    let fd = mix(1.0, fd90, schlick_weight(nl)) * mix(1.0, fd90, schlick_weight(nv));
+    let diffuse = base_color / PI * fd;
+    let sheen = principled.sheen * white.lerp(&tint, principled.sheen_tint) * fh;
+
+    let specular_color = 0.08 * principled.specular * white.lerp(&tint, principled.specular_tint);
+    let f0 = specular_color.lerp(&base_color, principled.metallic);
+    let f = f0.lerp(&white, fh);
+    let alpha = alpha(principled.roughness);
+    let g = smith_g(nl, alpha) * smith_g(nv, alpha);
+    let specular = f * gtr2(nh, alpha) * g / (4.0 * nl * nv);
+
+    let fc = mix(0.04, 1.0, fh) * principled.clearcoat;
+    let alpha_clearcoat = clearcoat_alpha(principled.clearcoat_roughness);
+    let gc = smith_g(nl, 0.25) * smith_g(nv, 0.25);
+    let clearcoat = fc * gtr1(nh, alpha_clearcoat) * gc / (4.0 * nl * nv);
+
+    let base = (1.0 - principled.metallic) * (white - f).component_mul(&(diffuse + sheen)) + specular;
+    ((1.0 - fc) * base + Vector3::repeat(clearcoat)) * nl
+}
+
+pub(super) fn principled_pdf_value(principled: &Principled, ray: &Ray, hit_record: &HitRecord) -> f32 {
+    let n = hit_record.normal;
+    let v = -hit_record.in_direction;
+    let l = ray.direction.normalize();
+    let nl = n.dot(&l);
+    if nl <= 0.0 {
+        return 0.0;
+    }
+    let h = (l + v).normalize();
+    let nh = n.dot(&h);
+    let vh = v.dot(&h);
+    if vh <= 0.0 {
+        return 0.0;
+    }
+
+    let weights = lobe_weights(principled);
+    let alpha = alpha(principled.roughness);
+    let alpha_clearcoat = clearcoat_alpha(principled.clearcoat_roughness);
+    weights.x * nl / PI
+        + weights.y * gtr2(nh, alpha) * nh / (4.0 * vh)
+        + weights.z * gtr1(nh, alpha_clearcoat) * nh / (4.0 * vh)
+}
+
+pub(super) fn principled_random(principled: &Principled, hit_record: &HitRecord, u: Vector2<f32>) -> Vector3<f32> {
+    let frame = rotation_matrix(&Vector3::y(), &hit_record.normal);
+
+    let weights = lobe_weights(principled);
+    let mut x = u.x;
+    if x < weights.x {
+        return frame * random_cosine_direction(Vector2::new(x / weights.x, u.y));
+    }
+    x -= weights.x;
+
+    let phi = 2.0 * PI * u.y;
+    let cos_theta = if x < weights.y || weights.z <= 0.0 {
+        let x = (x / weights.y).min(1.0);
+        let a2 = alpha(principled.roughness).powi(2);
+        ((1.0 - x) / (1.0 + (a2 - 1.0) * x)).sqrt()
+    } else {
+        let x = ((x - weights.y) / weights.z).min(1.0);
+        let a2 = clearcoat_alpha(principled.clearcoat_roughness).powi(2);
+        ((1.0 - a2.powf(1.0 - x)) / (1.0 - a2)).sqrt()
+    };
+    let sin_theta = (1.0 - cos_theta * cos_theta).max(0.0).sqrt();
+    let h = frame * Vector3::new(sin_theta * phi.sin(), cos_theta, sin_theta * phi.cos());
+
+    reflect(&hit_record.in_direction, &h)
+}
+
+fn lobe_weights(principled: &Principled) -> Vector3<f32> {
+    let weights = Vector3::new(1.0 - principled.metallic, 1.0, 0.25 * principled.clearcoat);
+    weights / weights.sum()
+}
+
+fn alpha(roughness: f32) -> f32 {
+    (roughness * roughness).max(0.001)
+}
+
+fn clearcoat_alpha(clearcoat_roughness: f32) -> f32 {
+    mix(0.001, 0.1, clearcoat_roughness)
+}
+
+fn tint(base_color: &Vector3<f32>) -> Vector3<f32> {
+    let luminance = base_color.dot(&Vector3::new(0.3, 0.6, 0.1));
+    if luminance > 0.0 {
+        base_color / luminance
+    } else {
+        Vector3::repeat(1.0)
+    }
+}
+
+fn schlick_weight(cosine: f32) -> f32 {
+    (1.0 - cosine).clamp(0.0, 1.0).powi(5)
+}
+
+fn gtr1(nh: f32, alpha: f32) -> f32 {
+    let a2 = alpha * alpha;
+    let t = 1.0 + (a2 - 1.0) * nh * nh;
+    (a2 - 1.0) / (PI * a2.ln() * t)
+}
+
+fn gtr2(nh: f32, alpha: f32) -> f32 {
+    let a2 = alpha * alpha;
+    let t = 1.0 + (a2 - 1.0) * nh * nh;
+    a2 / (PI * t * t)
+}
+
+fn smith_g(cosine: f32, alpha: f32) -> f32 {
+    let a2 = alpha * alpha;
+    2.0 * cosine / (cosine + (a2 + (1.0 - a2) * cosine * cosine).sqrt())
+}
+
+fn mix(x: f32, y: f32, a: f32) -> f32 {
+    x + (y - x) * a
+}
@@ -0,0 +1,224 @@
+use crate::rendering::SamplerType;
+use nalgebra::Vector2;
+
+const F32_POSITIVE_MIN: f32 = f32::MIN_POSITIVE;
+
+const HALTON_DIMENSIONS: u32 = 16;
+
+// 与 ray_tracing.wgsl 中的 BLUE_NOISE 相同
+const BLUE_NOISE_SIZE: u32 = 16;
+#[rustfmt::skip]
+const BLUE_NOISE: [u32; 256] = [
+    0x0a78, 0xb13d, 0x5386, 0x9bdf, 0x3b54, 0x9021, 0xb0a8, 0xf60c, 0x3a71, 0x0ee1, 0xe83f, 0xbbf6, 0xd4b9, 0x34e9, 0xf158, 0xc9a9,
+    0x8317, 0xface, 0x1fb5, 0xcd11, 0xbe6d, 0x10d6, 0x473a, 0xc78c, 0xa1c9, 0x6618, 0x87a1, 0x415d, 0x0422, 0x9c85, 0x630e, 0x4edd,
+    0xa390, 0x3549, 0x7dfa, 0x6431, 0xf29e, 0x82bb, 0x6051, 0x20fb, 0xde64, 0x1933, 0xfb8e, 0xa4d2, 0x76ac, 0xe039, 0x18bf, 0xbf6a,
+    0xe52a, 0x57a7, 0xd865, 0x127e, 0x2fdc, 0xa703, 0xea79, 0x7928, 0x95aa, 0x54e7, 0xb452, 0x2108, 0x5972, 0xc4fe, 0x9150, 0x2ae8,
+    0x01d4, 0x8a0b, 0xb7c3, 0x991f, 0x4b48, 0xd2ef, 0x0398, 0x3fc4, 0xc110, 0x307f, 0x73bc, 0xd3de, 0x372d, 0xec9d, 0x461a, 0x7180,
+    0xc89a, 0x4257, 0x24eb, 0xf58f, 0x6cb3, 0xba5e, 0x5a36, 0xa26c, 0xdbed, 0x1341, 0xef1d, 0x8d69, 0x078b, 0x7fcf, 0xb2b8, 0xfd42,
+    0x9ef8, 0x612f, 0xdc73, 0x7a3e, 0x0fd1, 0x8e14, 0x28a4, 0xffd9, 0x854f, 0x6292, 0xbcb2, 0x4df3, 0xad45, 0x685a, 0x1500, 0x3276,
+    0x841e, 0x0cbe, 0xafad, 0x3606, 0xcb83, 0xe6ff, 0x4829, 0x7088, 0x0b0a, 0x3ccc, 0xa02b, 0x1e9f, 0xe716, 0xcfe5, 0x55a2, 0xdfda,
+    0x6d4d, 0xeb94, 0x5163, 0x9ae2, 0x5f4a, 0x1ab6, 0xa975, 0xc5c0, 0x9656, 0xd7f7, 0xf777, 0x7861, 0x38c5, 0x9782, 0x2735, 0xab67,
+    0x1df2, 0xc613, 0x2cc6, 0xf92c, 0xbd9b, 0x8060, 0x333b, 0xede6, 0x501c, 0x2ba5, 0x5e3c, 0x0d05, 0xc3f0, 0x8827, 0xf4af, 0x45ca,
+    0xb689, 0x8f40, 0x7c7a, 0x05ee, 0x4319, 0xddd3, 0x6a01, 0x0895, 0xb368, 0x8be0, 0xca87, 0xa8b7, 0x6997, 0x5247, 0x0270, 0xd609,
+    0x395b, 0x4fd5, 0xe3a6, 0x6755, 0x94ba, 0x226f, 0x9df9, 0xd0ae, 0x7b30, 0x1c4b, 0x44d0, 0xee20, 0x2559, 0xe4cd, 0x9fec, 0x72a0,
+    0xfe25, 0xa6fc, 0x1112, 0xce37, 0xb58a, 0xf326, 0x584e, 0x3e7b, 0xfcc2, 0x650d, 0xda6b, 0x92fd, 0x3d7c, 0xb90f, 0x7e38, 0x14bd,
+    0xc04c, 0x5b91, 0x866e, 0x31e4, 0x4acb, 0x17a3, 0x89db, 0xa515, 0x2ef1, 0xb88d, 0x16ab, 0x7532, 0x099c, 0xd1e3, 0x5d66, 0x2d81,
+    0x9302, 0x23c7, 0xf0b0, 0xac44, 0x7707, 0xd962, 0xc234, 0x0096, 0xe25c, 0x4c24, 0x98d7, 0xae53, 0xf8c8, 0x491b, 0xaab1, 0xe1d8,
+    0x40f4, 0xd55f, 0x6e23, 0x0699, 0xe9f5, 0x5c7d, 0x29c1, 0x6fea, 0x8146, 0xccb4, 0x5684, 0x2604, 0x6b74, 0x8c43, 0x1b93, 0x742e,
+];
+const HALTON_PRIMES: [u32; 32] = [
+    2, 3, 5, 7, 11, 13, 17, 19, 23, 29, 31, 37, 41, 43, 47, 53, 59, 61, 67, 71, 73, 79, 83, 89, 97, 101, 103, 107, 109,
+    113, 127, 131,
+];
+
+// 对应 ray_tracing.wgsl 中的 SamplerState 和 Sampler_* 函数
+#[derive(Clone, Debug)]
+pub struct Sampler {
+    sampler_type: SamplerType,
+    seed: u32,
+    width: u32,
+    pixel_seed: u32,
+    pixel: Vector2<u32>,
+    sample_index: u32,
+    dimension: u32,
+    random: RandomGenerator,
+}
+
+impl Sampler {
+    // width 是图像的宽度，用来从 pixel_index 求出蓝噪声瓦片中的位置
+    pub fn new(sampler_type: SamplerType, seed: u32, width: u32) -> Self {
+        Self {
+            sampler_type,
+            seed,
+            width,
+            pixel_seed: 0,
+            pixel: Vector2::zeros(),
+            sample_index: 0,
+            dimension: 0,
+            random: RandomGenerator::default(),
+        }
+    }
+
+    pub fn start(&mut self, pixel_index: u32, sample_index: u32) {
+        self.pixel_seed = hash_u32(pixel_index ^ hash_u32(self.seed));
+        self.pixel = Vector2::new(pixel_index % self.width, pixel_index / self.width);
+        self.sample_index = sample_index;
+        self.dimension = 0;
+        self.random = RandomGenerator::new(hash_u32(self.pixel_seed ^ hash_u32(sample_index.wrapping_add(1))));
+    }
+
+    pub fn sampler_type(&self) -> SamplerType {
+        self.sampler_type
+    }
+
+    // 不占用维度的伪随机数，对应 WGSL 中直接调用的 randomf
+    pub fn random(&mut self) -> f32 {
+        self.random.next_f32()
+    }
+
+    pub fn sample_1d(&mut self) -> f32 {
+        self.sample_2d().x
+    }
+
+    pub fn sample_2d(&mut self) -> Vector2<f32> {
+        let dimension = self.dimension;
+        self.dimension += 1;
+
+        match self.sampler_type {
+            SamplerType::Independent => {
+                let x = self.random.next_f32();
+                let y = self.random.next_f32();
+                Vector2::new(x, y)
+            }
+            SamplerType::Sobol => self.sobol(dimension),
+            SamplerType::Halton => self.halton(dimension),
+            SamplerType::Rank1Lattice => self.rank1_lattice(dimension),
+        }
+    }
+
+    // Burley, Practical Hash-based Owen Scrambling, JCGT 2020
+    fn sobol(&self, dimension: u32) -> Vector2<f32> {
+        let seed = hash_u32(self.pixel_seed ^ hash_u32(dimension));
+        let index = nested_uniform_scramble(self.sample_index, seed);
+        let x = nested_uniform_scramble(index.reverse_bits(), hash_u32(seed ^ 0x9e3779b9));
+        let y = nested_uniform_scramble(sobol_dimension1(index), hash_u32(seed ^ 0x85ebca6b));
+        Vector2::new(u32_to_unit_float(x), u32_to_unit_float(y))
+    }
+
+    fn halton(&self, dimension: u32) -> Vector2<f32> {
+        if dimension >= HALTON_DIMENSIONS {
+            return self.sobol(dimension);
+        }
+
+        let pair = dimension as usize;
+        let x = radical_inverse(HALTON_PRIMES[2 * pair], self.sample_index);
+        let y = radical_inverse(HALTON_PRIMES[2 * pair + 1], self.sample_index);
+
+        let seed = hash_u32(self.pixel_seed ^ hash_u32(dimension));
+        let rotation = Vector2::new(u32_to_unit_float(seed), u32_to_unit_float(hash_u32(seed)));
+        Vector2::new(fract(x + rotation.x), fract(y + rotation.y))
+    }
+
+    fn rank1_lattice(&self, dimension: u32) -> Vector2<f32> {
+        let seed = hash_u32(hash_u32(self.seed) ^ hash_u32(dimension));
+        let index = nested_uniform_scramble(self.sample_index, seed);
+
+        let offset = hash_u32(seed ^ 0x68e31da4);
+        let texel_x = (self.pixel.x.wrapping_add(offset)) % BLUE_NOISE_SIZE;
+        let texel_y = (self.pixel.y.wrapping_add(offset >> 8)) % BLUE_NOISE_SIZE;
+        let noise = BLUE_NOISE[(texel_y * BLUE_NOISE_SIZE + texel_x) as usize];
+        let dither = ((noise & 0xff) << 24, (noise >> 8) << 24);
+
+        let x = index
+            .wrapping_mul(3242174889)
+            .wrapping_add(hash_u32(seed ^ 0x9e3779b9).wrapping_add(dither.0));
+        let y = index
+            .wrapping_mul(2447445414)
+            .wrapping_add(hash_u32(seed ^ 0x85ebca6b).wrapping_add(dither.1));
+        Vector2::new(u32_to_unit_float(x), u32_to_unit_float(y))
+    }
+}
+
+fn sobol_dimension1(index: u32) -> u32 {
+    let mut v = 1u32 << 31;
+    let mut x = 0;
+    let mut i = index;
+    while i != 0 {
+        if i & 1 == 1 {
+            x ^= v;
+        }
+        i >>= 1;
+        v ^= v >> 1;
+    }
+    x
+}
+
+fn nested_uniform_scramble(x: u32, seed: u32) -> u32 {
+    laine_karras_permutation(x.reverse_bits(), seed).reverse_bits()
+}
+
+fn laine_karras_permutation(value: u32, seed: u32) -> u32 {
+    let mut x = value.wrapping_add(seed);
+    x ^= x.wrapping_mul(0x6c50b47c);
+    x ^= x.wrapping_mul(0xb82f1e52);
+    x ^= x.wrapping_mul(0xc7afe638);
+    x ^= x.wrapping_mul(0x8d22f6e6);
+    x
+}
+
+fn radical_inverse(base: u32, index: u32) -> f32 {
+    let inverse_base = 1.0 / base as f32;
+    let mut i = index;
+    let mut factor = inverse_base;
+    let mut result = 0.0;
+    while i > 0 {
+        result += (i % base) as f32 * factor;
+        i /= base;
+        factor *= inverse_base;
+    }
+    result.min(1.0 - f32::EPSILON / 2.0)
+}
+
+fn fract(x: f32) -> f32 {
+    x - x.floor()
+}
+
+pub fn hash_u32(value: u32) -> u32 {
+    let state = value.wrapping_mul(747796405).wrapping_add(2891336453);
+    let word = ((state >> ((state >> 28) + 4)) ^ state).wrapping_mul(277803737);
+    (word >> 22) ^ word
+}
+
+pub fn u32_to_unit_float(x: u32) -> f32 {
+    (x >> 8) as f32 * (1.0 / (1u32 << 24) as f32)
+}
+
+// Hybrid Tausworthe Generator, https://indico.cern.ch/event/93877/papers/2118070/files/4416-acat3.pdf
+#[derive(Clone, Debug, Default)]
+struct RandomGenerator {
+    r: u32,
+}
+
+impl RandomGenerator {
+    fn new(id: u32) -> Self {
+        Self {
+            r: id.wrapping_mul(1099087573),
+        }
+    }
+
+    fn next_f32(&mut self) -> f32 {
+        let z1 = taus_step(self.r, 13, 19, 12, 4294967294);
+        let z2 = taus_step(self.r, 2, 25, 4, 4294967288);
+        let z3 = taus_step(self.r, 3, 11, 17, 4294967280);
+        let z4 = self.r.wrapping_mul(1664525).wrapping_add(1013904223);
+        self.r = z1 ^ z2 ^ z3 ^ z4;
+
+        let value = 2.328_306_4e-10_f32 * self.r as f32; // [0, 1]
+        (value - F32_POSITIVE_MIN).clamp(0.0, 1.0)
+    }
+}
+
+fn taus_step(z: u32, s1: u32, s2: u32, s3: u32, m: u32) -> u32 {
+    let b = ((z << s1) ^ z) >> s2;
+    ((z & m) << s3) ^ b
+}
@@ -1,3 +1,4 @@
+use crate::rendering::layout::wgsl_layout;
 use bytemuck::{Pod, Zeroable};
 use getset::Getters;
 
@@ -11,6 +12,8 @@ pub struct Interval {
     _padding: [u32; 2],
 }
 
+wgsl_layout!(Interval, "Interval", [min, max, _padding]);
+
 impl Interval {
     pub const fn empty() -> Self {
         const EMPTY: Interval = Interval {
@@ -0,0 +1,56 @@
+// 上传到 GPU 的 Rust 结构体与 ray_tracing.wgsl 中结构体的对应关系。
+// 两边的填充都是手写的，tests/shader_layout.rs 用 naga 解析着色器，检查大小、对齐、字段偏移和绑定顺序是否一致
+use bytemuck::Pod;
+
+// ray_tracing.wgsl 中 @group(0) 各个绑定的变量名，下标就是 @binding 的值。Renderer 按这个顺序创建绑定组
+pub const RAY_TRACING_BINDINGS: [&str; 9] = [
+    "context",
+    "pixel_color",
+    "pixel_statistics",
+    "adaptive_counters",
+    "bvh_tree",
+    "importance",
+    "primitives",
+    "materials",
+    "surface",
+];
+
+pub trait WgslLayout: Pod {
+    // WGSL 中对应的结构体名
+    const WGSL_NAME: &'static str;
+
+    // WGSL 结构体的大小。之后紧跟数组的结构体（如 SdfData 的节点）只算数组之前的部分，数组在 WGSL 中按需逐个读取
+    const WGSL_SIZE: usize;
+
+    // 按 WGSL 成员顺序排列的 (WGSL 成员名, Rust 字段偏移)，不包含 Rust 侧末尾补齐用的填充字段
+    fn wgsl_members() -> Vec<(&'static str, usize)>;
+}
+
+// wgsl_layout!(Rust 类型, "WGSL 结构体名", [字段, 字段 as "WGSL 中的成员名", ...], 紧跟在结构体之后的数组字段)
+// 最后一项可以省略。需要在结构体所在模块中使用，offset_of! 才能访问私有字段
+macro_rules! wgsl_layout {
+    ($type:ty, $wgsl_name:literal, [$($field:ident $(as $member:literal)?),* $(,)?] $(, $trailing:ident)?) => {
+        impl $crate::rendering::layout::WgslLayout for $type {
+            const WGSL_NAME: &'static str = $wgsl_name;
+            const WGSL_SIZE: usize = wgsl_layout!(@size $type $(, $trailing)?);
+
+            fn wgsl_members() -> Vec<(&'static str, usize)> {
+                vec![$((wgsl_layout!(@member $field $($member)?), std::mem::offset_of!($type, $field))),*]
+            }
+        }
+    };
+    (@member $field:ident $member:literal) => {
+        $member
+    };
+    (@member $field:ident) => {
+        stringify!($field)
+    };
+    (@size $type:ty) => {
+        std::mem::size_of::<$type>()
+    };
+    (@size $type:ty, $trailing:ident) => {
+        std::mem::offset_of!($type, $trailing)
+    };
+}
+
+pub(crate) use wgsl_layout;
@@ -1,125 +1,365 @@
-use bytemuck::{Pod, Zeroable};
 use getset::Getters;
 use nalgebra::Point3;
-use std::{any::Any, collections::HashMap};
-
-#[repr(u32)]
-#[derive(Copy, Clone, Hash, PartialEq, Eq)]
-pub enum MaterialType {
-    DebugNormal,
-    Lambertian,
-    DiffuseLight,
-    Dielectric,
+use std::fmt::Write;
+use std::ops::RangeInclusive;
+
+pub mod alpha_mask;
+pub mod debug_normal;
+pub mod dielectric;
+pub mod diffuse_light;
+pub mod hair;
+pub mod import;
+pub mod lambertian;
+pub mod normal_map;
+pub mod principled;
+pub mod terrain;
+pub mod texture;
+
+pub use alpha_mask::*;
+pub use debug_normal::*;
+pub use dielectric::*;
+pub use diffuse_light::*;
+pub use hair::*;
+pub use lambertian::*;
+pub use normal_map::*;
+pub use principled::*;
+pub use terrain::*;
+pub use texture::*;
+
+// 材质可以在 WGSL 中实现的函数。生成的 Material_{name} 按 hit_record.material_type 分派到 {wgsl_name}_{name}，
+// 没有实现的材质返回默认值
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum MaterialFunction {
+    Scatter,
+    ScatteringPdfValue,
+    // BSDF 与余弦项的乘积。没有实现时用 Scatter 给出的衰减乘以 ScatteringPdfValue
+    Eval,
+    Emit,
+    PdfValue,
+    Random,
+}
+
+impl MaterialFunction {
+    pub const ALL: [MaterialFunction; 6] = [
+        MaterialFunction::Scatter,
+        MaterialFunction::ScatteringPdfValue,
+        MaterialFunction::Eval,
+        MaterialFunction::Emit,
+        MaterialFunction::PdfValue,
+        MaterialFunction::Random,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            MaterialFunction::Scatter => "scatter",
+            MaterialFunction::ScatteringPdfValue => "scattering_pdf_value",
+            MaterialFunction::Eval => "eval",
+            MaterialFunction::Emit => "emit",
+            MaterialFunction::PdfValue => "pdf_value",
+            MaterialFunction::Random => "random",
+        }
+    }
+
+    fn parameters(&self) -> &'static [(&'static str, &'static str)] {
+        match self {
+            MaterialFunction::Scatter => &[
+                ("ray_in", "ptr<function, Ray>"),
+                ("hit_record", "ptr<function, HitRecord>"),
+                ("scatter_record", "ptr<function, ScatterRecord>"),
+            ],
+            MaterialFunction::Eval => &[
+                ("ray", "ptr<function, Ray>"),
+                ("hit_record", "ptr<function, HitRecord>"),
+                ("scatter_record", "ptr<function, ScatterRecord>"),
+            ],
+            MaterialFunction::ScatteringPdfValue | MaterialFunction::PdfValue => &[
+                ("ray", "ptr<function, Ray>"),
+                ("hit_record", "ptr<function, HitRecord>"),
+            ],
+            MaterialFunction::Emit => &[
+                ("ray_in", "ptr<function, Ray>"),
+                ("hit_record", "ptr<function, HitRecord>"),
+            ],
+            MaterialFunction::Random => &[
+                ("origin", "ptr<function, vec3f>"),
+                ("hit_record", "ptr<function, HitRecord>"),
+                ("u", "vec2f"),
+            ],
+        }
+    }
+
+    fn return_type(&self) -> &'static str {
+        match self {
+            MaterialFunction::Scatter => "bool",
+            MaterialFunction::ScatteringPdfValue | MaterialFunction::PdfValue => "f32",
+            MaterialFunction::Eval | MaterialFunction::Emit | MaterialFunction::Random => "vec3f",
+        }
+    }
+
+    fn default_value(&self) -> &'static str {
+        match self {
+            MaterialFunction::Scatter => "false",
+            MaterialFunction::ScatteringPdfValue | MaterialFunction::PdfValue => "0.0",
+            MaterialFunction::Eval => "(*scatter_record).attenuation * Material_scattering_pdf_value(ray, hit_record)",
+            MaterialFunction::Emit | MaterialFunction::Random => "VEC3F_ZEROS",
+        }
+    }
+}
+
+// 可以在 GPU 上使用的材质。同一个 wgsl_name 的材质共享一个类型标签和一份 WGSL 代码，
+// 数据按 4 字节一个字打包进同一个 storage buffer，新材质不需要改动渲染器和绑定。
+// 每个材质的数据之前有两个字，依次是它的透明度贴图和法线贴图数据的下标，0 表示没有
+pub trait GpuMaterial {
+    // WGSL 中的结构体名，也是材质函数的前缀
+    fn wgsl_name(&self) -> &'static str;
+
+    // 结构体定义、{wgsl_name}_load 以及 wgsl_functions 中列出的函数，
+    // 函数从 (*hit_record).material_id 开始读取 pack 写入的数据
+    fn wgsl_source(&self) -> &'static str;
+
+    fn wgsl_functions(&self) -> &'static [MaterialFunction];
+
+    fn pack(&self, data: &mut Vec<u32>);
+
+    // 可以在界面上编辑的参数。修改后打包的长度不能变
+    fn parameters(&mut self) -> Vec<MaterialParameter<'_>> {
+        Vec::new()
+    }
 }
 
-pub trait Material {
-    fn material_type(&self) -> MaterialType;
-    fn as_any(&self) -> &dyn Any;
+pub enum MaterialParameter<'a> {
+    Scalar {
+        name: &'static str,
+        value: &'a mut f32,
+        range: RangeInclusive<f32>,
+    },
+    // 线性空间的颜色，分量在 [0, 1] 内
+    Color {
+        name: &'static str,
+        value: &'a mut Point3<f32>,
+    },
+    Toggle {
+        name: &'static str,
+        value: &'a mut bool,
+    },
 }
 
 #[derive(Clone, Copy, Debug)]
 pub struct MaterialHandle {
-    pub material_type: u32,
-    pub material_id: u32,
+    pub material_type: u32, // 类型标签，由 MaterialRegistry 按注册顺序分配
+    pub material_id: u32,   // 数据在打包后的数组中的起始下标，前两个字是透明度贴图和法线贴图的下标
+}
+
+// 与材质类型无关、可以挂在任意材质上的贴图，打包在材质数据之后
+#[derive(Clone, Debug, Default)]
+pub struct SurfaceMaps {
+    pub alpha_mask: Option<AlphaMask>,
+    pub normal_map: Option<NormalMap>,
+}
+
+impl SurfaceMaps {
+    fn packed_len(&self) -> usize {
+        self.alpha_mask.as_ref().map_or(0, AlphaMask::packed_len)
+            + self.normal_map.as_ref().map_or(0, NormalMap::packed_len)
+    }
+}
+
+struct MaterialKind {
+    name: &'static str,
+    source: &'static str,
+    functions: &'static [MaterialFunction],
 }
 
 #[derive(Default, Getters)]
-pub struct MaterialList {
+pub struct MaterialRegistry {
+    kinds: Vec<MaterialKind>,
     #[getset(get = "pub")]
-    map: HashMap<MaterialType, Vec<Box<dyn Material>>>,
+    materials: Vec<(MaterialHandle, Box<dyn GpuMaterial>)>,
+    maps: Vec<SurfaceMaps>, // 与 materials 一一对应
+    packed: Vec<Vec<u32>>,  // 与 materials 一一对应，每个材质最近一次长度正确的打包数据
+    len: u32,
+    changed: bool, // 上一次 take_changed 之后是否添加或修改了材质
 }
 
-impl MaterialList {
-    pub fn add(&mut self, material: Box<dyn Material>) -> MaterialHandle {
-        self.map.entry(material.material_type()).or_default();
-        let vec = self.map.get_mut(&material.material_type()).unwrap();
-        let handler = MaterialHandle {
-            material_type: material.material_type() as u32,
-            material_id: vec.len() as u32,
-        };
-        vec.push(material);
-        handler
+impl MaterialRegistry {
+    pub fn add(&mut self, material: Box<dyn GpuMaterial>) -> MaterialHandle {
+        self.add_with_maps(material, SurfaceMaps::default())
+    }
+
+    pub fn add_with_normal_map(&mut self, material: Box<dyn GpuMaterial>, normal_map: NormalMap) -> MaterialHandle {
+        self.add_with_maps(
+            material,
+            SurfaceMaps {
+                normal_map: Some(normal_map),
+                ..Default::default()
+            },
+        )
     }
-}
 
-#[repr(C)]
-#[derive(Clone, Copy, Pod, Zeroable)]
-pub struct DebugNormal {}
+    pub fn add_with_maps(&mut self, material: Box<dyn GpuMaterial>, maps: SurfaceMaps) -> MaterialHandle {
+        let material_type = match self.kinds.iter().position(|kind| kind.name == material.wgsl_name()) {
+            Some(material_type) => material_type,
+            None => {
+                self.kinds.push(MaterialKind {
+                    name: material.wgsl_name(),
+                    source: material.wgsl_source(),
+                    functions: material.wgsl_functions(),
+                });
+                self.kinds.len() - 1
+            }
+        } as u32;
 
-impl Material for DebugNormal {
-    fn material_type(&self) -> MaterialType {
-        MaterialType::DebugNormal
+        let mut data = Vec::new();
+        material.pack(&mut data);
+
+        let handle = MaterialHandle {
+            material_type,
+            material_id: self.len + 2,
+        };
+        self.len += 2 + data.len() as u32 + maps.packed_len() as u32;
+        self.materials.push((handle, material));
+        self.maps.push(maps);
+        self.packed.push(data);
+        self.changed = true;
+        handle
     }
 
-    fn as_any(&self) -> &dyn Any {
-        self
+    // 运行时修改材质参数，打包后的长度不能变
+    pub fn get_mut(&mut self, handle: MaterialHandle) -> Option<&mut (dyn GpuMaterial + 'static)> {
+        let (_, material) = self
+            .materials
+            .iter_mut()
+            .find(|(other, _)| other.material_id == handle.material_id)?;
+        self.changed = true;
+        Some(material.as_mut())
     }
-}
 
-#[repr(C)]
-#[derive(Clone, Copy, Pod, Zeroable)]
-pub struct Lambertian {
-    pub albedo: Point3<f32>,
-    _padding: [u32; 1],
-}
+    // 界面通过 iter_mut 修改参数后调用
+    pub fn mark_changed(&mut self) {
+        self.changed = true;
+    }
 
-impl Lambertian {
-    pub fn new(albedo: Point3<f32>) -> Self {
-        Self {
-            albedo,
-            _padding: [0; 1],
+    // 渲染器每帧取一次，有变化时重新打包并上传
+    pub fn take_changed(&mut self) -> bool {
+        if self.changed {
+            // 记录长度仍然正确的打包数据，长度改变的材质保留之前的数据，由 pack 报告错误
+            for ((_, material), packed) in self.materials.iter().zip(&mut self.packed) {
+                let mut data = Vec::with_capacity(packed.len());
+                material.pack(&mut data);
+                if data.len() == packed.len() {
+                    *packed = data;
+                }
+            }
         }
+        std::mem::take(&mut self.changed)
+    }
+
+    pub fn iter_mut(&mut self) -> impl Iterator<Item = (MaterialHandle, &mut (dyn GpuMaterial + 'static))> {
+        self.materials
+            .iter_mut()
+            .map(|(handle, material)| (*handle, material.as_mut()))
     }
-}
 
-impl Material for Lambertian {
-    fn material_type(&self) -> MaterialType {
-        MaterialType::Lambertian
+    // 下标是类型标签
+    pub fn kind_names(&self) -> Vec<&'static str> {
+        self.kinds.iter().map(|kind| kind.name).collect()
     }
-    fn as_any(&self) -> &dyn Any {
-        self
+
+    // 与 ray_tracing.wgsl 中的 materials 对应
+    pub fn pack(&self) -> Vec<u32> {
+        let mut data = Vec::with_capacity(self.len as usize);
+        for (((_, material), maps), packed) in self.materials.iter().zip(&self.maps).zip(&self.packed) {
+            let header = data.len();
+            data.extend([0, 0]);
+            // 长度改变后之后所有材质的 material_id 都会错位，读到别的材质的数据，因此拒绝修改，保留之前的数据
+            material.pack(&mut data);
+            if data.len() != header + 2 + packed.len() {
+                log::error!(
+                    "Packed length of material {} changed from {} to {}, keeping the previous data",
+                    material.wgsl_name(),
+                    packed.len(),
+                    data.len() - header - 2
+                );
+                data.truncate(header + 2);
+                data.extend_from_slice(packed);
+            }
+            if let Some(alpha_mask) = &maps.alpha_mask {
+                data[header] = data.len() as u32;
+                alpha_mask.pack(&mut data);
+            }
+            if let Some(normal_map) = &maps.normal_map {
+                data[header + 1] = data.len() as u32;
+                normal_map.pack(&mut data);
+            }
+        }
+        data
     }
-}
 
-#[repr(C)]
-#[derive(Clone, Copy, Pod, Zeroable)]
-pub struct DiffuseLight {
-    pub emit: Point3<f32>,
-    _padding: [u32; 1],
-}
+    // 已注册材质的 WGSL 代码和分派函数，需要拼接在 ray_tracing.wgsl 之后
+    pub fn wgsl(&self) -> String {
+        let mut source = String::new();
+        for kind in &self.kinds {
+            source.push_str(kind.source);
+            source.push('\n');
+        }
+
+        source.push_str(
+            "/*------------------------------------ Material Dispatch ----------------------------------------*/\n",
+        );
+        for function in MaterialFunction::ALL {
+            let parameters = function.parameters();
+            let _ = writeln!(
+                source,
+                "\nfn Material_{}(\n{}) -> {} {{",
+                function.name(),
+                parameters
+                    .iter()
+                    .map(|(name, ty)| format!("    {}: {},\n", name, ty))
+                    .collect::<String>(),
+                function.return_type()
+            );
+            source.push_str("    switch ((*hit_record).material_type) {\n");
 
-impl DiffuseLight {
-    pub fn new(emit: Point3<f32>) -> Self {
-        Self { emit, _padding: [0; 1] }
+            let arguments = parameters.iter().map(|(name, _)| *name).collect::<Vec<_>>().join(", ");
+            for (material_type, kind) in self.kinds.iter().enumerate() {
+                if kind.functions.contains(&function) {
+                    let _ = writeln!(
+                        source,
+                        "        case {}u: {{\n            return {}_{}({});\n        }}",
+                        material_type,
+                        kind.name,
+                        function.name(),
+                        arguments
+                    );
+                }
+            }
+
+            let _ = writeln!(
+                source,
+                "        default: {{\n            return {};\n        }}\n    }}\n}}",
+                function.default_value()
+            );
+        }
+        source
     }
 }
 
-impl Material for DiffuseLight {
-    fn material_type(&self) -> MaterialType {
-        MaterialType::DiffuseLight
-    }
-    fn as_any(&self) -> &dyn Any {
-        self
-    }
+pub fn pack_f32(data: &mut Vec<u32>, value: f32) {
+    data.push(value.to_bits());
 }
 
-#[repr(C)]
-#[derive(Clone, Copy, Pod, Zeroable)]
-pub struct Dielectric {
-    pub reflection_index: f32,
+pub fn pack_point3(data: &mut Vec<u32>, value: &Point3<f32>) {
+    data.extend(value.iter().map(|x| x.to_bits()));
 }
 
-impl Dielectric {
-    pub fn new(reflection_index: f32) -> Self {
-        Self { reflection_index }
-    }
+pub fn unpack_f32(data: &[u32], offset: usize) -> f32 {
+    f32::from_bits(data[offset])
 }
 
-impl Material for Dielectric {
-    fn material_type(&self) -> MaterialType {
-        MaterialType::Dielectric
-    }
-    fn as_any(&self) -> &dyn Any {
-        self
-    }
+pub fn unpack_point3(data: &[u32], offset: usize) -> Point3<f32> {
+    Point3::new(
+        unpack_f32(data, offset),
+        unpack_f32(data, offset + 1),
+        unpack_f32(data, offset + 2),
+    )
 }
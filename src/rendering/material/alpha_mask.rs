@@ -0,0 +1,37 @@
+use crate::rendering::material::{pack_f32, Texture};
+
+// 挂在任意材质上的透明度贴图，取纹理的 alpha 通道。由 MaterialRegistry::add_with_maps 注册，
+// 在 Primitive_hit 中由 AlphaMask_passes 判断交点是否有效，测试失败的交点被所有光线穿过
+#[derive(Clone, Debug)]
+pub struct AlphaMask {
+    pub texture: Texture,
+    pub mode: AlphaMode,
+}
+
+#[derive(Clone, Copy, Debug)]
+pub enum AlphaMode {
+    // alpha 不小于阈值时不透明，适合树叶、铁丝网这样边缘清晰的镂空
+    Cutoff(f32),
+    // 以 alpha 为概率不透明，多个样本平均后得到半透明的效果
+    Stochastic,
+}
+
+impl AlphaMask {
+    // pack 写入的布局：模式、阈值，之后是纹理
+    pub const MODE_CUTOFF: u32 = 0;
+    pub const MODE_STOCHASTIC: u32 = 1;
+
+    pub fn pack(&self, data: &mut Vec<u32>) {
+        let (mode, cutoff) = match self.mode {
+            AlphaMode::Cutoff(cutoff) => (Self::MODE_CUTOFF, cutoff),
+            AlphaMode::Stochastic => (Self::MODE_STOCHASTIC, 0.0),
+        };
+        data.push(mode);
+        pack_f32(data, cutoff);
+        self.texture.pack(data);
+    }
+
+    pub fn packed_len(&self) -> usize {
+        2 + self.texture.packed_len()
+    }
+}
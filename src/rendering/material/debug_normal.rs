@@ -0,0 +1,25 @@
+use crate::rendering::material::{GpuMaterial, MaterialFunction};
+
+// 把法线映射为颜色直接发光，用于调试
+#[derive(Clone, Copy, Debug)]
+pub struct DebugNormal {}
+
+impl DebugNormal {
+    pub const WGSL_NAME: &'static str = "DebugNormal";
+}
+
+impl GpuMaterial for DebugNormal {
+    fn wgsl_name(&self) -> &'static str {
+        Self::WGSL_NAME
+    }
+
+    fn wgsl_source(&self) -> &'static str {
+        include_str!("../../shader/material/debug_normal.wgsl")
+    }
+
+    fn wgsl_functions(&self) -> &'static [MaterialFunction] {
+        &[MaterialFunction::Emit]
+    }
+
+    fn pack(&self, _data: &mut Vec<u32>) {}
+}
@@ -0,0 +1,44 @@
+use crate::rendering::material::{pack_f32, unpack_f32, GpuMaterial, MaterialFunction, MaterialParameter};
+
+#[derive(Clone, Copy, Debug)]
+pub struct Dielectric {
+    pub reflection_index: f32,
+}
+
+impl Dielectric {
+    pub const WGSL_NAME: &'static str = "Dielectric";
+
+    pub fn new(reflection_index: f32) -> Self {
+        Self { reflection_index }
+    }
+
+    pub fn unpack(data: &[u32]) -> Self {
+        Self::new(unpack_f32(data, 0))
+    }
+}
+
+impl GpuMaterial for Dielectric {
+    fn wgsl_name(&self) -> &'static str {
+        Self::WGSL_NAME
+    }
+
+    fn wgsl_source(&self) -> &'static str {
+        include_str!("../../shader/material/dielectric.wgsl")
+    }
+
+    fn wgsl_functions(&self) -> &'static [MaterialFunction] {
+        &[MaterialFunction::Scatter]
+    }
+
+    fn pack(&self, data: &mut Vec<u32>) {
+        pack_f32(data, self.reflection_index);
+    }
+
+    fn parameters(&mut self) -> Vec<MaterialParameter<'_>> {
+        vec![MaterialParameter::Scalar {
+            name: "Refraction Index",
+            value: &mut self.reflection_index,
+            range: 1.0..=3.0,
+        }]
+    }
+}
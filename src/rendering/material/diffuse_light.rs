@@ -0,0 +1,252 @@
+use crate::rendering::material::{
+    pack_f32, pack_point3, unpack_f32, unpack_point3, GpuMaterial, MaterialFunction, MaterialParameter, Texture,
+};
+use nalgebra::Point3;
+use std::f32::consts::PI;
+
+// 发光的颜色。颜色只决定色度，亮度由 LightIntensity 决定
+#[derive(Clone, Copy, Debug)]
+pub enum Emission {
+    Color(Point3<f32>), // 线性空间，最大的分量为 1，与 MaterialParameter::Color 的约定一致
+    Blackbody(f32),     // 色温，单位开尔文
+}
+
+// 渲染器中辐亮度的 1 个单位对应 1 nit (cd/m²)
+#[derive(Clone, Copy, Debug)]
+pub enum LightIntensity {
+    // 角度分布取 1 的方向上的亮度
+    Nits(f32),
+    // 总功率，需要光源的面积（单位与场景一致）才能换算成亮度，按 LUMINOUS_EFFICACY 换算为光通量
+    Watts { watts: f32, area: f32 },
+}
+
+// 555nm 单色光的光视效能，lm/W
+pub const LUMINOUS_EFFICACY: f32 = 683.0;
+
+#[derive(Clone, Debug)]
+pub struct DiffuseLight {
+    pub emission: Emission,
+    pub intensity: LightIntensity,
+    pub two_sided: bool,
+    // 与法线夹角从 0° 到 90° 均匀分布的相对强度，之间线性插值。为空时各个方向相同
+    pub angular_profile: Vec<f32>,
+    pub texture: Option<Texture>, // sRGB 编码，乘在发光颜色上
+}
+
+impl DiffuseLight {
+    pub const WGSL_NAME: &'static str = "DiffuseLight";
+
+    // pack 写入的布局：辐亮度、标志、角度分布的采样数，之后依次是角度分布和纹理
+    pub const RADIANCE: usize = 0;
+    pub const FLAGS: usize = 3;
+    pub const PROFILE_LEN: usize = 4;
+    pub const PROFILE: usize = 5;
+
+    pub const FLAG_TWO_SIDED: u32 = 1;
+    pub const FLAG_TEXTURE: u32 = 2;
+
+    // 辐亮度为 emit 的单面光源，emit 拆成色度和亮度
+    pub fn new(emit: Point3<f32>) -> Self {
+        Self {
+            emission: Emission::Color(normalize_color(&emit)),
+            intensity: LightIntensity::Nits(luminance(&emit)),
+            two_sided: false,
+            angular_profile: Vec::new(),
+            texture: None,
+        }
+    }
+
+    pub fn blackbody(kelvin: f32, intensity: LightIntensity) -> Self {
+        Self {
+            emission: Emission::Blackbody(kelvin),
+            intensity,
+            ..Self::new(Point3::origin())
+        }
+    }
+
+    pub fn unpack(data: &[u32]) -> Self {
+        let flags = data[Self::FLAGS];
+        let profile_len = data[Self::PROFILE_LEN] as usize;
+
+        let radiance = unpack_point3(data, Self::RADIANCE);
+        Self {
+            emission: Emission::Color(normalize_color(&radiance)),
+            intensity: LightIntensity::Nits(luminance(&radiance)),
+            two_sided: flags & Self::FLAG_TWO_SIDED != 0,
+            angular_profile: (0..profile_len).map(|i| unpack_f32(data, Self::PROFILE + i)).collect(),
+            texture: (flags & Self::FLAG_TEXTURE != 0).then(|| Texture::unpack(&data[Self::PROFILE + profile_len..])),
+        }
+    }
+
+    // 亮度为 1 的线性颜色
+    pub fn chromaticity(&self) -> Point3<f32> {
+        let color = match self.emission {
+            Emission::Color(color) => color,
+            Emission::Blackbody(kelvin) => blackbody(kelvin),
+        };
+        let y = luminance(&color);
+        if y > 0.0 {
+            color / y
+        } else {
+            Point3::origin()
+        }
+    }
+
+    // 角度分布取 1 的方向上的亮度
+    pub fn nits(&self) -> f32 {
+        match self.intensity {
+            LightIntensity::Nits(nits) => nits,
+            LightIntensity::Watts { watts, area } => {
+                // Φ = L · A · ∫ profile(θ) cosθ dω，每个发光的面各算一次
+                let sides = if self.two_sided { 2.0 } else { 1.0 };
+                let projected_solid_angle = self.projected_solid_angle();
+                if area <= 0.0 || projected_solid_angle <= 0.0 {
+                    0.0
+                } else {
+                    watts * LUMINOUS_EFFICACY / (area * sides * projected_solid_angle)
+                }
+            }
+        }
+    }
+
+    // 角度分布的投影立体角 ∫ profile(θ) cosθ dω，各向均匀时为 π
+    fn projected_solid_angle(&self) -> f32 {
+        const STEPS: usize = 256;
+        let delta = PI / 2.0 / STEPS as f32;
+        (0..STEPS)
+            .map(|i| {
+                let theta = (i as f32 + 0.5) * delta;
+                angular_profile(&self.angular_profile, theta.cos()) * theta.cos() * theta.sin() * delta
+            })
+            .sum::<f32>()
+            * 2.0
+            * PI
+    }
+}
+
+impl GpuMaterial for DiffuseLight {
+    fn wgsl_name(&self) -> &'static str {
+        Self::WGSL_NAME
+    }
+
+    fn wgsl_source(&self) -> &'static str {
+        include_str!("../../shader/material/diffuse_light.wgsl")
+    }
+
+    fn wgsl_functions(&self) -> &'static [MaterialFunction] {
+        &[MaterialFunction::Emit]
+    }
+
+    fn pack(&self, data: &mut Vec<u32>) {
+        pack_point3(data, &(self.chromaticity() * self.nits()));
+        let mut flags = 0;
+        if self.two_sided {
+            flags |= Self::FLAG_TWO_SIDED;
+        }
+        if self.texture.is_some() {
+            flags |= Self::FLAG_TEXTURE;
+        }
+        data.push(flags);
+        data.push(self.angular_profile.len() as u32);
+
+        for value in &self.angular_profile {
+            pack_f32(data, *value);
+        }
+        if let Some(texture) = &self.texture {
+            texture.pack(data);
+        }
+    }
+
+    fn parameters(&mut self) -> Vec<MaterialParameter<'_>> {
+        let mut parameters = Vec::new();
+        match &mut self.emission {
+            Emission::Color(color) => parameters.push(MaterialParameter::Color {
+                name: "Color",
+                value: color,
+            }),
+            Emission::Blackbody(kelvin) => parameters.push(MaterialParameter::Scalar {
+                name: "Temperature (K)",
+                value: kelvin,
+                range: 1000.0..=12000.0,
+            }),
+        }
+        match &mut self.intensity {
+            LightIntensity::Nits(nits) => parameters.push(MaterialParameter::Scalar {
+                name: "Intensity (nits)",
+                value: nits,
+                range: 0.0..=100.0,
+            }),
+            LightIntensity::Watts { watts, .. } => parameters.push(MaterialParameter::Scalar {
+                name: "Power (W)",
+                value: watts,
+                range: 0.0..=1000.0,
+            }),
+        }
+        parameters.push(MaterialParameter::Toggle {
+            name: "Two Sided",
+            value: &mut self.two_sided,
+        });
+        parameters
+    }
+}
+
+// 缩放到最大的分量为 1，黑色保持不变
+fn normalize_color(color: &Point3<f32>) -> Point3<f32> {
+    let max = color.iter().copied().fold(0.0, f32::max);
+    if max > 0.0 {
+        color / max
+    } else {
+        *color
+    }
+}
+
+// 与 ray_tracing.wgsl 中的 luminance 一致
+pub fn luminance(color: &Point3<f32>) -> f32 {
+    0.2126 * color.x + 0.7152 * color.y + 0.0722 * color.z
+}
+
+// 黑体辐射的线性 sRGB 颜色，亮度为 1。
+// 色度坐标使用 Kang et al. 2002 的三次拟合，适用于 1667K 到 25000K，范围外的色温取边界值
+pub fn blackbody(kelvin: f32) -> Point3<f32> {
+    let t = kelvin.clamp(1667.0, 25000.0) as f64;
+    let (t2, t3) = (t * t, t * t * t);
+
+    let x = if t <= 4000.0 {
+        -0.2661239e9 / t3 - 0.2343589e6 / t2 + 0.8776956e3 / t + 0.179910
+    } else {
+        -3.0258469e9 / t3 + 2.1070379e6 / t2 + 0.2226347e3 / t + 0.240390
+    };
+    let (x2, x3) = (x * x, x * x * x);
+    let y = if t <= 2222.0 {
+        -1.1063814 * x3 - 1.34811020 * x2 + 2.18555832 * x - 0.20219683
+    } else if t <= 4000.0 {
+        -0.9549476 * x3 - 1.37418593 * x2 + 2.09137015 * x - 0.16748867
+    } else {
+        3.0817580 * x3 - 5.87338670 * x2 + 3.75112997 * x - 0.37001483
+    };
+
+    // xyY (Y = 1) -> XYZ -> 线性 sRGB
+    let (cx, cy, cz) = (x / y, 1.0, (1.0 - x - y) / y);
+    let rgb = Point3::new(
+        3.2404542 * cx - 1.5371385 * cy - 0.4985314 * cz,
+        -0.9692660 * cx + 1.8760108 * cy + 0.0415560 * cz,
+        0.0556434 * cx - 0.2040259 * cy + 1.0572252 * cz,
+    )
+    .map(|c| c.max(0.0) as f32);
+
+    rgb / luminance(&rgb)
+}
+
+// 与 DiffuseLight_angular_profile 一致
+pub fn angular_profile(profile: &[f32], cos_theta: f32) -> f32 {
+    match profile.len() {
+        0 => 1.0,
+        1 => profile[0],
+        len => {
+            let x = cos_theta.clamp(0.0, 1.0).acos() / (PI / 2.0) * (len - 1) as f32;
+            let i = (x as usize).min(len - 2);
+            let t = x - i as f32;
+            profile[i] + (profile[i + 1] - profile[i]) * t
+        }
+    }
+}
@@ -0,0 +1,87 @@
+use crate::rendering::material::{
+    pack_f32, pack_point3, unpack_f32, unpack_point3, GpuMaterial, MaterialFunction, MaterialParameter,
+};
+use nalgebra::Point3;
+
+// 用于曲线的毛发材质，纤维方向是交点的切线。由漫反射和简化的 Marschner R 波瓣组成：
+// 漫反射在管子弯曲的法线上积分后接近 Kajiya-Kay 的漫反射项；
+// R 波瓣是纤维表面的镜面反射，反射光分布在以纤维为轴的圆锥附近，圆锥随毛鳞片的倾角偏移
+#[derive(Clone, Copy, Debug)]
+pub struct Hair {
+    pub color: Point3<f32>,
+    pub specular: f32,  // R 波瓣占的比例，其余是漫反射
+    pub roughness: f32, // 沿纤维方向的粗糙度，决定高光的宽度
+    pub tilt: f32,      // 毛鳞片的倾角，单位是度，人的头发大约是 -3
+}
+
+impl Default for Hair {
+    fn default() -> Self {
+        Self {
+            color: Point3::new(0.3, 0.15, 0.05),
+            specular: 0.3,
+            roughness: 0.3,
+            tilt: -3.0,
+        }
+    }
+}
+
+impl Hair {
+    pub const WGSL_NAME: &'static str = "Hair";
+
+    pub fn new(color: Point3<f32>, specular: f32, roughness: f32) -> Self {
+        Self {
+            color,
+            specular,
+            roughness,
+            ..Default::default()
+        }
+    }
+
+    pub fn unpack(data: &[u32]) -> Self {
+        Self {
+            color: unpack_point3(data, 0),
+            specular: unpack_f32(data, 3),
+            roughness: unpack_f32(data, 4),
+            tilt: unpack_f32(data, 5),
+        }
+    }
+}
+
+impl GpuMaterial for Hair {
+    fn wgsl_name(&self) -> &'static str {
+        Self::WGSL_NAME
+    }
+
+    fn wgsl_source(&self) -> &'static str {
+        include_str!("../../shader/material/hair.wgsl")
+    }
+
+    fn wgsl_functions(&self) -> &'static [MaterialFunction] {
+        &[
+            MaterialFunction::Scatter,
+            MaterialFunction::Eval,
+            MaterialFunction::PdfValue,
+            MaterialFunction::Random,
+        ]
+    }
+
+    fn pack(&self, data: &mut Vec<u32>) {
+        pack_point3(data, &self.color);
+        for value in [self.specular, self.roughness, self.tilt] {
+            pack_f32(data, value);
+        }
+    }
+
+    fn parameters(&mut self) -> Vec<MaterialParameter<'_>> {
+        let scalar = |name, value, range| MaterialParameter::Scalar { name, value, range };
+        vec![
+            MaterialParameter::Color {
+                name: "Color",
+                value: &mut self.color,
+            },
+            scalar("Specular", &mut self.specular, 0.0..=1.0),
+            scalar("Roughness", &mut self.roughness, 0.0..=1.0),
+            scalar("Tilt", &mut self.tilt, -10.0..=10.0),
+        ]
+    }
+}
@@ -0,0 +1,174 @@
+// 把外部格式的材质参数转换为 Principled。这里只处理参数本身，纹理和文件的读取由调用方负责
+use crate::rendering::material::{AlphaMask, AlphaMode, Principled, Texture};
+use image::{Rgba, RgbaImage};
+use nalgebra::Point3;
+
+// glTF 2.0 的 pbrMetallicRoughness 以及 KHR_materials_{specular, sheen, clearcoat, transmission, ior} 扩展中的系数，
+// 默认值与规范一致
+#[derive(Clone, Debug)]
+pub struct GltfMaterial {
+    pub base_color_factor: [f32; 4],
+    pub metallic_factor: f32,
+    pub roughness_factor: f32,
+    pub specular_factor: f32,
+    pub sheen_color_factor: [f32; 3],
+    pub clearcoat_factor: f32,
+    pub clearcoat_roughness_factor: f32,
+    pub transmission_factor: f32,
+    pub ior: f32,
+}
+
+impl Default for GltfMaterial {
+    fn default() -> Self {
+        Self {
+            base_color_factor: [1.0; 4],
+            metallic_factor: 1.0,
+            roughness_factor: 1.0,
+            specular_factor: 1.0,
+            sheen_color_factor: [0.0; 3],
+            clearcoat_factor: 0.0,
+            clearcoat_roughness_factor: 0.0,
+            transmission_factor: 0.0,
+            ior: 1.5,
+        }
+    }
+}
+
+impl From<&GltfMaterial> for Principled {
+    fn from(gltf: &GltfMaterial) -> Self {
+        let [r, g, b, _] = gltf.base_color_factor;
+        let sheen = gltf.sheen_color_factor.into_iter().fold(0.0, f32::max);
+        Self {
+            base_color: Point3::new(r, g, b),
+            metallic: gltf.metallic_factor,
+            roughness: gltf.roughness_factor,
+            // glTF 中非金属的 F0 是 0.04 * specularFactor，Principled 中是 0.08 * specular
+            specular: 0.5 * gltf.specular_factor,
+            sheen,
+            sheen_tint: if sheen > 0.0 { 1.0 } else { 0.0 },
+            clearcoat: gltf.clearcoat_factor,
+            clearcoat_roughness: gltf.clearcoat_roughness_factor,
+            transmission: gltf.transmission_factor,
+            ior: gltf.ior,
+            ..Default::default()
+        }
+    }
+}
+
+// Wavefront MTL 中的一个材质。除了经典的 Kd、Ks、Ns、Ni、d，还支持 PBR 扩展的 Pr、Pm、Ps、Pc、Pcr，
+// 没有出现的参数为 None
+#[derive(Clone, Debug, Default)]
+pub struct MtlMaterial {
+    pub name: String,
+    pub diffuse: Option<Point3<f32>>,     // Kd
+    pub specular: Option<Point3<f32>>,    // Ks
+    pub shininess: Option<f32>,           // Ns
+    pub optical_density: Option<f32>,     // Ni
+    pub dissolve: Option<f32>,            // d，或者 Tr = 1 - d
+    pub roughness: Option<f32>,           // Pr
+    pub metallic: Option<f32>,            // Pm
+    pub sheen: Option<f32>,               // Ps
+    pub clearcoat: Option<f32>,           // Pc
+    pub clearcoat_roughness: Option<f32>, // Pcr
+}
+
+// 解析 .mtl 文件的内容。不认识的语句和格式错误的行会被忽略
+pub fn parse_mtl(source: &str) -> Vec<MtlMaterial> {
+    let mut materials: Vec<MtlMaterial> = Vec::new();
+
+    for (line_number, line) in source.lines().enumerate() {
+        let line = line.split('#').next().unwrap_or_default().trim();
+        let mut tokens = line.split_whitespace();
+        let Some(keyword) = tokens.next() else {
+            continue;
+        };
+
+        if keyword == "newmtl" {
+            materials.push(MtlMaterial {
+                name: tokens.collect::<Vec<_>>().join(" "),
+                ..Default::default()
+            });
+            continue;
+        }
+
+        let Some(material) = materials.last_mut() else {
+            log::warn!("mtl line {}: `{}` before newmtl", line_number + 1, keyword);
+            continue;
+        };
+        let values: Option<Vec<f32>> = tokens.map(|token| token.parse().ok()).collect();
+        let Some(values) = values else {
+            log::warn!("mtl line {}: invalid number in `{}`", line_number + 1, line);
+            continue;
+        };
+        let scalar = values.first().copied();
+        let color = match values[..] {
+            [r, g, b, ..] => Some(Point3::new(r, g, b)),
+            [x] => Some(Point3::new(x, x, x)),
+            _ => None,
+        };
+
+        match keyword {
+            "Kd" => material.diffuse = color,
+            "Ks" => material.specular = color,
+            "Ns" => material.shininess = scalar,
+            "Ni" => material.optical_density = scalar,
+            "d" => material.dissolve = scalar,
+            "Tr" => material.dissolve = scalar.map(|tr| 1.0 - tr),
+            "Pr" => material.roughness = scalar,
+            "Pm" => material.metallic = scalar,
+            "Ps" => material.sheen = scalar,
+            "Pc" => material.clearcoat = scalar,
+            "Pcr" => material.clearcoat_roughness = scalar,
+            _ => {}
+        }
+    }
+
+    materials
+}
+
+impl MtlMaterial {
+    // d 是不透明度而不是透射率，半透明的树叶、贴花应当被光线直接穿过，而不是像玻璃一样折射。
+    // d < 1 时返回以 d 为概率不透明的透明度贴图，由 MaterialRegistry::add_with_maps 与 Principled 一起注册
+    pub fn alpha_mask(&self) -> Option<AlphaMask> {
+        let dissolve = self.dissolve?.clamp(0.0, 1.0);
+        let alpha = (dissolve * 255.0).round() as u8;
+        (alpha < 255).then(|| AlphaMask {
+            texture: Texture::from_image(&RgbaImage::from_pixel(1, 1, Rgba([255, 255, 255, alpha]))),
+            mode: AlphaMode::Stochastic,
+        })
+    }
+}
+
+impl From<&MtlMaterial> for Principled {
+    fn from(mtl: &MtlMaterial) -> Self {
+        let default = Principled::default();
+
+        // 没有 Pr 时由 Phong 指数换算：Beckmann 分布的 α = sqrt(2 / (Ns + 2))，roughness = sqrt(α)
+        let roughness = mtl
+            .roughness
+            .or_else(|| mtl.shininess.map(|ns| (2.0 / (ns.max(0.0) + 2.0)).sqrt().sqrt()))
+            .unwrap_or(default.roughness);
+
+        // 没有 Pm 时，镜面反射颜色远亮于漫反射颜色的材质视为金属
+        let diffuse = mtl.diffuse.unwrap_or(default.base_color);
+        let metallic = mtl.metallic.unwrap_or_else(|| match mtl.specular {
+            Some(specular) if specular.iter().sum::<f32>() > 2.0 * diffuse.iter().sum::<f32>() => 1.0,
+            _ => 0.0,
+        });
+        let base_color = match mtl.specular {
+            Some(specular) if mtl.metallic.is_none() && metallic > 0.0 => specular,
+            _ => diffuse,
+        };
+
+        Self {
+            base_color,
+            metallic,
+            roughness: roughness.clamp(0.0, 1.0),
+            sheen: mtl.sheen.unwrap_or(default.sheen),
+            clearcoat: mtl.clearcoat.unwrap_or(default.clearcoat),
+            clearcoat_roughness: mtl.clearcoat_roughness.unwrap_or(default.clearcoat_roughness),
+            ior: mtl.optical_density.filter(|&ni| ni >= 1.0).unwrap_or(default.ior),
+            ..default
+        }
+    }
+}
@@ -0,0 +1,49 @@
+use crate::rendering::material::{pack_point3, unpack_point3, GpuMaterial, MaterialFunction, MaterialParameter};
+use nalgebra::Point3;
+
+#[derive(Clone, Copy, Debug)]
+pub struct Lambertian {
+    pub albedo: Point3<f32>,
+}
+
+impl Lambertian {
+    pub const WGSL_NAME: &'static str = "Lambertian";
+
+    pub fn new(albedo: Point3<f32>) -> Self {
+        Self { albedo }
+    }
+
+    pub fn unpack(data: &[u32]) -> Self {
+        Self::new(unpack_point3(data, 0))
+    }
+}
+
+impl GpuMaterial for Lambertian {
+    fn wgsl_name(&self) -> &'static str {
+        Self::WGSL_NAME
+    }
+
+    fn wgsl_source(&self) -> &'static str {
+        include_str!("../../shader/material/lambertian.wgsl")
+    }
+
+    fn wgsl_functions(&self) -> &'static [MaterialFunction] {
+        &[
+            MaterialFunction::Scatter,
+            MaterialFunction::ScatteringPdfValue,
+            MaterialFunction::PdfValue,
+            MaterialFunction::Random,
+        ]
+    }
+
+    fn pack(&self, data: &mut Vec<u32>) {
+        pack_point3(data, &self.albedo);
+    }
+
+    fn parameters(&mut self) -> Vec<MaterialParameter<'_>> {
+        vec![MaterialParameter::Color {
+            name: "Albedo",
+            value: &mut self.albedo,
+        }]
+    }
+}
@@ -0,0 +1,33 @@
+use crate::rendering::material::{pack_f32, Texture};
+
+// 挂在任意材质上的法线扰动，由 MaterialRegistry::add_with_normal_map 注册，
+// 在 ray_color 求交之后、调用材质函数之前由 NormalMap_apply 修改 hit_record.normal
+#[derive(Clone, Debug)]
+pub enum NormalMap {
+    // 切线空间的法线贴图，线性编码 rgb = (n + 1) / 2，x 沿 u、y 沿 v、z 沿几何法线。strength 缩放 xy 分量
+    TangentSpace { texture: Texture, strength: f32 },
+    // 高度图，取红色通道。scale 是高度在 uv 空间中的尺度，越大凹凸越明显
+    Bump { texture: Texture, scale: f32 },
+}
+
+impl NormalMap {
+    // pack 写入的布局：类型、strength 或 scale，之后是纹理
+    pub const KIND_TANGENT_SPACE: u32 = 0;
+    pub const KIND_BUMP: u32 = 1;
+
+    pub fn pack(&self, data: &mut Vec<u32>) {
+        let (kind, strength, texture) = match self {
+            NormalMap::TangentSpace { texture, strength } => (Self::KIND_TANGENT_SPACE, *strength, texture),
+            NormalMap::Bump { texture, scale } => (Self::KIND_BUMP, *scale, texture),
+        };
+        data.push(kind);
+        pack_f32(data, strength);
+        texture.pack(data);
+    }
+
+    pub fn packed_len(&self) -> usize {
+        match self {
+            NormalMap::TangentSpace { texture, .. } | NormalMap::Bump { texture, .. } => 2 + texture.packed_len(),
+        }
+    }
+}
@@ -0,0 +1,124 @@
+use crate::rendering::material::{
+    pack_f32, pack_point3, unpack_f32, unpack_point3, GpuMaterial, MaterialFunction, MaterialParameter,
+};
+use nalgebra::Point3;
+
+// 参数都在 [0, 1] 内，ior 除外。导入 glTF 和 OBJ 材质时都转换为这个材质，见 material::import
+#[derive(Clone, Copy, Debug)]
+pub struct Principled {
+    pub base_color: Point3<f32>,
+    pub metallic: f32,
+    pub roughness: f32,
+    pub specular: f32, // 非金属的镜面反射强度，0.5 对应 F0 = 0.04
+    pub specular_tint: f32,
+    pub sheen: f32,
+    pub sheen_tint: f32,
+    pub clearcoat: f32,
+    pub clearcoat_roughness: f32,
+    pub transmission: f32,
+    pub ior: f32,
+}
+
+impl Default for Principled {
+    fn default() -> Self {
+        Self {
+            base_color: Point3::new(0.8, 0.8, 0.8),
+            metallic: 0.0,
+            roughness: 0.5,
+            specular: 0.5,
+            specular_tint: 0.0,
+            sheen: 0.0,
+            sheen_tint: 0.5,
+            clearcoat: 0.0,
+            clearcoat_roughness: 0.03,
+            transmission: 0.0,
+            ior: 1.5,
+        }
+    }
+}
+
+impl Principled {
+    pub const WGSL_NAME: &'static str = "Principled";
+
+    pub fn new(base_color: Point3<f32>, metallic: f32, roughness: f32) -> Self {
+        Self {
+            base_color,
+            metallic,
+            roughness,
+            ..Default::default()
+        }
+    }
+
+    pub fn unpack(data: &[u32]) -> Self {
+        Self {
+            base_color: unpack_point3(data, 0),
+            metallic: unpack_f32(data, 3),
+            roughness: unpack_f32(data, 4),
+            specular: unpack_f32(data, 5),
+            specular_tint: unpack_f32(data, 6),
+            sheen: unpack_f32(data, 7),
+            sheen_tint: unpack_f32(data, 8),
+            clearcoat: unpack_f32(data, 9),
+            clearcoat_roughness: unpack_f32(data, 10),
+            transmission: unpack_f32(data, 11),
+            ior: unpack_f32(data, 12),
+        }
+    }
+}
+
+impl GpuMaterial for Principled {
+    fn wgsl_name(&self) -> &'static str {
+        Self::WGSL_NAME
+    }
+
+    fn wgsl_source(&self) -> &'static str {
+        include_str!("../../shader/material/principled.wgsl")
+    }
+
+    fn wgsl_functions(&self) -> &'static [MaterialFunction] {
+        &[
+            MaterialFunction::Scatter,
+            MaterialFunction::Eval,
+            MaterialFunction::PdfValue,
+            MaterialFunction::Random,
+        ]
+    }
+
+    fn pack(&self, data: &mut Vec<u32>) {
+        pack_point3(data, &self.base_color);
+        for value in [
+            self.metallic,
+            self.roughness,
+            self.specular,
+            self.specular_tint,
+            self.sheen,
+            self.sheen_tint,
+            self.clearcoat,
+            self.clearcoat_roughness,
+            self.transmission,
+            self.ior,
+        ] {
+            pack_f32(data, value);
+        }
+    }
+
+    fn parameters(&mut self) -> Vec<MaterialParameter<'_>> {
+        let scalar = |name, value, range| MaterialParameter::Scalar { name, value, range };
+        vec![
+            MaterialParameter::Color {
+                name: "Base Color",
+                value: &mut self.base_color,
+            },
+            scalar("Metallic", &mut self.metallic, 0.0..=1.0),
+            scalar("Roughness", &mut self.roughness, 0.0..=1.0),
+            scalar("Specular", &mut self.specular, 0.0..=1.0),
+            scalar("Specular Tint", &mut self.specular_tint, 0.0..=1.0),
+            scalar("Sheen", &mut self.sheen, 0.0..=1.0),
+            scalar("Sheen Tint", &mut self.sheen_tint, 0.0..=1.0),
+            scalar("Clearcoat", &mut self.clearcoat, 0.0..=1.0),
+            scalar("Clearcoat Roughness", &mut self.clearcoat_roughness, 0.0..=1.0),
+            scalar("Transmission", &mut self.transmission, 0.0..=1.0),
+            scalar("IOR", &mut self.ior, 1.0..=3.0),
+        ]
+    }
+}
@@ -0,0 +1,188 @@
+use crate::rendering::material::{
+    pack_f32, pack_point3, unpack_f32, unpack_point3, GpuMaterial, MaterialFunction, MaterialParameter, Texture,
+};
+use nalgebra::Point3;
+
+// 地形的一层。高度是世界坐标的 y，坡度是着色法线与竖直方向的夹角，单位为度。范围的默认值不限制
+#[derive(Clone, Debug)]
+pub struct TerrainLayer {
+    pub color: Point3<f32>, // 线性空间
+    pub min_height: f32,
+    pub max_height: f32,
+    pub min_slope: f32,
+    pub max_slope: f32,
+    pub texture: Option<Texture>, // sRGB 编码，乘在颜色上
+    pub texture_scale: f32,       // 纹理在整个高度场上重复的次数
+}
+
+impl TerrainLayer {
+    pub fn new(color: Point3<f32>) -> Self {
+        Self {
+            color,
+            min_height: f32::MIN,
+            max_height: f32::MAX,
+            min_slope: f32::MIN,
+            max_slope: f32::MAX,
+            texture: None,
+            texture_scale: 1.0,
+        }
+    }
+
+    pub fn heights(mut self, min: f32, max: f32) -> Self {
+        self.min_height = min;
+        self.max_height = max;
+        self
+    }
+
+    pub fn slopes(mut self, min: f32, max: f32) -> Self {
+        self.min_slope = min;
+        self.max_slope = max;
+        self
+    }
+
+    pub fn texture(mut self, texture: Texture, scale: f32) -> Self {
+        self.texture = Some(texture);
+        self.texture_scale = scale;
+        self
+    }
+}
+
+// 按高度和坡度混合若干层颜色的漫反射材质。第一层铺满整个地形，它的范围不起作用；
+// 之后每一层按顺序盖在前面的层上，在范围边缘 height_blend、slope_blend 的宽度内平滑过渡
+#[derive(Clone, Debug)]
+pub struct Terrain {
+    pub layers: Vec<TerrainLayer>,
+    pub height_blend: f32,
+    pub slope_blend: f32, // 度
+}
+
+impl Terrain {
+    pub const WGSL_NAME: &'static str = "Terrain";
+
+    // pack 写入的布局：层数、两个过渡宽度，之后每层 LAYER_SIZE 个字，最后是各层的纹理
+    pub const LAYER_COUNT: usize = 0;
+    pub const HEIGHT_BLEND: usize = 1;
+    pub const SLOPE_BLEND: usize = 2;
+    pub const LAYERS: usize = 3;
+    pub const LAYER_SIZE: usize = 9;
+
+    // 每层中的偏移。纹理是相对于材质数据起点的下标，0 表示没有纹理
+    pub const LAYER_COLOR: usize = 0;
+    pub const LAYER_MIN_HEIGHT: usize = 3;
+    pub const LAYER_MAX_HEIGHT: usize = 4;
+    pub const LAYER_MIN_SLOPE: usize = 5;
+    pub const LAYER_MAX_SLOPE: usize = 6;
+    pub const LAYER_TEXTURE_SCALE: usize = 7;
+    pub const LAYER_TEXTURE: usize = 8;
+
+    // 界面上只能编辑前几层的颜色
+    const LAYER_NAMES: [&'static str; 8] = [
+        "Layer 1", "Layer 2", "Layer 3", "Layer 4", "Layer 5", "Layer 6", "Layer 7", "Layer 8",
+    ];
+
+    pub fn new(layers: Vec<TerrainLayer>) -> Self {
+        assert!(!layers.is_empty(), "a terrain needs at least one layer");
+        Self {
+            layers,
+            height_blend: 0.05,
+            slope_blend: 5.0,
+        }
+    }
+
+    pub fn unpack(data: &[u32]) -> Self {
+        let layers = (0..data[Self::LAYER_COUNT] as usize)
+            .map(|i| {
+                let layer = Self::LAYERS + i * Self::LAYER_SIZE;
+                let texture = data[layer + Self::LAYER_TEXTURE] as usize;
+                TerrainLayer {
+                    color: unpack_point3(data, layer + Self::LAYER_COLOR),
+                    min_height: unpack_f32(data, layer + Self::LAYER_MIN_HEIGHT),
+                    max_height: unpack_f32(data, layer + Self::LAYER_MAX_HEIGHT),
+                    min_slope: unpack_f32(data, layer + Self::LAYER_MIN_SLOPE),
+                    max_slope: unpack_f32(data, layer + Self::LAYER_MAX_SLOPE),
+                    texture: (texture != 0).then(|| Texture::unpack(&data[texture..])),
+                    texture_scale: unpack_f32(data, layer + Self::LAYER_TEXTURE_SCALE),
+                }
+            })
+            .collect();
+        Self {
+            layers,
+            height_blend: unpack_f32(data, Self::HEIGHT_BLEND),
+            slope_blend: unpack_f32(data, Self::SLOPE_BLEND),
+        }
+    }
+}
+
+impl GpuMaterial for Terrain {
+    fn wgsl_name(&self) -> &'static str {
+        Self::WGSL_NAME
+    }
+
+    fn wgsl_source(&self) -> &'static str {
+        include_str!("../../shader/material/terrain.wgsl")
+    }
+
+    fn wgsl_functions(&self) -> &'static [MaterialFunction] {
+        &[
+            MaterialFunction::Scatter,
+            MaterialFunction::ScatteringPdfValue,
+            MaterialFunction::PdfValue,
+            MaterialFunction::Random,
+        ]
+    }
+
+    fn pack(&self, data: &mut Vec<u32>) {
+        let start = data.len();
+        data.push(self.layers.len() as u32);
+        pack_f32(data, self.height_blend);
+        pack_f32(data, self.slope_blend);
+
+        let mut texture = Self::LAYERS + self.layers.len() * Self::LAYER_SIZE;
+        for layer in &self.layers {
+            pack_point3(data, &layer.color);
+            for value in [
+                layer.min_height,
+                layer.max_height,
+                layer.min_slope,
+                layer.max_slope,
+                layer.texture_scale,
+            ] {
+                pack_f32(data, value);
+            }
+            match &layer.texture {
+                Some(layer_texture) => {
+                    data.push(texture as u32);
+                    texture += layer_texture.packed_len();
+                }
+                None => data.push(0),
+            }
+        }
+
+        for layer_texture in self.layers.iter().filter_map(|layer| layer.texture.as_ref()) {
+            layer_texture.pack(data);
+        }
+        debug_assert_eq!(data.len() - start, texture);
+    }
+
+    fn parameters(&mut self) -> Vec<MaterialParameter<'_>> {
+        let mut parameters = vec![
+            MaterialParameter::Scalar {
+                name: "Height Blend",
+                value: &mut self.height_blend,
+                range: 0.0..=1.0,
+            },
+            MaterialParameter::Scalar {
+                name: "Slope Blend",
+                value: &mut self.slope_blend,
+                range: 0.0..=30.0,
+            },
+        ];
+        parameters.extend(self.layers.iter_mut().zip(Self::LAYER_NAMES).map(|(layer, name)| {
+            MaterialParameter::Color {
+                name,
+                value: &mut layer.color,
+            }
+        }));
+        parameters
+    }
+}
@@ -0,0 +1,49 @@
+use image::RgbaImage;
+
+// 打包进材质数据的 RGBA8 纹理，按 hit_record.uv 最近邻采样，见 ray_tracing.wgsl 中的 Texture_sample。
+// 纹理本身不区分颜色空间，由使用者决定是否按 sRGB 解码
+#[derive(Clone, Debug)]
+pub struct Texture {
+    pub width: u32,
+    pub height: u32,
+    pub texels: Vec<u32>, // 与 WGSL 的 unpack4x8unorm 一致，r 在最低字节，按行存储，第一行是 v = 0
+}
+
+impl Texture {
+    pub fn from_image(image: &RgbaImage) -> Self {
+        Self {
+            width: image.width(),
+            height: image.height(),
+            texels: image.pixels().map(|pixel| u32::from_le_bytes(pixel.0)).collect(),
+        }
+    }
+
+    pub fn packed_len(&self) -> usize {
+        2 + self.texels.len()
+    }
+
+    pub fn pack(&self, data: &mut Vec<u32>) {
+        data.push(self.width);
+        data.push(self.height);
+        data.extend(&self.texels);
+    }
+
+    pub fn unpack(data: &[u32]) -> Self {
+        let (width, height) = (data[0], data[1]);
+        Self {
+            width,
+            height,
+            texels: data[2..2 + (width * height) as usize].to_vec(),
+        }
+    }
+}
+
+// 与 Texture_sample 一致，从打包后的纹理中取 uv 处的像素，uv 按 1 循环
+pub fn texture_sample(data: &[u32], u: f32, v: f32) -> [f32; 4] {
+    let (width, height) = (data[0], data[1]);
+    let x = (((u - u.floor()) * width as f32) as u32).min(width - 1);
+    let y = (((v - v.floor()) * height as f32) as u32).min(height - 1);
+    data[2 + (y * width + x) as usize]
+        .to_le_bytes()
+        .map(|c| c as f32 / 255.0)
+}
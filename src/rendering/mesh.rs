@@ -4,6 +4,8 @@ use super::primitive::PrimitiveData;
 
 pub mod cube;
 pub mod mesh_list;
+pub mod moving;
+pub mod scene_graph;
 
 pub trait Mesh {
     fn primitives(&mut self, primitives: &mut Vec<Rc<PrimitiveData>>, important_indices: &mut Vec<u32>);
@@ -4,6 +4,7 @@ use crate::rendering::primitive::{PrimitiveData, TransformableMesh};
 use nalgebra::*;
 use std::rc::Rc;
 
+#[derive(Clone)]
 pub struct TransformableMeshList {
     objects: Vec<Box<dyn TransformableMesh>>,
 }
@@ -0,0 +1,48 @@
+use super::Mesh;
+use crate::rendering::primitive::{Motion, MotionData, PrimitiveData, Transformable, TransformableMesh};
+use nalgebra::*;
+use std::rc::Rc;
+
+// 在曝光期间运动的物体，展开出的所有图元共享同一个运动
+#[derive(Clone)]
+pub struct Moving<T: TransformableMesh> {
+    mesh: T,
+    motion: Motion,
+}
+
+impl<T: TransformableMesh> Moving<T> {
+    pub fn new(mesh: T, motion: Motion) -> Self {
+        Self { mesh, motion }
+    }
+}
+
+impl<T: TransformableMesh> Transformable for Moving<T> {
+    fn translate(&mut self, translation: Translation3<f32>) {
+        self.mesh.translate(translation);
+        self.motion.translate(translation);
+    }
+
+    fn rotate(&mut self, rotation: UnitQuaternion<f32>) {
+        self.mesh.rotate(rotation);
+        self.motion.rotate(rotation);
+    }
+
+    fn scale(&mut self, scale: Scale3<f32>) {
+        self.mesh.scale(scale);
+        self.motion.scale(scale);
+    }
+}
+
+impl<T: TransformableMesh> Mesh for Moving<T> {
+    fn primitives(&mut self, primitives: &mut Vec<Rc<PrimitiveData>>, important_indices: &mut Vec<u32>) {
+        let start = primitives.len();
+        self.mesh.primitives(primitives, important_indices);
+
+        let motion = MotionData::from(&self.motion);
+        for primitive in &mut primitives[start..] {
+            let mut data = **primitive;
+            data.set_motion(motion);
+            *primitive = Rc::new(data);
+        }
+    }
+}
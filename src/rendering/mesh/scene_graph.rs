@@ -0,0 +1,293 @@
+use super::Mesh;
+use crate::rendering::material::MaterialHandle;
+use crate::rendering::primitive::{PrimitiveData, Transformable, TransformableMesh};
+use getset::{CopyGetters, Getters};
+use nalgebra::{Matrix4, Scale3, Translation3, UnitQuaternion};
+use std::iter;
+use std::rc::Rc;
+
+// 节点相对父节点的变换，依次缩放、旋转、平移
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Transform {
+    pub translation: Translation3<f32>,
+    pub rotation: UnitQuaternion<f32>,
+    pub scale: Scale3<f32>,
+}
+
+impl Default for Transform {
+    fn default() -> Self {
+        Self::identity()
+    }
+}
+
+impl Transform {
+    pub fn identity() -> Self {
+        Self {
+            translation: Translation3::identity(),
+            rotation: UnitQuaternion::identity(),
+            scale: Scale3::identity(),
+        }
+    }
+
+    pub fn from_translation(translation: Translation3<f32>) -> Self {
+        Self {
+            translation,
+            ..Self::identity()
+        }
+    }
+
+    pub fn matrix(&self) -> Matrix4<f32> {
+        self.translation.to_homogeneous() * self.rotation.to_homogeneous() * self.scale.to_homogeneous()
+    }
+
+    pub fn apply<T: Transformable + ?Sized>(&self, object: &mut T) {
+        object.scale(self.scale);
+        object.rotate(self.rotation);
+        object.translate(self.translation);
+    }
+}
+
+// 节点在场景图中的编号，节点加入后编号不变
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct NodeId(usize);
+
+#[derive(Getters, CopyGetters)]
+pub struct SceneNode {
+    #[getset(get = "pub")]
+    name: String,
+    #[getset(get_copy = "pub")]
+    parent: Option<NodeId>,
+    #[getset(get = "pub")]
+    children: Vec<NodeId>,
+    #[getset(get_copy = "pub")]
+    transform: Transform,
+    #[getset(get_copy = "pub")]
+    material: Option<MaterialHandle>, // 覆盖子树中所有网格的材质，离网格最近的节点优先
+    mesh: Option<Box<dyn TransformableMesh>>,   // 节点局部坐标中的网格
+    primitives: Option<Vec<Rc<PrimitiveData>>>, // 网格在世界坐标中的图元，节点或祖先改变后重新生成
+    important_indices: Vec<u32>,                // 相对于节点的第一个图元
+}
+
+impl SceneNode {
+    pub fn has_mesh(&self) -> bool {
+        self.mesh.is_some()
+    }
+}
+
+// 由具名节点组成的森林。每个节点有相对父节点的变换，可以挂一个网格和一个材质，世界变换沿层级从根节点累积。
+// 同一个父节点下的名字不重复，节点用 "cornell/tall_box" 这样从根节点开始的路径查找
+#[derive(Default)]
+pub struct SceneGraph {
+    nodes: Vec<Option<SceneNode>>, // 删除的节点留下空位，其他节点的编号不变
+    roots: Vec<NodeId>,
+    changed: bool, // 上一次 take_changed 之后是否有节点增删或改变
+}
+
+impl SceneGraph {
+    pub const SEPARATOR: char = '/';
+
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // 只有一个挂着网格的根节点
+    pub fn from_mesh<T: TransformableMesh + 'static>(name: &str, mesh: T) -> Self {
+        let mut graph = Self::new();
+        graph.add_mesh(None, name, mesh);
+        graph
+    }
+
+    pub fn add_node(&mut self, parent: Option<NodeId>, name: &str, transform: Transform) -> NodeId {
+        assert!(
+            !name.is_empty() && !name.contains(Self::SEPARATOR),
+            "invalid scene node name {name:?}"
+        );
+        assert!(
+            self.child(parent, name).is_none(),
+            "scene node {name:?} already exists under {:?}",
+            parent.map(|parent| self.path(parent))
+        );
+
+        let id = NodeId(self.nodes.len());
+        self.nodes.push(Some(SceneNode {
+            name: name.to_string(),
+            parent,
+            children: Vec::new(),
+            transform,
+            material: None,
+            mesh: None,
+            primitives: None,
+            important_indices: Vec::new(),
+        }));
+        match parent {
+            Some(parent) => self.node_mut(parent).children.push(id),
+            None => self.roots.push(id),
+        }
+        self.changed = true;
+        id
+    }
+
+    pub fn add_mesh<T: TransformableMesh + 'static>(&mut self, parent: Option<NodeId>, name: &str, mesh: T) -> NodeId {
+        let id = self.add_node(parent, name, Transform::identity());
+        self.set_mesh(id, mesh);
+        id
+    }
+
+    pub fn node(&self, id: NodeId) -> &SceneNode {
+        self.nodes[id.0].as_ref().expect("scene node has been removed")
+    }
+
+    pub fn contains(&self, id: NodeId) -> bool {
+        self.nodes.get(id.0).is_some_and(Option::is_some)
+    }
+
+    pub fn roots(&self) -> &[NodeId] {
+        &self.roots
+    }
+
+    pub fn len(&self) -> usize {
+        self.nodes.iter().flatten().count()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.roots.is_empty()
+    }
+
+    // 深度优先，父节点在子节点之前，兄弟节点按加入的顺序
+    pub fn depth_first(&self) -> Vec<NodeId> {
+        let mut order = Vec::with_capacity(self.nodes.len());
+        let mut stack: Vec<_> = self.roots.iter().rev().copied().collect();
+        while let Some(id) = stack.pop() {
+            order.push(id);
+            stack.extend(self.node(id).children.iter().rev());
+        }
+        order
+    }
+
+    pub fn find(&self, path: &str) -> Option<NodeId> {
+        path.split(Self::SEPARATOR)
+            .try_fold(None, |parent, name| self.child(parent, name).map(Some))
+            .flatten()
+    }
+
+    pub fn path(&self, id: NodeId) -> String {
+        let mut names: Vec<_> = self.ancestors(id).map(|id| self.node(id).name.as_str()).collect();
+        names.reverse();
+        names.join(&Self::SEPARATOR.to_string())
+    }
+
+    // 节点局部坐标到世界坐标的变换
+    pub fn world_matrix(&self, id: NodeId) -> Matrix4<f32> {
+        self.ancestors(id).fold(Matrix4::identity(), |matrix, id| {
+            self.node(id).transform.matrix() * matrix
+        })
+    }
+
+    pub fn set_transform(&mut self, id: NodeId, transform: Transform) {
+        self.node_mut(id).transform = transform;
+        self.invalidate(id);
+    }
+
+    pub fn set_mesh<T: TransformableMesh + 'static>(&mut self, id: NodeId, mesh: T) {
+        let node = self.node_mut(id);
+        node.mesh = Some(Box::new(mesh));
+        node.primitives = None;
+        self.changed = true;
+    }
+
+    pub fn set_material(&mut self, id: NodeId, material: Option<MaterialHandle>) {
+        self.node_mut(id).material = material;
+        self.invalidate(id);
+    }
+
+    // 删除节点和它的整个子树
+    pub fn remove(&mut self, id: NodeId) {
+        let siblings = match self.node(id).parent {
+            Some(parent) => &mut self.node_mut(parent).children,
+            None => &mut self.roots,
+        };
+        siblings.retain(|&sibling| sibling != id);
+
+        let mut stack = vec![id];
+        while let Some(id) = stack.pop() {
+            let node = self.nodes[id.0].take().expect("scene node has been removed");
+            stack.extend(node.children);
+        }
+        self.changed = true;
+    }
+
+    // 渲染器每帧取一次，有变化时重新生成图元并上传
+    pub fn take_changed(&mut self) -> bool {
+        std::mem::take(&mut self.changed)
+    }
+
+    fn child(&self, parent: Option<NodeId>, name: &str) -> Option<NodeId> {
+        let siblings = match parent {
+            Some(parent) => &self.node(parent).children,
+            None => &self.roots,
+        };
+        siblings.iter().copied().find(|&id| self.node(id).name == name)
+    }
+
+    fn node_mut(&mut self, id: NodeId) -> &mut SceneNode {
+        self.nodes[id.0].as_mut().expect("scene node has been removed")
+    }
+
+    // 从节点自身到根节点
+    fn ancestors(&self, id: NodeId) -> impl Iterator<Item = NodeId> + '_ {
+        iter::successors(Some(id), |&id| self.node(id).parent)
+    }
+
+    fn invalidate(&mut self, id: NodeId) {
+        let mut stack = vec![id];
+        while let Some(id) = stack.pop() {
+            let node = self.node_mut(id);
+            node.primitives = None;
+            stack.extend(node.children.iter());
+        }
+        self.changed = true;
+    }
+
+    // 复制局部坐标中的网格，从节点自身到根节点依次施加各层的变换
+    fn update_primitives(&mut self, id: NodeId) {
+        let Some(mesh) = &self.node(id).mesh else {
+            return;
+        };
+        let mut mesh = mesh.clone_box();
+        for ancestor in self.ancestors(id) {
+            self.node(ancestor).transform.apply(mesh.as_mut());
+        }
+        let material = self.ancestors(id).find_map(|ancestor| self.node(ancestor).material);
+
+        let mut primitives = Vec::new();
+        let mut important_indices = Vec::new();
+        mesh.primitives(&mut primitives, &mut important_indices);
+        if let Some(material) = material {
+            for primitive in &mut primitives {
+                let mut data = **primitive;
+                data.set_material(material);
+                *primitive = Rc::new(data);
+            }
+        }
+
+        let node = self.node_mut(id);
+        node.primitives = Some(primitives);
+        node.important_indices = important_indices;
+    }
+}
+
+impl Mesh for SceneGraph {
+    fn primitives(&mut self, primitives: &mut Vec<Rc<PrimitiveData>>, important_indices: &mut Vec<u32>) {
+        for id in self.depth_first() {
+            if self.node(id).primitives.is_none() {
+                self.update_primitives(id);
+            }
+            let node = self.node(id);
+            if let Some(node_primitives) = &node.primitives {
+                let start = primitives.len() as u32;
+                important_indices.extend(node.important_indices.iter().map(|index| start + index));
+                primitives.extend(node_primitives.iter().map(Rc::clone));
+            }
+        }
+    }
+}
@@ -1,10 +1,24 @@
+pub mod csg;
+pub mod curve;
+pub mod heightfield;
+pub mod motion;
 pub mod quad;
+pub mod sdf;
+pub mod shape;
 pub mod sphere;
 pub mod transformable;
 
+pub use csg::*;
+pub use curve::*;
+pub use heightfield::*;
+pub use motion::*;
 pub use quad::*;
+pub use sdf::*;
+pub use shape::*;
 pub use transformable::*;
 
+use crate::rendering::layout::wgsl_layout;
+use crate::rendering::material::MaterialHandle;
 use crate::rendering::primitive::sphere::SphereData;
 use bytemuck::{Pod, Zeroable};
 
@@ -14,10 +28,17 @@ pub trait Bound {
     fn bounding_box(&self) -> BoundingBox;
 }
 
+// SdfData、CsgData 内嵌了整个节点数组，HeightfieldData 内嵌了一块的高度，比其他图元大得多。图元都放在 Rc 中共享，不需要再装箱
+#[allow(clippy::large_enum_variant)]
 #[derive(Debug, Copy, Clone)]
 pub enum PrimitiveData {
     Quad(QuadData),
     Sphere(SphereData),
+    Shape(ShapeData),
+    Sdf(SdfData),
+    Csg(CsgData),
+    Curve(CurveData),
+    Heightfield(HeightfieldData),
 }
 
 impl From<PrimitiveData> for u32 {
@@ -25,6 +46,92 @@ impl From<PrimitiveData> for u32 {
         match value {
             PrimitiveData::Quad(_) => 0,
             PrimitiveData::Sphere(_) => 1,
+            PrimitiveData::Shape(_) => 2,
+            PrimitiveData::Sdf(_) => 3,
+            PrimitiveData::Csg(_) => 4,
+            PrimitiveData::Curve(_) => 5,
+            PrimitiveData::Heightfield(_) => 6,
+        }
+    }
+}
+
+impl PrimitiveData {
+    pub fn motion(&self) -> MotionData {
+        match self {
+            PrimitiveData::Quad(quad_data) => quad_data.motion(),
+            PrimitiveData::Sphere(sphere_data) => sphere_data.motion(),
+            PrimitiveData::Shape(shape_data) => shape_data.motion(),
+            PrimitiveData::Sdf(sdf_data) => sdf_data.motion(),
+            PrimitiveData::Csg(csg_data) => csg_data.motion(),
+            PrimitiveData::Curve(curve_data) => curve_data.motion(),
+            PrimitiveData::Heightfield(heightfield_data) => heightfield_data.motion(),
+        }
+    }
+
+    pub fn set_motion(&mut self, motion: MotionData) {
+        match self {
+            PrimitiveData::Quad(quad_data) => {
+                quad_data.set_motion(motion);
+            }
+            PrimitiveData::Sphere(sphere_data) => {
+                sphere_data.set_motion(motion);
+            }
+            PrimitiveData::Shape(shape_data) => {
+                shape_data.set_motion(motion);
+            }
+            PrimitiveData::Sdf(sdf_data) => {
+                sdf_data.set_motion(motion);
+            }
+            PrimitiveData::Csg(csg_data) => {
+                csg_data.set_motion(motion);
+            }
+            PrimitiveData::Curve(curve_data) => {
+                curve_data.set_motion(motion);
+            }
+            PrimitiveData::Heightfield(heightfield_data) => {
+                heightfield_data.set_motion(motion);
+            }
+        }
+    }
+
+    // 场景图中节点指定的材质覆盖网格自己的材质
+    pub fn set_material(&mut self, material: MaterialHandle) {
+        match self {
+            PrimitiveData::Quad(quad_data) => {
+                quad_data
+                    .set_material_type(material.material_type)
+                    .set_material_id(material.material_id);
+            }
+            PrimitiveData::Sphere(sphere_data) => {
+                sphere_data
+                    .set_material_type(material.material_type)
+                    .set_material_id(material.material_id);
+            }
+            PrimitiveData::Shape(shape_data) => {
+                shape_data
+                    .set_material_type(material.material_type)
+                    .set_material_id(material.material_id);
+            }
+            PrimitiveData::Sdf(sdf_data) => {
+                sdf_data
+                    .set_material_type(material.material_type)
+                    .set_material_id(material.material_id);
+            }
+            PrimitiveData::Csg(csg_data) => {
+                csg_data
+                    .set_material_type(material.material_type)
+                    .set_material_id(material.material_id);
+            }
+            PrimitiveData::Curve(curve_data) => {
+                curve_data
+                    .set_material_type(material.material_type)
+                    .set_material_id(material.material_id);
+            }
+            PrimitiveData::Heightfield(heightfield_data) => {
+                heightfield_data
+                    .set_material_type(material.material_type)
+                    .set_material_id(material.material_id);
+            }
         }
     }
 }
@@ -34,6 +141,11 @@ impl Bound for PrimitiveData {
         match self {
             PrimitiveData::Quad(quad_data) => quad_data.bounding_box(),
             PrimitiveData::Sphere(sphere_data) => sphere_data.bounding_box(),
+            PrimitiveData::Shape(shape_data) => shape_data.bounding_box(),
+            PrimitiveData::Sdf(sdf_data) => sdf_data.bounding_box(),
+            PrimitiveData::Csg(csg_data) => csg_data.bounding_box(),
+            PrimitiveData::Curve(curve_data) => curve_data.bounding_box(),
+            PrimitiveData::Heightfield(heightfield_data) => heightfield_data.bounding_box(),
         }
     }
 }
@@ -44,3 +156,5 @@ pub struct PrimitiveIndex {
     pub primitive_type: u32,
     pub primitive_id: u32,
 }
+
+wgsl_layout!(PrimitiveIndex, "PrimitiveIndex", [primitive_type, primitive_id]);
@@ -0,0 +1,320 @@
+use crate::rendering::bounding_box::BoundingBox;
+use crate::rendering::layout::wgsl_layout;
+use crate::rendering::material::MaterialHandle;
+use crate::rendering::mesh::Mesh;
+use crate::rendering::primitive::{Bound, MotionData, PrimitiveData, Transformable};
+use bytemuck::{Pod, Zeroable};
+use getset::{CopyGetters, Setters};
+use nalgebra::{Affine3, Matrix3, Matrix4, Matrix4x3, Point3, Scale3, Translation3, UnitQuaternion, Vector3, Vector4};
+use std::rc::Rc;
+
+// 与 ray_tracing.wgsl 中的 CSG_MAX_NODES 一致
+pub const CSG_MAX_NODES: usize = 16;
+
+// 构造实体几何的节点树。叶子是以原点为中心的凸体，光线与每个叶子的交集是一个区间，
+// 内部节点对两个子树所围的实体做布尔运算
+#[derive(Clone, Debug)]
+pub enum CsgNode {
+    Sphere {
+        radius: f32,
+    },
+    // size 是三个方向的边长
+    Box {
+        size: Vector3<f32>,
+    },
+    // 轴沿 Y 的实心圆柱，height 是两个盖子之间的距离
+    Cylinder {
+        radius: f32,
+        height: f32,
+    },
+    Union {
+        a: Box<CsgNode>,
+        b: Box<CsgNode>,
+    },
+    Intersection {
+        a: Box<CsgNode>,
+        b: Box<CsgNode>,
+    },
+    // 从 a 中挖去 b
+    Difference {
+        a: Box<CsgNode>,
+        b: Box<CsgNode>,
+    },
+    // 求交只需要区间端点，叶子可以做任意仿射变换
+    Transformed {
+        node: Box<CsgNode>,
+        transform: Affine3<f32>,
+    },
+}
+
+impl CsgNode {
+    // CsgNodeData::kind 的取值，与 ray_tracing.wgsl 中的 CSG_* 一致
+    pub const SPHERE: u32 = 0;
+    pub const BOX: u32 = 1;
+    pub const CYLINDER: u32 = 2;
+    pub const UNION: u32 = 3;
+    pub const INTERSECTION: u32 = 4;
+    pub const DIFFERENCE: u32 = 5;
+
+    pub fn union(self, other: CsgNode) -> Self {
+        CsgNode::Union {
+            a: Box::new(self),
+            b: Box::new(other),
+        }
+    }
+
+    pub fn intersect(self, other: CsgNode) -> Self {
+        CsgNode::Intersection {
+            a: Box::new(self),
+            b: Box::new(other),
+        }
+    }
+
+    pub fn subtract(self, other: CsgNode) -> Self {
+        CsgNode::Difference {
+            a: Box::new(self),
+            b: Box::new(other),
+        }
+    }
+
+    pub fn translated(self, translation: Translation3<f32>) -> Self {
+        self.transformed(translation.to_homogeneous())
+    }
+
+    pub fn rotated(self, rotation: UnitQuaternion<f32>) -> Self {
+        self.transformed(rotation.to_homogeneous())
+    }
+
+    pub fn scaled(self, scale: Scale3<f32>) -> Self {
+        self.transformed(scale.to_homogeneous())
+    }
+
+    fn transformed(self, matrix: Matrix4<f32>) -> Self {
+        let transform = Affine3::from_matrix_unchecked(matrix);
+        match self {
+            CsgNode::Transformed { node, transform: inner } => CsgNode::Transformed {
+                node,
+                transform: transform * inner,
+            },
+            node => CsgNode::Transformed {
+                node: Box::new(node),
+                transform,
+            },
+        }
+    }
+
+    // 按后序展开，着色器用一个栈对叶子的内外状态求值。transform 是从叶子局部坐标到世界坐标的累积变换，
+    // 返回子树在世界坐标中的包围盒
+    fn flatten(&self, transform: &Affine3<f32>, nodes: &mut Vec<CsgNodeData>) -> (Vector3<f32>, Vector3<f32>) {
+        let (kind, parameters, half_size) = match *self {
+            CsgNode::Sphere { radius } => (
+                Self::SPHERE,
+                Vector4::new(radius, 0.0, 0.0, 0.0),
+                Vector3::repeat(radius),
+            ),
+            CsgNode::Box { size } => (Self::BOX, (size / 2.0).push(0.0), size / 2.0),
+            CsgNode::Cylinder { radius, height } => (
+                Self::CYLINDER,
+                Vector4::new(radius, height / 2.0, 0.0, 0.0),
+                Vector3::new(radius, height / 2.0, radius),
+            ),
+            CsgNode::Union { ref a, ref b } => {
+                let (a_min, a_max) = a.flatten(transform, nodes);
+                let (b_min, b_max) = b.flatten(transform, nodes);
+                nodes.push(CsgNodeData::operation(Self::UNION));
+                return (a_min.inf(&b_min), a_max.sup(&b_max));
+            }
+            CsgNode::Intersection { ref a, ref b } => {
+                let (a_min, a_max) = a.flatten(transform, nodes);
+                let (b_min, b_max) = b.flatten(transform, nodes);
+                nodes.push(CsgNodeData::operation(Self::INTERSECTION));
+                // 两个子树不相交时交集为空，退化成一个点
+                let (min, max) = (a_min.sup(&b_min), a_max.inf(&b_max));
+                return (min, max.sup(&min));
+            }
+            CsgNode::Difference { ref a, ref b } => {
+                let bounds = a.flatten(transform, nodes);
+                b.flatten(transform, nodes);
+                nodes.push(CsgNodeData::operation(Self::DIFFERENCE));
+                return bounds;
+            }
+            CsgNode::Transformed {
+                ref node,
+                transform: ref inner,
+            } => return node.flatten(&(transform * inner), nodes),
+        };
+
+        let matrix = transform.matrix();
+        let linear = matrix.fixed_view::<3, 3>(0, 0).into_owned();
+        let center = Point3::from(matrix.fixed_view::<3, 1>(0, 3).into_owned());
+        let inverse = linear.try_inverse().unwrap_or_else(Matrix3::zeros);
+        nodes.push(CsgNodeData {
+            kind,
+            parameters,
+            center,
+            transform: linear.insert_row(3, 0.0),
+            inverse_transform: inverse.insert_row(3, 0.0),
+            ..Zeroable::zeroed()
+        });
+
+        let extent = linear.abs() * half_size;
+        (center.coords - extent, center.coords + extent)
+    }
+}
+
+#[derive(Clone)]
+pub struct Csg {
+    root: CsgNode,
+    center: Point3<f32>,
+    transform: Matrix3<f32>, // 旋转和缩放，作用在以 center 为原点的 CSG 坐标上
+    material: MaterialHandle,
+    primitive: Option<Rc<PrimitiveData>>,
+}
+
+impl Csg {
+    // 布尔运算后的表面没有简单的参数化，所以 CSG 不能作为重要性采样的目标
+    pub fn new(root: CsgNode, center: Point3<f32>, material: MaterialHandle) -> Self {
+        let mut nodes = Vec::new();
+        root.flatten(&Affine3::identity(), &mut nodes);
+        assert!(
+            nodes.len() <= CSG_MAX_NODES,
+            "CSG has {} nodes, at most {CSG_MAX_NODES} are supported",
+            nodes.len()
+        );
+
+        Self {
+            root,
+            center,
+            transform: Matrix3::identity(),
+            material,
+            primitive: None,
+        }
+    }
+}
+
+impl Transformable for Csg {
+    fn translate(&mut self, translation: Translation3<f32>) {
+        self.center = translation * self.center;
+        self.primitive = None;
+    }
+
+    fn rotate(&mut self, rotation: UnitQuaternion<f32>) {
+        self.center = rotation * self.center;
+        self.transform = rotation.to_rotation_matrix().matrix() * self.transform;
+        self.primitive = None;
+    }
+
+    fn scale(&mut self, scale: Scale3<f32>) {
+        self.center = scale * self.center;
+        self.transform = Matrix3::from_diagonal(&scale.vector) * self.transform;
+        self.primitive = None;
+    }
+}
+
+impl Mesh for Csg {
+    fn primitives(&mut self, primitives: &mut Vec<Rc<PrimitiveData>>, _important_indices: &mut Vec<u32>) {
+        if self.primitive.is_none() {
+            // 整体的变换直接合并到每个叶子的变换中
+            let mut matrix = self.transform.to_homogeneous();
+            matrix.fixed_view_mut::<3, 1>(0, 3).copy_from(&self.center.coords);
+            let mut nodes = Vec::new();
+            let (bounds_min, bounds_max) = self.root.flatten(&Affine3::from_matrix_unchecked(matrix), &mut nodes);
+
+            let mut csg_data = CsgData {
+                bounds_min,
+                material_type: self.material.material_type,
+                bounds_max,
+                material_id: self.material.material_id,
+                node_count: nodes.len() as u32,
+                ..Zeroable::zeroed()
+            };
+            csg_data.nodes[..nodes.len()].copy_from_slice(&nodes);
+            self.primitive = Some(Rc::new(PrimitiveData::Csg(csg_data)));
+        }
+        primitives.push(Rc::clone(self.primitive.as_ref().unwrap()));
+    }
+}
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug, Pod, Zeroable, CopyGetters)]
+pub struct CsgNodeData {
+    #[getset(get_copy = "pub")]
+    kind: u32, // CsgNode::SPHERE 等
+    _padding: [u32; 3],
+    #[getset(get_copy = "pub")]
+    parameters: Vector4<f32>, // 叶子的尺寸：球的半径，长方体的半边长，圆柱的半径和半高
+    #[getset(get_copy = "pub")]
+    center: Point3<f32>, // 叶子局部坐标原点在世界坐标中的位置
+    _padding2: u32,
+    // 叶子局部坐标到世界坐标的线性部分及其逆，第 4 行是 WGSL 中 mat3x3f 每列的填充
+    transform: Matrix4x3<f32>,
+    inverse_transform: Matrix4x3<f32>,
+}
+
+wgsl_layout!(
+    CsgNodeData,
+    "CsgNode",
+    [kind, parameters, center, transform, inverse_transform]
+);
+
+impl CsgNodeData {
+    fn operation(kind: u32) -> Self {
+        Self {
+            kind,
+            ..Zeroable::zeroed()
+        }
+    }
+
+    pub fn is_leaf(&self) -> bool {
+        self.kind < CsgNode::UNION
+    }
+
+    pub fn transform(&self) -> Matrix3<f32> {
+        self.transform.fixed_rows::<3>(0).into_owned()
+    }
+
+    pub fn inverse_transform(&self) -> Matrix3<f32> {
+        self.inverse_transform.fixed_rows::<3>(0).into_owned()
+    }
+}
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug, Pod, Zeroable, CopyGetters, Setters)]
+pub struct CsgData {
+    #[getset(get_copy = "pub")]
+    bounds_min: Vector3<f32>, // 世界坐标中的包围盒
+    #[getset(get_copy = "pub", set = "pub")]
+    material_type: u32,
+    #[getset(get_copy = "pub")]
+    bounds_max: Vector3<f32>,
+    #[getset(get_copy = "pub", set = "pub")]
+    material_id: u32,
+    #[getset(get_copy = "pub")]
+    node_count: u32,
+    _padding: [u32; 3],
+    #[getset(get_copy = "pub", set = "pub")]
+    motion: MotionData, // 节点中的位置是时间 0 时的位置
+    nodes: [CsgNodeData; CSG_MAX_NODES], // 后序排列，前 node_count 个有效
+}
+
+wgsl_layout!(
+    CsgData,
+    "Csg",
+    [bounds_min, material_type, bounds_max, material_id, node_count, motion],
+    nodes
+);
+
+impl CsgData {
+    pub fn nodes(&self) -> &[CsgNodeData] {
+        &self.nodes[..self.node_count as usize]
+    }
+}
+
+impl Bound for CsgData {
+    fn bounding_box(&self) -> BoundingBox {
+        self.motion.sweep(&BoundingBox::new_from_points(
+            self.bounds_min.into(),
+            self.bounds_max.into(),
+        ))
+    }
+}
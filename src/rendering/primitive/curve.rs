@@ -0,0 +1,273 @@
+use crate::rendering::bounding_box::BoundingBox;
+use crate::rendering::layout::wgsl_layout;
+use crate::rendering::material::MaterialHandle;
+use crate::rendering::mesh::Mesh;
+use crate::rendering::primitive::{Bound, MotionData, PrimitiveData, Transformable};
+use bytemuck::{Pod, Zeroable};
+use getset::{CopyGetters, Setters};
+use nalgebra::{Point3, Scale3, Translation3, UnitQuaternion, Vector3, Vector4};
+use std::rc::Rc;
+
+// 一段曲线求交时最多近似为多少段折线
+const CURVE_MAX_PIECES: u32 = 32;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CurveKind {
+    // 总是朝向光线的扁平条带，适合草叶这类较宽的曲线
+    Ribbon,
+    // 法线在宽度方向上绕中心线弯曲，看起来像圆管，适合头发
+    Tube,
+}
+
+impl CurveKind {
+    // CurveData::kind 的取值，与 ray_tracing.wgsl 中的 CURVE_* 一致
+    pub const RIBBON: u32 = 0;
+    pub const TUBE: u32 = 1;
+
+    fn pack(&self) -> u32 {
+        match self {
+            CurveKind::Ribbon => Self::RIBBON,
+            CurveKind::Tube => Self::TUBE,
+        }
+    }
+}
+
+// 由首尾相接的三次 Bézier 曲线组成的一条曲线，控制点数为 3n + 1。每个控制点带有宽度，
+// 宽度和位置一样按 Bézier 插值。每一段是一个图元，BVH 建在这些段上
+#[derive(Clone)]
+pub struct Curve {
+    kind: CurveKind,
+    control_points: Vec<Point3<f32>>,
+    widths: Vec<f32>,
+    material: MaterialHandle,
+    primitives: Option<Vec<Rc<PrimitiveData>>>,
+}
+
+impl Curve {
+    pub fn new(kind: CurveKind, control_points: Vec<Point3<f32>>, widths: Vec<f32>, material: MaterialHandle) -> Self {
+        assert!(
+            control_points.len() >= 4 && control_points.len() % 3 == 1,
+            "a curve needs 3n + 1 control points, got {}",
+            control_points.len()
+        );
+        assert_eq!(control_points.len(), widths.len(), "every control point needs a width");
+
+        Self {
+            kind,
+            control_points,
+            widths,
+            material,
+            primitives: None,
+        }
+    }
+
+    pub fn segment_count(&self) -> usize {
+        self.control_points.len() / 3
+    }
+}
+
+impl Transformable for Curve {
+    fn translate(&mut self, translation: Translation3<f32>) {
+        self.control_points.iter_mut().for_each(|p| *p = translation * *p);
+        self.primitives = None;
+    }
+
+    fn rotate(&mut self, rotation: UnitQuaternion<f32>) {
+        self.control_points.iter_mut().for_each(|p| *p = rotation * *p);
+        self.primitives = None;
+    }
+
+    // 宽度没有方向，按三个方向缩放比例的几何平均缩放
+    fn scale(&mut self, scale: Scale3<f32>) {
+        self.control_points.iter_mut().for_each(|p| *p = scale * *p);
+        let factor = scale.vector.product().abs().cbrt();
+        self.widths.iter_mut().for_each(|width| *width *= factor);
+        self.primitives = None;
+    }
+}
+
+impl Mesh for Curve {
+    fn primitives(&mut self, primitives: &mut Vec<Rc<PrimitiveData>>, _important_indices: &mut Vec<u32>) {
+        if self.primitives.is_none() {
+            let segment_count = self.segment_count();
+            let segments = (0..segment_count)
+                .map(|i| {
+                    let control_points =
+                        std::array::from_fn(|j| self.control_points[3 * i + j].coords.push(self.widths[3 * i + j]));
+                    let curve_data = CurveData::new(
+                        self.kind,
+                        control_points,
+                        (i as f32 / segment_count as f32, (i + 1) as f32 / segment_count as f32),
+                        self.material,
+                    );
+                    Rc::new(PrimitiveData::Curve(curve_data))
+                })
+                .collect();
+            self.primitives = Some(segments);
+        }
+        primitives.extend(self.primitives.as_ref().unwrap().iter().map(Rc::clone));
+    }
+}
+
+// 解析曲线文件的内容。`ribbon` 或 `tube` 开始一条新的曲线，之后每行是一个控制点 `x y z width`，
+// # 之后是注释。格式错误的行会被忽略，控制点数不是 3n + 1 的曲线会被丢弃
+pub fn parse_curves(source: &str, material: MaterialHandle) -> Vec<Curve> {
+    struct ParsedCurve {
+        line_number: usize,
+        kind: CurveKind,
+        control_points: Vec<Point3<f32>>,
+        widths: Vec<f32>,
+    }
+    let mut curves: Vec<ParsedCurve> = Vec::new();
+
+    for (line_number, line) in source.lines().enumerate() {
+        let line = line.split('#').next().unwrap_or_default().trim();
+        let Some(keyword) = line.split_whitespace().next() else {
+            continue;
+        };
+
+        let kind = match keyword {
+            "ribbon" => Some(CurveKind::Ribbon),
+            "tube" => Some(CurveKind::Tube),
+            _ => None,
+        };
+        if let Some(kind) = kind {
+            curves.push(ParsedCurve {
+                line_number: line_number + 1,
+                kind,
+                control_points: Vec::new(),
+                widths: Vec::new(),
+            });
+            continue;
+        }
+
+        let Some(curve) = curves.last_mut() else {
+            log::warn!("curve line {}: control point before ribbon or tube", line_number + 1);
+            continue;
+        };
+        let values: Option<Vec<f32>> = line.split_whitespace().map(|token| token.parse().ok()).collect();
+        let Some([x, y, z, width]) = values.and_then(|values| <[f32; 4]>::try_from(values).ok()) else {
+            log::warn!("curve line {}: expected `x y z width`, got `{}`", line_number + 1, line);
+            continue;
+        };
+        curve.control_points.push(Point3::new(x, y, z));
+        curve.widths.push(width.max(0.0));
+    }
+
+    curves
+        .into_iter()
+        .filter_map(|curve| {
+            let count = curve.control_points.len();
+            if count < 4 || count % 3 != 1 {
+                log::warn!(
+                    "curve line {}: {} control points, expected 3n + 1",
+                    curve.line_number,
+                    count
+                );
+                return None;
+            }
+            Some(Curve::new(curve.kind, curve.control_points, curve.widths, material))
+        })
+        .collect()
+}
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug, Pod, Zeroable, CopyGetters, Setters)]
+pub struct CurveData {
+    // xyz 是位置，w 是宽度
+    #[getset(get_copy = "pub")]
+    control_points: [Vector4<f32>; 4],
+    #[getset(get_copy = "pub")]
+    kind: u32, // CurveKind::RIBBON 等
+    #[getset(get_copy = "pub", set = "pub")]
+    material_type: u32,
+    #[getset(get_copy = "pub", set = "pub")]
+    material_id: u32,
+    #[getset(get_copy = "pub")]
+    pieces: u32, // 求交时把这一段近似为多少段折线
+    #[getset(get_copy = "pub")]
+    u_min: f32, // 这一段在整条曲线上的参数范围，两端是整条曲线的端点时不延伸出圆头
+    #[getset(get_copy = "pub")]
+    u_max: f32,
+    _padding: [u32; 2],
+    #[getset(get_copy = "pub", set = "pub")]
+    motion: MotionData, // 控制点是时间 0 时的位置
+}
+
+wgsl_layout!(
+    CurveData,
+    "Curve",
+    [
+        control_points,
+        kind,
+        material_type,
+        material_id,
+        pieces,
+        u_min,
+        u_max,
+        motion
+    ]
+);
+
+impl CurveData {
+    pub fn new(
+        kind: CurveKind,
+        control_points: [Vector4<f32>; 4],
+        (u_min, u_max): (f32, f32),
+        material: MaterialHandle,
+    ) -> Self {
+        Self {
+            control_points,
+            kind: kind.pack(),
+            material_type: material.material_type,
+            material_id: material.material_id,
+            pieces: Self::piece_count(&control_points),
+            u_min,
+            u_max,
+            ..Zeroable::zeroed()
+        }
+    }
+
+    // 三次 Bézier 曲线的二阶导数不超过 6 L，L 是控制多边形二阶差分的最大长度，
+    // 用 n 段折线近似时的误差不超过 6 L / (8 n²)。位置和宽度一起计算，取 n 使误差不超过宽度的 5%
+    fn piece_count(control_points: &[Vector4<f32>; 4]) -> u32 {
+        let p = control_points;
+        let l = (0..2)
+            .map(|i| (p[i] - 2.0 * p[i + 1] + p[i + 2]).norm())
+            .fold(0.0, f32::max);
+        let width = control_points.iter().map(|p| p.w).fold(0.0, f32::max);
+        let tolerance = (0.05 * width).max(1e-6);
+        ((6.0 * l / (8.0 * tolerance)).sqrt().ceil() as u32).clamp(1, CURVE_MAX_PIECES)
+    }
+
+    // 位置和宽度
+    pub fn point(&self, u: f32) -> Vector4<f32> {
+        let [p0, p1, p2, p3] = self.control_points;
+        let v = 1.0 - u;
+        p0 * (v * v * v) + p1 * (3.0 * u * v * v) + p2 * (3.0 * u * u * v) + p3 * (u * u * u)
+    }
+
+    pub fn derivative(&self, u: f32) -> Vector4<f32> {
+        let [p0, p1, p2, p3] = self.control_points;
+        let v = 1.0 - u;
+        ((p1 - p0) * (v * v) + (p2 - p1) * (2.0 * u * v) + (p3 - p2) * (u * u)) * 3.0
+    }
+}
+
+// 曲线在控制点的凸包内，宽度不超过控制点宽度的最大值
+impl Bound for CurveData {
+    fn bounding_box(&self) -> BoundingBox {
+        let half_width = self.control_points.iter().map(|p| p.w).fold(0.0, f32::max) / 2.0;
+        let mut min = Vector3::repeat(f32::MAX);
+        let mut max = Vector3::repeat(f32::MIN);
+        for p in &self.control_points {
+            min = min.inf(&p.xyz());
+            max = max.sup(&p.xyz());
+        }
+        let extent = Vector3::repeat(half_width);
+        self.motion.sweep(&BoundingBox::new_from_points(
+            (min - extent).into(),
+            (max + extent).into(),
+        ))
+    }
+}
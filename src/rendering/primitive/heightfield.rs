@@ -0,0 +1,257 @@
+use crate::rendering::bounding_box::BoundingBox;
+use crate::rendering::layout::wgsl_layout;
+use crate::rendering::material::MaterialHandle;
+use crate::rendering::mesh::Mesh;
+use crate::rendering::primitive::{Bound, MotionData, PrimitiveData, Transformable};
+use bytemuck::{Pod, Zeroable};
+use getset::{CopyGetters, Setters};
+use image::{DynamicImage, ImageResult};
+use nalgebra::{Matrix3, Matrix4x3, Point3, Scale3, Translation3, UnitQuaternion, Vector2, Vector3};
+use std::path::Path;
+use std::rc::Rc;
+
+// 与 ray_tracing.wgsl 中的 HEIGHTFIELD_TILE_CELLS 一致。每块最多 8 × 8 个格子，
+// 数据中多存一圈采样点，用中心差分求块边缘的法线
+pub const HEIGHTFIELD_TILE_CELLS: usize = 8;
+pub const HEIGHTFIELD_TILE_SAMPLES: usize = HEIGHTFIELD_TILE_CELLS + 3;
+
+// 规则网格上的高度场。局部坐标中采样点 (x, z) 位于 (x, height, z)，height 归一化到 [0, 1]，
+// 每个格子沿 (x, z) 到 (x + 1, z + 1) 的对角线分成两个三角形。
+// 网格切成若干块，每块是一个图元，块的包围盒就是高度的 min/max 层级，BVH 建在这些块上
+#[derive(Clone)]
+pub struct Heightfield {
+    width: usize,            // x 方向的采样点数
+    depth: usize,            // z 方向的采样点数
+    heights: Vec<f32>,       // 按行存储，第 z 行第 x 个是 heights[z * width + x]
+    origin: Point3<f32>,     // 局部坐标原点在世界坐标中的位置
+    transform: Matrix3<f32>, // 局部坐标到世界坐标的线性部分
+    material: MaterialHandle,
+    primitives: Option<Vec<Rc<PrimitiveData>>>,
+}
+
+impl Heightfield {
+    // 在 XZ 平面上铺满以原点为中心、大小为 size 的矩形，高度 1 对应 height_scale
+    pub fn new(
+        width: usize,
+        depth: usize,
+        heights: Vec<f32>,
+        size: Vector2<f32>,
+        height_scale: f32,
+        material: MaterialHandle,
+    ) -> Self {
+        assert!(
+            width >= 2 && depth >= 2,
+            "a heightfield needs at least 2 × 2 samples, got {width} × {depth}"
+        );
+        assert_eq!(
+            heights.len(),
+            width * depth,
+            "a heightfield needs width × depth heights"
+        );
+
+        Self {
+            width,
+            depth,
+            heights,
+            origin: Point3::new(-size.x / 2.0, 0.0, -size.y / 2.0),
+            transform: Matrix3::from_diagonal(&Vector3::new(
+                size.x / (width - 1) as f32,
+                height_scale,
+                size.y / (depth - 1) as f32,
+            )),
+            material,
+            primitives: None,
+        }
+    }
+
+    // 灰度图的第 y 行对应 z = y，像素值按 16 位读取，8 位的图也会先扩展到 16 位
+    pub fn from_image(image: &DynamicImage, size: Vector2<f32>, height_scale: f32, material: MaterialHandle) -> Self {
+        let image = image.to_luma16();
+        let heights = image
+            .pixels()
+            .map(|pixel| pixel.0[0] as f32 / u16::MAX as f32)
+            .collect();
+        Self::new(
+            image.width() as usize,
+            image.height() as usize,
+            heights,
+            size,
+            height_scale,
+            material,
+        )
+    }
+
+    pub fn load(
+        path: impl AsRef<Path>,
+        size: Vector2<f32>,
+        height_scale: f32,
+        material: MaterialHandle,
+    ) -> ImageResult<Self> {
+        Ok(Self::from_image(&image::open(path)?, size, height_scale, material))
+    }
+
+    pub fn tile_count(&self) -> usize {
+        (self.width - 1).div_ceil(HEIGHTFIELD_TILE_CELLS) * (self.depth - 1).div_ceil(HEIGHTFIELD_TILE_CELLS)
+    }
+
+    // 超出网格的采样点按边缘的斜率线性外推，中心差分在边缘上退化为单侧差分
+    fn height(&self, x: isize, z: isize) -> f32 {
+        let sample = |x: isize, z: isize| {
+            let x = x.clamp(0, self.width as isize - 1) as usize;
+            let z = z.clamp(0, self.depth as isize - 1) as usize;
+            self.heights[z * self.width + x]
+        };
+        let (cx, cz) = (x.clamp(0, self.width as isize - 1), z.clamp(0, self.depth as isize - 1));
+        let mut height = sample(cx, cz);
+        if x != cx {
+            height += (x - cx).abs() as f32 * (sample(cx, cz) - sample(cx - (x - cx).signum(), cz));
+        }
+        if z != cz {
+            height += (z - cz).abs() as f32 * (sample(cx, cz) - sample(cx, cz - (z - cz).signum()));
+        }
+        height
+    }
+
+    fn tile(&self, x0: usize, z0: usize) -> HeightfieldData {
+        let cells_x = HEIGHTFIELD_TILE_CELLS.min(self.width - 1 - x0);
+        let cells_z = HEIGHTFIELD_TILE_CELLS.min(self.depth - 1 - z0);
+
+        let mut heights = [[0.0; HEIGHTFIELD_TILE_SAMPLES]; HEIGHTFIELD_TILE_SAMPLES];
+        for (j, row) in heights.iter_mut().enumerate() {
+            for (i, height) in row.iter_mut().enumerate() {
+                *height = self.height((x0 + i) as isize - 1, (z0 + j) as isize - 1);
+            }
+        }
+
+        let (mut min, mut max) = (f32::MAX, f32::MIN);
+        for row in &heights[1..cells_z + 2] {
+            for &height in &row[1..cells_x + 2] {
+                min = min.min(height);
+                max = max.max(height);
+            }
+        }
+
+        let inverse = self.transform.try_inverse().unwrap_or_else(Matrix3::zeros);
+        HeightfieldData {
+            origin: self.origin,
+            material_type: self.material.material_type,
+            bounds_min: Vector3::new(x0 as f32, min, z0 as f32),
+            material_id: self.material.material_id,
+            bounds_max: Vector3::new((x0 + cells_x) as f32, max, (z0 + cells_z) as f32),
+            grid_size: Vector2::new((self.width - 1) as f32, (self.depth - 1) as f32),
+            transform: self.transform.insert_row(3, 0.0),
+            inverse_transform: inverse.insert_row(3, 0.0),
+            heights,
+            ..Zeroable::zeroed()
+        }
+    }
+}
+
+impl Transformable for Heightfield {
+    fn translate(&mut self, translation: Translation3<f32>) {
+        self.origin = translation * self.origin;
+        self.primitives = None;
+    }
+
+    fn rotate(&mut self, rotation: UnitQuaternion<f32>) {
+        self.origin = rotation * self.origin;
+        self.transform = rotation.to_rotation_matrix().matrix() * self.transform;
+        self.primitives = None;
+    }
+
+    fn scale(&mut self, scale: Scale3<f32>) {
+        self.origin = scale * self.origin;
+        self.transform = Matrix3::from_diagonal(&scale.vector) * self.transform;
+        self.primitives = None;
+    }
+}
+
+impl Mesh for Heightfield {
+    fn primitives(&mut self, primitives: &mut Vec<Rc<PrimitiveData>>, _important_indices: &mut Vec<u32>) {
+        if self.primitives.is_none() {
+            let tiles = (0..self.depth - 1)
+                .step_by(HEIGHTFIELD_TILE_CELLS)
+                .flat_map(|z0| {
+                    (0..self.width - 1)
+                        .step_by(HEIGHTFIELD_TILE_CELLS)
+                        .map(move |x0| (x0, z0))
+                })
+                .map(|(x0, z0)| Rc::new(PrimitiveData::Heightfield(self.tile(x0, z0))))
+                .collect();
+            self.primitives = Some(tiles);
+        }
+        primitives.extend(self.primitives.as_ref().unwrap().iter().map(Rc::clone));
+    }
+}
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug, Pod, Zeroable, CopyGetters, Setters)]
+pub struct HeightfieldData {
+    #[getset(get_copy = "pub")]
+    origin: Point3<f32>,
+    #[getset(get_copy = "pub", set = "pub")]
+    material_type: u32,
+    // 这一块在局部坐标中的包围盒，x、z 是格子的下标范围，y 是高度范围
+    #[getset(get_copy = "pub")]
+    bounds_min: Vector3<f32>,
+    #[getset(get_copy = "pub", set = "pub")]
+    material_id: u32,
+    #[getset(get_copy = "pub")]
+    bounds_max: Vector3<f32>,
+    _padding: u32,
+    #[getset(get_copy = "pub")]
+    grid_size: Vector2<f32>, // 整个网格的格子数，uv 按它归一化到 [0, 1]
+    _padding2: [u32; 2],
+    // 局部坐标到世界坐标的线性部分及其逆，第 4 行是 WGSL 中 mat3x3f 每列的填充
+    transform: Matrix4x3<f32>,
+    inverse_transform: Matrix4x3<f32>,
+    #[getset(get_copy = "pub", set = "pub")]
+    motion: MotionData, // origin 是时间 0 时的位置
+    // heights[j][i] 是采样点 (bounds_min.x + i - 1, bounds_min.z + j - 1) 的高度
+    heights: [[f32; HEIGHTFIELD_TILE_SAMPLES]; HEIGHTFIELD_TILE_SAMPLES],
+    _padding3: [u32; 3],
+}
+
+wgsl_layout!(
+    HeightfieldData,
+    "Heightfield",
+    [
+        origin,
+        material_type,
+        bounds_min,
+        material_id,
+        bounds_max,
+        grid_size,
+        transform,
+        inverse_transform,
+        motion
+    ],
+    heights
+);
+
+impl HeightfieldData {
+    pub fn transform(&self) -> Matrix3<f32> {
+        self.transform.fixed_rows::<3>(0).into_owned()
+    }
+
+    pub fn inverse_transform(&self) -> Matrix3<f32> {
+        self.inverse_transform.fixed_rows::<3>(0).into_owned()
+    }
+
+    // 网格采样点 (x, z) 的高度，只能取这一块及其外面一圈
+    pub fn height(&self, x: i32, z: i32) -> f32 {
+        let i = x - self.bounds_min.x as i32 + 1;
+        let j = z - self.bounds_min.z as i32 + 1;
+        self.heights[j as usize][i as usize]
+    }
+}
+
+impl Bound for HeightfieldData {
+    fn bounding_box(&self) -> BoundingBox {
+        let transform = self.transform();
+        let middle = self.origin + transform * (self.bounds_min + self.bounds_max) / 2.0;
+        let extent = transform.abs() * (self.bounds_max - self.bounds_min) / 2.0;
+        self.motion
+            .sweep(&BoundingBox::new_from_points(middle - extent, middle + extent))
+    }
+}
@@ -0,0 +1,145 @@
+use crate::rendering::bounding_box::BoundingBox;
+use crate::rendering::layout::wgsl_layout;
+use crate::rendering::primitive::Transformable;
+use bytemuck::{Pod, Zeroable};
+use nalgebra::{Isometry3, Point3, Scale3, Translation3, Unit, UnitQuaternion, Vector3};
+
+// 物体在曝光期间的刚体运动。时间 0 时物体在 Transformable 给出的位置，
+// 时间 1 时先绕 pivot 旋转 rotation，再平移 translation，中间平移线性插值、旋转球面插值
+#[derive(Clone, Copy, Debug)]
+pub struct Motion {
+    pub pivot: Point3<f32>,
+    pub rotation: UnitQuaternion<f32>, // 旋转角需要小于 180°
+    pub translation: Translation3<f32>,
+}
+
+impl Default for Motion {
+    fn default() -> Self {
+        Self {
+            pivot: Point3::origin(),
+            rotation: UnitQuaternion::identity(),
+            translation: Translation3::identity(),
+        }
+    }
+}
+
+impl Motion {
+    pub fn translation(translation: Translation3<f32>) -> Self {
+        Self {
+            translation,
+            ..Default::default()
+        }
+    }
+
+    pub fn rotation(pivot: Point3<f32>, rotation: UnitQuaternion<f32>) -> Self {
+        Self {
+            pivot,
+            rotation,
+            ..Default::default()
+        }
+    }
+}
+
+// 运动的物体在世界空间中的位置随之改变，运动本身也跟着变换
+impl Transformable for Motion {
+    fn translate(&mut self, translation: Translation3<f32>) {
+        self.pivot = translation * self.pivot;
+    }
+
+    fn rotate(&mut self, rotation: UnitQuaternion<f32>) {
+        self.pivot = rotation * self.pivot;
+        self.rotation = rotation * self.rotation * rotation.inverse();
+        self.translation.vector = rotation * self.translation.vector;
+    }
+
+    // 非均匀缩放后的旋转不再是刚体运动，这里只缩放位置和平移
+    fn scale(&mut self, scale: Scale3<f32>) {
+        self.pivot = scale * self.pivot;
+        self.translation.vector = scale * self.translation.vector;
+    }
+}
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug, Default, Pod, Zeroable)]
+pub struct MotionData {
+    translation: Vector3<f32>,
+    angle: f32,
+    axis: Vector3<f32>,
+    moving: u32, // 为 0 时物体静止，求交时不需要变换光线
+    pivot: Point3<f32>,
+    _padding: u32,
+}
+
+wgsl_layout!(MotionData, "Motion", [translation, angle, axis, moving, pivot]);
+
+impl From<&Motion> for MotionData {
+    fn from(motion: &Motion) -> Self {
+        let (axis, angle) = motion
+            .rotation
+            .axis_angle()
+            .map_or((Vector3::x(), 0.0), |(axis, angle)| (axis.into_inner(), angle));
+        let moving = angle != 0.0 || motion.translation.vector != Vector3::zeros();
+        Self {
+            translation: motion.translation.vector,
+            angle,
+            axis,
+            moving: moving as u32,
+            pivot: motion.pivot,
+            _padding: 0,
+        }
+    }
+}
+
+impl MotionData {
+    pub fn is_moving(&self) -> bool {
+        self.moving != 0
+    }
+
+    // 时间 0 时的位置到 time 时的位置的变换，与 ray_tracing.wgsl 中的 Motion_* 一致
+    pub fn at(&self, time: f32) -> Isometry3<f32> {
+        let rotation = UnitQuaternion::from_axis_angle(&Unit::new_unchecked(self.axis), self.angle * time);
+        let translation = self.pivot.coords + self.translation * time - rotation * self.pivot.coords;
+        Isometry3::from_parts(Translation3::from(translation), rotation)
+    }
+
+    // 包围 0 到 1 之间所有时刻的 bounding_box。在若干个时刻取变换后的包围盒，
+    // 再按相邻时刻之间圆弧偏离弦的最大距离向外扩展
+    pub fn sweep(&self, bounding_box: &BoundingBox) -> BoundingBox {
+        if !self.is_moving() {
+            return *bounding_box;
+        }
+
+        const STEPS: usize = 16;
+        let corners: Vec<Point3<f32>> = (0..8)
+            .map(|i| {
+                let bound = |axis: i32, bit: usize| {
+                    let interval = bounding_box.axis(axis);
+                    if i & bit == 0 {
+                        *interval.min()
+                    } else {
+                        *interval.max()
+                    }
+                };
+                Point3::new(bound(0, 1), bound(1, 2), bound(2, 4))
+            })
+            .collect();
+        let radius = corners
+            .iter()
+            .map(|corner| (corner - self.pivot).norm())
+            .fold(0.0, f32::max);
+        let padding = radius * (1.0 - (self.angle.abs() / STEPS as f32 / 2.0).cos());
+
+        let mut swept = BoundingBox::empty();
+        for step in 0..=STEPS {
+            let transform = self.at(step as f32 / STEPS as f32);
+            for corner in &corners {
+                let p = transform * corner;
+                swept.merge(&BoundingBox::new_from_points(p, p));
+            }
+        }
+        for axis in 0..3 {
+            swept.axis_mut(axis).expand(2.0 * padding);
+        }
+        swept
+    }
+}
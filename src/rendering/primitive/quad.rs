@@ -1,13 +1,16 @@
+use crate::rendering::layout::wgsl_layout;
 use crate::rendering::material::MaterialHandle;
 use crate::rendering::primitive::transformable::Transformable;
-use crate::rendering::primitive::PrimitiveData;
+use crate::rendering::primitive::{MotionData, PrimitiveData};
 use crate::rendering::{bounding_box::BoundingBox, mesh::Mesh};
 use bytemuck::{Pod, Zeroable};
+use getset::{CopyGetters, Setters};
 use nalgebra::{Point3, Scale3, Translation3, UnitQuaternion, Vector3};
 use std::rc::Rc;
 
 use super::Bound;
 
+#[derive(Clone)]
 pub struct Quad {
     center: Point3<f32>,
     right: Vector3<f32>,
@@ -79,20 +82,48 @@ impl Mesh for Quad {
 }
 
 #[repr(C)]
-#[derive(Copy, Clone, Debug, Pod, Zeroable)]
+#[derive(Copy, Clone, Debug, Pod, Zeroable, CopyGetters, Setters)]
 pub struct QuadData {
+    #[getset(get_copy = "pub")]
     bottom_left: Point3<f32>,
+    #[getset(get_copy = "pub", set = "pub")]
     material_id: u32,
+    #[getset(get_copy = "pub")]
     right: Vector3<f32>,
+    #[getset(get_copy = "pub")]
     area: f32,
+    #[getset(get_copy = "pub")]
     up: Vector3<f32>,
+    #[getset(get_copy = "pub")]
     d: f32,
+    #[getset(get_copy = "pub")]
     normal: Vector3<f32>,
+    #[getset(get_copy = "pub", set = "pub")]
     material_type: u32,
+    #[getset(get_copy = "pub")]
     w: Vector3<f32>,
     _padding2: [u32; 1],
+    #[getset(get_copy = "pub", set = "pub")]
+    motion: MotionData, // 以上的几何数据是时间 0 时的位置
 }
 
+wgsl_layout!(
+    QuadData,
+    "Quad",
+    [
+        bottom_left,
+        material_id,
+        right,
+        area,
+        up,
+        d,
+        normal,
+        material_type,
+        w,
+        motion
+    ]
+);
+
 impl QuadData {
     pub fn new(
         center: Point3<f32>,
@@ -119,6 +150,7 @@ impl QuadData {
             w,
             material_type,
             _padding2: Default::default(),
+            motion: Default::default(),
         }
     }
 }
@@ -127,6 +159,6 @@ impl Bound for QuadData {
     fn bounding_box(&self) -> BoundingBox {
         let box1 = BoundingBox::new_from_points(self.bottom_left, self.bottom_left + self.right + self.up);
         let box2 = BoundingBox::new_from_points(self.bottom_left + self.right, self.bottom_left + self.up);
-        BoundingBox::new_from_boxes(&box1, &box2)
+        self.motion.sweep(&BoundingBox::new_from_boxes(&box1, &box2))
     }
 }
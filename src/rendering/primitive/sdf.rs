@@ -0,0 +1,375 @@
+use crate::rendering::bounding_box::BoundingBox;
+use crate::rendering::layout::wgsl_layout;
+use crate::rendering::material::MaterialHandle;
+use crate::rendering::mesh::Mesh;
+use crate::rendering::primitive::{Bound, MotionData, PrimitiveData, Transformable};
+use bytemuck::{Pod, Zeroable};
+use getset::{CopyGetters, Setters};
+use nalgebra::{Isometry3, Matrix3, Matrix4x3, Point3, Scale3, Translation3, UnitQuaternion, Vector3, Vector4};
+use std::rc::Rc;
+
+// 与 ray_tracing.wgsl 中的 SDF_MAX_NODES 一致
+pub const SDF_MAX_NODES: usize = 16;
+
+// 有符号距离场的节点树。叶子是以原点为中心的基本形状，内部节点组合两个子树，smoothness 为 0 时是普通的布尔运算
+#[derive(Clone, Debug)]
+pub enum SdfNode {
+    Sphere {
+        radius: f32,
+    },
+    // size 是三个方向的边长
+    Box {
+        size: Vector3<f32>,
+    },
+    // 棱边倒成半径为 radius 的圆角，整体尺寸仍然是 size
+    RoundedBox {
+        size: Vector3<f32>,
+        radius: f32,
+    },
+    // 在 XZ 平面上的环面
+    Torus {
+        major_radius: f32,
+        minor_radius: f32,
+    },
+    // 沿 Y 轴的胶囊，height 是两端半球球心之间的距离
+    Capsule {
+        radius: f32,
+        height: f32,
+    },
+    Union {
+        a: Box<SdfNode>,
+        b: Box<SdfNode>,
+        smoothness: f32,
+    },
+    // 从 a 中挖去 b
+    Subtraction {
+        a: Box<SdfNode>,
+        b: Box<SdfNode>,
+        smoothness: f32,
+    },
+    Intersection {
+        a: Box<SdfNode>,
+        b: Box<SdfNode>,
+        smoothness: f32,
+    },
+    // 只允许刚体变换，缩放会破坏距离场
+    Transformed {
+        node: Box<SdfNode>,
+        isometry: Isometry3<f32>,
+    },
+}
+
+impl SdfNode {
+    // SdfNodeData::kind 的取值，与 ray_tracing.wgsl 中的 SDF_* 一致
+    pub const SPHERE: u32 = 0;
+    pub const BOX: u32 = 1;
+    pub const ROUNDED_BOX: u32 = 2;
+    pub const TORUS: u32 = 3;
+    pub const CAPSULE: u32 = 4;
+    pub const UNION: u32 = 5;
+    pub const SUBTRACTION: u32 = 6;
+    pub const INTERSECTION: u32 = 7;
+
+    pub fn union(self, other: SdfNode, smoothness: f32) -> Self {
+        SdfNode::Union {
+            a: Box::new(self),
+            b: Box::new(other),
+            smoothness,
+        }
+    }
+
+    pub fn subtract(self, other: SdfNode, smoothness: f32) -> Self {
+        SdfNode::Subtraction {
+            a: Box::new(self),
+            b: Box::new(other),
+            smoothness,
+        }
+    }
+
+    pub fn intersect(self, other: SdfNode, smoothness: f32) -> Self {
+        SdfNode::Intersection {
+            a: Box::new(self),
+            b: Box::new(other),
+            smoothness,
+        }
+    }
+
+    pub fn translated(self, translation: Translation3<f32>) -> Self {
+        self.transformed(Isometry3::from_parts(translation, UnitQuaternion::identity()))
+    }
+
+    pub fn rotated(self, rotation: UnitQuaternion<f32>) -> Self {
+        self.transformed(Isometry3::from_parts(Translation3::identity(), rotation))
+    }
+
+    fn transformed(self, isometry: Isometry3<f32>) -> Self {
+        match self {
+            SdfNode::Transformed { node, isometry: inner } => SdfNode::Transformed {
+                node,
+                isometry: isometry * inner,
+            },
+            node => SdfNode::Transformed {
+                node: Box::new(node),
+                isometry,
+            },
+        }
+    }
+
+    // 按后序展开，着色器用一个栈依次求值。isometry 是从叶子局部坐标到 SDF 坐标的累积变换，返回子树在 SDF 坐标中的包围盒
+    fn flatten(&self, isometry: &Isometry3<f32>, nodes: &mut Vec<SdfNodeData>) -> (Vector3<f32>, Vector3<f32>) {
+        let (kind, parameters, half_size) = match *self {
+            SdfNode::Sphere { radius } => (
+                Self::SPHERE,
+                Vector4::new(radius, 0.0, 0.0, 0.0),
+                Vector3::repeat(radius),
+            ),
+            SdfNode::Box { size } => (Self::BOX, (size / 2.0).push(0.0), size / 2.0),
+            SdfNode::RoundedBox { size, radius } => {
+                let radius = radius.min(size.min() / 2.0);
+                (Self::ROUNDED_BOX, (size / 2.0).push(radius), size / 2.0)
+            }
+            SdfNode::Torus {
+                major_radius,
+                minor_radius,
+            } => {
+                let outer = major_radius + minor_radius;
+                (
+                    Self::TORUS,
+                    Vector4::new(major_radius, minor_radius, 0.0, 0.0),
+                    Vector3::new(outer, minor_radius, outer),
+                )
+            }
+            SdfNode::Capsule { radius, height } => (
+                Self::CAPSULE,
+                Vector4::new(radius, height / 2.0, 0.0, 0.0),
+                Vector3::new(radius, height / 2.0 + radius, radius),
+            ),
+            SdfNode::Union {
+                ref a,
+                ref b,
+                smoothness,
+            } => {
+                let (a_min, a_max) = a.flatten(isometry, nodes);
+                let (b_min, b_max) = b.flatten(isometry, nodes);
+                nodes.push(SdfNodeData::operation(Self::UNION, smoothness));
+                // 平滑并集最多向外鼓出 smoothness / 4
+                let margin = Vector3::repeat(smoothness / 4.0);
+                return (a_min.inf(&b_min) - margin, a_max.sup(&b_max) + margin);
+            }
+            SdfNode::Subtraction {
+                ref a,
+                ref b,
+                smoothness,
+            } => {
+                let bounds = a.flatten(isometry, nodes);
+                b.flatten(isometry, nodes);
+                nodes.push(SdfNodeData::operation(Self::SUBTRACTION, smoothness));
+                return bounds;
+            }
+            SdfNode::Intersection {
+                ref a,
+                ref b,
+                smoothness,
+            } => {
+                let (a_min, a_max) = a.flatten(isometry, nodes);
+                let (b_min, b_max) = b.flatten(isometry, nodes);
+                nodes.push(SdfNodeData::operation(Self::INTERSECTION, smoothness));
+                return (a_min.sup(&b_min), a_max.inf(&b_max));
+            }
+            SdfNode::Transformed {
+                ref node,
+                isometry: ref inner,
+            } => return node.flatten(&(isometry * inner), nodes),
+        };
+
+        let rotation = isometry.rotation.to_rotation_matrix().into_inner();
+        let center = Point3::from(isometry.translation.vector);
+        nodes.push(SdfNodeData {
+            kind,
+            parameters,
+            center,
+            inverse_rotation: rotation.transpose().insert_row(3, 0.0),
+            ..Zeroable::zeroed()
+        });
+
+        let extent = rotation.abs() * half_size;
+        (center.coords - extent, center.coords + extent)
+    }
+}
+
+#[derive(Clone)]
+pub struct Sdf {
+    sdf_data: SdfData,
+    transform: Matrix3<f32>, // 旋转和缩放，作用在以 center 为原点的 SDF 坐标上
+    primitive: Option<Rc<PrimitiveData>>,
+}
+
+impl Sdf {
+    // 没有办法在距离场的表面上均匀采样，所以 SDF 不能作为重要性采样的目标
+    pub fn new(root: &SdfNode, center: Point3<f32>, material: MaterialHandle) -> Self {
+        let mut nodes = Vec::new();
+        let (bounds_min, bounds_max) = root.flatten(&Isometry3::identity(), &mut nodes);
+        assert!(
+            nodes.len() <= SDF_MAX_NODES,
+            "SDF has {} nodes, at most {SDF_MAX_NODES} are supported",
+            nodes.len()
+        );
+
+        let mut sdf_data = SdfData {
+            center,
+            material_type: material.material_type,
+            bounds_min,
+            material_id: material.material_id,
+            bounds_max,
+            node_count: nodes.len() as u32,
+            ..Zeroable::zeroed()
+        };
+        sdf_data.nodes[..nodes.len()].copy_from_slice(&nodes);
+        sdf_data.set_transform(Matrix3::identity());
+
+        Self {
+            sdf_data,
+            transform: Matrix3::identity(),
+            primitive: None,
+        }
+    }
+}
+
+impl Transformable for Sdf {
+    fn translate(&mut self, translation: Translation3<f32>) {
+        self.sdf_data.center = translation * self.sdf_data.center;
+        self.primitive = None;
+    }
+
+    fn rotate(&mut self, rotation: UnitQuaternion<f32>) {
+        self.sdf_data.center = rotation * self.sdf_data.center;
+        self.transform = rotation.to_rotation_matrix().matrix() * self.transform;
+        self.primitive = None;
+    }
+
+    fn scale(&mut self, scale: Scale3<f32>) {
+        self.sdf_data.center = scale * self.sdf_data.center;
+        self.transform = Matrix3::from_diagonal(&scale.vector) * self.transform;
+        self.primitive = None;
+    }
+}
+
+impl Mesh for Sdf {
+    fn primitives(&mut self, primitives: &mut Vec<Rc<PrimitiveData>>, _important_indices: &mut Vec<u32>) {
+        if self.primitive.is_none() {
+            let mut sdf_data = self.sdf_data;
+            sdf_data.set_transform(self.transform);
+            self.primitive = Some(Rc::new(PrimitiveData::Sdf(sdf_data)));
+        }
+        primitives.push(Rc::clone(self.primitive.as_ref().unwrap()));
+    }
+}
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug, Pod, Zeroable, CopyGetters)]
+pub struct SdfNodeData {
+    #[getset(get_copy = "pub")]
+    kind: u32, // SdfNode::SPHERE 等
+    #[getset(get_copy = "pub")]
+    smoothness: f32,
+    _padding: [u32; 2],
+    #[getset(get_copy = "pub")]
+    parameters: Vector4<f32>, // 叶子的尺寸：球、胶囊的半径，长方体的半边长，圆角长方体在 w 中还有圆角半径
+    #[getset(get_copy = "pub")]
+    center: Point3<f32>, // 叶子在 SDF 坐标中的位置
+    _padding2: u32,
+    inverse_rotation: Matrix4x3<f32>, // SDF 坐标到叶子局部坐标的旋转，第 4 行是填充
+}
+
+wgsl_layout!(
+    SdfNodeData,
+    "SdfNode",
+    [kind, smoothness, parameters, center, inverse_rotation]
+);
+
+impl SdfNodeData {
+    fn operation(kind: u32, smoothness: f32) -> Self {
+        Self {
+            kind,
+            smoothness,
+            ..Zeroable::zeroed()
+        }
+    }
+
+    pub fn inverse_rotation(&self) -> Matrix3<f32> {
+        self.inverse_rotation.fixed_rows::<3>(0).into_owned()
+    }
+}
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug, Pod, Zeroable, CopyGetters, Setters)]
+pub struct SdfData {
+    #[getset(get_copy = "pub")]
+    center: Point3<f32>,
+    #[getset(get_copy = "pub", set = "pub")]
+    material_type: u32,
+    #[getset(get_copy = "pub")]
+    bounds_min: Vector3<f32>, // SDF 坐标中的包围盒
+    #[getset(get_copy = "pub", set = "pub")]
+    material_id: u32,
+    #[getset(get_copy = "pub")]
+    bounds_max: Vector3<f32>,
+    #[getset(get_copy = "pub")]
+    node_count: u32,
+    // SDF 坐标到世界坐标的线性部分及其逆，第 4 行是 WGSL 中 mat3x3f 每列的填充
+    transform: Matrix4x3<f32>,
+    inverse_transform: Matrix4x3<f32>,
+    #[getset(get_copy = "pub", set = "pub")]
+    motion: MotionData, // center 是时间 0 时的位置
+    nodes: [SdfNodeData; SDF_MAX_NODES], // 后序排列，前 node_count 个有效
+}
+
+wgsl_layout!(
+    SdfData,
+    "Sdf",
+    [
+        center,
+        material_type,
+        bounds_min,
+        material_id,
+        bounds_max,
+        node_count,
+        transform,
+        inverse_transform,
+        motion
+    ],
+    nodes
+);
+
+impl SdfData {
+    // 世界坐标中的点 p = center + transform * q，q 是 SDF 坐标中的点。
+    // 在 SDF 坐标中步进，非均匀缩放也不会破坏距离场
+    pub fn set_transform(&mut self, transform: Matrix3<f32>) -> &mut Self {
+        let inverse = transform.try_inverse().unwrap_or_else(Matrix3::zeros);
+        self.transform = transform.insert_row(3, 0.0);
+        self.inverse_transform = inverse.insert_row(3, 0.0);
+        self
+    }
+
+    pub fn transform(&self) -> Matrix3<f32> {
+        self.transform.fixed_rows::<3>(0).into_owned()
+    }
+
+    pub fn inverse_transform(&self) -> Matrix3<f32> {
+        self.inverse_transform.fixed_rows::<3>(0).into_owned()
+    }
+
+    pub fn nodes(&self) -> &[SdfNodeData] {
+        &self.nodes[..self.node_count as usize]
+    }
+}
+
+impl Bound for SdfData {
+    fn bounding_box(&self) -> BoundingBox {
+        let transform = self.transform();
+        let middle = self.center + transform * (self.bounds_min + self.bounds_max) / 2.0;
+        let extent = transform.abs() * (self.bounds_max - self.bounds_min) / 2.0;
+        self.motion
+            .sweep(&BoundingBox::new_from_points(middle - extent, middle + extent))
+    }
+}
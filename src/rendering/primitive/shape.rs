@@ -0,0 +1,227 @@
+use crate::rendering::bounding_box::BoundingBox;
+use crate::rendering::layout::wgsl_layout;
+use crate::rendering::material::MaterialHandle;
+use crate::rendering::mesh::Mesh;
+use crate::rendering::primitive::{Bound, MotionData, PrimitiveData, Transformable};
+use bytemuck::{Pod, Zeroable};
+use getset::{CopyGetters, Setters};
+use nalgebra::{Matrix3, Matrix4x3, Point3, Scale3, Translation3, UnitQuaternion, Vector3, Vector4};
+use std::f32::consts::PI;
+use std::rc::Rc;
+
+// 以原点为中心、绕 Y 轴旋转对称的解析形状，尺寸都是未经变换时的大小
+#[derive(Clone, Copy, Debug)]
+pub enum ShapeKind {
+    // XZ 平面上的圆盘，法线朝向 +Y
+    Disk { radius: f32 },
+    // 两端有盖的圆柱，高度沿 Y 轴
+    Cylinder { radius: f32, height: f32 },
+    // 底面有盖的圆锥，底面在下、顶点在上
+    Cone { radius: f32, height: f32 },
+    // 在 XZ 平面上的环面，minor_radius 需要小于 major_radius
+    Torus { major_radius: f32, minor_radius: f32 },
+    // 各个面与坐标轴对齐的长方体，size 是三个方向的边长
+    Box { size: Vector3<f32> },
+}
+
+impl ShapeKind {
+    // ShapeData::kind 的取值，与 ray_tracing.wgsl 中的 SHAPE_* 一致
+    pub const DISK: u32 = 0;
+    pub const CYLINDER: u32 = 1;
+    pub const CONE: u32 = 2;
+    pub const TORUS: u32 = 3;
+    pub const BOX: u32 = 4;
+
+    // 写入 ShapeData 的 (类型, 参数, 表面积)。圆柱和圆锥的参数是半径和半高，长方体是三个方向的半边长
+    fn pack(&self) -> (u32, Vector4<f32>, f32) {
+        match *self {
+            ShapeKind::Disk { radius } => (Self::DISK, Vector4::new(radius, 0.0, 0.0, 0.0), PI * radius * radius),
+            ShapeKind::Cylinder { radius, height } => (
+                Self::CYLINDER,
+                Vector4::new(radius, height / 2.0, 0.0, 0.0),
+                2.0 * PI * radius * (height + radius),
+            ),
+            ShapeKind::Cone { radius, height } => (
+                Self::CONE,
+                Vector4::new(radius, height / 2.0, 0.0, 0.0),
+                PI * radius * ((radius * radius + height * height).sqrt() + radius),
+            ),
+            ShapeKind::Torus {
+                major_radius,
+                minor_radius,
+            } => {
+                let minor_radius = minor_radius.min(major_radius);
+                (
+                    Self::TORUS,
+                    Vector4::new(major_radius, minor_radius, 0.0, 0.0),
+                    4.0 * PI * PI * major_radius * minor_radius,
+                )
+            }
+            ShapeKind::Box { size } => (
+                Self::BOX,
+                (size / 2.0).push(0.0),
+                2.0 * (size.x * size.y + size.y * size.z + size.z * size.x),
+            ),
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct Shape {
+    kind: ShapeKind,
+    center: Point3<f32>,
+    transform: Matrix3<f32>, // 旋转和缩放，作用在以 center 为原点的局部坐标上
+    material_id: u32,
+    material_type: u32,
+    primitive: Option<Rc<PrimitiveData>>,
+    important: bool,
+}
+
+impl Shape {
+    pub fn new(kind: ShapeKind, center: Point3<f32>, material: MaterialHandle, important: bool) -> Self {
+        Self {
+            kind,
+            center,
+            transform: Matrix3::identity(),
+            material_type: material.material_type,
+            material_id: material.material_id,
+            primitive: None,
+            important,
+        }
+    }
+}
+
+impl Transformable for Shape {
+    fn translate(&mut self, translation: Translation3<f32>) {
+        self.center = translation * self.center;
+        self.primitive = None;
+    }
+
+    fn rotate(&mut self, rotation: UnitQuaternion<f32>) {
+        self.center = rotation * self.center;
+        self.transform = rotation.to_rotation_matrix().matrix() * self.transform;
+        self.primitive = None;
+    }
+
+    fn scale(&mut self, scale: Scale3<f32>) {
+        self.center = scale * self.center;
+        self.transform = Matrix3::from_diagonal(&scale.vector) * self.transform;
+        self.primitive = None;
+    }
+}
+
+impl Mesh for Shape {
+    fn primitives(&mut self, primitives: &mut Vec<Rc<PrimitiveData>>, important_indices: &mut Vec<u32>) {
+        if self.important {
+            important_indices.push(primitives.len() as u32);
+        }
+
+        if self.primitive.is_none() {
+            let mut shape_data = ShapeData::new(&self.kind, self.center, self.material_type, self.material_id);
+            shape_data.set_transform(self.transform);
+            self.primitive = Some(Rc::new(PrimitiveData::Shape(shape_data)));
+        }
+        primitives.push(Rc::clone(self.primitive.as_ref().unwrap()));
+    }
+}
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug, Pod, Zeroable, CopyGetters, Setters)]
+pub struct ShapeData {
+    #[getset(get_copy = "pub")]
+    kind: u32, // ShapeKind::DISK 等
+    #[getset(get_copy = "pub", set = "pub")]
+    material_type: u32,
+    #[getset(get_copy = "pub", set = "pub")]
+    material_id: u32,
+    #[getset(get_copy = "pub")]
+    area: f32, // 局部坐标中的表面积
+    #[getset(get_copy = "pub")]
+    parameters: Vector4<f32>, // 局部坐标中的尺寸，见 ShapeKind::pack
+    #[getset(get_copy = "pub")]
+    center: Point3<f32>,
+    #[getset(get_copy = "pub")]
+    determinant: f32, // transform 行列式的绝对值
+    // 局部坐标到世界坐标的线性部分及其逆，第 4 行是 WGSL 中 mat3x3f 每列的填充
+    transform: Matrix4x3<f32>,
+    inverse_transform: Matrix4x3<f32>,
+    #[getset(get_copy = "pub", set = "pub")]
+    motion: MotionData, // center 是时间 0 时的位置
+}
+
+wgsl_layout!(
+    ShapeData,
+    "Shape",
+    [
+        kind,
+        material_type,
+        material_id,
+        area,
+        parameters,
+        center,
+        determinant,
+        transform,
+        inverse_transform,
+        motion
+    ]
+);
+
+impl ShapeData {
+    pub fn new(kind: &ShapeKind, center: Point3<f32>, material_type: u32, material_id: u32) -> Self {
+        let (kind, parameters, area) = kind.pack();
+        Self {
+            kind,
+            material_type,
+            material_id,
+            area,
+            parameters,
+            center,
+            determinant: 1.0,
+            transform: Matrix3::identity().insert_row(3, 0.0),
+            inverse_transform: Matrix3::identity().insert_row(3, 0.0),
+            motion: Default::default(),
+        }
+    }
+
+    // 世界坐标中的点 p = center + transform * q，q 是局部坐标中形状上的点。
+    // 不可逆的变换把形状压扁成没有体积的形状，此时光线与它没有交点
+    pub fn set_transform(&mut self, transform: Matrix3<f32>) -> &mut Self {
+        let inverse = transform.try_inverse().unwrap_or_else(Matrix3::zeros);
+        self.determinant = transform.determinant().abs();
+        self.transform = transform.insert_row(3, 0.0);
+        self.inverse_transform = inverse.insert_row(3, 0.0);
+        self
+    }
+
+    pub fn transform(&self) -> Matrix3<f32> {
+        self.transform.fixed_rows::<3>(0).into_owned()
+    }
+
+    pub fn inverse_transform(&self) -> Matrix3<f32> {
+        self.inverse_transform.fixed_rows::<3>(0).into_owned()
+    }
+
+    // 局部坐标中的包围盒的半边长
+    fn object_half_size(&self) -> Vector3<f32> {
+        let p = self.parameters;
+        match self.kind {
+            ShapeKind::DISK => Vector3::new(p.x, 0.0, p.x),
+            ShapeKind::CYLINDER | ShapeKind::CONE => Vector3::new(p.x, p.y, p.x),
+            ShapeKind::TORUS => Vector3::new(p.x + p.y, p.y, p.x + p.y),
+            _ => p.xyz(),
+        }
+    }
+}
+
+impl Bound for ShapeData {
+    // 变换后的椭球或长方体，每个轴上的半宽是局部半边长在 transform 对应行上的投影之和
+    fn bounding_box(&self) -> BoundingBox {
+        let transform = self.transform();
+        let half_size = self.object_half_size();
+        let extent = Vector3::from_fn(|axis, _| transform.row(axis).abs().transpose().dot(&half_size));
+        self.motion.sweep(&BoundingBox::new_from_points(
+            self.center - extent,
+            self.center + extent,
+        ))
+    }
+}
@@ -1,16 +1,20 @@
 use crate::rendering::bounding_box::BoundingBox;
+use crate::rendering::layout::wgsl_layout;
 use crate::rendering::material::MaterialHandle;
 use crate::rendering::mesh::Mesh;
-use crate::rendering::primitive::{PrimitiveData, Transformable};
+use crate::rendering::primitive::{MotionData, PrimitiveData, Transformable};
 use bytemuck::{Pod, Zeroable};
-use nalgebra::{Point3, Scale3, Translation3, UnitQuaternion, Vector3};
+use getset::{CopyGetters, Setters};
+use nalgebra::{Matrix3, Matrix4x3, Point3, Scale3, Translation3, UnitQuaternion, Vector3};
 use std::rc::Rc;
 
 use super::Bound;
 
+#[derive(Clone)]
 pub struct Sphere {
     center: Point3<f32>,
     radius: f32,
+    transform: Matrix3<f32>, // 旋转和缩放，作用在以 center 为原点的局部坐标上
     material_id: u32,
     material_type: u32,
     primitive: Option<Rc<PrimitiveData>>,
@@ -22,6 +26,7 @@ impl Sphere {
         Self {
             center,
             radius,
+            transform: Matrix3::identity(),
             material_type: material.material_type,
             material_id: material.material_id,
             primitive: None,
@@ -36,12 +41,17 @@ impl Transformable for Sphere {
         self.primitive = None;
     }
 
-    fn rotate(&mut self, _rotation: UnitQuaternion<f32>) {
-        todo!()
+    fn rotate(&mut self, rotation: UnitQuaternion<f32>) {
+        self.center = rotation * self.center;
+        self.transform = rotation.to_rotation_matrix().matrix() * self.transform;
+        self.primitive = None;
     }
 
-    fn scale(&mut self, _scale: Scale3<f32>) {
-        todo!()
+    // 非均匀缩放后成为椭球
+    fn scale(&mut self, scale: Scale3<f32>) {
+        self.center = scale * self.center;
+        self.transform = Matrix3::from_diagonal(&scale.vector) * self.transform;
+        self.primitive = None;
     }
 }
 
@@ -52,42 +62,90 @@ impl Mesh for Sphere {
         }
 
         if self.primitive.is_none() {
-            self.primitive = Some(Rc::new(PrimitiveData::Sphere(SphereData::new(
-                self.center,
-                self.radius,
-                self.material_type,
-                self.material_id,
-            ))));
+            let mut sphere_data = SphereData::new(self.center, self.radius, self.material_type, self.material_id);
+            sphere_data.set_transform(self.transform);
+            self.primitive = Some(Rc::new(PrimitiveData::Sphere(sphere_data)));
         }
         primitives.push(Rc::clone(self.primitive.as_ref().unwrap()));
     }
 }
 
 #[repr(C)]
-#[derive(Copy, Clone, Debug, Pod, Zeroable)]
+#[derive(Copy, Clone, Debug, Pod, Zeroable, CopyGetters, Setters)]
 pub struct SphereData {
+    #[getset(get_copy = "pub")]
     center: Point3<f32>,
+    #[getset(get_copy = "pub")]
     radius: f32,
-    primitive_type: u32,
-    primitive_id: u32,
-    _padding: [u32; 2],
+    #[getset(get_copy = "pub", set = "pub")]
+    material_type: u32,
+    #[getset(get_copy = "pub", set = "pub")]
+    material_id: u32,
+    #[getset(get_copy = "pub")]
+    bounding_radius: f32, // 包住变换后椭球的最小球面的半径，重要性采样时对这个球面采样
+    _padding: u32,
+    // 局部坐标到世界坐标的线性部分及其逆，第 4 行是 WGSL 中 mat3x3f 每列的填充
+    transform: Matrix4x3<f32>,
+    inverse_transform: Matrix4x3<f32>,
+    #[getset(get_copy = "pub", set = "pub")]
+    motion: MotionData, // center 是时间 0 时的位置
 }
 
+wgsl_layout!(
+    SphereData,
+    "Sphere",
+    [
+        center,
+        radius,
+        material_type,
+        material_id,
+        bounding_radius,
+        transform,
+        inverse_transform,
+        motion
+    ]
+);
+
 impl SphereData {
-    pub fn new(center: Point3<f32>, radius: f32, primitive_type: u32, primitive_id: u32) -> Self {
+    pub fn new(center: Point3<f32>, radius: f32, material_type: u32, material_id: u32) -> Self {
         Self {
             center,
             radius,
-            primitive_type,
-            primitive_id,
-            _padding: [0; 2],
+            material_type,
+            material_id,
+            bounding_radius: radius,
+            _padding: 0,
+            transform: Matrix3::identity().insert_row(3, 0.0),
+            inverse_transform: Matrix3::identity().insert_row(3, 0.0),
+            motion: Default::default(),
         }
     }
+
+    // 世界坐标中的点 p = center + transform * q，q 在半径为 radius、以原点为中心的球面上。
+    // 不可逆的变换把球压扁成没有体积的形状，此时光线与它没有交点
+    pub fn set_transform(&mut self, transform: Matrix3<f32>) -> &mut Self {
+        let inverse = transform.try_inverse().unwrap_or_else(Matrix3::zeros);
+        self.bounding_radius = self.radius * transform.singular_values().max();
+        self.transform = transform.insert_row(3, 0.0);
+        self.inverse_transform = inverse.insert_row(3, 0.0);
+        self
+    }
+
+    pub fn transform(&self) -> Matrix3<f32> {
+        self.transform.fixed_rows::<3>(0).into_owned()
+    }
+
+    pub fn inverse_transform(&self) -> Matrix3<f32> {
+        self.inverse_transform.fixed_rows::<3>(0).into_owned()
+    }
 }
 
 impl Bound for SphereData {
+    // 椭球在每个轴上的半宽是 radius 乘以 transform 对应行的长度
     fn bounding_box(&self) -> BoundingBox {
-        let r_vec = Vector3::new(self.radius, self.radius, self.radius);
-        BoundingBox::new_from_points(self.center - r_vec, self.center + r_vec)
+        let transform = self.transform();
+        let r_vec = Vector3::from_fn(|axis, _| self.radius * transform.row(axis).norm());
+        self.motion
+            .sweep(&BoundingBox::new_from_points(self.center - r_vec, self.center + r_vec))
     }
 }
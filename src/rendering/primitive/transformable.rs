@@ -7,5 +7,19 @@ pub trait Transformable {
     fn scale(&mut self, scale: Scale3<f32>);
 }
 
-pub trait TransformableMesh: Transformable + Mesh {}
-impl<T: Transformable + Mesh> TransformableMesh for T {}
\ No newline at end of file
+pub trait TransformableMesh: Transformable + Mesh {
+    // 变换会直接修改网格，场景图保留局部坐标中的网格，每次变换改变时复制一份再变换到世界坐标
+    fn clone_box(&self) -> Box<dyn TransformableMesh>;
+}
+
+impl<T: Transformable + Mesh + Clone + 'static> TransformableMesh for T {
+    fn clone_box(&self) -> Box<dyn TransformableMesh> {
+        Box::new(self.clone())
+    }
+}
+
+impl Clone for Box<dyn TransformableMesh> {
+    fn clone(&self) -> Self {
+        self.clone_box()
+    }
+}
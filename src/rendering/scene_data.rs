@@ -0,0 +1,223 @@
+use crate::rendering::bounding_box::BoundingBox;
+use crate::rendering::bvh::{
+    build_bvh_tree, bvh_sah_cost, refit_bvh_tree, update_bvh_leaves, BvhBuildingEntry, BvhNode, BvhRefitMode,
+    BVH_GPU_REFIT_CHECK_INTERVAL, BVH_REFIT_MAX_COST_RATIO,
+};
+use crate::rendering::material::MaterialRegistry;
+use crate::rendering::primitive::sphere::SphereData;
+use crate::rendering::primitive::{
+    Bound, CsgData, CurveData, HeightfieldData, PrimitiveData, PrimitiveIndex, QuadData, SdfData, ShapeData,
+};
+use crate::time;
+use bytemuck::Pod;
+use getset::{CopyGetters, Getters};
+use std::rc::Rc;
+use std::time::Duration;
+
+// 场景展开后的数组，各种图元的数组打包成 primitives，布局与 ray_tracing.wgsl 中的 storage buffer 一一对应。
+// GPU 渲染器把它们上传到显存，CPU 路径追踪器直接在按类型分开的数组上求交和着色
+#[derive(Default, Getters, CopyGetters)]
+pub struct SceneData {
+    #[getset(get = "pub")]
+    bvh_tree: Vec<BvhNode>, // BvhUpdate::RefitLeaves 之后只有叶节点的包围盒是最新的
+    #[getset(get = "pub")]
+    importance: Vec<PrimitiveIndex>,
+    #[getset(get = "pub")]
+    quads: Vec<QuadData>,
+    #[getset(get = "pub")]
+    spheres: Vec<SphereData>,
+    #[getset(get = "pub")]
+    shapes: Vec<ShapeData>,
+    #[getset(get = "pub")]
+    sdfs: Vec<SdfData>,
+    #[getset(get = "pub")]
+    csgs: Vec<CsgData>,
+    #[getset(get = "pub")]
+    curves: Vec<CurveData>,
+    #[getset(get = "pub")]
+    heightfields: Vec<HeightfieldData>,
+    #[getset(get = "pub")]
+    primitives: Vec<u32>, // 见 pack_primitives
+    #[getset(get = "pub")]
+    materials: Vec<u32>,
+    #[getset(get = "pub")]
+    material_names: Vec<&'static str>, // 下标是材质的类型标签
+    #[getset(get_copy = "pub")]
+    bvh_build: Duration,
+    #[getset(get_copy = "pub")]
+    bvh_cost: f32, // 上一次完整构建后的 SAH 代价，重新拟合后的代价与它比较
+    leaf_refits: u32, // 上一次在 CPU 上评估 SAH 代价之后，只更新叶节点的次数
+}
+
+// SceneData::update 对 BVH 做了什么
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum BvhUpdate {
+    Refit,
+    RefitLeaves, // 只更新了叶节点，内部节点需要在 GPU 上合并
+    Rebuild,
+}
+
+impl SceneData {
+    pub fn new(primitives: &[Rc<PrimitiveData>], important_indices: &[u32], materials: &MaterialRegistry) -> Self {
+        let (mut scene_data, mut bvh_building) = Self::collect(primitives, important_indices);
+        scene_data.build(&mut bvh_building);
+        scene_data.materials = materials.pack();
+        scene_data.material_names = materials.kind_names();
+        scene_data
+    }
+
+    // 每种图元的数量都不变时，叶节点引用的图元仍然有效，只需要重新拟合包围盒。
+    // 数量变化，或者重新拟合后 SAH 代价超过构建时的 BVH_REFIT_MAX_COST_RATIO 倍，就重新构建。
+    // BvhRefitMode::Gpu 时只更新叶节点，每 BVH_GPU_REFIT_CHECK_INTERVAL 次更新才在 CPU 上完整拟合并评估代价
+    pub fn update(
+        &mut self,
+        primitives: &[Rc<PrimitiveData>],
+        important_indices: &[u32],
+        materials: &MaterialRegistry,
+        refit_mode: BvhRefitMode,
+    ) -> BvhUpdate {
+        let (mut updated, mut bvh_building) = Self::collect(primitives, important_indices);
+        updated.materials = materials.pack();
+        updated.material_names = materials.kind_names();
+
+        let mut update = BvhUpdate::Rebuild;
+        if updated.primitive_counts() == self.primitive_counts() {
+            // 同一种图元按编号排列，与 collect 分配编号的顺序一致
+            let mut boxes: Vec<Vec<BoundingBox>> = Vec::new();
+            for entry in &bvh_building {
+                let primitive_type = entry.primitive_type as usize;
+                if boxes.len() <= primitive_type {
+                    boxes.resize(primitive_type + 1, Vec::new());
+                }
+                boxes[primitive_type].push(entry.bounding_box);
+            }
+
+            let leaf_box =
+                |primitive_type: u32, primitive_id: u32| boxes[primitive_type as usize][primitive_id as usize];
+
+            let refit_start = time::Instant::now();
+            let mut bvh_tree = self.bvh_tree.clone();
+            if refit_mode == BvhRefitMode::Gpu && self.leaf_refits + 1 < BVH_GPU_REFIT_CHECK_INTERVAL {
+                update_bvh_leaves(&mut bvh_tree, leaf_box);
+                updated.bvh_tree = bvh_tree;
+                updated.bvh_build = refit_start.elapsed();
+                updated.bvh_cost = self.bvh_cost;
+                updated.leaf_refits = self.leaf_refits + 1;
+                *self = updated;
+                return BvhUpdate::RefitLeaves;
+            }
+
+            // 内部节点全部由叶节点重新合并，之前只更新叶节点留下的旧包围盒不影响结果
+            refit_bvh_tree(&mut bvh_tree, leaf_box);
+            if bvh_sah_cost(&bvh_tree) <= self.bvh_cost * BVH_REFIT_MAX_COST_RATIO {
+                updated.bvh_tree = bvh_tree;
+                updated.bvh_build = refit_start.elapsed();
+                updated.bvh_cost = self.bvh_cost;
+                update = BvhUpdate::Refit;
+            }
+        }
+        if update == BvhUpdate::Rebuild {
+            updated.build(&mut bvh_building);
+        }
+
+        *self = updated;
+        update
+    }
+
+    fn collect(primitives: &[Rc<PrimitiveData>], important_indices: &[u32]) -> (Self, Vec<BvhBuildingEntry>) {
+        let mut scene_data = Self::default();
+        let mut primitives_indices = Vec::new();
+        let mut bvh_building = Vec::new();
+        for primitive in primitives.iter().map(Rc::clone) {
+            let primitive_id = match primitive.as_ref() {
+                PrimitiveData::Quad(quad) => {
+                    scene_data.quads.push(*quad);
+                    scene_data.quads.len() - 1
+                }
+                PrimitiveData::Sphere(sphere) => {
+                    scene_data.spheres.push(*sphere);
+                    scene_data.spheres.len() - 1
+                }
+                PrimitiveData::Shape(shape) => {
+                    scene_data.shapes.push(*shape);
+                    scene_data.shapes.len() - 1
+                }
+                PrimitiveData::Sdf(sdf) => {
+                    scene_data.sdfs.push(*sdf);
+                    scene_data.sdfs.len() - 1
+                }
+                PrimitiveData::Csg(csg) => {
+                    scene_data.csgs.push(*csg);
+                    scene_data.csgs.len() - 1
+                }
+                PrimitiveData::Curve(curve) => {
+                    scene_data.curves.push(*curve);
+                    scene_data.curves.len() - 1
+                }
+                PrimitiveData::Heightfield(heightfield) => {
+                    scene_data.heightfields.push(*heightfield);
+                    scene_data.heightfields.len() - 1
+                }
+            } as u32;
+
+            bvh_building.push(BvhBuildingEntry {
+                primitive: Rc::clone(&primitive),
+                primitive_type: (*primitive).into(),
+                primitive_id,
+                bounding_box: primitive.bounding_box(),
+            });
+            primitives_indices.push(PrimitiveIndex {
+                primitive_type: (*primitive).into(),
+                primitive_id,
+            });
+        }
+
+        for important in important_indices {
+            scene_data.importance.push(primitives_indices[*important as usize]);
+        }
+        scene_data.primitives = scene_data.pack_primitives();
+
+        (scene_data, bvh_building)
+    }
+
+    // 与 ray_tracing.wgsl 中的 primitives 对应。开头按类型标签排列每种图元的起始下标和每个图元占的字数，
+    // 之后依次是各种图元的数据，第 i 个图元的数据从 起始下标 + i * 字数 开始
+    fn pack_primitives(&self) -> Vec<u32> {
+        fn append<T: Pod>(data: &mut Vec<u32>, primitive_type: usize, primitives: &[T]) {
+            data[primitive_type * 2] = data.len() as u32;
+            data[primitive_type * 2 + 1] = (size_of::<T>() / size_of::<u32>()) as u32;
+            data.extend_from_slice(bytemuck::cast_slice(primitives));
+        }
+
+        let mut data = vec![0; 7 * 2];
+        append(&mut data, 0, &self.quads);
+        append(&mut data, 1, &self.spheres);
+        append(&mut data, 2, &self.shapes);
+        append(&mut data, 3, &self.sdfs);
+        append(&mut data, 4, &self.csgs);
+        append(&mut data, 5, &self.curves);
+        append(&mut data, 6, &self.heightfields);
+        data
+    }
+
+    fn build(&mut self, bvh_building: &mut [BvhBuildingEntry]) {
+        let bvh_build_start = time::Instant::now();
+        let len = bvh_building.len();
+        self.bvh_tree.clear();
+        build_bvh_tree(&mut self.bvh_tree, bvh_building, 0, len, 0);
+        self.bvh_build = bvh_build_start.elapsed();
+        self.bvh_cost = bvh_sah_cost(&self.bvh_tree);
+    }
+
+    fn primitive_counts(&self) -> [usize; 7] {
+        [
+            self.quads.len(),
+            self.spheres.len(),
+            self.shapes.len(),
+            self.sdfs.len(),
+            self.csgs.len(),
+            self.curves.len(),
+            self.heightfields.len(),
+        ]
+    }
+}
@@ -45,9 +45,11 @@ impl Wgpu {
 
         info!("{:?}", adapter.get_info());
 
+        // 时间戳查询仅用于性能分析，适配器不支持时不启用
+        let optional_features = Features::TIMESTAMP_QUERY | Features::TIMESTAMP_QUERY_INSIDE_ENCODERS;
         let device_descriptor = DeviceDescriptor {
             label: wgpu::Label::from("default device"),
-            required_features: Features::empty(),
+            required_features: adapter.features() & optional_features,
             required_limits: Limits::default(),
             memory_hints: MemoryHints::default(),
         };
@@ -97,7 +99,8 @@ impl Wgpu {
             .unwrap();
 
         surface_configuration.format = Rgba8Unorm;
-        surface_configuration.usage = TextureUsages::RENDER_ATTACHMENT | TextureUsages::STORAGE_BINDING;
+        // 光线追踪的结果从输出纹理复制到交换链纹理中
+        surface_configuration.usage = TextureUsages::RENDER_ATTACHMENT | TextureUsages::COPY_DST;
         info!("{:?}", surface_configuration);
 
         surface.configure(&device, &surface_configuration);
@@ -1,9 +1,13 @@
 pub mod bind_buffer;
 pub mod index_buffer;
+pub mod mirrored_buffer;
+pub mod readback_buffer;
 pub mod vertex_buffer;
 
 pub use bind_buffer::*;
 pub use index_buffer::*;
+pub use mirrored_buffer::*;
+pub use readback_buffer::*;
 pub use vertex_buffer::*;
 
 use crate::rendering::wgpu::Wgpu;
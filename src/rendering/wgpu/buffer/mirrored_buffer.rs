@@ -0,0 +1,123 @@
+use crate::rendering::wgpu::{IWgpuBuffer, Wgpu, WgpuBindBuffer, WgpuBindable};
+use std::ops::Range;
+use wgpu::{
+    BindGroupLayoutEntry, BindingResource, BindingType, BufferAddress, BufferBindingType, BufferUsages, ShaderStages,
+    COPY_BUFFER_ALIGNMENT,
+};
+
+// 默认以只读方式绑定的 storage buffer，在内存中保留上一次上传的内容。更新时只上传与上一次不同的字节范围，
+// 内容放不下时重新分配更大的缓冲区，此时绑定组需要重新创建
+pub struct WgpuMirroredBuffer {
+    buffer: WgpuBindBuffer,
+    label: String,
+    capacity: usize,
+    contents: Vec<u8>,
+}
+
+#[derive(Clone, Copy, Debug, Default)]
+pub struct WgpuBufferUpdate {
+    pub uploaded: usize,   // 上传的字节数
+    pub reallocated: bool, // 缓冲区被重新分配，原来的绑定组失效
+}
+
+impl WgpuMirroredBuffer {
+    // min_size 至少是一个元素的大小，空数组也要满足着色器中运行时数组的最小绑定大小
+    pub fn new(wgpu: &Wgpu, label: &str, min_size: usize, data: &[u8]) -> Self {
+        let capacity = data.len().max(min_size);
+        let buffer = Self::create_buffer(wgpu, label, capacity);
+        buffer.write(wgpu, 0, data);
+
+        Self {
+            buffer,
+            label: label.to_string(),
+            capacity,
+            contents: data.to_vec(),
+        }
+    }
+
+    pub fn update(&mut self, wgpu: &Wgpu, data: &[u8]) -> WgpuBufferUpdate {
+        let mut update = WgpuBufferUpdate::default();
+        if data.len() > self.capacity {
+            // 按 1.5 倍增长，连续添加物体时不用每次都重新分配
+            self.capacity = data.len().max(self.capacity + self.capacity / 2);
+            self.buffer = Self::create_buffer(wgpu, &self.label, self.capacity);
+            self.buffer.write(wgpu, 0, data);
+            update.uploaded = data.len();
+            update.reallocated = true;
+        } else {
+            let range = changed_range(&self.contents, data);
+            if !range.is_empty() {
+                self.buffer.write(wgpu, range.start, &data[range.clone()]);
+            }
+            update.uploaded = range.len();
+        }
+        self.contents.clear();
+        self.contents.extend_from_slice(data);
+        update
+    }
+
+    // 计算通道在 GPU 上修改了内容，丢弃内存中的副本，下一次更新时上传全部内容
+    pub fn invalidate(&mut self) {
+        self.contents.clear();
+    }
+
+    // 以可写的 storage buffer 绑定，供在 GPU 上修改内容的计算通道使用
+    pub fn writable(&self) -> WgpuWritableBinding<'_> {
+        WgpuWritableBinding(&self.buffer)
+    }
+
+    fn create_buffer(wgpu: &Wgpu, label: &str, size: usize) -> WgpuBindBuffer {
+        WgpuBindBuffer::new(
+            wgpu,
+            label,
+            size as BufferAddress,
+            BufferUsages::STORAGE | BufferUsages::COPY_DST,
+            ShaderStages::COMPUTE,
+            true,
+        )
+    }
+}
+
+impl<'a> WgpuBindable<'a> for WgpuMirroredBuffer {
+    fn bind_group_layout_entry(&self) -> BindGroupLayoutEntry {
+        self.buffer.bind_group_layout_entry()
+    }
+
+    fn binding_resource(&'a self) -> BindingResource<'a> {
+        self.buffer.binding_resource()
+    }
+}
+
+pub struct WgpuWritableBinding<'b>(&'b WgpuBindBuffer);
+
+impl<'a> WgpuBindable<'a> for WgpuWritableBinding<'_> {
+    fn bind_group_layout_entry(&self) -> BindGroupLayoutEntry {
+        BindGroupLayoutEntry {
+            ty: BindingType::Buffer {
+                ty: BufferBindingType::Storage { read_only: false },
+                has_dynamic_offset: false,
+                min_binding_size: None,
+            },
+            ..self.0.bind_group_layout_entry()
+        }
+    }
+
+    fn binding_resource(&'a self) -> BindingResource<'a> {
+        self.0.binding_resource()
+    }
+}
+
+// 新内容中与旧内容不同的字节所在的范围，首尾按 COPY_BUFFER_ALIGNMENT 对齐。
+// 新内容更短时不需要清除多出的部分，着色器按场景中的数量访问
+pub fn changed_range(old: &[u8], new: &[u8]) -> Range<usize> {
+    let differs = |&i: &usize| old.get(i) != Some(&new[i]);
+    let Some(start) = (0..new.len()).find(differs) else {
+        return 0..0;
+    };
+    let end = (start..new.len()).rfind(differs).map_or(start, |end| end + 1);
+
+    let alignment = COPY_BUFFER_ALIGNMENT as usize;
+    let start = start / alignment * alignment;
+    let end = end.next_multiple_of(alignment).min(new.len());
+    start..end
+}
@@ -0,0 +1,104 @@
+use crate::rendering::wgpu::buffer::WgpuBuffer;
+use crate::rendering::wgpu::{IWgpuBuffer, Wgpu};
+use getset::CopyGetters;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use wgpu::{Buffer, BufferAddress, BufferUsages, CommandEncoder, Maintain, MapMode};
+
+#[derive(Copy, Clone, PartialEq, Eq)]
+enum ReadbackState {
+    Idle,
+    Copied,
+    Mapping,
+}
+
+// 将 GPU 上的数据异步读回 CPU 。
+// 每帧调用顺序：try_read -> copy_from（录制到 encoder）-> submit -> map
+#[derive(CopyGetters)]
+pub struct WgpuReadbackBuffer {
+    buffer: WgpuBuffer,
+    #[getset(get_copy = "pub")]
+    size: BufferAddress,
+    state: ReadbackState,
+    mapped: Arc<AtomicBool>,
+    failed: Arc<AtomicBool>, // 映射失败时由回调设置，try_read 回到 Idle 以便下一帧重试
+}
+
+impl IWgpuBuffer for WgpuReadbackBuffer {
+    fn buffer(&self) -> &Buffer {
+        self.buffer.buffer()
+    }
+}
+
+impl WgpuReadbackBuffer {
+    pub fn new(wgpu: &Wgpu, label: &str, size: BufferAddress) -> Self {
+        let buffer = WgpuBuffer::new(
+            wgpu,
+            format!("{} readback", label).as_str(),
+            size,
+            BufferUsages::MAP_READ | BufferUsages::COPY_DST,
+        );
+
+        Self {
+            buffer,
+            size,
+            state: ReadbackState::Idle,
+            mapped: Arc::new(AtomicBool::new(false)),
+            failed: Arc::new(AtomicBool::new(false)),
+        }
+    }
+
+    pub fn is_idle(&self) -> bool {
+        self.state == ReadbackState::Idle
+    }
+
+    // 上一次读回尚未完成时不会录制新的复制，返回 false
+    pub fn copy_from(&mut self, encoder: &mut CommandEncoder, source: &Buffer, offset: BufferAddress) -> bool {
+        if !self.is_idle() {
+            return false;
+        }
+
+        encoder.copy_buffer_to_buffer(source, offset, self.buffer(), 0, self.size);
+        self.state = ReadbackState::Copied;
+        true
+    }
+
+    // 必须在包含 copy_from 的命令提交之后调用
+    pub fn map(&mut self) {
+        if self.state != ReadbackState::Copied {
+            return;
+        }
+
+        let mapped = Arc::clone(&self.mapped);
+        let failed = Arc::clone(&self.failed);
+        self.buffer()
+            .slice(..)
+            .map_async(MapMode::Read, move |result| match result {
+                Ok(()) => mapped.store(true, Ordering::Release),
+                Err(_) => failed.store(true, Ordering::Release),
+            });
+        self.state = ReadbackState::Mapping;
+    }
+
+    pub fn try_read(&mut self, wgpu: &Wgpu) -> Option<Vec<u8>> {
+        if self.state != ReadbackState::Mapping {
+            return None;
+        }
+
+        wgpu.device.poll(Maintain::Poll);
+        if self.failed.swap(false, Ordering::Acquire) {
+            log::warn!("Failed to map readback buffer, retrying");
+            self.state = ReadbackState::Idle;
+            return None;
+        }
+        if !self.mapped.swap(false, Ordering::Acquire) {
+            return None;
+        }
+
+        let data = self.buffer().slice(..).get_mapped_range().to_vec();
+        self.buffer().unmap();
+        self.state = ReadbackState::Idle;
+
+        Some(data)
+    }
+}
@@ -14,6 +14,18 @@ impl WgpuComputePass {
         bind_group_layouts: Option<&[&BindGroupLayout]>,
         shader: &ShaderModule,
         work_group_size: [u32; 3],
+    ) -> Self {
+        Self::with_entry_point(wgpu, label, bind_group_layouts, shader, "compute_main", work_group_size)
+    }
+
+    // 一个着色器模块中有多个计算入口时使用
+    pub fn with_entry_point(
+        wgpu: &Wgpu,
+        label: &str,
+        bind_group_layouts: Option<&[&BindGroupLayout]>,
+        shader: &ShaderModule,
+        entry_point: &str,
+        work_group_size: [u32; 3],
     ) -> Self {
         let pipeline_layout = wgpu.device.create_pipeline_layout(&PipelineLayoutDescriptor {
             label: Label::from(format!("{label} compute pipeline layout").as_str()),
@@ -25,7 +37,7 @@ impl WgpuComputePass {
             label: Label::from(format!("{label} compute pipeline").as_str()),
             layout: Some(&pipeline_layout),
             module: shader,
-            entry_point: Some("compute_main"),
+            entry_point: Some(entry_point),
             compilation_options: PipelineCompilationOptions {
                 constants: &Default::default(), // overridable constants
                 zero_initialize_workgroup_memory: false,
@@ -40,10 +52,15 @@ impl WgpuComputePass {
         }
     }
 
-    pub fn render(&self, encoder: &mut CommandEncoder, bind_groups: Option<&[&WgpuBindGroup]>) {
+    pub fn render(
+        &self,
+        encoder: &mut CommandEncoder,
+        bind_groups: Option<&[&WgpuBindGroup]>,
+        timestamp_writes: Option<ComputePassTimestampWrites>,
+    ) {
         let mut compute_pass = encoder.begin_compute_pass(&ComputePassDescriptor {
             label: Label::from(self.label.as_ref()),
-            timestamp_writes: None,
+            timestamp_writes,
         });
 
         compute_pass.set_pipeline(&self.pipeline);
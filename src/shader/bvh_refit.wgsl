@@ -0,0 +1,109 @@
+// 在 GPU 上重新拟合 BVH。调用前 refit_bounds 清零，先用 BvhRefit_propagate 把每个叶节点的包围盒沿 parent
+// 合并到所有祖先，再用 BvhRefit_write 把结果写回 bvh_tree。合并用整数原子操作，不依赖线程之间的执行顺序
+
+const WORKGROUP_SIZE: u32 = 64;
+
+/*---------------------------------------- Bindings ---------------------------------------------*/
+
+@group(0) @binding(0)
+var<uniform> refit: BvhRefitContext;
+
+@group(0) @binding(1)
+var<storage, read> refit_leaves: array<BvhRefitLeaf>;
+
+// 每个节点 6 个字，依次是 x、y、z 三个轴的 min 和 max 映射成保序的整数，min 取反后同样用 atomicMax 合并，
+// 这样全零的缓冲区对 min 和 max 都表示空包围盒
+@group(0) @binding(2)
+var<storage, read_write> refit_bounds: array<atomic<u32>>;
+
+@group(0) @binding(3)
+var<storage, read_write> bvh_tree: array<BvhNode>;
+
+/*----------------------------------------- Refit -----------------------------------------------*/
+
+@compute @workgroup_size(WORKGROUP_SIZE)
+fn BvhRefit_propagate(
+    @builtin(global_invocation_id)
+    gid : vec3<u32>
+) {
+    if gid.x >= refit.leaf_count {
+        return;
+    }
+
+    let leaf = refit_leaves[gid.x];
+    var node = leaf.node;
+    loop {
+        var grown = false;
+        for (var axis = 0u; axis < 3u; axis++) {
+            let base = node * 6u + axis * 2u;
+            let min_key = ~BvhRefit_key(leaf.box.xyz[axis].min);
+            let max_key = BvhRefit_key(leaf.box.xyz[axis].max);
+            grown |= atomicMax(&refit_bounds[base], min_key) < min_key;
+            grown |= atomicMax(&refit_bounds[base + 1u], max_key) < max_key;
+        }
+
+        // 节点已经包含这个包围盒时，扩大过它的线程会继续向上合并，祖先最终也会包含。根节点的 parent 是它自己
+        if !grown || node == 0u {
+            return;
+        }
+        node = bvh_tree[node].parent;
+    }
+}
+
+@compute @workgroup_size(WORKGROUP_SIZE)
+fn BvhRefit_write(
+    @builtin(global_invocation_id)
+    gid : vec3<u32>
+) {
+    let node = gid.x;
+    if node >= refit.node_count {
+        return;
+    }
+
+    for (var axis = 0u; axis < 3u; axis++) {
+        let base = node * 6u + axis * 2u;
+        bvh_tree[node].box.xyz[axis].min = BvhRefit_value(~atomicLoad(&refit_bounds[base]));
+        bvh_tree[node].box.xyz[axis].max = BvhRefit_value(atomicLoad(&refit_bounds[base + 1u]));
+    }
+}
+
+// 浮点数到无符号整数的保序映射：正数翻转符号位，负数翻转所有位
+fn BvhRefit_key(value: f32) -> u32 {
+    let bits = bitcast<u32>(value);
+    return select(bits | 0x80000000u, ~bits, (bits & 0x80000000u) != 0u);
+}
+
+fn BvhRefit_value(key: u32) -> f32 {
+    return bitcast<f32>(select(~key, key & 0x7fffffffu, (key & 0x80000000u) != 0u));
+}
+
+/*----------------------------------------- Structs ---------------------------------------------*/
+
+struct BvhRefitContext {
+    leaf_count: u32,
+    node_count: u32
+}
+
+struct BvhRefitLeaf {
+    node: u32,
+    box: BoundingBox
+}
+
+// 与 ray_tracing.wgsl 中的定义相同
+struct BvhNode {
+    left_or_primitive_type: u32,
+    right_or_primitive_id: u32,
+    parent: u32,
+    is_leaf: u32,
+    box: BoundingBox
+}
+
+struct BoundingBox {
+    xyz: array<Interval, 3>
+}
+
+struct Interval {
+    min: f32,
+    max: f32,
+    _padding: array<u32, 2>
+}
@@ -0,0 +1,8 @@
+/*------------------------------------- DebugNormal Material ------------------------------------*/
+
+fn DebugNormal_emit(
+    ray_in: ptr<function, Ray>,
+    hit_record: ptr<function, HitRecord>,
+) -> vec3f {
+    return (*hit_record).normal * 0.5 + 0.5;
+}
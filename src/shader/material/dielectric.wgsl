@@ -0,0 +1,50 @@
+/*------------------------------------- Dielectric Material -------------------------------------*/
+
+struct Dielectric {
+    refraction_index: f32
+}
+
+fn Dielectric_load(offset: u32) -> Dielectric {
+    return Dielectric(material_f32(offset));
+}
+
+fn Dielectric_scatter(
+    ray_in: ptr<function, Ray>,
+    hit_record: ptr<function, HitRecord>,
+    scatter_record: ptr<function, ScatterRecord>
+) -> bool {
+    let dielectric = Dielectric_load((*hit_record).material_id);
+
+    (*scatter_record).attenuation = vec3f(1.0, 1.0, 1.0);
+    (*scatter_record).skip_pdf = true;
+
+    var refraction_index = dielectric.refraction_index;
+    if (*hit_record).is_front_face {
+        refraction_index = 1.0 / dielectric.refraction_index;
+    }
+
+    let in_direction = normalize((*ray_in).direction);
+    let cos_theta = min(dot(-in_direction, (*hit_record).normal), 1.0);
+    let sin_theta = sqrt(1 - pow(cos_theta, 2.0));
+
+    let cannot_refract = refraction_index * sin_theta > 1.0;
+
+    var out_direction: vec3f;
+    if cannot_refract || Dielectric_reflectance(dielectric, cos_theta) > Sampler_1d() {
+        out_direction = reflect(in_direction, (*hit_record).normal);
+    } else {
+        out_direction = refract(in_direction, (*hit_record).normal, refraction_index);
+    }
+
+    (*scatter_record).skip_pdf_ray = Ray_init((*hit_record).position, out_direction);
+    return true;
+}
+
+fn Dielectric_reflectance(
+    dielectric: Dielectric,
+    cosine: f32,
+) -> f32 {
+    var r0 = (1 - dielectric.refraction_index) / (1 + dielectric.refraction_index);
+    r0 = pow(r0, 2.0);
+    return r0 + (1 - r0) * pow((1 - cosine), 5.0);
+}
@@ -0,0 +1,51 @@
+/*----------------------------------- Diffuse Light Material ------------------------------------*/
+
+// 数据布局见 DiffuseLight::pack：辐亮度、标志、角度分布的采样数，之后依次是角度分布和纹理
+
+const DIFFUSE_LIGHT_TWO_SIDED: u32 = 1;
+const DIFFUSE_LIGHT_TEXTURE: u32 = 2;
+
+struct DiffuseLight {
+    radiance: vec3f,
+    flags: u32,
+    profile_len: u32,
+}
+
+fn DiffuseLight_load(offset: u32) -> DiffuseLight {
+    return DiffuseLight(material_vec3f(offset), material_u32(offset + 3), material_u32(offset + 4));
+}
+
+fn DiffuseLight_emit(
+    ray_in: ptr<function, Ray>,
+    hit_record: ptr<function, HitRecord>
+) -> vec3f {
+    let offset = (*hit_record).material_id;
+    let light = DiffuseLight_load(offset);
+    if !(*hit_record).is_front_face && (light.flags & DIFFUSE_LIGHT_TWO_SIDED) == 0 {
+        return VEC3F_ZEROS;
+    }
+
+    let profile_offset = offset + 5;
+    let cos_theta = dot(-(*hit_record).in_direction, (*hit_record).normal);
+    var radiance = light.radiance * DiffuseLight_angular_profile(profile_offset, light.profile_len, cos_theta);
+
+    if (light.flags & DIFFUSE_LIGHT_TEXTURE) != 0 {
+        let texture = profile_offset + light.profile_len;
+        radiance *= srgb_to_linear(Texture_sample(texture, (*hit_record).uv).rgb);
+    }
+    return radiance;
+}
+
+// 从法线方向 0° 到 90° 均匀分布的采样之间线性插值
+fn DiffuseLight_angular_profile(offset: u32, len: u32, cos_theta: f32) -> f32 {
+    if len == 0 {
+        return 1.0;
+    }
+    if len == 1 {
+        return material_f32(offset);
+    }
+    let x = acos(clamp(cos_theta, 0.0, 1.0)) / (PI / 2) * f32(len - 1);
+    let i = min(u32(x), len - 2);
+    let t = x - f32(i);
+    return mix(material_f32(offset + i), material_f32(offset + i + 1), t);
+}
@@ -0,0 +1,129 @@
+/*---------------------------------------- Hair Material ----------------------------------------*/
+
+// 漫反射和简化的 Marschner R 波瓣，纤维方向是 hit_record.tangent。
+// R 波瓣把方向分解为与纤维的夹角 θ 和绕纤维的方位角 φ：θ 服从以镜面反射方向为中心、截断到 [-π/2, π/2] 的 logistic 分布，
+// 中心随毛鳞片的倾角偏移；φ 在法线一侧的半圆上均匀分布。BSDF 与余弦项的乘积取为 specular 乘以这个分布的概率密度
+
+struct Hair {
+    color: vec3f,
+    specular: f32,
+    roughness: f32,
+    tilt: f32, // 单位是度
+}
+
+fn Hair_load(offset: u32) -> Hair {
+    return Hair(
+        material_vec3f(offset),
+        material_f32(offset + 3),
+        material_f32(offset + 4),
+        material_f32(offset + 5),
+    );
+}
+
+fn Hair_scatter(
+    ray_in: ptr<function, Ray>,
+    hit_record: ptr<function, HitRecord>,
+    scatter_record: ptr<function, ScatterRecord>
+) -> bool {
+    let hair = Hair_load((*hit_record).material_id);
+
+    (*scatter_record).attenuation = hair.color;
+    (*scatter_record).skip_pdf = false;
+
+    return true;
+}
+
+fn Hair_eval(
+    ray: ptr<function, Ray>,
+    hit_record: ptr<function, HitRecord>,
+    scatter_record: ptr<function, ScatterRecord>
+) -> vec3f {
+    let hair = Hair_load((*hit_record).material_id);
+
+    let l = normalize((*ray).direction);
+    let nl = dot((*hit_record).normal, l);
+    if nl <= 0 {
+        return VEC3F_ZEROS;
+    }
+    let diffuse = (1 - hair.specular) * hair.color * nl / PI;
+    return diffuse + vec3f(hair.specular * Hair_specular_pdf(hair, hit_record, l));
+}
+
+fn Hair_pdf_value(
+    ray: ptr<function, Ray>,
+    hit_record: ptr<function, HitRecord>,
+) -> f32 {
+    let hair = Hair_load((*hit_record).material_id);
+
+    let l = normalize((*ray).direction);
+    let nl = dot((*hit_record).normal, l);
+    if nl <= 0 {
+        return 0.0;
+    }
+    return (1 - hair.specular) * nl / PI + hair.specular * Hair_specular_pdf(hair, hit_record, l);
+}
+
+fn Hair_random(
+    origin: ptr<function, vec3f>,
+    hit_record: ptr<function, HitRecord>,
+    u: vec2f,
+) -> vec3f {
+    let hair = Hair_load((*hit_record).material_id);
+    let n = (*hit_record).normal;
+
+    // 用 u.x 选择波瓣，再把它重新映射到 [0, 1) 用于波瓣内的采样
+    if u.x >= hair.specular {
+        let x = (u.x - hair.specular) / (1 - hair.specular);
+        return rotation_matrix(VEC3F_UNIT_Y, n) * random_cosine_direction(vec2f(x, u.y));
+    }
+
+    let peak = Hair_specular_peak(hair, hit_record);
+    let scale = Hair_scale(hair);
+    let theta = peak + Hair_sample_trimmed_logistic(u.x / hair.specular, scale, -PI / 2 - peak, PI / 2 - peak);
+    let phi = (u.y - 0.5) * PI;
+    let t = (*hit_record).tangent;
+    let b = cross(t, n);
+    return sin(theta) * t + cos(theta) * (cos(phi) * n + sin(phi) * b);
+}
+
+// R 波瓣在方向 l 上的概率密度。θ 的密度换算到立体角需要除以 cos θ，φ 的密度是 1 / π
+fn Hair_specular_pdf(hair: Hair, hit_record: ptr<function, HitRecord>, l: vec3f) -> f32 {
+    let sin_theta = clamp(dot(l, (*hit_record).tangent), -1.0, 1.0);
+    let cos_theta = sqrt(1 - sin_theta * sin_theta);
+    if cos_theta < 1e-4 {
+        return 0.0;
+    }
+    let peak = Hair_specular_peak(hair, hit_record);
+    let m = Hair_trimmed_logistic(asin(sin_theta) - peak, Hair_scale(hair), -PI / 2 - peak, PI / 2 - peak);
+    return m / (PI * cos_theta);
+}
+
+// 光滑纤维的镜面反射方向与纤维的夹角是 -θ_o，毛鳞片倾斜 α 时偏移 2α
+fn Hair_specular_peak(hair: Hair, hit_record: ptr<function, HitRecord>) -> f32 {
+    let sin_theta_o = clamp(dot(-(*hit_record).in_direction, (*hit_record).tangent), -1.0, 1.0);
+    return -asin(sin_theta_o) + 2 * radians(hair.tilt);
+}
+
+fn Hair_scale(hair: Hair) -> f32 {
+    return max(hair.roughness, 0.01) * 0.5;
+}
+
+fn Hair_logistic(x: f32, s: f32) -> f32 {
+    let e = exp(-abs(x) / s);
+    return e / (s * (1 + e) * (1 + e));
+}
+
+fn Hair_logistic_cdf(x: f32, s: f32) -> f32 {
+    return 1 / (1 + exp(min(-x / s, 80.0)));
+}
+
+// 截断到 [a, b] 的 logistic 分布
+fn Hair_trimmed_logistic(x: f32, s: f32, a: f32, b: f32) -> f32 {
+    return Hair_logistic(x, s) / (Hair_logistic_cdf(b, s) - Hair_logistic_cdf(a, s));
+}
+
+fn Hair_sample_trimmed_logistic(u: f32, s: f32, a: f32, b: f32) -> f32 {
+    let cdf_a = Hair_logistic_cdf(a, s);
+    let c = clamp(u * (Hair_logistic_cdf(b, s) - cdf_a) + cdf_a, 1e-6, 1 - 1e-6);
+    return clamp(-s * log(1 / c - 1), a, b);
+}
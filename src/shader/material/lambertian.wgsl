@@ -0,0 +1,50 @@
+/*------------------------------------- Lambertian Material -------------------------------------*/
+
+struct Lambertian {
+    albedo: vec3f
+}
+
+fn Lambertian_load(offset: u32) -> Lambertian {
+    return Lambertian(material_vec3f(offset));
+}
+
+fn Lambertian_scatter(
+    ray_in: ptr<function, Ray>,
+    hit_record: ptr<function, HitRecord>,
+    scatter_record: ptr<function, ScatterRecord>
+) -> bool {
+    let lambertian = Lambertian_load((*hit_record).material_id);
+
+    (*scatter_record).attenuation = lambertian.albedo;
+    (*scatter_record).skip_pdf = false;
+
+    return true;
+}
+
+fn Lambertian_pdf_value(
+    ray: ptr<function, Ray>,
+    hit_record: ptr<function, HitRecord>,
+) -> f32 {
+    let cosine_theta = dot(normalize((*ray).direction), (*hit_record).normal);
+    return max(0.0, cosine_theta / PI);
+}
+
+fn Lambertian_random(
+    origin: ptr<function, vec3f>,
+    hit_record: ptr<function, HitRecord>,
+    u: vec2f,
+) -> vec3f {
+    return rotation_matrix(VEC3F_UNIT_Y, (*hit_record).normal) * random_cosine_direction(u);
+}
+
+fn Lambertian_scattering_pdf_value(
+    ray: ptr<function, Ray>,
+    hit_record: ptr<function, HitRecord>,
+) -> f32 {
+    let cos_theta = dot((*hit_record).normal, normalize((*ray).direction));
+    if cos_theta < 0 {
+        return 0f;
+    } else {
+        return cos_theta / PI;
+    }
+}
@@ -0,0 +1,237 @@
+/*------------------------------------- Principled Material -------------------------------------*/
+
+// 简化的 Disney principled BSDF。不透明部分由漫反射、sheen、GGX 镜面反射和 GTR1 清漆层组成，
+// 漫反射和 sheen 按镜面反射的菲涅尔项衰减，整个基础层再按清漆层的菲涅尔项衰减，保证能量守恒。
+// 透射是光滑的电介质，在 scatter 中以 (1 - metallic) * transmission 的概率选择，与不透明部分是凸组合
+
+struct Principled {
+    base_color: vec3f,
+    metallic: f32,
+    roughness: f32,
+    specular: f32,
+    specular_tint: f32,
+    sheen: f32,
+    sheen_tint: f32,
+    clearcoat: f32,
+    clearcoat_roughness: f32,
+    transmission: f32,
+    ior: f32,
+}
+
+fn Principled_load(offset: u32) -> Principled {
+    return Principled(
+        material_vec3f(offset),
+        material_f32(offset + 3),
+        material_f32(offset + 4),
+        material_f32(offset + 5),
+        material_f32(offset + 6),
+        material_f32(offset + 7),
+        material_f32(offset + 8),
+        material_f32(offset + 9),
+        material_f32(offset + 10),
+        material_f32(offset + 11),
+        material_f32(offset + 12),
+    );
+}
+
+fn Principled_scatter(
+    ray_in: ptr<function, Ray>,
+    hit_record: ptr<function, HitRecord>,
+    scatter_record: ptr<function, ScatterRecord>
+) -> bool {
+    let principled = Principled_load((*hit_record).material_id);
+
+    (*scatter_record).attenuation = principled.base_color;
+    (*scatter_record).skip_pdf = false;
+
+    // 固定消耗一个维度，选中透射时重新映射到 [0, 1) 用于菲涅尔项
+    let u = Sampler_1d();
+    let transmission_weight = (1.0 - principled.metallic) * principled.transmission;
+    if u >= transmission_weight {
+        return true;
+    }
+
+    var refraction_index = principled.ior;
+    if (*hit_record).is_front_face {
+        refraction_index = 1.0 / principled.ior;
+    }
+
+    let in_direction = (*hit_record).in_direction;
+    let cos_theta = min(dot(-in_direction, (*hit_record).normal), 1.0);
+    let sin_theta = sqrt(1 - pow(cos_theta, 2.0));
+    let cannot_refract = refraction_index * sin_theta > 1.0;
+
+    var r0 = (1 - principled.ior) / (1 + principled.ior);
+    r0 = pow(r0, 2.0);
+    let reflectance = r0 + (1 - r0) * Principled_schlick_weight(cos_theta);
+
+    var out_direction: vec3f;
+    if cannot_refract || reflectance > u / transmission_weight {
+        out_direction = reflect(in_direction, (*hit_record).normal);
+        (*scatter_record).attenuation = vec3f(1.0, 1.0, 1.0);
+    } else {
+        out_direction = refract(in_direction, (*hit_record).normal, refraction_index);
+        // 只在进入物体时染色
+        if !(*hit_record).is_front_face {
+            (*scatter_record).attenuation = vec3f(1.0, 1.0, 1.0);
+        }
+    }
+
+    (*scatter_record).skip_pdf = true;
+    (*scatter_record).skip_pdf_ray = Ray_init((*hit_record).position, out_direction);
+    return true;
+}
+
+fn Principled_eval(
+    ray: ptr<function, Ray>,
+    hit_record: ptr<function, HitRecord>,
+    scatter_record: ptr<function, ScatterRecord>
+) -> vec3f {
+    let principled = Principled_load((*hit_record).material_id);
+
+    let n = (*hit_record).normal;
+    let v = -(*hit_record).in_direction;
+    let l = normalize((*ray).direction);
+    let nl = dot(n, l);
+    let nv = dot(n, v);
+    if nl <= 0 || nv <= 0 {
+        return VEC3F_ZEROS;
+    }
+    let h = normalize(l + v);
+    let nh = dot(n, h);
+    let lh = dot(l, h);
+
+    let base_color = principled.base_color;
+    let tint = Principled_tint(base_color);
+    let fh = Principled_schlick_weight(lh);
+
+    // 漫反射，带有粗糙表面在掠射角的回射
+    let fd90 = 0.5 + 2 * lh * lh * principled.roughness;
+    let fd = mix(1.0, fd90, Principled_schlick_weight(nl)) * mix(1.0, fd90, Principled_schlick_weight(nv));
+    let diffuse = base_color / PI * fd;
+    let sheen = principled.sheen * mix(vec3f(1.0), tint, principled.sheen_tint) * fh;
+
+    // GGX 镜面反射
+    let specular_color = 0.08 * principled.specular * mix(vec3f(1.0), tint, principled.specular_tint);
+    let f0 = mix(specular_color, base_color, principled.metallic);
+    let f = mix(f0, vec3f(1.0), fh);
+    let alpha = Principled_alpha(principled.roughness);
+    let g = Principled_smith_g(nl, alpha) * Principled_smith_g(nv, alpha);
+    let specular = f * Principled_gtr2(nh, alpha) * g / (4 * nl * nv);
+
+    // 清漆层，固定的 F0 = 0.04
+    let fc = mix(0.04, 1.0, fh) * principled.clearcoat;
+    let alpha_clearcoat = Principled_clearcoat_alpha(principled.clearcoat_roughness);
+    let gc = Principled_smith_g(nl, 0.25) * Principled_smith_g(nv, 0.25);
+    let clearcoat = fc * Principled_gtr1(nh, alpha_clearcoat) * gc / (4 * nl * nv);
+
+    let base = (1.0 - principled.metallic) * (vec3f(1.0) - f) * (diffuse + sheen) + specular;
+    return ((1.0 - fc) * base + clearcoat) * nl;
+}
+
+fn Principled_pdf_value(
+    ray: ptr<function, Ray>,
+    hit_record: ptr<function, HitRecord>,
+) -> f32 {
+    let principled = Principled_load((*hit_record).material_id);
+
+    let n = (*hit_record).normal;
+    let v = -(*hit_record).in_direction;
+    let l = normalize((*ray).direction);
+    let nl = dot(n, l);
+    if nl <= 0 {
+        return 0.0;
+    }
+    let h = normalize(l + v);
+    let nh = dot(n, h);
+    let vh = dot(v, h);
+    if vh <= 0 {
+        return 0.0;
+    }
+
+    // 微表面波瓣按半程向量采样，换算到出射方向需要除以 4 (v·h)
+    let weights = Principled_lobe_weights(principled);
+    let alpha = Principled_alpha(principled.roughness);
+    let alpha_clearcoat = Principled_clearcoat_alpha(principled.clearcoat_roughness);
+    return weights.x * nl / PI
+        + weights.y * Principled_gtr2(nh, alpha) * nh / (4 * vh)
+        + weights.z * Principled_gtr1(nh, alpha_clearcoat) * nh / (4 * vh);
+}
+
+fn Principled_random(
+    origin: ptr<function, vec3f>,
+    hit_record: ptr<function, HitRecord>,
+    u: vec2f,
+) -> vec3f {
+    let principled = Principled_load((*hit_record).material_id);
+    let frame = rotation_matrix(VEC3F_UNIT_Y, (*hit_record).normal);
+
+    // 用 u.x 选择波瓣，再把它重新映射到 [0, 1) 用于波瓣内的采样
+    let weights = Principled_lobe_weights(principled);
+    var x = u.x;
+    if x < weights.x {
+        return frame * random_cosine_direction(vec2f(x / weights.x, u.y));
+    }
+    x -= weights.x;
+
+    let phi = 2 * PI * u.y;
+    var cos_theta: f32;
+    if x < weights.y || weights.z <= 0 {
+        x = min(x / weights.y, 1.0);
+        let a2 = pow(Principled_alpha(principled.roughness), 2.0);
+        cos_theta = sqrt((1 - x) / (1 + (a2 - 1) * x));
+    } else {
+        x = min((x - weights.y) / weights.z, 1.0);
+        let a2 = pow(Principled_clearcoat_alpha(principled.clearcoat_roughness), 2.0);
+        cos_theta = sqrt((1 - pow(a2, 1 - x)) / (1 - a2));
+    }
+    let sin_theta = sqrt(max(0.0, 1 - cos_theta * cos_theta));
+    let h = frame * vec3f(sin_theta * sin(phi), cos_theta, sin_theta * cos(phi));
+
+    return reflect((*hit_record).in_direction, h);
+}
+
+// 漫反射、镜面反射和清漆层的采样概率
+fn Principled_lobe_weights(principled: Principled) -> vec3f {
+    let weights = vec3f(1.0 - principled.metallic, 1.0, 0.25 * principled.clearcoat);
+    return weights / (weights.x + weights.y + weights.z);
+}
+
+fn Principled_alpha(roughness: f32) -> f32 {
+    return max(0.001, roughness * roughness);
+}
+
+fn Principled_clearcoat_alpha(clearcoat_roughness: f32) -> f32 {
+    return mix(0.001, 0.1, clearcoat_roughness);
+}
+
+// 按亮度归一化的色调
+fn Principled_tint(base_color: vec3f) -> vec3f {
+    let luminance = dot(base_color, vec3f(0.3, 0.6, 0.1));
+    if luminance > 0 {
+        return base_color / luminance;
+    }
+    return vec3f(1.0);
+}
+
+fn Principled_schlick_weight(cosine: f32) -> f32 {
+    return pow(clamp(1 - cosine, 0.0, 1.0), 5.0);
+}
+
+fn Principled_gtr1(nh: f32, alpha: f32) -> f32 {
+    let a2 = alpha * alpha;
+    let t = 1 + (a2 - 1) * nh * nh;
+    return (a2 - 1) / (PI * log(a2) * t);
+}
+
+fn Principled_gtr2(nh: f32, alpha: f32) -> f32 {
+    let a2 = alpha * alpha;
+    let t = 1 + (a2 - 1) * nh * nh;
+    return a2 / (PI * t * t);
+}
+
+// Smith G1，镜面反射的遮蔽项是入射和出射两个方向的乘积
+fn Principled_smith_g(cosine: f32, alpha: f32) -> f32 {
+    let a2 = alpha * alpha;
+    return 2 * cosine / (cosine + sqrt(a2 + (1 - a2) * cosine * cosine));
+}
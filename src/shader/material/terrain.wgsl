@@ -0,0 +1,92 @@
+/*--------------------------------------- Terrain Material --------------------------------------*/
+
+// 数据布局见 Terrain::pack：层数、两个过渡宽度，之后每层 TERRAIN_LAYER_SIZE 个字，最后是各层的纹理
+
+const TERRAIN_LAYERS: u32 = 3;
+const TERRAIN_LAYER_SIZE: u32 = 9;
+const TERRAIN_LAYER_MIN_HEIGHT: u32 = 3;
+const TERRAIN_LAYER_MAX_HEIGHT: u32 = 4;
+const TERRAIN_LAYER_MIN_SLOPE: u32 = 5;
+const TERRAIN_LAYER_MAX_SLOPE: u32 = 6;
+const TERRAIN_LAYER_TEXTURE_SCALE: u32 = 7;
+const TERRAIN_LAYER_TEXTURE: u32 = 8; // 相对于材质数据起点的下标，0 表示没有纹理
+
+struct Terrain {
+    layer_count: u32,
+    height_blend: f32,
+    slope_blend: f32,
+}
+
+fn Terrain_load(offset: u32) -> Terrain {
+    return Terrain(material_u32(offset), material_f32(offset + 1), material_f32(offset + 2));
+}
+
+// 第一层铺满整个地形，之后每一层按高度和坡度的权重盖在前面的层上
+fn Terrain_albedo(hit_record: ptr<function, HitRecord>) -> vec3f {
+    let offset = (*hit_record).material_id;
+    let terrain = Terrain_load(offset);
+    let height = (*hit_record).position.y;
+    let slope = degrees(acos(clamp(abs((*hit_record).normal.y), 0.0, 1.0)));
+
+    var albedo = VEC3F_ZEROS;
+    for (var i = 0u; i < terrain.layer_count; i++) {
+        let layer = offset + TERRAIN_LAYERS + i * TERRAIN_LAYER_SIZE;
+        var color = material_vec3f(layer);
+        let texture = material_u32(layer + TERRAIN_LAYER_TEXTURE);
+        if texture != 0 {
+            let uv = (*hit_record).uv * material_f32(layer + TERRAIN_LAYER_TEXTURE_SCALE);
+            color *= srgb_to_linear(Texture_sample(offset + texture, uv).rgb);
+        }
+
+        var weight = 1.0;
+        if i > 0 {
+            weight = Terrain_ramp(height - material_f32(layer + TERRAIN_LAYER_MIN_HEIGHT), terrain.height_blend)
+                * Terrain_ramp(material_f32(layer + TERRAIN_LAYER_MAX_HEIGHT) - height, terrain.height_blend)
+                * Terrain_ramp(slope - material_f32(layer + TERRAIN_LAYER_MIN_SLOPE), terrain.slope_blend)
+                * Terrain_ramp(material_f32(layer + TERRAIN_LAYER_MAX_SLOPE) - slope, terrain.slope_blend);
+        }
+        albedo = mix(albedo, color, weight);
+    }
+    return albedo;
+}
+
+// x 是到范围边缘的有向距离，在 [-blend, blend] 内从 0 平滑过渡到 1。先截断再相除，范围不限制时也不会溢出
+fn Terrain_ramp(x: f32, blend: f32) -> f32 {
+    let b = max(blend, 1e-6);
+    let t = clamp(x, -b, b) / (2 * b) + 0.5;
+    return t * t * (3 - 2 * t);
+}
+
+fn Terrain_scatter(
+    ray_in: ptr<function, Ray>,
+    hit_record: ptr<function, HitRecord>,
+    scatter_record: ptr<function, ScatterRecord>
+) -> bool {
+    (*scatter_record).attenuation = Terrain_albedo(hit_record);
+    (*scatter_record).skip_pdf = false;
+
+    return true;
+}
+
+fn Terrain_pdf_value(
+    ray: ptr<function, Ray>,
+    hit_record: ptr<function, HitRecord>,
+) -> f32 {
+    let cosine_theta = dot(normalize((*ray).direction), (*hit_record).normal);
+    return max(0.0, cosine_theta / PI);
+}
+
+fn Terrain_random(
+    origin: ptr<function, vec3f>,
+    hit_record: ptr<function, HitRecord>,
+    u: vec2f,
+) -> vec3f {
+    return rotation_matrix(VEC3F_UNIT_Y, (*hit_record).normal) * random_cosine_direction(u);
+}
+
+fn Terrain_scattering_pdf_value(
+    ray: ptr<function, Ray>,
+    hit_record: ptr<function, HitRecord>,
+) -> f32 {
+    return max(0.0, dot((*hit_record).normal, normalize((*ray).direction)) / PI);
+}
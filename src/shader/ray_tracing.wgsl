@@ -24,27 +24,24 @@ var<uniform> context: RenderContext;
 var<storage, read_write> pixel_color: array<array<f32, 3>>; // 这里如果使用 vec3 会浪费 4 个字节用于对齐
 
 @group(0) @binding(2)
-var<storage, read> bvh_tree: array<BvhNode>;
+var<storage, read_write> pixel_statistics: array<PixelStatistics>;
 
 @group(0) @binding(3)
-var<storage, read> importance: array<PrimitiveIndex>;
+var<storage, read_write> adaptive_counters: AdaptiveSamplingCounters;
 
 @group(0) @binding(4)
-var<storage, read> quads: array<Quad>;
+var<storage, read> bvh_tree: array<BvhNode>;
 
 @group(0) @binding(5)
-var<storage, read> spheres: array<Sphere>;
+var<storage, read> importance: array<PrimitiveIndex>;
 
 @group(0) @binding(6)
-var<storage, read> lambertian_materials: array<Lambertian>;
+var<storage, read> primitives: array<u32>; // 按类型标签打包的图元数据，见 Primitive
 
 @group(0) @binding(7)
-var<storage, read> diffuse_light_materials: array<DiffuseLight>;
+var<storage, read> materials: array<u32>; // 所有材质打包后的数据，见 Materials
 
 @group(0) @binding(8)
-var<storage, read> dielectric_materials: array<Dielectric>;
-
-@group(0) @binding(9)
 var surface: texture_storage_2d<rgba8unorm, write>;
 
 /*----------------------------------------- Ray Tracing -----------------------------------------*/
@@ -59,40 +56,54 @@ fn compute_main(
     }
 
     let pixel_index = gid.x + gid.y * context.width;
-    init_random_generator(
-        pixel_index + context.width * context.height * context.sample_id
-         );
+    let statistics = &pixel_statistics[pixel_index];
 
-    var sample_color: vec3f;
-    if context.sample_id < context.samples_per_pixel {
-        var ray = get_ray(vec2f(f32(gid.x), f32(gid.y)));
-        sample_color = ray_color(&ray);
+    if context.sample_id == 0 {
+        pixel_color[pixel_index] = array<f32, 3>(0.0, 0.0, 0.0);
+        *statistics = PixelStatistics(0.0, 0.0, 0u, 0u);
     }
 
-    var sample_count = f32(context.sample_id) + 1.0;
-    if context.sample_id == 0 {
-        pixel_color[pixel_index][0] = sample_color.x;
-        pixel_color[pixel_index][1] = sample_color.y;
-        pixel_color[pixel_index][2] = sample_color.z;
-    } else if context.sample_id < context.samples_per_pixel {
+    let samples = AdaptiveSampling_samples(pixel_index);
+    for (var i = 0u; i < samples; i++) {
+        let sample_index = (*statistics).sample_count;
+        Sampler_start(pixel_index, sample_index);
+
+        var ray = get_ray(vec2f(f32(gid.x), f32(gid.y)), sample_index);
+        let sample_color = ray_color(&ray);
+
         pixel_color[pixel_index][0] += sample_color.x;
         pixel_color[pixel_index][1] += sample_color.y;
         pixel_color[pixel_index][2] += sample_color.z;
-    } else {
-        sample_count -= 1.0;
+        PixelStatistics_add_sample(pixel_index, sample_color);
+    }
+
+    AdaptiveSampling_update_convergence(pixel_index);
+    if (*statistics).converged == 1u {
+        atomicAdd(&adaptive_counters.converged_pixels, 1u);
     }
 
+    let sample_count = max(f32((*statistics).sample_count), 1.0);
     let color = vec3f(
         pixel_color[pixel_index][0] / sample_count,
         pixel_color[pixel_index][1] / sample_count,
         pixel_color[pixel_index][2] / sample_count
     );
 
-    textureStore(surface, gid.xy, vec4(linear_to_srgb(color), 1.0));
+    if context.sample_view == SAMPLE_VIEW_SAMPLE_COUNT_HEATMAP {
+        let ratio = f32((*statistics).sample_count) / f32(context.samples_per_pixel);
+        textureStore(surface, gid.xy, vec4(heatmap(ratio), 1.0));
+    } else {
+        textureStore(surface, gid.xy, vec4(linear_to_srgb(color), 1.0));
+    }
 }
 
-fn get_ray(pixel_position: vec2f) -> Ray {
-    let offset = sample_unit_square_stratified();
+fn get_ray(pixel_position: vec2f, sample_index: u32) -> Ray {
+    var offset: vec2f;
+    if context.sampler_type == SAMPLER_INDEPENDENT {
+        offset = sample_unit_square_stratified(sample_index);
+    } else { // 低差异序列本身已经分层
+        offset = Sampler_2d() - 0.5;
+    }
     let pixel_world_position = context.pixel_origin
                                 + (pixel_position.x + offset.x) * context.pixel_delta_u
                                 + (pixel_position.y + offset.y) * context.pixel_delta_v;
@@ -104,21 +115,27 @@ fn get_ray(pixel_position: vec2f) -> Ray {
         ray_origin = defocus_disk_sample();
     }
 
+    // 快门打开期间均匀采样时间，相机随时间平移。快门时间为零时不消耗采样维度
+    var time = context.shutter_open;
+    if context.shutter_close > context.shutter_open {
+        time = mix(context.shutter_open, context.shutter_close, Sampler_1d());
+    }
+    let camera_offset = time * context.camera_motion;
+
     let ray_direction = normalize(pixel_world_position - ray_origin);
 
-    return Ray(ray_origin, ray_direction);
+    return Ray(ray_origin + camera_offset, ray_direction, time);
 }
 
 fn defocus_disk_sample() -> vec3f {
-    let s = sample_unit_disk();
+    let s = sample_unit_disk(Sampler_2d());
     return context.camera_position + s.x * context.defocus_disk_u + s.y * context.defocus_disk_v;
 }
 
 struct RayColorCalculationEntry {
     color_from_emission: vec3f,
     pdf_val: f32,
-    attenuation: vec3f,
-    scattering_pdf: f32,
+    attenuation: vec3f, // skip_pdf 时是衰减，否则是 BSDF 与余弦项的乘积，见 Material_eval
     skip_pdf: bool,
 }
 var<private> ray_color_stack: array<RayColorCalculationEntry, MAX_RAY_BOUNCES>;
@@ -140,6 +157,8 @@ fn ray_color(
             let background = vec3f(0.0, 0.0, 0.0);
             return resolve_ray_color(stack_id, background);
         }
+        hit_record.in_direction = normalize((*ray).direction);
+        NormalMap_apply(&hit_record);
 
         let emitted_color = Material_emit(ray, &hit_record);
 
@@ -153,13 +172,16 @@ fn ray_color(
             stack_id += 1;
             ray_color_stack[stack_id].skip_pdf = true;
             ray_color_stack[stack_id].attenuation = scatter_record.attenuation;
+            let time = (*ray).time;
             *ray = scatter_record.skip_pdf_ray;
+            (*ray).time = time;
             continue;
         }
 
         var scattered_ray: Ray;
         var scattered_origin = hit_record.position;
         scattered_ray.origin = scattered_origin;
+        scattered_ray.time = (*ray).time;
 
         // importance only
         // scattered_ray.direction = importance_random(&scattered_origin);
@@ -173,27 +195,39 @@ fn ray_color(
         // let pdf_value = Material_pdf_value(&scattered_ray, &hit_record);
 
         // Mixed
+        // 每次反弹固定消耗三个维度，保证同一维度在不同路径之间含义一致
+        let strategy_u = Sampler_1d();
+        let light_u = Sampler_1d();
+        let direction_u = Sampler_2d();
+
         let material_weight = 0.6; // this is an empirical value
-        if randomf() > material_weight {
-            scattered_ray.direction = importance_random(&scattered_origin);
+        if strategy_u > material_weight {
+            scattered_ray.direction = importance_random(&scattered_origin, scattered_ray.time, light_u, direction_u);
             let pdf_value = importance_pdf_value(&scattered_ray);
             if pdf_value == 0 {
                 return VEC3F_ZEROS;
             }
         } else {
-            scattered_ray.direction = Material_random(&scattered_origin, &hit_record);
+            scattered_ray.direction = Material_random(&scattered_origin, &hit_record, direction_u);
+        }
+        // 着色法线与几何法线不同时，采样的方向可能在几何表面之下，沿这样的方向继续追踪会漏光
+        if dot(scattered_ray.direction, hit_record.geometric_normal) <= 0 {
+            return resolve_ray_color(stack_id, emitted_color);
         }
+
         let pdf_value = (1.0 - material_weight) * importance_pdf_value(&scattered_ray) 
                         + material_weight * Material_pdf_value(&scattered_ray, &hit_record);
+        if pdf_value <= 0 {
+            return resolve_ray_color(stack_id, emitted_color);
+        }
 
-        let scattering_pdf_value = Material_scattering_pdf_value(&scattered_ray, &hit_record);
+        let bsdf_cosine = Material_eval(&scattered_ray, &hit_record, &scatter_record);
         
         stack_id += 1;
         *ray = scattered_ray;
         ray_color_stack[stack_id].skip_pdf = false;
         ray_color_stack[stack_id].color_from_emission = emitted_color;
-        ray_color_stack[stack_id].attenuation = scatter_record.attenuation;
-        ray_color_stack[stack_id].scattering_pdf = scattering_pdf_value;
+        ray_color_stack[stack_id].attenuation = bsdf_cosine;
         ray_color_stack[stack_id].pdf_val = pdf_value;
     }
     
@@ -211,7 +245,7 @@ fn resolve_ray_color(
         if (*entry).skip_pdf {
             color *= (*entry).attenuation;
         } else {
-            color = (*entry).color_from_emission + ((*entry).attenuation * (*entry).scattering_pdf * color) / (*entry).pdf_val;
+            color = (*entry).color_from_emission + ((*entry).attenuation * color) / (*entry).pdf_val;
         }
     }
     return color;
@@ -230,13 +264,13 @@ fn importance_pdf_value(ray: ptr<function, Ray>) -> f32 {
     return pdf / f32(len);
 }
 
-fn importance_random(origin: ptr<function, vec3f>) -> vec3f {
+fn importance_random(origin: ptr<function, vec3f>, time: f32, light_u: f32, u: vec2f) -> vec3f {
     // let len = arrayLength(&importance);
     let len = context.important_index_len;
-    var i = randomi_range(0, i32(len - 1));
+    let i = min(u32(light_u * f32(len)), len - 1);
     let primitive_type = importance[i].primitive_type;
     let primitive_id = importance[i].primitive_id;
-    return Primitive_random(primitive_type, primitive_id, origin);
+    return Primitive_random(primitive_type, primitive_id, origin, time, u);
 }
 
 /*-------------------------------------- Render Context -----------------------------------------*/
@@ -244,7 +278,8 @@ fn importance_random(origin: ptr<function, vec3f>) -> vec3f {
 struct RenderContext {
     width: u32,
     height: u32,
-    sample_position: vec2<u32>,
+    sample_grid_per_dimension: u32,
+    adaptive_sampling_mode: u32,
     pixel_origin: vec3f,
     samples_per_pixel: u32,
     pixel_delta_u: vec3f,
@@ -257,8 +292,131 @@ struct RenderContext {
     sample_id: u32,
     camera_position: vec3f,
     ray_bounces: u32,
-    important_index_len: u32
+    important_index_len: u32,
+    convergence_threshold: f32,
+    adaptive_min_samples: u32,
+    sample_view: u32,
+    samples_per_dispatch: u32,
+    seed: u32,
+    sampler_type: u32,
+    shutter_open: f32,
+    shutter_close: f32,
+    max_samples_per_pixel: u32,
+    camera_motion: vec3f, // 时间 0 到 1 之间相机的位移
+}
+
+/*--------------------------------------- Adaptive Sampling -------------------------------------*/
+
+const ADAPTIVE_SAMPLING_OFF: u32 = 0;
+const ADAPTIVE_SAMPLING_STOP_CONVERGED: u32 = 1;
+const ADAPTIVE_SAMPLING_REDISTRIBUTE: u32 = 2;
+
+const MAX_REDISTRIBUTED_SAMPLES: u32 = 4;
+
+const SAMPLE_VIEW_COLOR: u32 = 0;
+const SAMPLE_VIEW_SAMPLE_COUNT_HEATMAP: u32 = 1;
+
+struct AdaptiveSamplingCounters {
+    converged_pixels: atomic<u32>,
+    freed_samples: atomic<u32>,
+    extra_samples: atomic<u32>,
+}
+
+struct PixelStatistics {
+    luminance_sum: f32,
+    luminance_square_sum: f32,
+    sample_count: u32,
+    converged: u32,
+}
+
+fn PixelStatistics_add_sample(pixel_index: u32, color: vec3f) {
+    let statistics = &pixel_statistics[pixel_index];
+    let y = luminance(color);
+
+    (*statistics).luminance_sum += y;
+    (*statistics).luminance_square_sum += y * y;
+    (*statistics).sample_count += 1u;
+}
+
+// 像素亮度均值的相对标准误差
+fn PixelStatistics_relative_error(pixel_index: u32) -> f32 {
+    let statistics = &pixel_statistics[pixel_index];
+    let n = f32((*statistics).sample_count);
+    if n < 2.0 {
+        return MAX;
+    }
+
+    let mean = (*statistics).luminance_sum / n;
+    let variance = max(0.0, ((*statistics).luminance_square_sum - n * mean * mean) / (n - 1.0));
+    let standard_error = sqrt(variance / n);
+    return standard_error / (mean + 1e-3);
+}
+
+// 本次调度中该像素需要的采样数，每个采样使用各自的随机数序列。
+// 只有 Redistribute 模式下 max_samples_per_pixel 才会超过 samples_per_pixel，超出的部分计入 extra_samples
+fn AdaptiveSampling_samples(pixel_index: u32) -> u32 {
+    let statistics = &pixel_statistics[pixel_index];
+    let sample_count = (*statistics).sample_count;
+
+    if sample_count >= context.max_samples_per_pixel {
+        return 0u;
+    }
+
+    let remaining = context.max_samples_per_pixel - sample_count;
+
+    if context.adaptive_sampling_mode == ADAPTIVE_SAMPLING_OFF {
+        return min(context.samples_per_dispatch, remaining);
+    }
+
+    if (*statistics).converged == 1u {
+        return 0u;
+    }
+
+    if context.adaptive_sampling_mode != ADAPTIVE_SAMPLING_REDISTRIBUTE {
+        return min(context.samples_per_dispatch, remaining);
+    }
+
+    var scale = 1u;
+    if sample_count >= context.adaptive_min_samples {
+        // 误差越大，追加的采样越多
+        let ratio = PixelStatistics_relative_error(pixel_index) / context.convergence_threshold;
+        scale = u32(clamp(ceil(ratio), 1.0, f32(MAX_REDISTRIBUTED_SAMPLES)));
+    }
+    let samples = min(context.samples_per_dispatch * scale, remaining);
+
+    let extra = max(sample_count + samples, context.samples_per_pixel) - max(sample_count, context.samples_per_pixel);
+    if extra > 0u {
+        atomicAdd(&adaptive_counters.extra_samples, extra);
+    }
+    return samples;
+}
+
+fn AdaptiveSampling_update_convergence(pixel_index: u32) {
+    let statistics = &pixel_statistics[pixel_index];
+
+    if context.adaptive_sampling_mode == ADAPTIVE_SAMPLING_OFF
+        || (*statistics).converged == 1u
+        || (*statistics).sample_count < context.adaptive_min_samples {
+        return;
+    }
+
+    // 达到上限的像素也不再采样。在 samples_per_pixel 之前收敛的像素把剩下的采样数留给 Redistribute 的预算
+    let sample_count = (*statistics).sample_count;
+    if sample_count >= context.max_samples_per_pixel
+        || PixelStatistics_relative_error(pixel_index) < context.convergence_threshold {
+        (*statistics).converged = 1u;
+        if context.adaptive_sampling_mode == ADAPTIVE_SAMPLING_REDISTRIBUTE && sample_count < context.samples_per_pixel {
+            atomicAdd(&adaptive_counters.freed_samples, context.samples_per_pixel - sample_count);
+        }
+    }
+}
+
+// 0 -> 蓝，0.5 -> 绿，1 -> 红
+fn heatmap(x: f32) -> vec3f {
+    let t = clamp(x, 0.0, 1.0);
+    return clamp(vec3f(2.0 * t - 1.0, 1.0 - abs(2.0 * t - 1.0), 1.0 - 2.0 * t), VEC3F_ZEROS, vec3f(1.0));
 }
+
 /*------------------------------------------ BVH ------------------------------------------------*/
 
 struct BoundingBox {
@@ -442,221 +600,153 @@ struct ScatterRecord {
 
 /*---------------------------------------- Materials --------------------------------------------*/
 
-fn Material_scatter(
-    ray_in: ptr<function, Ray>,
-    hit_record: ptr<function, HitRecord>,
-    scatter_record: ptr<function, ScatterRecord>
-) -> bool {
-    switch ((*hit_record).material_type) {
-        case 1u: { // Lambertian
-            return Lambertian_scatter(ray_in, hit_record, scatter_record);
-        }
-        case 3u: { // Dielectric
-            return Dielectric_scatter(ray_in, hit_record, scatter_record);
-        }
-        default: {
-            return false;
-        }
-    }
-}
-
-fn Material_scattering_pdf_value(
-    ray: ptr<function, Ray>,
-    hit_record: ptr<function, HitRecord>,
-) -> f32 {
-    switch ((*hit_record).material_type) {
-        case 1u: { // Lambertian
-            return Lambertian_scattering_pdf_value(ray, hit_record);
-        }
-        default: {
-            return 0.0;
-        }
-    }
-}
+// hit_record.material_type 是材质类型的标签，material_id 是材质数据在 materials 中的起始下标。
+// 各材质的结构体、{材质}_load 和材质函数，以及按标签分派的 Material_scatter、Material_emit 等函数
+// 由 MaterialRegistry::wgsl 生成，拼接在本文件之后，见 src/shader/material
 
-fn Material_emit(
-    ray_in: ptr<function, Ray>,
-    hit_record: ptr<function, HitRecord>,
-) -> vec3f {
-    switch ((*hit_record).material_type) {
-        case 0u: { // DebugNormal
-            return DebugNormal_emit(ray_in, hit_record);
-        }
-        case 2u: { // Diffuse Light
-            return DiffuseLight_emit(ray_in, hit_record);
-        }
-        default: {
-            return vec3f(0.0, 0.0, 0.0);
-        }
-    }
+fn material_u32(offset: u32) -> u32 {
+    return materials[offset];
 }
 
-fn Material_pdf_value(
-    ray: ptr<function, Ray>,
-    hit_record: ptr<function, HitRecord>,
-) -> f32 {
-    switch ((*hit_record).material_type) {
-        case 1u: { // Lambertian
-            return Lambertian_pdf_value(ray, hit_record);
-        }
-        default: {
-            return 0.0;
-        }
-    }
+fn material_f32(offset: u32) -> f32 {
+    return bitcast<f32>(materials[offset]);
 }
 
-fn Material_random(
-    origin: ptr<function, vec3f>,
-    hit_record: ptr<function, HitRecord>,
-) -> vec3f {
-    switch ((*hit_record).material_type) {
-        case 1u: { // Lambertian
-            return Lambertian_random(origin, hit_record);
-        }
-        default: {
-            return vec3f(0.0, 0.0, 0.0);
-        }
-    }
+fn material_vec3f(offset: u32) -> vec3f {
+    return vec3f(material_f32(offset), material_f32(offset + 1), material_f32(offset + 2));
 }
 
-/*------------------------------------- DebugNormal Material ------------------------------------*/
-
-fn DebugNormal_emit(
-    ray_in: ptr<function, Ray>,
-    hit_record: ptr<function, HitRecord>,
-) -> vec3f {
-    return (*hit_record).normal * 0.5 + 0.5;
+// 打包的纹理：宽、高，之后是按行存储的 RGBA8 像素，见 Texture::pack
+fn Texture_size(offset: u32) -> vec2u {
+    return vec2u(material_u32(offset), material_u32(offset + 1));
 }
 
-/*------------------------------------- Lambertian Material -------------------------------------*/
-
-struct Lambertian {
-    albedo: vec3f
+// 最近邻采样，uv 按 1 循环
+fn Texture_sample(offset: u32, uv: vec2f) -> vec4f {
+    let size = Texture_size(offset);
+    let texel = min(vec2u(fract(uv) * vec2f(size)), size - 1);
+    return unpack4x8unorm(material_u32(offset + 2 + texel.y * size.x + texel.x));
 }
 
-fn Lambertian_scatter(
-    ray_in: ptr<function, Ray>,
-    hit_record: ptr<function, HitRecord>,
-    scatter_record: ptr<function, ScatterRecord>
-) -> bool {
-    let lambertian = &lambertian_materials[(*hit_record).material_id];
-
-    (*scatter_record).attenuation = (*lambertian).albedo;
-    (*scatter_record).skip_pdf = false;
+/*---------------------------------------- Normal Map -------------------------------------------*/
 
-    return true;
-}
+// 紧挨在材质数据之前的字是法线贴图数据的下标，0 表示没有法线贴图。数据布局见 NormalMap::pack
 
-fn Lambertian_pdf_value(
-    ray: ptr<function, Ray>,
-    hit_record: ptr<function, HitRecord>,
-) -> f32 {
-    let cosine_theta = dot(normalize((*ray).direction), (*hit_record).normal);
-    return max(0.0, cosine_theta / PI);
-}
+const NORMAL_MAP_TANGENT_SPACE: u32 = 0;
+const NORMAL_MAP_BUMP: u32 = 1;
 
-fn Lambertian_random(
-    origin: ptr<function, vec3f>,
-    hit_record: ptr<function, HitRecord>,
-) -> vec3f {
-    return rotation_matrix(VEC3F_UNIT_Y, (*hit_record).normal) * random_cosine_direction();
-}
+fn NormalMap_apply(hit_record: ptr<function, HitRecord>) {
+    let offset = material_u32((*hit_record).material_id - 1);
+    if offset == 0 {
+        return;
+    }
 
-fn Lambertian_scattering_pdf_value(
-    ray: ptr<function, Ray>,
-    hit_record: ptr<function, HitRecord>,
-) -> f32 {
-    let cos_theta = dot((*hit_record).normal, normalize((*ray).direction));
-    if cos_theta < 0 {
-        return 0f;
+    let kind = material_u32(offset);
+    let strength = material_f32(offset + 1);
+    let texture = offset + 2;
+    let uv = (*hit_record).uv;
+
+    // 切线空间的法线
+    var n: vec3f;
+    if kind == NORMAL_MAP_BUMP {
+        let size = vec2f(Texture_size(texture));
+        let h = Texture_sample(texture, uv).r;
+        let dhdu = (Texture_sample(texture, uv + vec2f(1.0 / size.x, 0.0)).r - h) * size.x;
+        let dhdv = (Texture_sample(texture, uv + vec2f(0.0, 1.0 / size.y)).r - h) * size.y;
+        n = vec3f(-strength * dhdu, -strength * dhdv, 1.0);
     } else {
-        return cos_theta / PI;
+        let t = Texture_sample(texture, uv).rgb * 2.0 - 1.0;
+        n = vec3f(t.xy * strength, t.z);
     }
-}
-
-fn random_cosine_direction() -> vec3f {
-    let xi1 = randomf();
-    let xi2 = randomf();
-
-    let phi = 2 * PI * xi1;
-    let z = cos(phi) * sqrt(xi2);
-    let x = sin(phi) * sqrt(xi2);
-    let y = sqrt(1 - xi2);
-
-    return vec3f(x, y, z);
-}
 
-/*------------------------------------- Dielectric Material -------------------------------------*/
+    var outward_normal = (*hit_record).geometric_normal;
+    if !(*hit_record).is_front_face {
+        outward_normal = -outward_normal;
+    }
+    var normal = normalize(n.x * (*hit_record).tangent + n.y * (*hit_record).bitangent + n.z * outward_normal);
+    if !(*hit_record).is_front_face {
+        normal = -normal;
+    }
 
-struct Dielectric {
-    refraction_index: f32
+    // 背向观察者的着色法线会让表面在背光面发亮，把它向观察方向弯折
+    let v = -(*hit_record).in_direction;
+    let nv = dot(normal, v);
+    if nv < 0.01 {
+        normal = normalize(normal + (0.01 - nv) * v);
+    }
+    (*hit_record).normal = normal;
 }
 
-fn Dielectric_scatter(
-    ray_in: ptr<function, Ray>,
-    hit_record: ptr<function, HitRecord>,
-    scatter_record: ptr<function, ScatterRecord>
-) -> bool {
-    let dielectric = &dielectric_materials[(*hit_record).material_id];
+/*---------------------------------------- Alpha Mask -------------------------------------------*/
 
-    (*scatter_record).attenuation = vec3f(1.0, 1.0, 1.0);
-    (*scatter_record).skip_pdf = true;
+const ALPHA_MASK_CUTOFF: u32 = 0;
+const ALPHA_MASK_STOCHASTIC: u32 = 1;
 
-    var refraction_index = (*dielectric).refraction_index;
-    if (*hit_record).is_front_face {
-        refraction_index = 1.0 / (*dielectric).refraction_index;
+// 法线贴图下标之前的字是透明度贴图数据的下标，0 表示没有透明度贴图。数据布局见 AlphaMask::pack。
+// 测试失败的交点被所有光线穿过，包括朝光源采样的光线。光源采样的概率密度仍然按整个图元计算，
+// 落在透明部分的样本沿原方向穿过去，估计仍然是无偏的
+fn AlphaMask_passes(hit_record: ptr<function, HitRecord>) -> bool {
+    let offset = material_u32((*hit_record).material_id - 2);
+    if offset == 0 {
+        return true;
     }
 
-    let in_direction = normalize((*ray_in).direction);
-    let cos_theta = min(dot(-in_direction, (*hit_record).normal), 1.0);
-    let sin_theta = sqrt(1 - pow(cos_theta, 2.0));
+    let mode = material_u32(offset);
+    let alpha = Texture_sample(offset + 2, (*hit_record).uv).a;
+    if mode == ALPHA_MASK_STOCHASTIC {
+        // 用交点位置的哈希代替随机数，遍历 BVH 时不消耗采样器的维度
+        let p = bitcast<vec3u>((*hit_record).position);
+        return alpha > u32_to_unit_float(hash_u32(p.x ^ hash_u32(p.y ^ hash_u32(p.z))));
+    }
+    return alpha >= material_f32(offset + 1);
+}
 
-    let cannot_refract = refraction_index * sin_theta > 1.0;
+/*----------------------------------------- Primitive --------------------------------------------*/
 
-    var out_direction: vec3f;
-    if cannot_refract || Dielectric_reflectance((*hit_record).material_id, cos_theta, refraction_index) > randomf() {
-        out_direction = reflect(in_direction, (*hit_record).normal);
-    } else {
-        out_direction = refract(in_direction, (*hit_record).normal, refraction_index);
-    }
+// primitives 开头是按类型标签排列的 (起始下标, 每个图元占的字数)，之后依次是各种图元的数据，见 SceneData::pack_primitives。
+// 各种图元的结构体只描述数据的布局，用 {图元}_load 从 primitives 中读出。
+// Sdf、Csg 之后紧跟节点数组，Heightfield 之后紧跟高度数组，按需逐个读取，不整个读出
+const PRIMITIVE_QUAD = 0u;
+const PRIMITIVE_SPHERE = 1u;
+const PRIMITIVE_SHAPE = 2u;
+const PRIMITIVE_SDF = 3u;
+const PRIMITIVE_CSG = 4u;
+const PRIMITIVE_CURVE = 5u;
+const PRIMITIVE_HEIGHTFIELD = 6u;
 
-    (*scatter_record).skip_pdf_ray = Ray_init((*hit_record).position, out_direction);
-    return true;
+struct PrimitiveIndex {
+    primitive_type: u32,
+    primitive_id: u32,
 }
 
-fn Dielectric_reflectance(
-    material_id: u32,
-    cosine: f32,
-    refraction_index: f32
-) -> f32 {
-    let dielectric = &dielectric_materials[material_id];
+// 第 primitive_id 个 primitive_type 类型的图元数据的起始下标
+fn Primitive_offset(primitive_type: u32, primitive_id: u32) -> u32 {
+    return primitives[primitive_type * 2] + primitive_id * primitives[primitive_type * 2 + 1];
+}
 
-    var r0 = (1 - (*dielectric).refraction_index) / (1 + (*dielectric).refraction_index);
-    r0 = pow(r0, 2.0);
-    return r0 + (1 - r0) * pow((1 - cosine), 5.0);
+fn primitive_u32(offset: u32) -> u32 {
+    return primitives[offset];
 }
 
-/*----------------------------------- Diffuse Light Material ------------------------------------*/
+fn primitive_f32(offset: u32) -> f32 {
+    return bitcast<f32>(primitives[offset]);
+}
 
-struct DiffuseLight {
-    emit: vec3f
+fn primitive_vec2f(offset: u32) -> vec2f {
+    return vec2f(primitive_f32(offset), primitive_f32(offset + 1));
 }
 
-fn DiffuseLight_emit(
-    ray_in: ptr<function, Ray>,
-    hit_record: ptr<function, HitRecord>
-) -> vec3f {
-    if !(*hit_record).is_front_face {
-        return VEC3F_ZEROS;
-    }
-    return diffuse_light_materials[(*hit_record).material_id].emit;
+fn primitive_vec3f(offset: u32) -> vec3f {
+    return vec3f(primitive_f32(offset), primitive_f32(offset + 1), primitive_f32(offset + 2));
 }
 
-/*----------------------------------------- Primitive --------------------------------------------*/
+fn primitive_vec4f(offset: u32) -> vec4f {
+    return vec4f(primitive_f32(offset), primitive_f32(offset + 1), primitive_f32(offset + 2), primitive_f32(offset + 3));
+}
 
-struct PrimitiveIndex {
-    primitive_type: u32,
-    primitive_id: u32,
+// mat3x3f 的每一列按 vec4f 对齐
+fn primitive_mat3x3f(offset: u32) -> mat3x3f {
+    return mat3x3f(primitive_vec3f(offset), primitive_vec3f(offset + 4), primitive_vec3f(offset + 8));
 }
 
 fn Primitive_hit(
@@ -666,22 +756,59 @@ fn Primitive_hit(
     interval: ptr<function, Interval>,
     hit_record: ptr<function, HitRecord>,
 ) -> bool {
-    var hit: bool;
-    switch (primitive_type) {
-        case 0u: { // Quad
-            hit = Quad_hit(primitive_id, ray, interval, hit_record);
-        }
-        case 1u: { // Sphere
-            hit = Sphere_hit(primitive_id, ray, interval, hit_record);
+    // 运动的图元在时间 0 时的坐标系中求交，再把交点变换回光线所在的时刻
+    let motion = Primitive_motion(primitive_type, primitive_id);
+    var object_ray = *ray;
+    if motion.moving != 0u {
+        object_ray = Motion_ray_to_object(motion, ray);
+    }
+
+    // 先写入 candidate，透明度测试失败时不能覆盖已经找到的最近交点。
+    // 失败时从这个交点之后继续找（例如球的背面），一条光线与图元最多有 SHAPE_MAX_HITS 个交点
+    var candidate = *hit_record;
+    var candidate_interval = *interval;
+    for (var i = 0; i < SHAPE_MAX_HITS; i++) {
+        var hit: bool;
+        switch (primitive_type) {
+            case 0u: { // Quad
+                hit = Quad_hit(primitive_id, &object_ray, &candidate_interval, &candidate);
+            }
+            case 1u: { // Sphere
+                hit = Sphere_hit(primitive_id, &object_ray, &candidate_interval, &candidate);
+            }
+            case 2u: { // Shape
+                hit = Shape_hit(primitive_id, &object_ray, &candidate_interval, &candidate);
+            }
+            case 3u: { // Sdf
+                hit = Sdf_hit(primitive_id, &object_ray, &candidate_interval, &candidate);
+            }
+            case 4u: { // Csg
+                hit = Csg_hit(primitive_id, &object_ray, &candidate_interval, &candidate);
+            }
+            case 5u: { // Curve
+                hit = Curve_hit(primitive_id, &object_ray, &candidate_interval, &candidate);
+            }
+            case 6u: { // Heightfield
+                hit = Heightfield_hit(primitive_id, &object_ray, &candidate_interval, &candidate);
+            }
+            default: {
+                return false;
+            }
         }
-        default: {
+        if !hit {
             return false;
         }
+        if motion.moving != 0u {
+            Motion_hit_record_to_world(motion, (*ray).time, &candidate);
+        }
+        if AlphaMask_passes(&candidate) {
+            *hit_record = candidate;
+            (*interval).max = (*hit_record).ray_t;
+            return true;
+        }
+        candidate_interval.min = candidate.ray_t;
     }
-    if hit {
-        (*interval).max = (*hit_record).ray_t;
-    }
-    return hit;
+    return false;
 }
 
 fn Primitive_pdf_value(
@@ -689,12 +816,21 @@ fn Primitive_pdf_value(
     primitive_id: u32,
     ray: ptr<function, Ray>,
 ) -> f32 {
+    let motion = Primitive_motion(primitive_type, primitive_id);
+    var object_ray = *ray;
+    if motion.moving != 0u {
+        object_ray = Motion_ray_to_object(motion, ray);
+    }
+
     switch (primitive_type) {
         case 0u: { // Quad
-            return Quad_pdf_value(primitive_id, ray);
+            return Quad_pdf_value(primitive_id, &object_ray);
         }
         case 1u: { // Sphere
-            return Sphere_pdf_value(primitive_id, ray);
+            return Sphere_pdf_value(primitive_id, &object_ray);
+        }
+        case 2u: { // Shape
+            return Shape_pdf_value(primitive_id, &object_ray);
         }
         default: {
             return 0.0;
@@ -705,19 +841,131 @@ fn Primitive_pdf_value(
 fn Primitive_random(
     primitive_type: u32,
     primitive_id: u32,
-    origin: ptr<function, vec3f>
+    origin: ptr<function, vec3f>,
+    time: f32,
+    u: vec2f,
 ) -> vec3f {
+    let motion = Primitive_motion(primitive_type, primitive_id);
+    var object_origin = *origin;
+    if motion.moving != 0u {
+        object_origin = Motion_point_to_object(motion, time, *origin);
+    }
+
+    var direction: vec3f;
     switch (primitive_type) {
         case 0u: { // Quad
-            return Quad_random(primitive_id, origin);
+            direction = Quad_random(primitive_id, &object_origin, u);
         }
         case 1u: { // Sphere
-            return Sphere_random(primitive_id, origin);
+            direction = Sphere_random(primitive_id, &object_origin, u);
+        }
+        case 2u: { // Shape
+            direction = Shape_random(primitive_id, &object_origin, u);
         }
         default: {
             return VEC3F_ZEROS;
         }
     }
+
+    if motion.moving != 0u {
+        return Motion_vector_to_world(motion, time, direction);
+    }
+    return direction;
+}
+
+fn Primitive_motion(
+    primitive_type: u32,
+    primitive_id: u32,
+) -> Motion {
+    switch (primitive_type) {
+        case 0u: { // Quad
+            return Quad_load(primitive_id).motion;
+        }
+        case 1u: { // Sphere
+            return Sphere_load(primitive_id).motion;
+        }
+        case 2u: { // Shape
+            return Shape_load(primitive_id).motion;
+        }
+        case 3u: { // Sdf
+            return Sdf_load(primitive_id).motion;
+        }
+        case 4u: { // Csg
+            return Csg_load(primitive_id).motion;
+        }
+        case 5u: { // Curve
+            return Curve_load(primitive_id).motion;
+        }
+        case 6u: { // Heightfield
+            return Heightfield_load(primitive_id).motion;
+        }
+        default: {
+            return Motion();
+        }
+    }
+}
+
+/*------------------------------------------- Motion --------------------------------------------*/
+
+// 图元在曝光期间的刚体运动，见 MotionData。primitives 中的几何数据是时间 0 时的位置，
+// time 时先绕过 pivot 的 axis 旋转 angle * time，再平移 translation * time
+struct Motion {
+    translation: vec3f,
+    angle: f32,
+    axis: vec3f,
+    moving: u32,
+    pivot: vec3f,
+}
+
+fn Motion_load(offset: u32) -> Motion {
+    return Motion(
+        primitive_vec3f(offset),
+        primitive_f32(offset + 3),
+        primitive_vec3f(offset + 4),
+        primitive_u32(offset + 7),
+        primitive_vec3f(offset + 8),
+    );
+}
+
+// Rodrigues 旋转公式
+fn rotate_axis_angle(v: vec3f, axis: vec3f, angle: f32) -> vec3f {
+    let c = cos(angle);
+    let s = sin(angle);
+    return v * c + cross(axis, v) * s + axis * dot(axis, v) * (1.0 - c);
+}
+
+fn Motion_point_to_world(motion: Motion, time: f32, p: vec3f) -> vec3f {
+    return rotate_axis_angle(p - motion.pivot, motion.axis, motion.angle * time) + motion.pivot + motion.translation * time;
+}
+
+fn Motion_vector_to_world(motion: Motion, time: f32, v: vec3f) -> vec3f {
+    return rotate_axis_angle(v, motion.axis, motion.angle * time);
+}
+
+fn Motion_point_to_object(motion: Motion, time: f32, p: vec3f) -> vec3f {
+    return rotate_axis_angle(p - motion.translation * time - motion.pivot, motion.axis, -motion.angle * time) + motion.pivot;
+}
+
+fn Motion_vector_to_object(motion: Motion, time: f32, v: vec3f) -> vec3f {
+    return rotate_axis_angle(v, motion.axis, -motion.angle * time);
+}
+
+// 刚体变换不改变光线的参数 t，求交得到的 ray_t 在两个坐标系中相同
+fn Motion_ray_to_object(motion: Motion, ray: ptr<function, Ray>) -> Ray {
+    let time = (*ray).time;
+    return Ray(
+        Motion_point_to_object(motion, time, (*ray).origin),
+        Motion_vector_to_object(motion, time, (*ray).direction),
+        time
+    );
+}
+
+fn Motion_hit_record_to_world(motion: Motion, time: f32, hit_record: ptr<function, HitRecord>) {
+    (*hit_record).position = Motion_point_to_world(motion, time, (*hit_record).position);
+    (*hit_record).normal = Motion_vector_to_world(motion, time, (*hit_record).normal);
+    (*hit_record).geometric_normal = Motion_vector_to_world(motion, time, (*hit_record).geometric_normal);
+    (*hit_record).tangent = Motion_vector_to_world(motion, time, (*hit_record).tangent);
+    (*hit_record).bitangent = Motion_vector_to_world(motion, time, (*hit_record).bitangent);
 }
 
 /*------------------------------------------ Sphere ---------------------------------------------*/
@@ -727,6 +975,40 @@ struct Sphere {
     radius: f32,
     material_type: u32,
     material_id: u32,
+    bounding_radius: f32, // 包住变换后椭球的最小球面的半径
+    transform: mat3x3f, // 以 center 为原点的局部坐标到世界坐标的旋转和缩放
+    inverse_transform: mat3x3f,
+    motion: Motion,
+}
+
+fn Sphere_load(id: u32) -> Sphere {
+    let offset = Primitive_offset(PRIMITIVE_SPHERE, id);
+    return Sphere(
+        primitive_vec3f(offset),
+        primitive_f32(offset + 3),
+        primitive_u32(offset + 4),
+        primitive_u32(offset + 5),
+        primitive_f32(offset + 6),
+        primitive_mat3x3f(offset + 8),
+        primitive_mat3x3f(offset + 20),
+        Motion_load(offset + 32),
+    );
+}
+
+// 光线与以原点为中心的球面的两个交点，即光线在球内的区间，不相交时返回空区间
+fn Sphere_span(origin: vec3f, direction: vec3f, radius: f32) -> Interval {
+    let oc = -origin;
+    let a = length_squared(direction);
+    let h = dot(direction, oc);
+    let c = length_squared(oc) - radius * radius;
+
+    let discriminant = h * h - a * c;
+    if discriminant < 0 {
+        return Interval_init_empty();
+    }
+
+    let sqrt_discriminant = sqrt(discriminant);
+    return Interval_init_2f((h - sqrt_discriminant) / a, (h + sqrt_discriminant) / a);
 }
 
 fn Sphere_hit(
@@ -735,24 +1017,19 @@ fn Sphere_hit(
     interval: ptr<function, Interval>,
     hit_record: ptr<function, HitRecord>,
 ) -> bool {
-    let sphere: ptr<storage, Sphere, read> = &spheres[id];
-
-    let oc = (*sphere).center - (*ray).origin;
-    let a = length_squared((*ray).direction);
-    let h = dot((*ray).direction, oc);
-    let c = length_squared(oc) - (*sphere).radius * (*sphere).radius;
+    let sphere = Sphere_load(id);
 
-    let discriminant = h * h - a * c;
-    if discriminant < 0 {
-        return false;
-    }
+    // 在局部坐标中与球面求交，t 在两个坐标系中相同
+    let origin = sphere.inverse_transform * ((*ray).origin - sphere.center);
+    let direction = sphere.inverse_transform * (*ray).direction;
 
-    let sqrt_discriminant = sqrt(discriminant);
+    let span = Sphere_span(origin, direction, sphere.radius);
 
     // Find the nearest root that lies in the acceptable range.
-    var root = (h - sqrt_discriminant) / a;
+    // 不相交时 span 为空区间，两个端点都不在 interval 中
+    var root = span.min;
     if !Interval_surrounds(interval, root) {
-        root = (h + sqrt_discriminant) / a;
+        root = span.max;
         if !Interval_surrounds(interval, root) {
             return false;
         }
@@ -762,21 +1039,44 @@ fn Sphere_hit(
     (*hit_record).ray_t = root;
     (*hit_record).position = Ray_at(ray, root);
 
-    let outward_normal = ((*hit_record).position - (*sphere).center) / (*sphere).radius;
-    // let outward_normal = normalize((*hit_record).position - (*sphere).center);
+    // 法线按逆转置变换到世界坐标
+    let local_normal = (origin + root * direction) / sphere.radius;
+    let outward_normal = normalize(transpose(sphere.inverse_transform) * local_normal);
     HitRecord_set_face_normal(hit_record, ray, outward_normal);
-    (*hit_record).uv = Sphere_uv(outward_normal);
-    (*hit_record).material_id = (*sphere).material_id;
-    (*hit_record).material_type = (*sphere).material_type;
+    (*hit_record).uv = Sphere_uv(local_normal);
+    Sphere_set_tangent_frame(hit_record, sphere.transform, local_normal, outward_normal);
+    (*hit_record).material_id = sphere.material_id;
+    (*hit_record).material_type = sphere.material_type;
 
     return true;
 }
 
-fn Sphere_uv(position: vec3f) -> vec2f {
-    // p: a given point on the sphere of radius one, centered at the origin.
-    // u: returned value [0,1] of angle around the Y axis from X=-1.
-    // v: returned value [0,1] of angle from Y=-1 to Y=+1.
-    //     <1 0 0> yields <0.50 0.50>       <-1  0  0> yields <0.00 0.50>
+// 切线沿 u 增加的方向（绕 Y 轴），副切线沿 v 增加的方向（从 Y=-1 到 Y=+1）。两极退化时任取一个方向。
+// 先在局部坐标中求出，再变换到世界坐标并与法线正交化
+fn Sphere_set_tangent_frame(
+    hit_record: ptr<function, HitRecord>,
+    transform: mat3x3f,
+    local_normal: vec3f,
+    outward_normal: vec3f,
+) {
+    var local_tangent = vec3f(local_normal.z, 0.0, -local_normal.x);
+    if length_squared(local_tangent) < ZERO_TOLERANCE {
+        local_tangent = VEC3F_UNIT_X;
+    }
+    let tangent = transform * local_tangent;
+    (*hit_record).tangent = normalize(tangent - outward_normal * dot(outward_normal, tangent));
+    (*hit_record).bitangent = cross(outward_normal, (*hit_record).tangent);
+    // 镜像变换会翻转手性，副切线仍要沿 v 增加的方向
+    if dot((*hit_record).bitangent, transform * cross(local_normal, local_tangent)) < 0 {
+        (*hit_record).bitangent = -(*hit_record).bitangent;
+    }
+}
+
+fn Sphere_uv(position: vec3f) -> vec2f {
+    // p: a given point on the sphere of radius one, centered at the origin.
+    // u: returned value [0,1] of angle around the Y axis from X=-1.
+    // v: returned value [0,1] of angle from Y=-1 to Y=+1.
+    //     <1 0 0> yields <0.50 0.50>       <-1  0  0> yields <0.00 0.50>
     //     <0 1 0> yields <0.50 1.00>       < 0 -1  0> yields <0.50 0.00>
     //     <0 0 1> yields <0.25 0.50>       < 0  0 -1> yields <0.75 0.50>
     let theta = acos(-position.y);
@@ -784,22 +1084,24 @@ fn Sphere_uv(position: vec3f) -> vec2f {
     return vec2f(phi / (2 * PI), theta / PI);
 }
 
+// 对包住椭球的球面所张的圆锥均匀采样，方向不一定击中椭球，pdf 也按圆锥计算
 fn Sphere_pdf_value(
     id: u32,
     ray: ptr<function, Ray>,
 ) -> f32 {
-    let sphere = &spheres[id];
+    let sphere = Sphere_load(id);
 
     // This method only works for stationary spheres.
-    var hit_record: HitRecord;
-    var interval = Interval_init_2f(0.001, MAX);
-
-    if !Sphere_hit(id, ray, &interval, &hit_record) {
+    let oc = sphere.center - (*ray).origin;
+    let a = length_squared((*ray).direction);
+    let h = dot((*ray).direction, oc);
+    let c = length_squared(oc) - sphere.bounding_radius * sphere.bounding_radius;
+    let discriminant = h * h - a * c;
+    if discriminant < 0 || (h + sqrt(discriminant)) / a <= 0.001 {
         return 0.0;
     }
 
-    let cp = (*sphere).center - (*ray).origin;
-    let cos_theta_max = sqrt(1 - pow((*sphere).radius, 2.0)  / length_squared(cp));
+    let cos_theta_max = sqrt(1 - pow(sphere.bounding_radius, 2.0) / length_squared(oc));
 
     let solid_angle = 2 * PI * (1 - cos_theta_max);
     return 1.0 / solid_angle;
@@ -807,21 +1109,24 @@ fn Sphere_pdf_value(
 
 fn Sphere_random(
     id: u32,
-    origin: ptr<function, vec3f>
+    origin: ptr<function, vec3f>,
+    u: vec2f,
 ) -> vec3f {
-    let sphere = &spheres[id];
-    var direction = (*sphere).center - *origin;
+    let sphere = Sphere_load(id);
+    var direction = sphere.center - *origin;
     let distance_squared = length_squared(direction);
-    return rotation_matrix(VEC3F_UNIT_Y, normalize(direction)) * (random_to_sphere((*sphere).radius, distance_squared));
+    return rotation_matrix(VEC3F_UNIT_Y, normalize(direction))
+        * random_to_sphere(sphere.bounding_radius, distance_squared, u);
 }
 
 // Ray Tracing: The Rest of Your Life, p80
 fn random_to_sphere(
     radius: f32,
-    distance_squared: f32
+    distance_squared: f32,
+    u: vec2f,
 ) -> vec3f {
-    let xi1 = randomf();
-    let xi2 = randomf();
+    let xi1 = u.x;
+    let xi2 = u.y;
 
     let cos_theta_max = sqrt(1 - radius * radius / distance_squared);
     let y = 1 + xi2 * (cos_theta_max - 1);
@@ -832,6 +1137,1476 @@ fn random_to_sphere(
     return vec3f(x, y, z);
 }
 
+/*------------------------------------------- Shape ---------------------------------------------*/
+
+// 以原点为中心的解析形状经过仿射变换得到，见 ShapeData。光线变换到局部坐标中求交，t 在两个坐标系中相同
+const SHAPE_DISK = 0u;
+const SHAPE_CYLINDER = 1u;
+const SHAPE_CONE = 2u;
+const SHAPE_TORUS = 3u;
+const SHAPE_BOX = 4u;
+
+const SHAPE_MAX_HITS = 4; // 一条光线与形状最多的交点数，环面最多有 4 个
+const SHAPE_TORUS_STEPS = 256;
+
+struct Shape {
+    kind: u32,
+    material_type: u32,
+    material_id: u32,
+    area: f32, // 局部坐标中的表面积
+    parameters: vec4f, // 局部坐标中的尺寸：圆盘的半径；圆柱、圆锥的半径和半高；环面的两个半径；长方体的半边长
+    center: vec3f,
+    determinant: f32, // transform 行列式的绝对值
+    transform: mat3x3f, // 局部坐标到世界坐标的旋转和缩放
+    inverse_transform: mat3x3f,
+    motion: Motion,
+}
+
+fn Shape_load(id: u32) -> Shape {
+    let offset = Primitive_offset(PRIMITIVE_SHAPE, id);
+    return Shape(
+        primitive_u32(offset),
+        primitive_u32(offset + 1),
+        primitive_u32(offset + 2),
+        primitive_f32(offset + 3),
+        primitive_vec4f(offset + 4),
+        primitive_vec3f(offset + 8),
+        primitive_f32(offset + 11),
+        primitive_mat3x3f(offset + 12),
+        primitive_mat3x3f(offset + 24),
+        Motion_load(offset + 36),
+    );
+}
+
+// 局部坐标中的交点
+struct ShapeHit {
+    t: f32,
+    position: vec3f,
+    normal: vec3f, // 朝外的单位法线
+    uv: vec2f,
+    dpdu: vec3f,
+    dpdv: vec3f,
+}
+
+fn Shape_hit(
+    id: u32,
+    ray: ptr<function, Ray>,
+    interval: ptr<function, Interval>,
+    hit_record: ptr<function, HitRecord>,
+) -> bool {
+    let shape = Shape_load(id);
+
+    var shape_hit: ShapeHit;
+    if !Shape_object_hit(id, ray, interval, &shape_hit) {
+        return false;
+    }
+
+    (*hit_record).hit = true;
+    (*hit_record).ray_t = shape_hit.t;
+    (*hit_record).position = Ray_at(ray, shape_hit.t);
+
+    // 法线按逆转置变换到世界坐标
+    let outward_normal = normalize(transpose(shape.inverse_transform) * shape_hit.normal);
+    HitRecord_set_face_normal(hit_record, ray, outward_normal);
+    (*hit_record).uv = shape_hit.uv;
+    Shape_set_tangent_frame(
+        hit_record,
+        outward_normal,
+        shape.transform * shape_hit.dpdu,
+        shape.transform * shape_hit.dpdv,
+    );
+    (*hit_record).material_id = shape.material_id;
+    (*hit_record).material_type = shape.material_type;
+
+    return true;
+}
+
+fn Shape_object_hit(
+    id: u32,
+    ray: ptr<function, Ray>,
+    interval: ptr<function, Interval>,
+    shape_hit: ptr<function, ShapeHit>,
+) -> bool {
+    let shape = Shape_load(id);
+    let origin = shape.inverse_transform * ((*ray).origin - shape.center);
+    let direction = shape.inverse_transform * (*ray).direction;
+    let parameters = shape.parameters;
+
+    switch (shape.kind) {
+        case SHAPE_DISK: {
+            return Shape_disk_hit(parameters, origin, direction, interval, shape_hit);
+        }
+        case SHAPE_CYLINDER: {
+            return Shape_cylinder_hit(parameters, origin, direction, interval, shape_hit);
+        }
+        case SHAPE_CONE: {
+            return Shape_cone_hit(parameters, origin, direction, interval, shape_hit);
+        }
+        case SHAPE_TORUS: {
+            return Shape_torus_hit(parameters, origin, direction, interval, shape_hit);
+        }
+        case SHAPE_BOX: {
+            return Shape_box_hit(parameters, origin, direction, interval, shape_hit);
+        }
+        default: {
+            return false;
+        }
+    }
+}
+
+// 切线沿 u 增加的方向，副切线沿 v 增加的方向，都与法线正交。dpdu 退化时（例如圆盘中心）由 dpdv 或法线构造
+fn Shape_set_tangent_frame(
+    hit_record: ptr<function, HitRecord>,
+    outward_normal: vec3f,
+    dpdu: vec3f,
+    dpdv: vec3f,
+) {
+    var tangent = dpdu - outward_normal * dot(outward_normal, dpdu);
+    if length_squared(tangent) < ZERO_TOLERANCE {
+        tangent = cross(dpdv, outward_normal);
+    }
+    if length_squared(tangent) < ZERO_TOLERANCE {
+        tangent = cross(select(VEC3F_UNIT_X, VEC3F_UNIT_Y, abs(outward_normal.x) > 0.9), outward_normal);
+    }
+    tangent = normalize(tangent);
+
+    var bitangent = cross(outward_normal, tangent);
+    if dot(bitangent, dpdv) < 0 {
+        bitangent = -bitangent;
+    }
+    (*hit_record).tangent = tangent;
+    (*hit_record).bitangent = bitangent;
+}
+
+// 绕 Y 轴的角度映射到 [0, 1]，与 Sphere_uv 的 u 一致
+fn Shape_azimuth(p: vec3f) -> f32 {
+    return (atan2(-p.z, p.x) + PI) / (2 * PI);
+}
+
+// 沿 Shape_azimuth 增加的方向，长度等于到 Y 轴的距离
+fn Shape_azimuth_tangent(p: vec3f) -> vec3f {
+    return vec3f(p.z, 0.0, -p.x);
+}
+
+// 高度为 y、法线为 (0, side, 0) 的圆形盖子上的交点，v 是到中心的距离与半径之比
+fn Shape_cap_hit(t: f32, p: vec3f, radius: f32, y: f32, side: f32) -> ShapeHit {
+    let position = vec3f(p.x, y, p.z);
+    let rho = length(position.xz);
+    var radial = VEC3F_ZEROS;
+    if rho > ZERO_TOLERANCE {
+        radial = vec3f(position.x, 0.0, position.z) / rho;
+    }
+    return ShapeHit(
+        t,
+        position,
+        vec3f(0.0, side, 0.0),
+        vec2f(Shape_azimuth(position), rho / radius),
+        Shape_azimuth_tangent(position),
+        radial,
+    );
+}
+
+fn Shape_disk_hit(
+    parameters: vec4f,
+    origin: vec3f,
+    direction: vec3f,
+    interval: ptr<function, Interval>,
+    shape_hit: ptr<function, ShapeHit>,
+) -> bool {
+    let radius = parameters.x;
+    if abs(direction.y) < ZERO_TOLERANCE {
+        return false;
+    }
+
+    let t = -origin.y / direction.y;
+    let p = origin + t * direction;
+    if !Interval_surrounds(interval, t) || dot(p.xz, p.xz) > radius * radius {
+        return false;
+    }
+    *shape_hit = Shape_cap_hit(t, p, radius, 0.0, 1.0);
+    return true;
+}
+
+fn Shape_cylinder_hit(
+    parameters: vec4f,
+    origin: vec3f,
+    direction: vec3f,
+    interval: ptr<function, Interval>,
+    shape_hit: ptr<function, ShapeHit>,
+) -> bool {
+    let radius = parameters.x;
+    let half_height = parameters.y;
+    var closest = (*interval).max;
+
+    // 侧面 x² + z² = r²
+    let a = dot(direction.xz, direction.xz);
+    let h = -dot(origin.xz, direction.xz);
+    let c = dot(origin.xz, origin.xz) - radius * radius;
+    let discriminant = h * h - a * c;
+    if a > ZERO_TOLERANCE && discriminant >= 0 {
+        let sqrt_discriminant = sqrt(discriminant);
+        for (var i = 0; i < 2; i++) {
+            let t = (h + select(-sqrt_discriminant, sqrt_discriminant, i == 1)) / a;
+            let p = origin + t * direction;
+            if t > (*interval).min && t < closest && abs(p.y) <= half_height {
+                closest = t;
+                *shape_hit = ShapeHit(
+                    t,
+                    p,
+                    vec3f(p.x, 0.0, p.z) / radius,
+                    vec2f(Shape_azimuth(p), (p.y + half_height) / (2 * half_height)),
+                    Shape_azimuth_tangent(p),
+                    VEC3F_UNIT_Y,
+                );
+            }
+        }
+    }
+
+    // 上下两个盖子
+    if abs(direction.y) > ZERO_TOLERANCE {
+        for (var i = 0; i < 2; i++) {
+            let side = select(-1.0, 1.0, i == 1);
+            let t = (side * half_height - origin.y) / direction.y;
+            let p = origin + t * direction;
+            if t > (*interval).min && t < closest && dot(p.xz, p.xz) <= radius * radius {
+                closest = t;
+                *shape_hit = Shape_cap_hit(t, p, radius, side * half_height, side);
+            }
+        }
+    }
+
+    return closest < (*interval).max;
+}
+
+fn Shape_cone_hit(
+    parameters: vec4f,
+    origin: vec3f,
+    direction: vec3f,
+    interval: ptr<function, Interval>,
+    shape_hit: ptr<function, ShapeHit>,
+) -> bool {
+    let radius = parameters.x;
+    let half_height = parameters.y;
+    var closest = (*interval).max;
+
+    // 侧面 x² + z² = k²(h - y)²，k 是半径与高之比，顶点在 y = h
+    let k = radius / (2 * half_height);
+    let w = half_height - origin.y;
+    let a = dot(direction.xz, direction.xz) - k * k * direction.y * direction.y;
+    let b = dot(origin.xz, direction.xz) + k * k * w * direction.y;
+    let c = dot(origin.xz, origin.xz) - k * k * w * w;
+
+    // a t² + 2b t + c = 0，光线平行于母线时只有一个根
+    var roots = array<f32, 2>(MAX, MAX);
+    if abs(a) > ZERO_TOLERANCE {
+        let discriminant = b * b - a * c;
+        if discriminant >= 0 {
+            let sqrt_discriminant = sqrt(discriminant);
+            roots = array<f32, 2>((-b - sqrt_discriminant) / a, (-b + sqrt_discriminant) / a);
+        }
+    } else if abs(b) > ZERO_TOLERANCE {
+        roots[0] = -c / (2 * b);
+    }
+
+    for (var i = 0; i < 2; i++) {
+        let t = roots[i];
+        let p = origin + t * direction;
+        // y 不超过顶点时排除另一半圆锥
+        if t > (*interval).min && t < closest && abs(p.y) <= half_height {
+            closest = t;
+            let rho = length(p.xz);
+            var normal = VEC3F_UNIT_Y;
+            var dpdv = VEC3F_UNIT_Y;
+            if rho > ZERO_TOLERANCE {
+                normal = normalize(vec3f(p.x, k * rho, p.z));
+                dpdv = vec3f(-k * p.x / rho, 1.0, -k * p.z / rho);
+            }
+            *shape_hit = ShapeHit(
+                t,
+                p,
+                normal,
+                vec2f(Shape_azimuth(p), (p.y + half_height) / (2 * half_height)),
+                Shape_azimuth_tangent(p),
+                dpdv,
+            );
+        }
+    }
+
+    // 底面
+    if abs(direction.y) > ZERO_TOLERANCE {
+        let t = (-half_height - origin.y) / direction.y;
+        let p = origin + t * direction;
+        if t > (*interval).min && t < closest && dot(p.xz, p.xz) <= radius * radius {
+            closest = t;
+            *shape_hit = Shape_cap_hit(t, p, radius, -half_height, -1.0);
+        }
+    }
+
+    return closest < (*interval).max;
+}
+
+// 到环面的有符号距离
+fn Shape_torus_distance(p: vec3f, major_radius: f32, minor_radius: f32) -> f32 {
+    return length(vec2f(length(p.xz) - major_radius, p.y)) - minor_radius;
+}
+
+// 四次方程在 f32 下误差太大，在包围球内用距离场步进求交。
+// 从表面上出发时（例如透明度测试失败后继续求交）先离开表面，避免重复找到同一个交点
+fn Shape_torus_hit(
+    parameters: vec4f,
+    origin: vec3f,
+    direction: vec3f,
+    interval: ptr<function, Interval>,
+    shape_hit: ptr<function, ShapeHit>,
+) -> bool {
+    let major_radius = parameters.x;
+    let minor_radius = parameters.y;
+
+    // s 是沿单位方向的距离，s = t * scale
+    let scale = length(direction);
+    if scale < ZERO_TOLERANCE {
+        return false;
+    }
+    let unit_direction = direction / scale;
+
+    let bound = major_radius + minor_radius;
+    let b = dot(origin, unit_direction);
+    let discriminant = b * b - (length_squared(origin) - bound * bound);
+    if discriminant < 0 {
+        return false;
+    }
+    let sqrt_discriminant = sqrt(discriminant);
+    let start = (*interval).min * scale;
+    let end = min(-b + sqrt_discriminant, (*interval).max * scale);
+
+    let epsilon = 1e-4 * bound;
+    var s = max(-b - sqrt_discriminant, start);
+    // 从区间起点出发时，起点所在的表面是上一次的交点；外赤道上的点也在包围球上，从包围球出发时不能跳过
+    var left_surface = s > start
+        || abs(Shape_torus_distance(origin + s * unit_direction, major_radius, minor_radius)) >= epsilon;
+    for (var i = 0; i < SHAPE_TORUS_STEPS; i++) {
+        if s > end {
+            return false;
+        }
+
+        let p = origin + s * unit_direction;
+        let distance = abs(Shape_torus_distance(p, major_radius, minor_radius));
+        if distance >= epsilon {
+            left_surface = true;
+        } else if left_surface {
+            let rho = length(p.xz);
+            let normal = normalize(p - vec3f(p.x, 0.0, p.z) * (major_radius / rho));
+            let radial = vec3f(p.x, 0.0, p.z) / rho;
+            let cos_theta = dot(normal, radial);
+            *shape_hit = ShapeHit(
+                s / scale,
+                p,
+                normal,
+                vec2f(Shape_azimuth(p), (atan2(normal.y, cos_theta) + PI) / (2 * PI)),
+                Shape_azimuth_tangent(p),
+                -normal.y * radial + cos_theta * VEC3F_UNIT_Y,
+            );
+            return true;
+        }
+        s += max(distance, epsilon);
+    }
+    return false;
+}
+
+// 垂直于 axis 的面上 u、v 对应的轴
+fn Shape_box_face_axes(axis: i32) -> vec2i {
+    switch (axis) {
+        case 0: {
+            return vec2i(2, 1);
+        }
+        case 1: {
+            return vec2i(0, 2);
+        }
+        default: {
+            return vec2i(0, 1);
+        }
+    }
+}
+
+fn Shape_axis(axis: i32) -> vec3f {
+    var e = VEC3F_ZEROS;
+    e[axis] = 1.0;
+    return e;
+}
+
+fn Shape_box_hit(
+    parameters: vec4f,
+    origin: vec3f,
+    direction: vec3f,
+    interval: ptr<function, Interval>,
+    shape_hit: ptr<function, ShapeHit>,
+) -> bool {
+    let half_size = parameters.xyz;
+    let span = Shape_box_span(half_size, origin, direction);
+
+    // 起点在长方体内时取离开的面
+    var t = span.min;
+    if !Interval_surrounds(interval, t) {
+        t = span.max;
+        if !Interval_surrounds(interval, t) {
+            return false;
+        }
+    }
+
+    var p = origin + t * direction;
+    let axis = Shape_box_face(half_size, p);
+    let side = sign(p[axis]);
+    p[axis] = side * half_size[axis];
+    let face = Shape_box_face_axes(axis);
+    *shape_hit = ShapeHit(
+        t,
+        p,
+        side * Shape_axis(axis),
+        (vec2f(p[face.x], p[face.y]) / vec2f(half_size[face.x], half_size[face.y]) + 1.0) / 2.0,
+        Shape_axis(face.x),
+        Shape_axis(face.y),
+    );
+    return true;
+}
+
+// slab 方法求光线在以原点为中心的长方体内的区间，不相交时返回空区间
+fn Shape_box_span(half_size: vec3f, origin: vec3f, direction: vec3f) -> Interval {
+    var near = MIN;
+    var far = MAX;
+    for (var axis = 0; axis < 3; axis++) {
+        if abs(direction[axis]) < ZERO_TOLERANCE {
+            if abs(origin[axis]) > half_size[axis] {
+                return Interval_init_empty();
+            }
+            continue;
+        }
+        let t0 = (-half_size[axis] - origin[axis]) / direction[axis];
+        let t1 = (half_size[axis] - origin[axis]) / direction[axis];
+        near = max(near, min(t0, t1));
+        far = min(far, max(t0, t1));
+    }
+    if near > far {
+        return Interval_init_empty();
+    }
+    return Interval_init_2f(near, far);
+}
+
+// 长方体表面上的点所在的面：按半边长归一化后坐标绝对值最大的轴
+fn Shape_box_face(half_size: vec3f, p: vec3f) -> i32 {
+    let q = abs(p) / half_size;
+    if q.x >= q.y && q.x >= q.z {
+        return 0;
+    }
+    return select(2, 1, q.y >= q.z);
+}
+
+// 光线在以原点为中心、轴沿 y 的实心圆柱内的区间，即无限长圆柱内的区间与两个盖子之间的区间的交集
+fn Shape_cylinder_span(radius: f32, half_height: f32, origin: vec3f, direction: vec3f) -> Interval {
+    var near = MIN;
+    var far = MAX;
+
+    let a = dot(direction.xz, direction.xz);
+    let h = -dot(origin.xz, direction.xz);
+    let c = dot(origin.xz, origin.xz) - radius * radius;
+    if a > ZERO_TOLERANCE {
+        let discriminant = h * h - a * c;
+        if discriminant < 0 {
+            return Interval_init_empty();
+        }
+        let sqrt_discriminant = sqrt(discriminant);
+        near = (h - sqrt_discriminant) / a;
+        far = (h + sqrt_discriminant) / a;
+    } else if c > 0 {
+        return Interval_init_empty();
+    }
+
+    if abs(direction.y) > ZERO_TOLERANCE {
+        let t0 = (-half_height - origin.y) / direction.y;
+        let t1 = (half_height - origin.y) / direction.y;
+        near = max(near, min(t0, t1));
+        far = min(far, max(t0, t1));
+    } else if abs(origin.y) > half_height {
+        return Interval_init_empty();
+    }
+
+    if near > far {
+        return Interval_init_empty();
+    }
+    return Interval_init_2f(near, far);
+}
+
+// 在局部坐标中按面积均匀采样（环面按 uv 均匀采样），方向的概率密度是光线上所有交点处密度之和：
+// 世界坐标中的面积元是局部坐标中的 |det M| |M⁻ᵀ n| 倍，再按距离和夹角换算成立体角
+fn Shape_pdf_value(
+    id: u32,
+    ray: ptr<function, Ray>,
+) -> f32 {
+    let shape = Shape_load(id);
+
+    var interval = Interval_init_2f(0.001, MAX);
+    var pdf = 0.0;
+    for (var i = 0; i < SHAPE_MAX_HITS; i++) {
+        var shape_hit: ShapeHit;
+        if !Shape_object_hit(id, ray, &interval, &shape_hit) {
+            break;
+        }
+
+        let scaled_normal = transpose(shape.inverse_transform) * shape_hit.normal;
+        let area_pdf = Shape_object_area_pdf(id, shape_hit.position) / (shape.determinant * length(scaled_normal));
+        let distance_squared = shape_hit.t * shape_hit.t * length_squared((*ray).direction);
+        let cosine = abs(dot((*ray).direction, normalize(scaled_normal))) / length((*ray).direction);
+        pdf += area_pdf * distance_squared / cosine;
+        interval.min = shape_hit.t;
+    }
+    return pdf;
+}
+
+// 局部坐标中 p 处单位面积的概率密度
+fn Shape_object_area_pdf(id: u32, p: vec3f) -> f32 {
+    let shape = Shape_load(id);
+    if shape.kind == SHAPE_TORUS {
+        // uv 均匀时密度与到 Y 轴的距离成反比
+        return 1.0 / (4 * PI * PI * shape.parameters.y * length(p.xz));
+    }
+    return 1.0 / shape.area;
+}
+
+fn Shape_random(
+    id: u32,
+    origin: ptr<function, vec3f>,
+    u: vec2f,
+) -> vec3f {
+    let shape = Shape_load(id);
+    let p = shape.center + shape.transform * Shape_object_sample(id, u);
+    return normalize(p - *origin);
+}
+
+// 局部坐标中的采样点，概率密度见 Shape_object_area_pdf。由多个面组成的形状先按面积用 u.x 选择一个面，
+// 再把 u.x 重新映射到 [0, 1)
+fn Shape_object_sample(id: u32, u: vec2f) -> vec3f {
+    let shape = Shape_load(id);
+    let parameters = shape.parameters;
+    let s = u.x * shape.area;
+
+    switch (shape.kind) {
+        case SHAPE_DISK: {
+            return Shape_disk_sample(parameters.x, 0.0, u);
+        }
+        case SHAPE_CYLINDER: {
+            let radius = parameters.x;
+            let half_height = parameters.y;
+            let side = 4 * PI * radius * half_height;
+            if s < side {
+                let phi = 2 * PI * u.y;
+                return vec3f(radius * cos(phi), half_height * (2 * s / side - 1), radius * sin(phi));
+            }
+            let cap = (s - side) / (PI * radius * radius);
+            return Shape_disk_sample(radius, select(-half_height, half_height, cap >= 1.0), vec2f(fract(cap), u.y));
+        }
+        case SHAPE_CONE: {
+            let radius = parameters.x;
+            let half_height = parameters.y;
+            let side = PI * radius * sqrt(radius * radius + 4 * half_height * half_height);
+            if s < side {
+                // 到顶点的距离的概率密度与距离成正比
+                let distance = sqrt(s / side);
+                let phi = 2 * PI * u.y;
+                return vec3f(radius * distance * cos(phi), half_height * (1 - 2 * distance), radius * distance * sin(phi));
+            }
+            return Shape_disk_sample(radius, -half_height, vec2f((s - side) / (PI * radius * radius), u.y));
+        }
+        case SHAPE_TORUS: {
+            let phi = 2 * PI * u.x;
+            let theta = 2 * PI * u.y;
+            let rho = parameters.x + parameters.y * cos(theta);
+            return vec3f(rho * cos(phi), parameters.y * sin(theta), rho * sin(phi));
+        }
+        case SHAPE_BOX: {
+            let half_size = parameters.xyz;
+            // 垂直于 X、Y、Z 的每个面的面积
+            let face_areas = 4 * vec3f(half_size.y * half_size.z, half_size.x * half_size.z, half_size.x * half_size.y);
+            var remaining = s;
+            var axis = 0;
+            for (; axis < 2; axis++) {
+                if remaining < 2 * face_areas[axis] {
+                    break;
+                }
+                remaining -= 2 * face_areas[axis];
+            }
+            let face = remaining / face_areas[axis];
+            let axes = Shape_box_face_axes(axis);
+            var p: vec3f;
+            p[axis] = select(-half_size[axis], half_size[axis], face >= 1.0);
+            p[axes.x] = (2 * fract(face) - 1) * half_size[axes.x];
+            p[axes.y] = (2 * u.y - 1) * half_size[axes.y];
+            return p;
+        }
+        default: {
+            return VEC3F_ZEROS;
+        }
+    }
+}
+
+// 高度为 y 的圆上按面积均匀采样
+fn Shape_disk_sample(radius: f32, y: f32, u: vec2f) -> vec3f {
+    let rho = radius * sqrt(u.x);
+    let phi = 2 * PI * u.y;
+    return vec3f(rho * cos(phi), y, rho * sin(phi));
+}
+
+/*-------------------------------------------- SDF ----------------------------------------------*/
+
+// 有符号距离场的节点树按后序排列，见 SdfData。光线变换到 SDF 坐标中，在包围盒内用球面追踪求交，t 在两个坐标系中相同
+const SDF_SPHERE = 0u;
+const SDF_BOX = 1u;
+const SDF_ROUNDED_BOX = 2u;
+const SDF_TORUS = 3u;
+const SDF_CAPSULE = 4u;
+const SDF_UNION = 5u;
+const SDF_SUBTRACTION = 6u;
+const SDF_INTERSECTION = 7u;
+
+const SDF_MAX_NODES = 16;
+const SDF_HEADER_WORDS = 48u; // Sdf 占的字数，之后是 SDF_MAX_NODES 个 SdfNode
+const SDF_NODE_WORDS = 24u;
+const SDF_STEPS = 256;
+
+struct SdfNode {
+    kind: u32,
+    smoothness: f32, // 平滑布尔运算的过渡宽度，为 0 时是普通的布尔运算
+    parameters: vec4f, // 叶子的尺寸：球、胶囊的半径和半高；长方体的半边长，圆角长方体在 w 中还有圆角半径；环面的两个半径
+    center: vec3f, // 叶子在 SDF 坐标中的位置
+    inverse_rotation: mat3x3f, // SDF 坐标到叶子局部坐标的旋转
+}
+
+struct Sdf {
+    center: vec3f,
+    material_type: u32,
+    bounds_min: vec3f, // SDF 坐标中的包围盒
+    material_id: u32,
+    bounds_max: vec3f,
+    node_count: u32,
+    transform: mat3x3f, // SDF 坐标到世界坐标的旋转和缩放
+    inverse_transform: mat3x3f,
+    motion: Motion,
+}
+
+fn Sdf_load(id: u32) -> Sdf {
+    let offset = Primitive_offset(PRIMITIVE_SDF, id);
+    return Sdf(
+        primitive_vec3f(offset),
+        primitive_u32(offset + 3),
+        primitive_vec3f(offset + 4),
+        primitive_u32(offset + 7),
+        primitive_vec3f(offset + 8),
+        primitive_u32(offset + 11),
+        primitive_mat3x3f(offset + 12),
+        primitive_mat3x3f(offset + 24),
+        Motion_load(offset + 36),
+    );
+}
+
+// 第 i 个节点，前 node_count 个有效
+fn Sdf_node(id: u32, i: u32) -> SdfNode {
+    let offset = Primitive_offset(PRIMITIVE_SDF, id) + SDF_HEADER_WORDS + i * SDF_NODE_WORDS;
+    return SdfNode(
+        primitive_u32(offset),
+        primitive_f32(offset + 1),
+        primitive_vec4f(offset + 4),
+        primitive_vec3f(offset + 8),
+        primitive_mat3x3f(offset + 12),
+    );
+}
+
+fn Sdf_hit(
+    id: u32,
+    ray: ptr<function, Ray>,
+    interval: ptr<function, Interval>,
+    hit_record: ptr<function, HitRecord>,
+) -> bool {
+    let sdf = Sdf_load(id);
+    let origin = sdf.inverse_transform * ((*ray).origin - sdf.center);
+    let direction = sdf.inverse_transform * (*ray).direction;
+
+    // s 是沿单位方向的距离，s = t * scale
+    let scale = length(direction);
+    if scale < ZERO_TOLERANCE {
+        return false;
+    }
+    let unit_direction = direction / scale;
+
+    // 只在光线穿过包围盒的一段中步进
+    let range = Sdf_bounds_range(id, origin, unit_direction);
+    let start = (*interval).min * scale;
+    let end = min(range.y, (*interval).max * scale);
+
+    let epsilon = 1e-4 * length(sdf.bounds_max - sdf.bounds_min);
+    var s = max(range.x, start);
+    // 从区间起点出发时，起点所在的表面是上一次的交点
+    var left_surface = s > start || abs(Sdf_distance(id, origin + s * unit_direction)) >= epsilon;
+    for (var i = 0; i < SDF_STEPS; i++) {
+        if s > end {
+            return false;
+        }
+
+        let p = origin + s * unit_direction;
+        let distance = abs(Sdf_distance(id, p));
+        if distance >= epsilon {
+            left_surface = true;
+        } else if left_surface {
+            Sdf_set_hit_record(id, ray, s / scale, p, epsilon, hit_record);
+            return true;
+        }
+        s += max(distance, epsilon);
+    }
+    return false;
+}
+
+fn Sdf_set_hit_record(
+    id: u32,
+    ray: ptr<function, Ray>,
+    t: f32,
+    p: vec3f,
+    epsilon: f32,
+    hit_record: ptr<function, HitRecord>,
+) {
+    let sdf = Sdf_load(id);
+
+    (*hit_record).hit = true;
+    (*hit_record).ray_t = t;
+    (*hit_record).position = Ray_at(ray, t);
+
+    // 法线按逆转置变换到世界坐标
+    let outward_normal = normalize(transpose(sdf.inverse_transform) * Sdf_normal(id, p, epsilon));
+    HitRecord_set_face_normal(hit_record, ray, outward_normal);
+
+    // 距离场没有自然的参数化，以包围盒中心为球心做球面投影
+    let q = p - (sdf.bounds_min + sdf.bounds_max) / 2;
+    (*hit_record).uv = Sphere_uv(normalize(q));
+    Shape_set_tangent_frame(
+        hit_record,
+        outward_normal,
+        sdf.transform * Shape_azimuth_tangent(q),
+        sdf.transform * VEC3F_UNIT_Y,
+    );
+    (*hit_record).material_id = sdf.material_id;
+    (*hit_record).material_type = sdf.material_type;
+}
+
+// 光线与 SDF 坐标中的包围盒相交的一段，不相交时 x > y
+fn Sdf_bounds_range(id: u32, origin: vec3f, direction: vec3f) -> vec2f {
+    let sdf = Sdf_load(id);
+    var near = MIN;
+    var far = MAX;
+    for (var axis = 0; axis < 3; axis++) {
+        if abs(direction[axis]) < ZERO_TOLERANCE {
+            if origin[axis] < sdf.bounds_min[axis] || origin[axis] > sdf.bounds_max[axis] {
+                return vec2f(MAX, MIN);
+            }
+            continue;
+        }
+        let t0 = (sdf.bounds_min[axis] - origin[axis]) / direction[axis];
+        let t1 = (sdf.bounds_max[axis] - origin[axis]) / direction[axis];
+        near = max(near, min(t0, t1));
+        far = min(far, max(t0, t1));
+    }
+    return vec2f(near, far);
+}
+
+// 按后序用栈求值，叶子压入距离，布尔运算弹出两个距离再压入结果
+fn Sdf_distance(id: u32, p: vec3f) -> f32 {
+    let sdf = Sdf_load(id);
+    var stack: array<f32, SDF_MAX_NODES>;
+    var top = 0;
+    for (var i = 0u; i < sdf.node_count; i++) {
+        let node = Sdf_node(id, i);
+        if node.kind >= SDF_UNION {
+            top--;
+            stack[top - 1] = Sdf_combine(node.kind, stack[top - 1], stack[top], node.smoothness);
+        } else {
+            stack[top] = Sdf_leaf_distance(node.kind, node.parameters, node.inverse_rotation * (p - node.center));
+            top++;
+        }
+    }
+    if top == 0 {
+        return MAX;
+    }
+    return stack[0];
+}
+
+fn Sdf_leaf_distance(kind: u32, parameters: vec4f, p: vec3f) -> f32 {
+    switch (kind) {
+        case SDF_SPHERE: {
+            return length(p) - parameters.x;
+        }
+        case SDF_BOX: {
+            return Sdf_box_distance(p, parameters.xyz);
+        }
+        case SDF_ROUNDED_BOX: {
+            return Sdf_box_distance(p, parameters.xyz - parameters.w) - parameters.w;
+        }
+        case SDF_TORUS: {
+            return Shape_torus_distance(p, parameters.x, parameters.y);
+        }
+        case SDF_CAPSULE: {
+            return length(vec3f(p.x, p.y - clamp(p.y, -parameters.y, parameters.y), p.z)) - parameters.x;
+        }
+        default: {
+            return MAX;
+        }
+    }
+}
+
+fn Sdf_box_distance(p: vec3f, half_size: vec3f) -> f32 {
+    let q = abs(p) - half_size;
+    return length(max(q, VEC3F_ZEROS)) + min(max(q.x, max(q.y, q.z)), 0.0);
+}
+
+fn Sdf_combine(kind: u32, a: f32, b: f32, smoothness: f32) -> f32 {
+    switch (kind) {
+        case SDF_UNION: {
+            return Sdf_smooth_min(a, b, smoothness);
+        }
+        case SDF_SUBTRACTION: {
+            return -Sdf_smooth_min(-a, b, smoothness);
+        }
+        default: {
+            return -Sdf_smooth_min(-a, -b, smoothness);
+        }
+    }
+}
+
+// 二次多项式的平滑最小值，比 min 最多小 smoothness / 4
+fn Sdf_smooth_min(a: f32, b: f32, smoothness: f32) -> f32 {
+    if smoothness <= 0 {
+        return min(a, b);
+    }
+    let h = max(smoothness - abs(a - b), 0.0) / smoothness;
+    return min(a, b) - h * h * smoothness / 4;
+}
+
+// 中心差分求距离场的梯度
+fn Sdf_normal(id: u32, p: vec3f, h: f32) -> vec3f {
+    let dx = vec3f(h, 0.0, 0.0);
+    let dy = vec3f(0.0, h, 0.0);
+    let dz = vec3f(0.0, 0.0, h);
+    return normalize(vec3f(
+        Sdf_distance(id, p + dx) - Sdf_distance(id, p - dx),
+        Sdf_distance(id, p + dy) - Sdf_distance(id, p - dy),
+        Sdf_distance(id, p + dz) - Sdf_distance(id, p - dz),
+    ));
+}
+
+/*-------------------------------------------- CSG ----------------------------------------------*/
+
+// 构造实体几何，见 CsgData。叶子是凸体，光线在每个叶子内的部分是一个区间。
+// 按 t 从小到大依次经过各个区间的端点，更新这个叶子的内外状态后对布尔树求值，整体的内外状态改变的地方就是交点
+const CSG_SPHERE = 0u;
+const CSG_BOX = 1u;
+const CSG_CYLINDER = 2u;
+const CSG_UNION = 3u;
+const CSG_INTERSECTION = 4u;
+const CSG_DIFFERENCE = 5u;
+
+const CSG_MAX_NODES = 16;
+const CSG_HEADER_WORDS = 24u; // Csg 占的字数，之后是 CSG_MAX_NODES 个 CsgNode
+const CSG_NODE_WORDS = 36u;
+
+struct CsgNode {
+    kind: u32,
+    parameters: vec4f, // 叶子的尺寸：球的半径，长方体的半边长，圆柱的半径和半高
+    center: vec3f, // 叶子局部坐标原点在世界坐标中的位置
+    transform: mat3x3f, // 叶子局部坐标到世界坐标的线性部分
+    inverse_transform: mat3x3f,
+}
+
+struct Csg {
+    bounds_min: vec3f, // 世界坐标中的包围盒
+    material_type: u32,
+    bounds_max: vec3f,
+    material_id: u32,
+    node_count: u32,
+    motion: Motion,
+}
+
+fn Csg_load(id: u32) -> Csg {
+    let offset = Primitive_offset(PRIMITIVE_CSG, id);
+    return Csg(
+        primitive_vec3f(offset),
+        primitive_u32(offset + 3),
+        primitive_vec3f(offset + 4),
+        primitive_u32(offset + 7),
+        primitive_u32(offset + 8),
+        Motion_load(offset + 12),
+    );
+}
+
+// 第 i 个节点，后序排列，前 node_count 个有效
+fn Csg_node(id: u32, i: u32) -> CsgNode {
+    let offset = Primitive_offset(PRIMITIVE_CSG, id) + CSG_HEADER_WORDS + i * CSG_NODE_WORDS;
+    return CsgNode(
+        primitive_u32(offset),
+        primitive_vec4f(offset + 4),
+        primitive_vec3f(offset + 8),
+        primitive_mat3x3f(offset + 12),
+        primitive_mat3x3f(offset + 24),
+    );
+}
+
+fn Csg_hit(
+    id: u32,
+    ray: ptr<function, Ray>,
+    interval: ptr<function, Interval>,
+    hit_record: ptr<function, HitRecord>,
+) -> bool {
+    let csg = Csg_load(id);
+    let node_count = i32(csg.node_count);
+
+    // 每个叶子的区间和 interval 起点处的内外状态，inside 的第 i 位对应第 i 个节点
+    var spans: array<Interval, CSG_MAX_NODES>;
+    var inside = 0u;
+    for (var i = 0; i < node_count; i++) {
+        spans[i] = Interval_init_empty();
+        if Csg_node(id, u32(i)).kind < CSG_UNION {
+            spans[i] = Csg_leaf_span(id, i, ray);
+            if spans[i].min <= (*interval).min && (*interval).min < spans[i].max {
+                inside |= 1u << u32(i);
+            }
+        }
+    }
+    var was_inside = Csg_evaluate(id, inside);
+
+    // 端点按 (t, key) 排序，key = 2i 是进入第 i 个叶子，2i + 1 是离开，t 相同时先进入后离开。
+    // 起点处的端点已经计入初始状态，从 key 最大的位置开始
+    var t = (*interval).min;
+    var key = 2 * CSG_MAX_NODES;
+    for (var n = 0; n < 2 * node_count; n++) {
+        var next_t = MAX;
+        var next_key = 2 * CSG_MAX_NODES;
+        for (var i = 0; i < node_count; i++) {
+            if spans[i].min > spans[i].max {
+                continue;
+            }
+            for (var e = 0; e < 2; e++) {
+                let event_t = select(spans[i].min, spans[i].max, e == 1);
+                let event_key = 2 * i + e;
+                let after = event_t > t || (event_t == t && event_key > key);
+                let before_next = event_t < next_t || (event_t == next_t && event_key < next_key);
+                if after && before_next {
+                    next_t = event_t;
+                    next_key = event_key;
+                }
+            }
+        }
+        if next_t >= (*interval).max {
+            return false;
+        }
+
+        t = next_t;
+        key = next_key;
+        let leaf = next_key / 2;
+        let entering = next_key % 2 == 0;
+        let bit = 1u << u32(leaf);
+        inside = select(inside & ~bit, inside | bit, entering);
+        let is_inside = Csg_evaluate(id, inside);
+        if is_inside != was_inside {
+            // 从被挖去的叶子中出来时进入整体，法线与叶子的外法线相反
+            Csg_set_hit_record(id, leaf, ray, t, entering != is_inside, hit_record);
+            return true;
+        }
+        was_inside = is_inside;
+    }
+    return false;
+}
+
+// 光线在第 i 个叶子内的区间，t 在局部坐标和世界坐标中相同
+fn Csg_leaf_span(id: u32, i: i32, ray: ptr<function, Ray>) -> Interval {
+    let node = Csg_node(id, u32(i));
+    let origin = node.inverse_transform * ((*ray).origin - node.center);
+    let direction = node.inverse_transform * (*ray).direction;
+    let parameters = node.parameters;
+    switch (node.kind) {
+        case CSG_SPHERE: {
+            return Sphere_span(origin, direction, parameters.x);
+        }
+        case CSG_BOX: {
+            return Shape_box_span(parameters.xyz, origin, direction);
+        }
+        case CSG_CYLINDER: {
+            return Shape_cylinder_span(parameters.x, parameters.y, origin, direction);
+        }
+        default: {
+            return Interval_init_empty();
+        }
+    }
+}
+
+// 按后序用栈对布尔树求值，inside 给出每个叶子的内外状态
+fn Csg_evaluate(id: u32, inside: u32) -> bool {
+    let csg = Csg_load(id);
+    var stack: array<bool, CSG_MAX_NODES>;
+    var top = 0;
+    for (var i = 0u; i < csg.node_count; i++) {
+        let kind = Csg_node(id, i).kind;
+        if kind >= CSG_UNION {
+            top--;
+            let a = stack[top - 1];
+            let b = stack[top];
+            switch (kind) {
+                case CSG_UNION: {
+                    stack[top - 1] = a || b;
+                }
+                case CSG_INTERSECTION: {
+                    stack[top - 1] = a && b;
+                }
+                default: {
+                    stack[top - 1] = a && !b;
+                }
+            }
+        } else {
+            stack[top] = (inside & (1u << i)) != 0u;
+            top++;
+        }
+    }
+    return top > 0 && stack[0];
+}
+
+fn Csg_set_hit_record(
+    id: u32,
+    leaf: i32,
+    ray: ptr<function, Ray>,
+    t: f32,
+    flip: bool,
+    hit_record: ptr<function, HitRecord>,
+) {
+    let csg = Csg_load(id);
+    let node = Csg_node(id, u32(leaf));
+
+    (*hit_record).hit = true;
+    (*hit_record).ray_t = t;
+    (*hit_record).position = Ray_at(ray, t);
+
+    // 法线按逆转置变换到世界坐标
+    let p = node.inverse_transform * ((*hit_record).position - node.center);
+    let leaf_hit = Csg_leaf_hit(node.kind, node.parameters, t, p);
+    var outward_normal = normalize(transpose(node.inverse_transform) * leaf_hit.normal);
+    if flip {
+        outward_normal = -outward_normal;
+    }
+    HitRecord_set_face_normal(hit_record, ray, outward_normal);
+    (*hit_record).uv = leaf_hit.uv;
+    Shape_set_tangent_frame(
+        hit_record,
+        outward_normal,
+        node.transform * leaf_hit.dpdu,
+        node.transform * leaf_hit.dpdv,
+    );
+    (*hit_record).material_id = csg.material_id;
+    (*hit_record).material_type = csg.material_type;
+}
+
+// 叶子局部坐标中表面上的点 p 处的外法线和参数化
+fn Csg_leaf_hit(kind: u32, parameters: vec4f, t: f32, p: vec3f) -> ShapeHit {
+    switch (kind) {
+        case CSG_SPHERE: {
+            let n = normalize(p);
+            let dpdu = vec3f(n.z, 0.0, -n.x);
+            return ShapeHit(t, p, n, Sphere_uv(n), dpdu, cross(n, dpdu));
+        }
+        case CSG_BOX: {
+            let half_size = parameters.xyz;
+            let axis = Shape_box_face(half_size, p);
+            let face = Shape_box_face_axes(axis);
+            return ShapeHit(
+                t,
+                p,
+                sign(p[axis]) * Shape_axis(axis),
+                (vec2f(p[face.x], p[face.y]) / vec2f(half_size[face.x], half_size[face.y]) + 1.0) / 2.0,
+                Shape_axis(face.x),
+                Shape_axis(face.y),
+            );
+        }
+        default: {
+            // 圆柱：取离 p 较近的侧面或盖子
+            let radius = parameters.x;
+            let half_height = parameters.y;
+            if abs(abs(p.y) - half_height) < abs(length(p.xz) - radius) {
+                let side = sign(p.y);
+                return Shape_cap_hit(t, p, radius, side * half_height, side);
+            }
+            return ShapeHit(
+                t,
+                p,
+                normalize(vec3f(p.x, 0.0, p.z)),
+                vec2f(Shape_azimuth(p), (p.y + half_height) / (2 * half_height)),
+                Shape_azimuth_tangent(p),
+                VEC3F_UNIT_Y,
+            );
+        }
+    }
+}
+
+/*------------------------------------------- Curve ---------------------------------------------*/
+
+// 三次 Bézier 曲线的一段，见 CurveData。控制点的 w 是宽度，和位置一样按 Bézier 插值。
+// 求交在光线坐标系中进行：光线沿 z 轴，把这一段近似为 pieces 段折线，光线到中心线的距离不超过半宽时相交。
+// 条带的法线总是朝向光线；管子的法线在宽度方向上从一侧转到另一侧，看起来像圆管
+const CURVE_RIBBON = 0u;
+const CURVE_TUBE = 1u;
+
+struct Curve {
+    control_points: array<vec4f, 4>, // xyz 是位置，w 是宽度
+    kind: u32,
+    material_type: u32,
+    material_id: u32,
+    pieces: u32, // 求交时把这一段近似为多少段折线
+    u_min: f32, // 这一段在整条曲线上的参数范围
+    u_max: f32,
+    motion: Motion,
+}
+
+fn Curve_load(id: u32) -> Curve {
+    let offset = Primitive_offset(PRIMITIVE_CURVE, id);
+    return Curve(
+        array(
+            primitive_vec4f(offset),
+            primitive_vec4f(offset + 4),
+            primitive_vec4f(offset + 8),
+            primitive_vec4f(offset + 12),
+        ),
+        primitive_u32(offset + 16),
+        primitive_u32(offset + 17),
+        primitive_u32(offset + 18),
+        primitive_u32(offset + 19),
+        primitive_f32(offset + 20),
+        primitive_f32(offset + 21),
+        Motion_load(offset + 24),
+    );
+}
+
+fn Curve_hit(
+    id: u32,
+    ray: ptr<function, Ray>,
+    interval: ptr<function, Interval>,
+    hit_record: ptr<function, HitRecord>,
+) -> bool {
+    let curve = Curve_load(id);
+    let ray_length = length((*ray).direction);
+    if ray_length < ZERO_TOLERANCE {
+        return false;
+    }
+
+    // 光线坐标系，原点是光线起点，z 轴沿光线方向
+    let z = (*ray).direction / ray_length;
+    let x = normalize(select(cross(VEC3F_UNIT_Y, z), cross(VEC3F_UNIT_X, z), abs(z.y) > 0.9));
+    let y = cross(z, x);
+    var points: array<vec4f, 4>;
+    for (var i = 0; i < 4; i++) {
+        let p = curve.control_points[i].xyz - (*ray).origin;
+        points[i] = vec4f(dot(p, x), dot(p, y), dot(p, z), curve.control_points[i].w);
+    }
+
+    // 每段折线上离光线最近的点。整条曲线的两端是平的，其他端点处取折线端点，相当于圆头，避免拐角处出现缝隙
+    let pieces = i32(curve.pieces);
+    let is_start = curve.u_min <= 0.0;
+    let is_end = curve.u_max >= 1.0;
+    var found = false;
+    var closest = (*interval).max;
+    var hit_u = 0.0;
+    var a = points[0];
+    for (var i = 1; i <= pieces; i++) {
+        let b = Curve_bezier(points, f32(i) / f32(pieces));
+        let ab = b.xy - a.xy;
+        let length2 = dot(ab, ab);
+        var s = 0.0;
+        if length2 > ZERO_TOLERANCE {
+            s = -dot(a.xy, ab) / length2;
+        }
+        if !((is_start && i == 1 && s < 0) || (is_end && i == pieces && s > 1)) {
+            s = clamp(s, 0.0, 1.0);
+            let p = mix(a, b, s);
+            let half_width = p.w / 2;
+            // 离起点不到半宽的交点是光线刚刚离开的那一处曲线，从曲线上散射出来的光线不再与它自己相交
+            if dot(p.xy, p.xy) <= half_width * half_width && p.z > half_width {
+                let t = p.z / ray_length;
+                if t > (*interval).min && t < closest {
+                    found = true;
+                    closest = t;
+                    hit_u = (f32(i - 1) + s) / f32(pieces);
+                }
+            }
+        }
+        a = b;
+    }
+    if !found {
+        return false;
+    }
+
+    (*hit_record).hit = true;
+    (*hit_record).ray_t = closest;
+    (*hit_record).position = Ray_at(ray, closest);
+    Curve_set_hit_record(id, z, hit_u, hit_record);
+    return true;
+}
+
+// 交点处的法线和参数化。uv 的 u 沿整条曲线，v 沿宽度方向，切线沿曲线方向
+fn Curve_set_hit_record(id: u32, direction: vec3f, u: f32, hit_record: ptr<function, HitRecord>) {
+    let curve = Curve_load(id);
+    let control_points = curve.control_points;
+    let center = Curve_bezier(control_points, u);
+
+    var tangent = Curve_bezier_derivative(control_points, u).xyz;
+    if length_squared(tangent) < ZERO_TOLERANCE {
+        tangent = control_points[3].xyz - control_points[0].xyz;
+    }
+    tangent = normalize(tangent);
+
+    // 条带的法线是光线反方向中与切线正交的部分，side 沿宽度方向，与光线和切线都正交
+    var normal = tangent * dot(direction, tangent) - direction;
+    if length_squared(normal) < ZERO_TOLERANCE {
+        normal = cross(select(VEC3F_UNIT_X, VEC3F_UNIT_Y, abs(tangent.x) > 0.9), tangent);
+    }
+    normal = normalize(normal);
+    let side = cross(tangent, normal);
+    let v = clamp(0.5 + dot((*hit_record).position - center.xyz, side) / max(center.w, F32_POSITIVE_MIN), 0.0, 1.0);
+    if curve.kind == CURVE_TUBE {
+        let theta = (v - 0.5) * PI;
+        normal = cos(theta) * normal + sin(theta) * side;
+    }
+
+    // 曲线没有内外之分，法线总是朝向光线一侧
+    (*hit_record).is_front_face = true;
+    (*hit_record).normal = normal;
+    (*hit_record).geometric_normal = normal;
+    (*hit_record).uv = vec2f(mix(curve.u_min, curve.u_max, u), v);
+    Shape_set_tangent_frame(hit_record, normal, tangent, side);
+    (*hit_record).material_id = curve.material_id;
+    (*hit_record).material_type = curve.material_type;
+}
+
+// 位置和宽度
+fn Curve_bezier(p: array<vec4f, 4>, u: f32) -> vec4f {
+    let v = 1 - u;
+    return p[0] * (v * v * v) + p[1] * (3 * u * v * v) + p[2] * (3 * u * u * v) + p[3] * (u * u * u);
+}
+
+fn Curve_bezier_derivative(p: array<vec4f, 4>, u: f32) -> vec4f {
+    let v = 1 - u;
+    return 3 * ((p[1] - p[0]) * (v * v) + (p[2] - p[1]) * (2 * u * v) + (p[3] - p[2]) * (u * u));
+}
+
+/*---------------------------------------- Heightfield ------------------------------------------*/
+
+// 高度场的一块，见 HeightfieldData。局部坐标中采样点 (x, z) 位于 (x, height, z)，
+// 每个格子沿 (x, z) 到 (x + 1, z + 1) 的对角线分成两个三角形。
+// 先与这一块的包围盒求交，再用 2D DDA 依次走过光线经过的格子，第一个有交点的格子里就是最近的交点
+const HEIGHTFIELD_TILE_CELLS = 8;
+const HEIGHTFIELD_TILE_SAMPLES = 11;
+const HEIGHTFIELD_HEADER_WORDS = 52u; // Heightfield 占的字数，之后是高度数组
+const HEIGHTFIELD_EPSILON = 1e-5; // 三角形边界的容差，避免光线从相邻三角形的公共边之间漏过去
+
+struct Heightfield {
+    origin: vec3f,
+    material_type: u32,
+    bounds_min: vec3f, // 局部坐标中的包围盒，x、z 是格子的下标范围，y 是高度范围
+    material_id: u32,
+    bounds_max: vec3f,
+    grid_size: vec2f, // 整个网格的格子数
+    transform: mat3x3f, // 局部坐标到世界坐标的线性部分
+    inverse_transform: mat3x3f,
+    motion: Motion,
+    // 之后是 HEIGHTFIELD_TILE_SAMPLES 行、每行 HEIGHTFIELD_TILE_SAMPLES 个高度，第 j 行第 i 个是
+    // 采样点 (bounds_min.x + i - 1, bounds_min.z + j - 1) 的高度，多出的一圈用于求边缘的法线，见 Heightfield_height
+}
+
+fn Heightfield_load(id: u32) -> Heightfield {
+    let offset = Primitive_offset(PRIMITIVE_HEIGHTFIELD, id);
+    return Heightfield(
+        primitive_vec3f(offset),
+        primitive_u32(offset + 3),
+        primitive_vec3f(offset + 4),
+        primitive_u32(offset + 7),
+        primitive_vec3f(offset + 8),
+        primitive_vec2f(offset + 12),
+        primitive_mat3x3f(offset + 16),
+        primitive_mat3x3f(offset + 28),
+        Motion_load(offset + 40),
+    );
+}
+
+fn Heightfield_hit(
+    id: u32,
+    ray: ptr<function, Ray>,
+    interval: ptr<function, Interval>,
+    hit_record: ptr<function, HitRecord>,
+) -> bool {
+    let heightfield = Heightfield_load(id);
+    // 仿射变换不改变光线的参数 t
+    let origin = heightfield.inverse_transform * ((*ray).origin - heightfield.origin);
+    let direction = heightfield.inverse_transform * (*ray).direction;
+
+    let range = Heightfield_bounds_range(id, origin, direction);
+    let t_min = max(range.x, (*interval).min);
+    let t_max = min(range.y, (*interval).max);
+    if t_min > t_max {
+        return false;
+    }
+
+    // 光线进入包围盒时所在的格子，以及沿 x、z 走到下一条格子边界时的 t
+    let first = vec2i(heightfield.bounds_min.xz);
+    let last = vec2i(heightfield.bounds_max.xz) - 1;
+    var cell = clamp(vec2i(floor(origin.xz + t_min * direction.xz)), first, last);
+    let step = select(vec2i(-1), vec2i(1), direction.xz >= vec2f(0.0));
+    var t_next = vec2f(MAX);
+    var t_delta = vec2f(MAX);
+    for (var axis = 0; axis < 2; axis++) {
+        if abs(direction.xz[axis]) >= ZERO_TOLERANCE {
+            let boundary = f32(cell[axis] + max(step[axis], 0));
+            t_next[axis] = (boundary - origin.xz[axis]) / direction.xz[axis];
+            t_delta[axis] = abs(1 / direction.xz[axis]);
+        }
+    }
+
+    // 光线在一块中最多经过 2 * HEIGHTFIELD_TILE_CELLS - 1 个格子
+    for (var i = 0; i < 2 * HEIGHTFIELD_TILE_CELLS; i++) {
+        let t = Heightfield_cell_hit(id, cell, origin, direction, interval);
+        if t < MAX {
+            Heightfield_set_hit_record(id, ray, t, cell, origin + t * direction, hit_record);
+            return true;
+        }
+        if min(t_next.x, t_next.y) >= t_max {
+            return false;
+        }
+
+        if t_next.x < t_next.y {
+            cell.x += step.x;
+            t_next.x += t_delta.x;
+        } else {
+            cell.y += step.y;
+            t_next.y += t_delta.y;
+        }
+        if any(cell < first) || any(cell > last) {
+            return false;
+        }
+    }
+    return false;
+}
+
+// 光线与格子 cell 中两个三角形在 interval 内的最近交点，没有交点时返回 MAX
+fn Heightfield_cell_hit(
+    id: u32,
+    cell: vec2i,
+    origin: vec3f,
+    direction: vec3f,
+    interval: ptr<function, Interval>,
+) -> f32 {
+    let f = origin.xz - vec2f(cell);
+    var closest = MAX;
+    for (var k = 0; k < 2; k++) {
+        // 三角形所在的平面 y = h00 + dot(slope, q)，q 是格子内的坐标
+        let slope = Heightfield_triangle_slope(id, cell, k == 0);
+        let denominator = direction.y - dot(slope, direction.xz);
+        if abs(denominator) < ZERO_TOLERANCE {
+            continue;
+        }
+        let t = (Heightfield_height(id, cell) + dot(slope, f) - origin.y) / denominator;
+        let q = f + t * direction.xz;
+
+        // 第一个三角形是 q.x >= q.y 的一半，交换坐标后第二个三角形满足同样的条件
+        let r = select(q.yx, q, k == 0);
+        let inside = r.x <= 1 + HEIGHTFIELD_EPSILON && r.y >= -HEIGHTFIELD_EPSILON && r.x - r.y >= -HEIGHTFIELD_EPSILON;
+        if inside && t > (*interval).min && t < min((*interval).max, closest) {
+            closest = t;
+        }
+    }
+    return closest;
+}
+
+// 格子中两个三角形的高度对格子内坐标的偏导数。第一个三角形的顶点是 (0, 0)、(1, 0)、(1, 1)，第二个是 (0, 0)、(1, 1)、(0, 1)
+fn Heightfield_triangle_slope(id: u32, cell: vec2i, first: bool) -> vec2f {
+    let h00 = Heightfield_height(id, cell);
+    let h10 = Heightfield_height(id, cell + vec2i(1, 0));
+    let h01 = Heightfield_height(id, cell + vec2i(0, 1));
+    let h11 = Heightfield_height(id, cell + vec2i(1, 1));
+    return select(vec2f(h11 - h01, h01 - h00), vec2f(h10 - h00, h11 - h10), first);
+}
+
+// 几何法线是所在三角形的法线，着色法线由格子四个角上的梯度双线性插值得到。uv 在整个网格上从 0 到 1
+fn Heightfield_set_hit_record(
+    id: u32,
+    ray: ptr<function, Ray>,
+    t: f32,
+    cell: vec2i,
+    p: vec3f,
+    hit_record: ptr<function, HitRecord>,
+) {
+    let heightfield = Heightfield_load(id);
+
+    (*hit_record).hit = true;
+    (*hit_record).ray_t = t;
+    (*hit_record).position = Ray_at(ray, t);
+
+    // 高度场 y = h(x, z) 的法线是 (-∂h/∂x, 1, -∂h/∂z)，按逆转置变换到世界坐标
+    let f = clamp(p.xz - vec2f(cell), vec2f(0.0), vec2f(1.0));
+    let inverse_transpose = transpose(heightfield.inverse_transform);
+    let slope = Heightfield_triangle_slope(id, cell, f.x >= f.y);
+    let outward_normal = normalize(inverse_transpose * vec3f(-slope.x, 1.0, -slope.y));
+    HitRecord_set_face_normal(hit_record, ray, outward_normal);
+
+    let gradient = mix(
+        mix(Heightfield_gradient(id, cell), Heightfield_gradient(id, cell + vec2i(1, 0)), f.x),
+        mix(Heightfield_gradient(id, cell + vec2i(0, 1)), Heightfield_gradient(id, cell + vec2i(1, 1)), f.x),
+        f.y
+    );
+    let shading_normal = normalize(inverse_transpose * vec3f(-gradient.x, 1.0, -gradient.y));
+    (*hit_record).normal = select(-shading_normal, shading_normal, (*hit_record).is_front_face);
+
+    (*hit_record).uv = p.xz / heightfield.grid_size;
+    Shape_set_tangent_frame(
+        hit_record,
+        outward_normal,
+        heightfield.transform * vec3f(1.0, gradient.x, 0.0),
+        heightfield.transform * vec3f(0.0, gradient.y, 1.0),
+    );
+    (*hit_record).material_id = heightfield.material_id;
+    (*hit_record).material_type = heightfield.material_type;
+}
+
+// 网格采样点的高度，只能取这一块及其外面一圈
+fn Heightfield_height(id: u32, sample: vec2i) -> f32 {
+    let index = vec2u(sample - vec2i(Heightfield_load(id).bounds_min.xz) + 1);
+    let offset = Primitive_offset(PRIMITIVE_HEIGHTFIELD, id) + HEIGHTFIELD_HEADER_WORDS;
+    return primitive_f32(offset + index.y * u32(HEIGHTFIELD_TILE_SAMPLES) + index.x);
+}
+
+// 采样点处高度的中心差分
+fn Heightfield_gradient(id: u32, sample: vec2i) -> vec2f {
+    return vec2f(
+        Heightfield_height(id, sample + vec2i(1, 0)) - Heightfield_height(id, sample - vec2i(1, 0)),
+        Heightfield_height(id, sample + vec2i(0, 1)) - Heightfield_height(id, sample - vec2i(0, 1)),
+    ) / 2;
+}
+
+// 光线与局部坐标中的包围盒相交的一段，不相交时 x > y
+fn Heightfield_bounds_range(id: u32, origin: vec3f, direction: vec3f) -> vec2f {
+    let heightfield = Heightfield_load(id);
+    var near = MIN;
+    var far = MAX;
+    for (var axis = 0; axis < 3; axis++) {
+        if abs(direction[axis]) < ZERO_TOLERANCE {
+            if origin[axis] < heightfield.bounds_min[axis] || origin[axis] > heightfield.bounds_max[axis] {
+                return vec2f(MAX, MIN);
+            }
+            continue;
+        }
+        let t0 = (heightfield.bounds_min[axis] - origin[axis]) / direction[axis];
+        let t1 = (heightfield.bounds_max[axis] - origin[axis]) / direction[axis];
+        near = max(near, min(t0, t1));
+        far = min(far, max(t0, t1));
+    }
+    return vec2f(near, far);
+}
+
 /*------------------------------------------- Quad ----------------------------------------------*/
 
 struct Quad {
@@ -843,8 +2618,25 @@ struct Quad {
     d: f32,       // quad 所在平面的方程 ax + by + cz + d 中的 d
     normal: vec3f,
     material_type: u32,
-    w: vec3f  // w 是将 quad 所在平面上的点转换到 quad 定义的坐标系（bottom_left, right, up）上时需要用到的变量
-                  // w = normal / dot(normal, normal) ，详见 Ray Tracing: The Next Week, p59
+    w: vec3f, // w 是将 quad 所在平面上的点转换到 quad 定义的坐标系（bottom_left, right, up）上时需要用到的变量
+              // w = normal / dot(normal, normal) ，详见 Ray Tracing: The Next Week, p59
+    motion: Motion,
+}
+
+fn Quad_load(id: u32) -> Quad {
+    let offset = Primitive_offset(PRIMITIVE_QUAD, id);
+    return Quad(
+        primitive_vec3f(offset),
+        primitive_u32(offset + 3),
+        primitive_vec3f(offset + 4),
+        primitive_f32(offset + 7),
+        primitive_vec3f(offset + 8),
+        primitive_f32(offset + 11),
+        primitive_vec3f(offset + 12),
+        primitive_u32(offset + 15),
+        primitive_vec3f(offset + 16),
+        Motion_load(offset + 20),
+    );
 }
 
 fn Quad_hit(
@@ -853,8 +2645,8 @@ fn Quad_hit(
     interval: ptr<function, Interval>,
     hit_record: ptr<function, HitRecord>,
 ) -> bool {
-    let quad: ptr<storage, Quad> = &quads[id];
-    let nd = dot((*quad).normal, (*ray).direction);
+    let quad = Quad_load(id);
+    let nd = dot(quad.normal, (*ray).direction);
 
     // No hit if the ray is parallel to the plane.
     if abs(nd) < ZERO_TOLERANCE {
@@ -862,16 +2654,16 @@ fn Quad_hit(
     }
 
     // Return false if the hit point parameter t is outside the ray interval.
-    let t = ((*quad).d - dot((*quad).normal, (*ray).origin)) / nd;
+    let t = (quad.d - dot(quad.normal, (*ray).origin)) / nd;
     if !Interval_contains(interval, t) {
         return false;
     }
 
     // Determine the hit point lies within the planar shape using its plane coordinates.
     let intersection = Ray_at(ray, t);
-    let planar_hit_vector = intersection - (*quad).bottom_left;
-    let alpha = dot((*quad).w, cross(planar_hit_vector, (*quad).up));
-    let beta = dot((*quad).w, cross((*quad).right, planar_hit_vector));
+    let planar_hit_vector = intersection - quad.bottom_left;
+    let alpha = dot(quad.w, cross(planar_hit_vector, quad.up));
+    let beta = dot(quad.w, cross(quad.right, planar_hit_vector));
 
     if !Quad_is_interior(alpha, beta, hit_record) {
         return false;
@@ -880,8 +2672,8 @@ fn Quad_hit(
     (*hit_record).hit = true;
     (*hit_record).ray_t = t;
     (*hit_record).position = intersection;
-    (*hit_record).material_id = (*quad).material_id;
-    (*hit_record).material_type = (*quad).material_type;
+    (*hit_record).material_id = quad.material_id;
+    (*hit_record).material_type = quad.material_type;
 
     // 如果这里的第 3 个参数传入指针，就应该是 &quad.normal ，但这种写法要求支持 WGSL 扩展 unrestricted_pointer_parameters
     // https://www.w3.org/TR/WGSL/#language_extension-unrestricted_pointer_parameters
@@ -889,7 +2681,12 @@ fn Quad_hit(
     //
     // 在不支持的平台上在这里用指针会报一个奇怪的错：
     // internal error: entered unreachable code: Expression [50] is not cached!
-    HitRecord_set_face_normal(hit_record, ray, (*quad).normal);
+    HitRecord_set_face_normal(hit_record, ray, quad.normal);
+
+    // uv 分别沿 right 和 up，up 正交化之后作为副切线
+    let tangent = normalize(quad.right);
+    (*hit_record).tangent = tangent;
+    (*hit_record).bitangent = normalize(quad.up - dot(quad.up, tangent) * tangent);
 
     return true;
 }
@@ -927,15 +2724,16 @@ fn Quad_pdf_value(
     let distance_squared = pow(hit_record.ray_t, 2.0) * length_squared((*ray).direction);
     let cosine = abs(dot((*ray).direction, hit_record.normal) / length((*ray).direction));
 
-    return distance_squared / (cosine * quads[id].area);
+    return distance_squared / (cosine * Quad_load(id).area);
 }
 
 fn Quad_random(
     id: u32,
     origin: ptr<function, vec3f>,
+    u: vec2f,
 ) -> vec3f {
-    let quad = &quads[id];
-    let p = (*quad).bottom_left + randomf() * (*quad).up + randomf() * (*quad).right;
+    let quad = Quad_load(id);
+    let p = quad.bottom_left + u.x * quad.up + u.y * quad.right;
     return normalize(p - *origin);
 }
 
@@ -950,6 +2748,10 @@ struct HitRecord {
     material_type: u32,
     hit: bool,
     is_front_face: bool,
+    in_direction: vec3f, // 入射光线的单位方向，由 ray_color 在求交后填写
+    geometric_normal: vec3f, // 朝向入射光线一侧的几何法线，normal 是可能被法线贴图修改过的着色法线
+    tangent: vec3f,   // 与几何法线正交的切线，沿 u 增加的方向
+    bitangent: vec3f, // 与几何法线正交的副切线，沿 v 增加的方向
 }
 
 fn HitRecord_set_face_normal(
@@ -962,6 +2764,7 @@ fn HitRecord_set_face_normal(
     if !(*s).is_front_face {
         (*s).normal = -outward_normal;
     }
+    (*s).geometric_normal = (*s).normal;
 }
 
 /*----------------------------------------- Interval --------------------------------------------*/
@@ -1046,6 +2849,7 @@ fn Interval_displace(s: ptr<function, Interval>, displacement: f32) {
 struct Ray {
     origin: vec3f,
     direction: vec3f,
+    time: f32, // 光线所在的时刻，散射出的光线与入射光线相同
 }
 
 fn Ray_init(
@@ -1064,17 +2868,30 @@ fn Ray_at(s: ptr<function, Ray>, t: f32) -> vec3f {
 
 /*------------------------------------------- Sampling ------------------------------------------*/
 
-fn sample_unit_disk() -> vec2<f32> {
-    let phi = 2 * PI * randomf();
-    let r = sqrt(randomf());
+fn sample_unit_disk(u: vec2f) -> vec2<f32> {
+    let phi = 2 * PI * u.x;
+    let r = sqrt(u.y);
     return vec2<f32>(cos(phi), sin(phi)) * r;
 }
 
-fn sample_unit_square_stratified() -> vec2<f32> {
-    if context.sample_id < context.sample_grid_num { // 分层采样
+fn random_cosine_direction(u: vec2f) -> vec3f {
+    let xi1 = u.x;
+    let xi2 = u.y;
+
+    let phi = 2 * PI * xi1;
+    let z = cos(phi) * sqrt(xi2);
+    let x = sin(phi) * sqrt(xi2);
+    let y = sqrt(1 - xi2);
+
+    return vec3f(x, y, z);
+}
+
+fn sample_unit_square_stratified(sample_index: u32) -> vec2<f32> {
+    if sample_index < context.sample_grid_num { // 分层采样
+        let n = context.sample_grid_per_dimension;
         return vec2<f32>(
-            f32(context.sample_position.x) + randomf(),
-            f32(context.sample_position.y) + randomf()
+            f32(sample_index % n) + randomf(),
+            f32(sample_index / n) + randomf()
         ) * context.sample_grid_len - 0.5;
     } else { // 直接采样
         return sample_unit_square();
@@ -1085,6 +2902,185 @@ fn sample_unit_square() -> vec2<f32> {
     return vec2<f32>(randomf(), randomf()) - 0.5;
 }
 
+/*------------------------------------------- Sampler -------------------------------------------*/
+
+// 路径上的每个随机决策占用一个维度（1D 决策只使用 2D 样本的 x），
+// 不同采样器在同一维度上给出 sample_index 对应的点，像素之间通过 context.seed 派生的种子去相关
+
+const SAMPLER_INDEPENDENT: u32 = 0;
+const SAMPLER_SOBOL: u32 = 1;
+const SAMPLER_HALTON: u32 = 2;
+const SAMPLER_RANK1_LATTICE: u32 = 3;
+
+struct SamplerState {
+    pixel_seed: u32,
+    pixel: vec2<u32>,
+    sample_index: u32,
+    dimension: u32,
+}
+var<private> sampler_state: SamplerState;
+
+fn Sampler_start(pixel_index: u32, sample_index: u32) {
+    sampler_state.pixel_seed = hash_u32(pixel_index ^ hash_u32(context.seed));
+    sampler_state.pixel = vec2<u32>(pixel_index % context.width, pixel_index / context.width);
+    sampler_state.sample_index = sample_index;
+    sampler_state.dimension = 0u;
+    init_random_generator(hash_u32(sampler_state.pixel_seed ^ hash_u32(sample_index + 1u)));
+}
+
+fn Sampler_1d() -> f32 {
+    return Sampler_2d().x;
+}
+
+fn Sampler_2d() -> vec2f {
+    let dimension = sampler_state.dimension;
+    sampler_state.dimension += 1u;
+
+    switch (context.sampler_type) {
+        case SAMPLER_SOBOL: {
+            return Sobol_sample(dimension);
+        }
+        case SAMPLER_HALTON: {
+            return Halton_sample(dimension);
+        }
+        case SAMPLER_RANK1_LATTICE: {
+            return Rank1Lattice_sample(dimension);
+        }
+        default: {
+            return vec2f(randomf(), randomf());
+        }
+    }
+}
+
+// Burley, Practical Hash-based Owen Scrambling, JCGT 2020
+// 前两维 Sobol 序列，每个维度用不同的种子打乱索引和数值（padding）
+fn Sobol_sample(dimension: u32) -> vec2f {
+    let seed = hash_u32(sampler_state.pixel_seed ^ hash_u32(dimension));
+    let index = nested_uniform_scramble(sampler_state.sample_index, seed);
+    let x = nested_uniform_scramble(reverseBits(index), hash_u32(seed ^ 0x9e3779b9u));
+    let y = nested_uniform_scramble(sobol_dimension1(index), hash_u32(seed ^ 0x85ebca6bu));
+    return vec2f(u32_to_unit_float(x), u32_to_unit_float(y));
+}
+
+// 本原多项式 x + 1 对应的方向数
+fn sobol_dimension1(index: u32) -> u32 {
+    var v = 1u << 31u;
+    var x = 0u;
+    var i = index;
+    while i != 0u {
+        if (i & 1u) == 1u {
+            x ^= v;
+        }
+        i >>= 1u;
+        v ^= v >> 1u;
+    }
+    return x;
+}
+
+fn nested_uniform_scramble(x: u32, seed: u32) -> u32 {
+    return reverseBits(laine_karras_permutation(reverseBits(x), seed));
+}
+
+fn laine_karras_permutation(value: u32, seed: u32) -> u32 {
+    var x = value + seed;
+    x ^= x * 0x6c50b47cu;
+    x ^= x * 0xb82f1e52u;
+    x ^= x * 0xc7afe638u;
+    x ^= x * 0x8d22f6e6u;
+    return x;
+}
+
+const HALTON_DIMENSIONS: u32 = 16;
+var<private> HALTON_PRIMES: array<u32, 32> = array<u32, 32>(
+    2u, 3u, 5u, 7u, 11u, 13u, 17u, 19u, 23u, 29u, 31u, 37u, 41u, 43u, 47u, 53u,
+    59u, 61u, 67u, 71u, 73u, 79u, 83u, 89u, 97u, 101u, 103u, 107u, 109u, 113u, 127u, 131u
+);
+
+// 每个维度不同的 Cranley-Patterson 旋转让像素之间去相关。表中只有 16 对素数，循环使用会让维度之间只差一个
+// 常数旋转，之后的维度改用 Owen 打乱的 Sobol 点
+fn Halton_sample(dimension: u32) -> vec2f {
+    if dimension >= HALTON_DIMENSIONS {
+        return Sobol_sample(dimension);
+    }
+
+    let pair = dimension;
+    let index = sampler_state.sample_index;
+    let point = vec2f(
+        radical_inverse(HALTON_PRIMES[2u * pair], index),
+        radical_inverse(HALTON_PRIMES[2u * pair + 1u], index)
+    );
+
+    let seed = hash_u32(sampler_state.pixel_seed ^ hash_u32(dimension));
+    let rotation = vec2f(u32_to_unit_float(seed), u32_to_unit_float(hash_u32(seed)));
+    return fract(point + rotation);
+}
+
+fn radical_inverse(base: u32, index: u32) -> f32 {
+    let inverse_base = 1.0 / f32(base);
+    var i = index;
+    var factor = inverse_base;
+    var result = 0.0;
+    while i > 0u {
+        result += f32(i % base) * factor;
+        i /= base;
+        factor *= inverse_base;
+    }
+    return min(result, 0x1.fffffep-1f);
+}
+
+// 生成向量取自 R2 序列（塑料数），用 32 位定点数计算避免大索引时的精度损失。
+// 所有维度使用同一个索引时，一个像素内各维度的点只差一个常数平移，路径空间塌缩成二维的切片。
+// 按维度打乱索引：前 2^k 个索引仍然映射到一个对齐的 2^k 块内，只相当于多一个旋转，分层的性质不变。
+// 同一维度上所有像素共用一组格点，再用蓝噪声瓦片给每个像素不同的 Cranley-Patterson 旋转，每个维度把瓦片
+// 平移一个随机的偏移，相邻像素的误差呈蓝噪声分布（Georgiev and Fajardo, Blue-noise Dithered Sampling, 2016）。
+// 叠加的全局随机旋转让每个像素的旋转都是均匀分布，估计仍然无偏
+fn Rank1Lattice_sample(dimension: u32) -> vec2f {
+    let seed = hash_u32(hash_u32(context.seed) ^ hash_u32(dimension));
+    let index = nested_uniform_scramble(sampler_state.sample_index, seed);
+
+    let offset = hash_u32(seed ^ 0x68e31da4u);
+    let texel = (sampler_state.pixel + vec2<u32>(offset, offset >> 8u)) % BLUE_NOISE_SIZE;
+    let noise = BLUE_NOISE[texel.y * BLUE_NOISE_SIZE + texel.x];
+    // 瓦片中的值是 0 到 255 的排名，放到 32 位定点数的最高 8 位
+    let dither = vec2<u32>(noise & 0xffu, noise >> 8u) << vec2<u32>(24u);
+
+    let shift = vec2<u32>(hash_u32(seed ^ 0x9e3779b9u), hash_u32(seed ^ 0x85ebca6bu)) + dither;
+    let point = vec2<u32>(index * 3242174889u, index * 2447445414u) + shift;
+    return vec2f(u32_to_unit_float(point.x), u32_to_unit_float(point.y));
+}
+
+// 16x16 的二维蓝噪声瓦片，由 void-and-cluster 算法生成。低 8 位和高 8 位分别是 x 和 y 两个独立瓦片中的排名
+const BLUE_NOISE_SIZE: u32 = 16;
+var<private> BLUE_NOISE: array<u32, 256> = array<u32, 256>(
+    0x0a78u, 0xb13du, 0x5386u, 0x9bdfu, 0x3b54u, 0x9021u, 0xb0a8u, 0xf60cu, 0x3a71u, 0x0ee1u, 0xe83fu, 0xbbf6u, 0xd4b9u, 0x34e9u, 0xf158u, 0xc9a9u,
+    0x8317u, 0xfaceu, 0x1fb5u, 0xcd11u, 0xbe6du, 0x10d6u, 0x473au, 0xc78cu, 0xa1c9u, 0x6618u, 0x87a1u, 0x415du, 0x0422u, 0x9c85u, 0x630eu, 0x4eddu,
+    0xa390u, 0x3549u, 0x7dfau, 0x6431u, 0xf29eu, 0x82bbu, 0x6051u, 0x20fbu, 0xde64u, 0x1933u, 0xfb8eu, 0xa4d2u, 0x76acu, 0xe039u, 0x18bfu, 0xbf6au,
+    0xe52au, 0x57a7u, 0xd865u, 0x127eu, 0x2fdcu, 0xa703u, 0xea79u, 0x7928u, 0x95aau, 0x54e7u, 0xb452u, 0x2108u, 0x5972u, 0xc4feu, 0x9150u, 0x2ae8u,
+    0x01d4u, 0x8a0bu, 0xb7c3u, 0x991fu, 0x4b48u, 0xd2efu, 0x0398u, 0x3fc4u, 0xc110u, 0x307fu, 0x73bcu, 0xd3deu, 0x372du, 0xec9du, 0x461au, 0x7180u,
+    0xc89au, 0x4257u, 0x24ebu, 0xf58fu, 0x6cb3u, 0xba5eu, 0x5a36u, 0xa26cu, 0xdbedu, 0x1341u, 0xef1du, 0x8d69u, 0x078bu, 0x7fcfu, 0xb2b8u, 0xfd42u,
+    0x9ef8u, 0x612fu, 0xdc73u, 0x7a3eu, 0x0fd1u, 0x8e14u, 0x28a4u, 0xffd9u, 0x854fu, 0x6292u, 0xbcb2u, 0x4df3u, 0xad45u, 0x685au, 0x1500u, 0x3276u,
+    0x841eu, 0x0cbeu, 0xafadu, 0x3606u, 0xcb83u, 0xe6ffu, 0x4829u, 0x7088u, 0x0b0au, 0x3cccu, 0xa02bu, 0x1e9fu, 0xe716u, 0xcfe5u, 0x55a2u, 0xdfdau,
+    0x6d4du, 0xeb94u, 0x5163u, 0x9ae2u, 0x5f4au, 0x1ab6u, 0xa975u, 0xc5c0u, 0x9656u, 0xd7f7u, 0xf777u, 0x7861u, 0x38c5u, 0x9782u, 0x2735u, 0xab67u,
+    0x1df2u, 0xc613u, 0x2cc6u, 0xf92cu, 0xbd9bu, 0x8060u, 0x333bu, 0xede6u, 0x501cu, 0x2ba5u, 0x5e3cu, 0x0d05u, 0xc3f0u, 0x8827u, 0xf4afu, 0x45cau,
+    0xb689u, 0x8f40u, 0x7c7au, 0x05eeu, 0x4319u, 0xddd3u, 0x6a01u, 0x0895u, 0xb368u, 0x8be0u, 0xca87u, 0xa8b7u, 0x6997u, 0x5247u, 0x0270u, 0xd609u,
+    0x395bu, 0x4fd5u, 0xe3a6u, 0x6755u, 0x94bau, 0x226fu, 0x9df9u, 0xd0aeu, 0x7b30u, 0x1c4bu, 0x44d0u, 0xee20u, 0x2559u, 0xe4cdu, 0x9fecu, 0x72a0u,
+    0xfe25u, 0xa6fcu, 0x1112u, 0xce37u, 0xb58au, 0xf326u, 0x584eu, 0x3e7bu, 0xfcc2u, 0x650du, 0xda6bu, 0x92fdu, 0x3d7cu, 0xb90fu, 0x7e38u, 0x14bdu,
+    0xc04cu, 0x5b91u, 0x866eu, 0x31e4u, 0x4acbu, 0x17a3u, 0x89dbu, 0xa515u, 0x2ef1u, 0xb88du, 0x16abu, 0x7532u, 0x099cu, 0xd1e3u, 0x5d66u, 0x2d81u,
+    0x9302u, 0x23c7u, 0xf0b0u, 0xac44u, 0x7707u, 0xd962u, 0xc234u, 0x0096u, 0xe25cu, 0x4c24u, 0x98d7u, 0xae53u, 0xf8c8u, 0x491bu, 0xaab1u, 0xe1d8u,
+    0x40f4u, 0xd55fu, 0x6e23u, 0x0699u, 0xe9f5u, 0x5c7du, 0x29c1u, 0x6feau, 0x8146u, 0xccb4u, 0x5684u, 0x2604u, 0x6b74u, 0x8c43u, 0x1b93u, 0x742eu
+);
+
+// https://www.reedbeta.com/blog/hash-functions-for-gpu-rendering/
+fn hash_u32(value: u32) -> u32 {
+    let state = value * 747796405u + 2891336453u;
+    let word = ((state >> ((state >> 28u) + 4u)) ^ state) * 277803737u;
+    return (word >> 22u) ^ word;
+}
+
+fn u32_to_unit_float(x: u32) -> f32 {
+    return f32(x >> 8u) * 0x1p-24f; // [0, 1)
+}
+
 /*---------------------------------- Random Number Generation -----------------------------------*/
 
 // https://indico.cern.ch/event/93877/papers/2118070/files/4416-acat3.pdf
@@ -1154,6 +3150,10 @@ fn linear_to_srgb(color: vec3f) -> vec3f {
     return select(higher, lower, cutoff);
 }
 
+fn luminance(color: vec3f) -> f32 {
+    return dot(color, vec3f(0.2126, 0.7152, 0.0722));
+}
+
 fn srgb_to_linear(color: vec3f) -> vec3f {
     let cutoff = color.rgb < vec3(0.04045);
     let higher = pow((color.rgb + vec3(0.055)) / vec3(1.055), vec3(2.4));
@@ -0,0 +1,244 @@
+// 关键帧的插值和采样、动画对相机参数、场景图和材质的应用，以及序列帧使用的 EXR 输出
+
+use nalgebra::{Point3, Translation3, UnitQuaternion, Vector3};
+use proptest::prelude::*;
+use renderer_core::app::animation::{Animation, Interpolation, Track};
+use renderer_core::app::camera::CameraParameters;
+use renderer_core::app::scene::Scene;
+use renderer_core::rendering::cpu::CpuImage;
+use renderer_core::rendering::material::{Lambertian, MaterialParameter, MaterialRegistry};
+use renderer_core::rendering::mesh::scene_graph::{SceneGraph, Transform};
+use renderer_core::rendering::primitive::sphere::Sphere;
+use std::f32::consts::FRAC_PI_2;
+
+const EPSILON: f32 = 1e-4;
+
+fn track(interpolation: Interpolation) -> Track<f32> {
+    let mut track = Track::new();
+    track
+        .insert(1.0, 10.0, interpolation)
+        .insert(3.0, 20.0, Interpolation::Linear);
+    track
+}
+
+#[test]
+fn empty_track_has_no_value() {
+    assert_eq!(Track::<f32>::new().sample(0.0), None);
+    assert_eq!(Animation::default().duration(), 0.0);
+}
+
+#[test]
+fn track_holds_end_values_outside_its_keyframes() {
+    let track = track(Interpolation::Linear);
+    assert_eq!(track.sample(0.0), Some(10.0));
+    assert_eq!(track.sample(1.0), Some(10.0));
+    assert_eq!(track.sample(3.0), Some(20.0));
+    assert_eq!(track.sample(5.0), Some(20.0));
+    assert_eq!(track.end_time(), 3.0);
+}
+
+#[test]
+fn step_holds_until_the_next_keyframe() {
+    let track = track(Interpolation::Step);
+    assert_eq!(track.sample(2.999), Some(10.0));
+    assert_eq!(track.sample(3.0), Some(20.0));
+}
+
+#[test]
+fn linear_interpolates_by_elapsed_time() {
+    let track = track(Interpolation::Linear);
+    assert!((track.sample(1.5).unwrap() - 12.5).abs() < EPSILON);
+    assert!((track.sample(2.0).unwrap() - 15.0).abs() < EPSILON);
+}
+
+#[test]
+fn ease_in_out_is_slow_at_the_ends_and_symmetric() {
+    let ease = |t| Interpolation::EASE_IN_OUT.ease(t);
+    assert!(ease(0.0).abs() < EPSILON);
+    assert!((ease(1.0) - 1.0).abs() < EPSILON);
+    assert!((ease(0.5) - 0.5).abs() < EPSILON);
+    assert!(ease(0.1) < 0.1);
+    assert!(ease(0.9) > 0.9);
+    for t in [0.1, 0.25, 0.4] {
+        assert!((ease(t) + ease(1.0 - t) - 1.0).abs() < EPSILON);
+    }
+}
+
+#[test]
+fn inserting_at_the_same_time_replaces_the_keyframe() {
+    let mut track = Track::new();
+    track
+        .insert(2.0, 1.0, Interpolation::Linear)
+        .insert(0.0, 0.0, Interpolation::Linear)
+        .insert(2.0, 4.0, Interpolation::Step);
+
+    let keyframes = track.keyframes();
+    assert_eq!(keyframes.len(), 2);
+    assert_eq!(keyframes[0].time, 0.0);
+    assert_eq!(keyframes[1].value, 4.0);
+    assert_eq!(keyframes[1].interpolation, Interpolation::Step);
+}
+
+#[test]
+fn transform_rotation_is_interpolated_on_the_sphere() {
+    let mut track = Track::new();
+    track.insert(0.0, Transform::identity(), Interpolation::Linear).insert(
+        1.0,
+        Transform {
+            translation: Translation3::new(2.0, 0.0, 0.0),
+            rotation: UnitQuaternion::from_axis_angle(&Vector3::y_axis(), FRAC_PI_2),
+            ..Transform::identity()
+        },
+        Interpolation::Linear,
+    );
+
+    let transform = track.sample(0.5).unwrap();
+    assert!((transform.translation.vector - Vector3::new(1.0, 0.0, 0.0)).norm() < EPSILON);
+    assert!((transform.rotation.angle() - FRAC_PI_2 / 2.0).abs() < EPSILON);
+    assert!((transform.scale.vector - Vector3::repeat(1.0)).norm() < EPSILON);
+}
+
+#[test]
+fn animation_applies_camera_nodes_and_materials() {
+    let mut materials = MaterialRegistry::default();
+    let handle = materials.add(Box::new(Lambertian::new(Point3::new(0.0, 0.0, 0.0))));
+    let mut objects = SceneGraph::new();
+    let group = objects.add_node(None, "group", Transform::identity());
+    let ball = objects.add_mesh(Some(group), "ball", Sphere::new(Point3::origin(), 1.0, handle, false));
+    objects.take_changed();
+    materials.take_changed();
+
+    let mut animation = Animation::new(10.0);
+    animation
+        .camera_position
+        .insert(0.0, Point3::new(0.0, 0.0, 0.0), Interpolation::Linear)
+        .insert(2.0, Point3::new(0.0, 0.0, 4.0), Interpolation::Linear);
+    animation
+        .vfov
+        .insert(0.0, 30.0, Interpolation::Step)
+        .insert(2.0, 60.0, Interpolation::Step);
+    animation
+        .node("group/ball")
+        .insert(0.0, Transform::identity(), Interpolation::Linear)
+        .insert(
+            2.0,
+            Transform::from_translation(Translation3::new(0.0, 2.0, 0.0)),
+            Interpolation::Linear,
+        );
+    animation
+        .material_color(handle, "Albedo")
+        .insert(0.0, Point3::new(0.0, 0.0, 0.0), Interpolation::Linear)
+        .insert(4.0, Point3::new(0.8, 0.4, 0.0), Interpolation::Linear);
+    animation
+        .node("missing")
+        .insert(0.0, Transform::identity(), Interpolation::Linear);
+    assert_eq!(animation.duration(), 4.0);
+    assert_eq!(animation.frame_time(15), 1.5);
+    assert_eq!(animation.frame_at(1.5), 15);
+
+    let mut camera = CameraParameters::default();
+    animation.apply(1.0, &mut camera, &mut objects, &mut materials);
+    assert!((camera.initial_position - Point3::new(0.0, 0.0, 2.0)).norm() < EPSILON);
+    assert_eq!(camera.vfov, 30.0);
+    assert_eq!(camera.initial_look_at, Point3::origin());
+    assert!((objects.node(ball).transform().translation.vector.y - 1.0).abs() < EPSILON);
+    assert!(objects.take_changed());
+    assert!(materials.take_changed());
+
+    let albedo = match materials.get_mut(handle).unwrap().parameters().remove(0) {
+        MaterialParameter::Color { value, .. } => *value,
+        _ => unreachable!(),
+    };
+    assert!((albedo - Point3::new(0.2, 0.1, 0.0)).norm() < EPSILON);
+}
+
+#[test]
+fn animating_a_parameter_as_another_kind_replaces_the_track() {
+    let mut materials = MaterialRegistry::default();
+    let handle = materials.add(Box::new(Lambertian::new(Point3::new(0.0, 0.0, 0.0))));
+    let mut objects = SceneGraph::new();
+    materials.take_changed();
+
+    let mut animation = Animation::default();
+    animation
+        .material_scalar(handle, "Albedo")
+        .insert(3.0, 0.5, Interpolation::Linear);
+    let track = animation.material_color(handle, "Albedo");
+    assert!(track.is_empty());
+    track.insert(1.0, Point3::new(0.5, 0.5, 0.5), Interpolation::Linear);
+    assert_eq!(animation.duration(), 1.0);
+
+    animation.apply(1.0, &mut CameraParameters::default(), &mut objects, &mut materials);
+    assert!(materials.take_changed());
+}
+
+#[test]
+fn applying_the_same_time_again_changes_nothing() {
+    let mut scene = Scene::scene_cornell_box();
+    scene.objects.take_changed();
+    scene.materials.take_changed();
+
+    scene.animate(1.0);
+    assert!(scene.objects.take_changed());
+    assert!(!scene.materials.take_changed());
+
+    scene.animate(1.0);
+    assert!(!scene.objects.take_changed());
+    assert!(!scene.materials.take_changed());
+
+    scene.animate(2.5);
+    assert!(scene.materials.take_changed());
+}
+
+#[test]
+fn demo_animation_starts_at_the_static_scene() {
+    let mut scene = Scene::scene_cornell_box();
+    let position = scene.camera_parameters.initial_position;
+    scene.objects.take_changed();
+    scene.materials.take_changed();
+
+    scene.animate(0.0);
+    assert_eq!(scene.camera_parameters.initial_position, position);
+    assert!(!scene.objects.take_changed());
+    assert!(!scene.materials.take_changed());
+    assert!(scene.animation.duration() > 0.0);
+}
+
+#[test]
+fn exr_round_trips_linear_float_colors() {
+    let pixels = vec![
+        Vector3::new(0.0, 0.5, 1.0),
+        Vector3::new(2.0, 3.0, 4.0),
+        Vector3::new(5.0, 6.0, 7.0),
+        Vector3::new(8.0, 9.0, 10.0),
+        Vector3::new(11.0, 12.0, 13.0),
+        Vector3::new(14.0, 15.0, 16.0),
+    ];
+    let image = CpuImage::new(3, 2, pixels);
+    let path = std::env::temp_dir().join(format!("frame-{}.exr", std::process::id()));
+    image.to_rgb32f().save(&path).unwrap();
+    let loaded = image::open(&path);
+    std::fs::remove_file(&path).unwrap();
+
+    let loaded = loaded.unwrap().into_rgb32f();
+    assert_eq!(loaded.dimensions(), (3, 2));
+    for (x, y, pixel) in loaded.enumerate_pixels() {
+        assert_eq!(Vector3::from(pixel.0), image.pixel(x, y));
+    }
+}
+
+proptest! {
+    #[test]
+    fn bezier_on_the_diagonal_is_linear(a in 0.0f32..=1.0, b in 0.0f32..=1.0, t in 0.0f32..=1.0) {
+        let bezier = Interpolation::Bezier { x1: a, y1: a, x2: b, y2: b };
+        prop_assert!((bezier.ease(t) - t).abs() < 1e-3);
+    }
+
+    #[test]
+    fn ease_stays_between_keyframes_for_monotonic_curves(
+        x1 in 0.0f32..=1.0, y1 in 0.0f32..=1.0, x2 in 0.0f32..=1.0, y2 in 0.0f32..=1.0, t in 0.0f32..=1.0,
+    ) {
+        let value = Interpolation::Bezier { x1, y1, x2, y2 }.ease(t);
+        prop_assert!((-EPSILON..=1.0 + EPSILON).contains(&value));
+    }
+}
@@ -0,0 +1,1683 @@
+// 几何基础代码的单元测试和基于随机输入的性质测试。
+// 求交部分测试的是 rendering::cpu 中与 ray_tracing.wgsl 对应的实现
+
+use image::{ImageBuffer, Luma};
+use nalgebra::{Matrix3, Point3, Scale3, Translation3, Unit, UnitQuaternion, Vector2, Vector3};
+use proptest::prelude::*;
+use renderer_core::rendering::bounding_box::BoundingBox;
+use renderer_core::rendering::bvh::{
+    bvh_refit_leaves, bvh_sah_cost, refit_bvh_tree, BvhNode, BvhRefitMode, BVH_GPU_REFIT_CHECK_INTERVAL,
+    BVH_REFIT_MAX_COST_RATIO,
+};
+use renderer_core::rendering::cpu::{sphere_span, Hit, HitRecord, ImportanceSampling, Ray};
+use renderer_core::rendering::interval::Interval;
+use renderer_core::rendering::material::{MaterialHandle, MaterialRegistry};
+use renderer_core::rendering::mesh::Mesh;
+use renderer_core::rendering::primitive::sphere::{Sphere, SphereData};
+use renderer_core::rendering::primitive::{
+    parse_curves, Bound, Csg, CsgData, CsgNode, CsgNodeData, Curve, CurveData, CurveKind, Heightfield, HeightfieldData,
+    Motion, MotionData, PrimitiveData, QuadData, Sdf, SdfData, SdfNode, ShapeData, ShapeKind, Transformable,
+};
+use renderer_core::rendering::scene_data::{BvhUpdate, SceneData};
+use std::rc::Rc;
+
+const EPSILON: f32 = 1e-3;
+
+const HANDLE: MaterialHandle = MaterialHandle {
+    material_type: 0,
+    material_id: 0,
+};
+
+fn approx_eq(a: f32, b: f32) -> bool {
+    (a - b).abs() <= EPSILON * a.abs().max(b.abs()).max(1.0)
+}
+
+fn vector_approx_eq(a: &Vector3<f32>, b: &Vector3<f32>) -> bool {
+    (0..3).all(|i| approx_eq(a[i], b[i]))
+}
+
+fn coordinate() -> impl Strategy<Value = f32> {
+    -10.0f32..10.0
+}
+
+fn point() -> impl Strategy<Value = Point3<f32>> {
+    (coordinate(), coordinate(), coordinate()).prop_map(|(x, y, z)| Point3::new(x, y, z))
+}
+
+fn vector() -> impl Strategy<Value = Vector3<f32>> {
+    (coordinate(), coordinate(), coordinate()).prop_map(|(x, y, z)| Vector3::new(x, y, z))
+}
+
+fn unit_vector() -> impl Strategy<Value = Vector3<f32>> {
+    vector()
+        .prop_filter("direction must not be degenerate", |v| v.norm() > 0.1)
+        .prop_map(|v| v.normalize())
+}
+
+fn interval() -> impl Strategy<Value = Interval> {
+    (coordinate(), coordinate()).prop_map(|(a, b)| Interval::new(a.min(b), a.max(b)))
+}
+
+// right 和 up 不共线且长度不太小
+fn quad() -> impl Strategy<Value = QuadData> {
+    (point(), vector(), vector())
+        .prop_filter("quad must not be degenerate", |(_, right, up)| {
+            right.norm() > 0.1 && up.norm() > 0.1 && right.cross(up).norm() > 0.05 * right.norm() * up.norm()
+        })
+        .prop_map(|(center, right, up)| QuadData::new(center, right, up, 0, 0))
+}
+
+fn sphere() -> impl Strategy<Value = SphereData> {
+    (point(), 0.1f32..5.0).prop_map(|(center, radius)| SphereData::new(center, radius, 0, 0))
+}
+
+// 先非均匀缩放再旋转得到的椭球
+fn ellipsoid() -> impl Strategy<Value = SphereData> {
+    (
+        sphere(),
+        unit_vector(),
+        -3.0f32..3.0,
+        (0.3f32..3.0, 0.3f32..3.0, 0.3f32..3.0),
+    )
+        .prop_map(|(mut sphere, axis, angle, (x, y, z))| {
+            let rotation = UnitQuaternion::from_axis_angle(&Unit::new_normalize(axis), angle);
+            sphere
+                .set_transform(rotation.to_rotation_matrix().matrix() * Matrix3::from_diagonal(&Vector3::new(x, y, z)));
+            sphere
+        })
+}
+
+fn shape_kind() -> impl Strategy<Value = ShapeKind> {
+    prop_oneof![
+        (0.1f32..3.0).prop_map(|radius| ShapeKind::Disk { radius }),
+        (0.1f32..3.0, 0.1f32..3.0).prop_map(|(radius, height)| ShapeKind::Cylinder { radius, height }),
+        (0.1f32..3.0, 0.1f32..3.0).prop_map(|(radius, height)| ShapeKind::Cone { radius, height }),
+        (0.5f32..3.0, 0.1f32..0.9).prop_map(|(major_radius, ratio)| ShapeKind::Torus {
+            major_radius,
+            minor_radius: major_radius * ratio,
+        }),
+        (0.1f32..3.0, 0.1f32..3.0, 0.1f32..3.0).prop_map(|(x, y, z)| ShapeKind::Box {
+            size: Vector3::new(x, y, z)
+        }),
+    ]
+}
+
+// 与 ellipsoid 一样先非均匀缩放再旋转
+fn shape() -> impl Strategy<Value = ShapeData> {
+    (
+        shape_kind(),
+        point(),
+        unit_vector(),
+        -3.0f32..3.0,
+        (0.3f32..3.0, 0.3f32..3.0, 0.3f32..3.0),
+    )
+        .prop_map(|(kind, center, axis, angle, (x, y, z))| {
+            let rotation = UnitQuaternion::from_axis_angle(&Unit::new_normalize(axis), angle);
+            let mut shape = ShapeData::new(&kind, center, 0, 0);
+            shape
+                .set_transform(rotation.to_rotation_matrix().matrix() * Matrix3::from_diagonal(&Vector3::new(x, y, z)));
+            shape
+        })
+}
+
+fn sdf_leaf() -> impl Strategy<Value = SdfNode> {
+    let leaf = prop_oneof![
+        (0.1f32..2.0).prop_map(|radius| SdfNode::Sphere { radius }),
+        (0.1f32..2.0, 0.1f32..2.0, 0.1f32..2.0).prop_map(|(x, y, z)| SdfNode::Box {
+            size: Vector3::new(x, y, z)
+        }),
+        (0.1f32..2.0, 0.1f32..2.0, 0.1f32..2.0, 0.0f32..0.5).prop_map(|(x, y, z, radius)| SdfNode::RoundedBox {
+            size: Vector3::new(x, y, z),
+            radius,
+        }),
+        (0.3f32..2.0, 0.1f32..0.9).prop_map(|(major_radius, ratio)| SdfNode::Torus {
+            major_radius,
+            minor_radius: major_radius * ratio,
+        }),
+        (0.1f32..1.0, 0.0f32..2.0).prop_map(|(radius, height)| SdfNode::Capsule { radius, height }),
+    ];
+    (leaf, unit_vector(), -3.0f32..3.0, vector()).prop_map(|(leaf, axis, angle, translation)| {
+        leaf.rotated(UnitQuaternion::from_axis_angle(&Unit::new_normalize(axis), angle))
+            .translated(Translation3::from(translation / 5.0))
+    })
+}
+
+// 两个叶子的布尔运算，或者单个叶子
+fn sdf_node() -> impl Strategy<Value = SdfNode> {
+    prop_oneof![
+        sdf_leaf(),
+        (sdf_leaf(), sdf_leaf(), 0..3, 0.0f32..0.5).prop_map(|(a, b, operation, smoothness)| match operation {
+            0 => a.union(b, smoothness),
+            1 => a.subtract(b, smoothness),
+            _ => a.intersect(b, smoothness),
+        }),
+    ]
+}
+
+fn sdf() -> impl Strategy<Value = SdfData> {
+    (
+        sdf_node(),
+        point(),
+        unit_vector(),
+        -3.0f32..3.0,
+        (0.3f32..3.0, 0.3f32..3.0, 0.3f32..3.0),
+    )
+        .prop_map(|(node, center, axis, angle, (x, y, z))| {
+            let mut sdf = Sdf::new(&node, center, HANDLE);
+            sdf.scale(Scale3::new(x, y, z));
+            sdf.rotate(UnitQuaternion::from_axis_angle(&Unit::new_normalize(axis), angle));
+            sdf_data(&mut sdf)
+        })
+}
+
+fn sdf_data(sdf: &mut Sdf) -> SdfData {
+    let mut primitives = Vec::new();
+    sdf.primitives(&mut primitives, &mut Vec::new());
+    match *primitives[0] {
+        PrimitiveData::Sdf(sdf_data) => sdf_data,
+        _ => unreachable!(),
+    }
+}
+
+// 先非均匀缩放再旋转的叶子
+fn csg_leaf() -> impl Strategy<Value = CsgNode> {
+    let leaf = prop_oneof![
+        (0.1f32..2.0).prop_map(|radius| CsgNode::Sphere { radius }),
+        (0.1f32..2.0, 0.1f32..2.0, 0.1f32..2.0).prop_map(|(x, y, z)| CsgNode::Box {
+            size: Vector3::new(x, y, z)
+        }),
+        (0.1f32..2.0, 0.1f32..2.0).prop_map(|(radius, height)| CsgNode::Cylinder { radius, height }),
+    ];
+    (
+        leaf,
+        (0.5f32..2.0, 0.5f32..2.0, 0.5f32..2.0),
+        unit_vector(),
+        -3.0f32..3.0,
+        vector(),
+    )
+        .prop_map(|(leaf, (x, y, z), axis, angle, translation)| {
+            leaf.scaled(Scale3::new(x, y, z))
+                .rotated(UnitQuaternion::from_axis_angle(&Unit::new_normalize(axis), angle))
+                .translated(Translation3::from(translation / 5.0))
+        })
+}
+
+// 三个叶子的布尔运算，或者更简单的树
+fn csg_node() -> impl Strategy<Value = CsgNode> {
+    fn operation(a: CsgNode, b: CsgNode, operation: u32) -> CsgNode {
+        match operation {
+            0 => a.union(b),
+            1 => a.subtract(b),
+            _ => a.intersect(b),
+        }
+    }
+    prop_oneof![
+        csg_leaf(),
+        (csg_leaf(), csg_leaf(), 0..3u32).prop_map(|(a, b, op)| operation(a, b, op)),
+        (csg_leaf(), csg_leaf(), csg_leaf(), 0..3u32, 0..3u32).prop_map(|(a, b, c, op_ab, op)| operation(
+            operation(a, b, op_ab),
+            c,
+            op
+        )),
+    ]
+}
+
+fn csg() -> impl Strategy<Value = CsgData> {
+    (
+        csg_node(),
+        point(),
+        unit_vector(),
+        -3.0f32..3.0,
+        (0.3f32..3.0, 0.3f32..3.0, 0.3f32..3.0),
+    )
+        .prop_map(|(node, center, axis, angle, (x, y, z))| {
+            let mut csg = Csg::new(node, center, HANDLE);
+            csg.scale(Scale3::new(x, y, z));
+            csg.rotate(UnitQuaternion::from_axis_angle(&Unit::new_normalize(axis), angle));
+            csg_data(&mut csg)
+        })
+}
+
+fn csg_data(csg: &mut Csg) -> CsgData {
+    let mut primitives = Vec::new();
+    csg.primitives(&mut primitives, &mut Vec::new());
+    match *primitives[0] {
+        PrimitiveData::Csg(csg_data) => csg_data,
+        _ => unreachable!(),
+    }
+}
+
+// 一段曲线，控制点在起点附近，宽度变化不大
+fn curve() -> impl Strategy<Value = CurveData> {
+    (
+        prop_oneof![Just(CurveKind::Ribbon), Just(CurveKind::Tube)],
+        point(),
+        [vector(), vector(), vector()],
+        [0.1f32..0.3, 0.1f32..0.3, 0.1f32..0.3, 0.1f32..0.3],
+    )
+        .prop_map(|(kind, start, offsets, widths)| {
+            let mut control_points = vec![start];
+            for offset in offsets {
+                control_points.push(control_points.last().unwrap() + offset / 5.0);
+            }
+            curve_data(&mut Curve::new(kind, control_points, widths.to_vec(), HANDLE))[0]
+        })
+}
+
+fn curve_data(curve: &mut Curve) -> Vec<CurveData> {
+    let mut primitives = Vec::new();
+    curve.primitives(&mut primitives, &mut Vec::new());
+    primitives
+        .iter()
+        .map(|primitive| match **primitive {
+            PrimitiveData::Curve(curve_data) => curve_data,
+            _ => unreachable!(),
+        })
+        .collect()
+}
+
+// 不超过 3 × 3 块的高度场，格子边长在 0.2 到 1 之间，随机旋转和平移
+fn heightfield() -> impl Strategy<Value = Vec<HeightfieldData>> {
+    (2usize..20, 2usize..20)
+        .prop_flat_map(|(width, depth)| {
+            (
+                Just(width),
+                Just(depth),
+                prop::collection::vec(0.0f32..1.0, width * depth),
+                (0.2f32..1.0, 0.2f32..1.0, 0.1f32..3.0),
+                unit_vector(),
+                0.0f32..3.0,
+                vector(),
+            )
+        })
+        .prop_map(
+            |(width, depth, heights, (cell_x, cell_z, height_scale), axis, angle, translation)| {
+                let size = Vector2::new(cell_x * (width - 1) as f32, cell_z * (depth - 1) as f32);
+                let mut heightfield = Heightfield::new(width, depth, heights, size, height_scale, HANDLE);
+                heightfield.rotate(UnitQuaternion::from_axis_angle(&Unit::new_normalize(axis), angle));
+                heightfield.translate(Translation3::from(translation));
+                heightfield_data(&mut heightfield)
+            },
+        )
+}
+
+fn heightfield_data(heightfield: &mut Heightfield) -> Vec<HeightfieldData> {
+    let mut primitives = Vec::new();
+    heightfield.primitives(&mut primitives, &mut Vec::new());
+    assert_eq!(primitives.len(), heightfield.tile_count());
+    primitives
+        .iter()
+        .map(|primitive| match **primitive {
+            PrimitiveData::Heightfield(heightfield_data) => heightfield_data,
+            _ => unreachable!(),
+        })
+        .collect()
+}
+
+fn primitive() -> impl Strategy<Value = PrimitiveData> {
+    prop_oneof![
+        quad().prop_map(PrimitiveData::Quad),
+        sphere().prop_map(PrimitiveData::Sphere),
+        shape().prop_map(PrimitiveData::Shape),
+        sdf().prop_map(PrimitiveData::Sdf),
+        csg().prop_map(PrimitiveData::Csg),
+        curve().prop_map(PrimitiveData::Curve),
+        heightfield().prop_map(|tiles| PrimitiveData::Heightfield(tiles[0]))
+    ]
+}
+
+// 旋转角小于 180°
+fn motion() -> impl Strategy<Value = MotionData> {
+    (point(), unit_vector(), 0.0f32..3.0, vector()).prop_map(|(pivot, axis, angle, translation)| {
+        MotionData::from(&Motion {
+            pivot,
+            rotation: UnitQuaternion::from_axis_angle(&Unit::new_normalize(axis), angle),
+            translation: Translation3::from(translation),
+        })
+    })
+}
+
+fn contains_point(bounding_box: &BoundingBox, p: &Point3<f32>) -> bool {
+    (0..3).all(|axis| {
+        let interval = bounding_box.axis(axis);
+        interval.min() - EPSILON <= p[axis as usize] && p[axis as usize] <= interval.max() + EPSILON
+    })
+}
+
+fn contains_box(outer: &BoundingBox, inner: &BoundingBox) -> bool {
+    (0..3).all(|axis| {
+        outer.axis(axis).min() <= inner.axis(axis).min() && inner.axis(axis).max() <= outer.axis(axis).max()
+    })
+}
+
+fn scene_data(primitives: &[PrimitiveData]) -> SceneData {
+    let primitives: Vec<_> = primitives.iter().map(|primitive| Rc::new(*primitive)).collect();
+    SceneData::new(&primitives, &[], &MaterialRegistry::default())
+}
+
+/*----------------------------------------- Interval --------------------------------------------*/
+
+proptest! {
+    #[test]
+    fn interval_merge_covers_both(a in interval(), b in interval()) {
+        let mut merged = a;
+        merged.merge(&b);
+
+        prop_assert_eq!(*merged.min(), a.min().min(*b.min()));
+        prop_assert_eq!(*merged.max(), a.max().max(*b.max()));
+
+        let mut reversed = b;
+        reversed.merge(&a);
+        prop_assert_eq!((*merged.min(), *merged.max()), (*reversed.min(), *reversed.max()));
+    }
+
+    #[test]
+    fn interval_merge_with_empty_is_identity(a in interval()) {
+        let mut merged = Interval::empty();
+        merged.merge(&a);
+        prop_assert_eq!((*merged.min(), *merged.max()), (*a.min(), *a.max()));
+    }
+
+    #[test]
+    fn interval_expand_keeps_center(a in interval(), delta in 0.0f32..5.0) {
+        let mut expanded = a;
+        expanded.expand(delta);
+
+        prop_assert!(approx_eq(expanded.size(), a.size() + delta));
+        prop_assert!(approx_eq(expanded.min() + expanded.max(), a.min() + a.max()));
+    }
+}
+
+#[test]
+fn interval_contains_and_surrounds_differ_at_bounds() {
+    let interval = Interval::new(0.0, 1.0);
+    assert!(interval.contains(0.0) && interval.contains(1.0));
+    assert!(!interval.surrounds(0.0) && !interval.surrounds(1.0));
+    assert!(interval.surrounds(0.5));
+    assert_eq!(interval.clamp(2.0), 1.0);
+}
+
+/*--------------------------------------- Bounding Box ------------------------------------------*/
+
+proptest! {
+    #[test]
+    fn longest_axis_is_the_largest_extent(origin in point(), sizes in (0.01f32..10.0, 0.01f32..10.0, 0.01f32..10.0)) {
+        let sizes = [sizes.0, sizes.1, sizes.2];
+        let expected = (0..3).max_by(|a, b| sizes[*a].total_cmp(&sizes[*b])).unwrap();
+        prop_assume!((0..3).filter(|i| *i != expected).all(|i| sizes[expected] - sizes[i] > 1e-3));
+
+        let bounding_box = BoundingBox::new_from_points(origin, origin + Vector3::new(sizes[0], sizes[1], sizes[2]));
+        prop_assert_eq!(bounding_box.longest_axis(), expected as i32);
+    }
+
+    #[test]
+    fn bounding_box_from_points_contains_both(a in point(), b in point()) {
+        let bounding_box = BoundingBox::new_from_points(a, b);
+        for axis in 0..3 {
+            let interval = bounding_box.axis(axis);
+            prop_assert!(interval.contains(a[axis as usize]) && interval.contains(b[axis as usize]));
+            prop_assert!(interval.size() >= 1e-4 * 0.999);
+        }
+    }
+
+    #[test]
+    fn merged_box_contains_both(a in (point(), point()), b in (point(), point())) {
+        let a = BoundingBox::new_from_points(a.0, a.1);
+        let b = BoundingBox::new_from_points(b.0, b.1);
+        let mut merged = a;
+        merged.merge(&b);
+
+        prop_assert!(contains_box(&merged, &a) && contains_box(&merged, &b));
+    }
+}
+
+/*------------------------------------------- Quad ----------------------------------------------*/
+
+proptest! {
+    #[test]
+    fn quad_data_matches_plane_equation(center in point(), right in vector(), up in vector()) {
+        let n = right.cross(&up);
+        prop_assume!(n.norm() > 1e-2);
+
+        let quad = QuadData::new(center, right, up, 0, 0);
+
+        prop_assert!(approx_eq(quad.normal().norm(), 1.0));
+        prop_assert!(approx_eq(quad.normal().dot(&right) / right.norm(), 0.0));
+        prop_assert!(approx_eq(quad.normal().dot(&up) / up.norm(), 0.0));
+        prop_assert!(vector_approx_eq(&quad.bottom_left().coords, &(center - right / 2.0 - up / 2.0).coords));
+        prop_assert!(approx_eq(quad.area(), n.norm()));
+        prop_assert!(approx_eq(quad.w().dot(&n), 1.0));
+
+        for corner in [Vector3::zeros(), right, up, right + up] {
+            let p = quad.bottom_left() + corner;
+            prop_assert!(approx_eq(quad.normal().dot(&p.coords), quad.d()));
+        }
+    }
+
+    #[test]
+    fn quad_bounding_box_contains_corners(quad in quad()) {
+        let bounding_box = quad.bounding_box();
+        for corner in [Vector3::zeros(), quad.right(), quad.up(), quad.right() + quad.up()] {
+            let p = quad.bottom_left() + corner;
+            for axis in 0..3 {
+                let interval = bounding_box.axis(axis);
+                prop_assert!(interval.min() - EPSILON <= p[axis as usize] && p[axis as usize] <= interval.max() + EPSILON);
+            }
+        }
+    }
+}
+
+/*------------------------------------------ Sphere ---------------------------------------------*/
+
+proptest! {
+    #[test]
+    fn sphere_bounding_box_is_tight(sphere in sphere(), direction in unit_vector()) {
+        let bounding_box = sphere.bounding_box();
+        for axis in 0..3 {
+            let interval = bounding_box.axis(axis);
+            prop_assert!(approx_eq(*interval.min(), sphere.center()[axis as usize] - sphere.radius()));
+            prop_assert!(approx_eq(*interval.max(), sphere.center()[axis as usize] + sphere.radius()));
+        }
+
+        let surface_point = sphere.center() + direction * sphere.radius();
+        for axis in 0..3 {
+            let interval = bounding_box.axis(axis);
+            prop_assert!(interval.min() - EPSILON <= surface_point[axis as usize]);
+            prop_assert!(surface_point[axis as usize] <= interval.max() + EPSILON);
+        }
+    }
+}
+
+proptest! {
+    #[test]
+    fn ellipsoid_bounding_box_contains_surface(sphere in ellipsoid(), direction in unit_vector()) {
+        let surface_point = sphere.center() + sphere.transform() * (direction * sphere.radius());
+        prop_assert!(contains_point(&sphere.bounding_box(), &surface_point));
+        prop_assert!((surface_point - sphere.center()).norm() <= sphere.bounding_radius() * (1.0 + EPSILON));
+    }
+
+    #[test]
+    fn ellipsoid_bounding_box_is_tight(sphere in ellipsoid()) {
+        // 每个轴上包围盒的边界恰好是椭球在该方向上的最远点
+        let transform = sphere.transform();
+        let bounding_box = sphere.bounding_box();
+        for axis in 0..3 {
+            let extreme = transform.row(axis).transpose().normalize() * sphere.radius();
+            let farthest = sphere.center() + transform * extreme;
+            prop_assert!(approx_eq(*bounding_box.axis(axis as i32).max(), farthest[axis]));
+        }
+    }
+}
+
+#[test]
+fn sphere_mesh_rotates_and_scales() {
+    let handle = MaterialHandle {
+        material_type: 0,
+        material_id: 0,
+    };
+    let mut sphere = Sphere::new(Point3::new(1.0, 0.0, 0.0), 1.0, handle, false);
+    sphere.scale(Scale3::new(2.0, 1.0, 1.0));
+    sphere.rotate(UnitQuaternion::from_axis_angle(
+        &Vector3::z_axis(),
+        std::f32::consts::FRAC_PI_2,
+    ));
+
+    let mut primitives = Vec::new();
+    sphere.primitives(&mut primitives, &mut Vec::new());
+    let bounding_box = primitives[0].bounding_box();
+
+    // 沿 x 拉长的椭球转到了 y 轴上
+    let expected = [(-1.0, 1.0), (0.0, 4.0), (-1.0, 1.0)];
+    for (axis, (min, max)) in expected.into_iter().enumerate() {
+        let interval = bounding_box.axis(axis as i32);
+        assert!(
+            approx_eq(*interval.min(), min) && approx_eq(*interval.max(), max),
+            "axis {axis}: {interval:?}"
+        );
+    }
+}
+
+/*------------------------------------------ Shape ----------------------------------------------*/
+
+// 局部坐标中的采样点变换到世界坐标
+fn shape_sample(shape: &ShapeData, u: Vector2<f32>) -> Point3<f32> {
+    shape.center() + shape.transform() * shape.object_sample(u).coords
+}
+
+// 局部坐标中采样点处的法线，不区分朝向
+fn shape_object_normal(shape: &ShapeData, p: &Point3<f32>) -> Vector3<f32> {
+    let parameters = shape.parameters();
+    let (radius, half_height) = (parameters.x, parameters.y);
+    let radial = Vector3::new(p.x, 0.0, p.z).normalize();
+    match shape.kind() {
+        ShapeKind::CYLINDER if p.y.abs() < half_height * (1.0 - EPSILON) => radial,
+        ShapeKind::CONE if p.y > -half_height * (1.0 - EPSILON) => {
+            (radial + Vector3::new(0.0, radius / (2.0 * half_height), 0.0)).normalize()
+        }
+        ShapeKind::TORUS => (p.coords - radial * parameters.x).normalize(),
+        ShapeKind::BOX => {
+            let axis = (0..3).max_by(|&a, &b| (p[a] / parameters[a]).abs().total_cmp(&(p[b] / parameters[b]).abs()));
+            Vector3::ith(axis.unwrap(), 1.0)
+        }
+        _ => Vector3::y(),
+    }
+}
+
+proptest! {
+    #[test]
+    fn shape_bounding_box_contains_samples(shape in shape(), u in (0.0f32..1.0, 0.0f32..1.0)) {
+        let p = shape_sample(&shape, Vector2::new(u.0, u.1));
+        prop_assert!(contains_point(&shape.bounding_box(), &p), "{:?} outside {:?}", p, shape.bounding_box());
+    }
+
+    #[test]
+    fn ray_hits_shape_at_sampled_point(
+        shape in shape(),
+        u in (0.0f32..1.0, 0.0f32..1.0),
+        direction in unit_vector(),
+        distance in 0.5f32..10.0,
+    ) {
+        // 在局部坐标中擦边时交点对误差过于敏感
+        let object_point = shape.object_sample(Vector2::new(u.0, u.1));
+        let direction = (shape.transform() * steep_direction(direction, &shape_object_normal(&shape, &object_point))).normalize();
+
+        // 只在采样点附近找交点，避免被形状的其他部分挡住。
+        // 区间不能离表面太近，环面会把起点所在的表面当作上一次的交点跳过
+        let p = shape_sample(&shape, Vector2::new(u.0, u.1));
+        let ray = Ray::new(p - direction * distance, direction);
+        let interval = Interval::new(distance * 0.99, distance * 1.01);
+        prop_assert!(shape.hit(&ray, &interval).is_some(), "missed {:?}", p);
+    }
+}
+
+// 均匀分布在单位球面上的方向
+fn fibonacci_direction(i: u32, count: u32) -> Vector3<f32> {
+    let golden_angle = std::f32::consts::PI * (3.0 - 5.0f32.sqrt());
+    let y = 1.0 - 2.0 * (i as f32 + 0.5) / count as f32;
+    let r = (1.0 - y * y).sqrt();
+    let phi = golden_angle * i as f32;
+    Vector3::new(r * phi.cos(), y, r * phi.sin())
+}
+
+#[test]
+fn shape_pdf_integrates_to_one() {
+    const COUNT: u32 = 40000;
+    let kinds = [
+        ShapeKind::Disk { radius: 1.0 },
+        ShapeKind::Cylinder {
+            radius: 0.5,
+            height: 1.5,
+        },
+        ShapeKind::Cone {
+            radius: 0.8,
+            height: 1.2,
+        },
+        ShapeKind::Torus {
+            major_radius: 1.0,
+            minor_radius: 0.3,
+        },
+        ShapeKind::Box {
+            size: Vector3::new(1.0, 0.5, 2.0),
+        },
+    ];
+    let rotation = UnitQuaternion::from_axis_angle(&Vector3::x_axis(), 0.7);
+    let transform = rotation.to_rotation_matrix().matrix() * Matrix3::from_diagonal(&Vector3::new(1.2, 0.8, 1.0));
+    let origin = Point3::new(0.5, 1.0, 3.0);
+
+    for kind in kinds {
+        let mut shape = ShapeData::new(&kind, Point3::origin(), 0, 0);
+        shape.set_transform(transform);
+
+        // 对所有方向积分，立体角上的概率密度之和应该是 1
+        let integral = (0..COUNT)
+            .map(|i| shape.pdf_value(&Ray::new(origin, fibonacci_direction(i, COUNT))))
+            .sum::<f32>()
+            * 4.0
+            * std::f32::consts::PI
+            / COUNT as f32;
+        assert!((integral - 1.0).abs() < 0.05, "{kind:?}: {integral}");
+
+        // 采样得到的方向一定能打到形状上
+        let direction = shape.random(&origin, Vector2::new(0.3, 0.6));
+        assert!(shape.pdf_value(&Ray::new(origin, direction)) > 0.0, "{kind:?}");
+    }
+}
+
+/*-------------------------------------------- SDF ----------------------------------------------*/
+
+// 球面追踪在距离小于这个值时停止，与 Sdf_hit 一致
+fn sdf_epsilon(sdf: &SdfData) -> f32 {
+    1e-4 * (sdf.bounds_max() - sdf.bounds_min()).norm()
+}
+
+proptest! {
+    #[test]
+    fn sdf_sphere_matches_analytic_sphere(
+        sphere in sphere(),
+        direction in unit_vector(),
+        tilt in unit_vector(),
+        distance in 0.5f32..10.0,
+    ) {
+        let sdf = sdf_data(&mut Sdf::new(&SdfNode::Sphere { radius: sphere.radius() }, sphere.center(), HANDLE));
+
+        // 从切平面外侧射向球面上的 target
+        let target = sphere.center() + direction * sphere.radius();
+        let toward = steep_direction(tilt, &direction);
+        let toward = if toward.dot(&direction) > 0.0 { -toward } else { toward };
+        let ray = Ray::new(target - toward * distance, toward);
+
+        let hit = sdf.hit(&ray, &universe());
+        prop_assert!(hit.is_some());
+        let hit = hit.unwrap();
+        // 距离小于 epsilon 时停止，光线与法线的夹角余弦至少是 1/3
+        prop_assert!((hit.ray_t - distance).abs() <= 3.0 * sdf_epsilon(&sdf) + EPSILON, "t {} expected {}", hit.ray_t, distance);
+        prop_assert!(hit.is_front_face);
+        prop_assert!((hit.normal - direction).norm() < 1e-2, "normal {:?} expected {:?}", hit.normal, direction);
+    }
+
+    #[test]
+    fn sdf_smooth_union_is_below_both_children(
+        a in sdf_leaf(),
+        b in sdf_leaf(),
+        smoothness in 0.0f32..1.0,
+        p in point(),
+    ) {
+        let distance_a = sdf_data(&mut Sdf::new(&a, Point3::origin(), HANDLE)).distance(&p);
+        let distance_b = sdf_data(&mut Sdf::new(&b, Point3::origin(), HANDLE)).distance(&p);
+        let union = sdf_data(&mut Sdf::new(&a.union(b, smoothness), Point3::origin(), HANDLE)).distance(&p);
+
+        // 平滑的并集比 min 小，但最多小 smoothness / 4
+        let min = distance_a.min(distance_b);
+        prop_assert!(union <= min + EPSILON);
+        prop_assert!(union >= min - smoothness / 4.0 - EPSILON);
+    }
+
+    #[test]
+    fn sdf_bounding_box_contains_interior(sdf in sdf()) {
+        // 在 SDF 坐标中放大一倍的包围盒里取网格点，距离场内部的点都要在世界坐标的包围盒中
+        let (bounds_min, bounds_max) = (sdf.bounds_min(), sdf.bounds_max());
+        let middle = (bounds_min + bounds_max) / 2.0;
+        let bounding_box = sdf.bounding_box();
+        for i in 0..8 * 8 * 8 {
+            let grid = Vector3::new(i % 8, i / 8 % 8, i / 64).cast::<f32>() / 7.0 * 2.0 - Vector3::repeat(1.0);
+            let q = Point3::from(middle + (bounds_max - bounds_min).component_mul(&grid));
+            if sdf.distance(&q) <= 0.0 {
+                let p = sdf.center() + sdf.transform() * q.coords;
+                prop_assert!(contains_point(&bounding_box, &p), "{:?} outside {:?}", p, bounding_box);
+            }
+        }
+    }
+
+    #[test]
+    fn ray_hits_sdf_on_its_surface(sdf in sdf(), origin in point(), direction in unit_vector()) {
+        if let Some(hit) = sdf.hit(&Ray::new(origin, direction), &universe()) {
+            let inverse = sdf.inverse_transform();
+            let q = Point3::from(inverse * (hit.position - sdf.center()));
+            prop_assert!(sdf.distance(&q).abs() <= 2.0 * sdf_epsilon(&sdf), "distance {}", sdf.distance(&q));
+
+            // 着色法线是中心差分的梯度变换到世界坐标
+            let gradient = inverse.transpose() * sdf.normal(&q, sdf_epsilon(&sdf));
+            prop_assert!(approx_eq(hit.normal.dot(&gradient.normalize()).abs(), 1.0));
+            prop_assert!(approx_eq(hit.tangent.dot(&hit.normal), 0.0) && approx_eq(hit.tangent.norm(), 1.0));
+        }
+    }
+}
+
+/*-------------------------------------------- CSG ----------------------------------------------*/
+
+// 世界坐标中的点是否在叶子内
+fn csg_leaf_contains(node: &CsgNodeData, p: &Point3<f32>) -> bool {
+    let q = node.inverse_transform() * (p - node.center());
+    let parameters = node.parameters();
+    match node.kind() {
+        CsgNode::SPHERE => q.norm() <= parameters.x,
+        CsgNode::BOX => (0..3).all(|axis| q[axis].abs() <= parameters[axis]),
+        _ => q.y.abs() <= parameters.y && q.xz().norm() <= parameters.x,
+    }
+}
+
+// 按每个叶子的内外状态对布尔树求值
+fn csg_contains(csg: &CsgData, p: &Point3<f32>) -> bool {
+    let inside = csg
+        .nodes()
+        .iter()
+        .enumerate()
+        .filter(|(_, node)| node.is_leaf() && csg_leaf_contains(node, p))
+        .fold(0, |inside, (i, _)| inside | 1 << i);
+    csg.evaluate(inside)
+}
+
+proptest! {
+    #[test]
+    fn sphere_span_ends_on_the_sphere(sphere in sphere(), origin in point(), direction in unit_vector()) {
+        let span = sphere_span(&Point3::from(origin - sphere.center()), &direction, sphere.radius());
+        if span.min() <= span.max() {
+            for t in [*span.min(), *span.max()] {
+                let p = origin + t * direction;
+                prop_assert!(approx_eq((p - sphere.center()).norm(), sphere.radius()), "t {} off the sphere", t);
+            }
+        } else {
+            // 不相交时球心到直线的距离大于半径
+            let oc = sphere.center() - origin;
+            prop_assert!((oc - direction * oc.dot(&direction)).norm() >= sphere.radius() * (1.0 - EPSILON));
+        }
+    }
+
+    #[test]
+    fn csg_leaf_span_matches_membership(leaf in csg_leaf(), origin in point(), direction in unit_vector(), s in 0.01f32..0.99) {
+        let csg = csg_data(&mut Csg::new(leaf, Point3::origin(), HANDLE));
+        let ray = Ray::new(origin, direction);
+        let span = csg.leaf_span(0, &ray);
+        let node = &csg.nodes()[0];
+        if span.min() <= span.max() {
+            let t = span.min() + s * span.size();
+            prop_assert!(csg_leaf_contains(node, &ray.at(t)));
+            prop_assert!(!csg_leaf_contains(node, &ray.at(span.min() - 1e-2)));
+            prop_assert!(!csg_leaf_contains(node, &ray.at(span.max() + 1e-2)));
+        } else {
+            prop_assert!(!csg_leaf_contains(node, &ray.at(origin.coords.dot(&-direction))));
+        }
+    }
+
+    #[test]
+    fn csg_lens_matches_analytic_spheres(
+        radius in 0.2f32..3.0,
+        overlap in 0.05f32..0.95,
+        center in point(),
+        origin in point(),
+        jitter in vector(),
+    ) {
+        // 两个球心相距 2d，d < r 时交集是透镜。光线射向透镜中心附近
+        let direction = (center + jitter * radius / 10.0 - origin).normalize();
+        let d = radius * (1.0 - overlap);
+        let lens = CsgNode::Sphere { radius }
+            .translated(Translation3::new(0.0, 0.0, -d))
+            .intersect(CsgNode::Sphere { radius }.translated(Translation3::new(0.0, 0.0, d)));
+        let csg = csg_data(&mut Csg::new(lens, center, HANDLE));
+
+        let span = |z: f32| sphere_span(&Point3::from(origin - center - Vector3::new(0.0, 0.0, z)), &direction, radius);
+        let (a, b) = (span(-d), span(d));
+        let (near, far) = (a.min().max(*b.min()), a.max().min(*b.max()));
+        let hit = csg.hit(&Ray::new(origin, direction), &universe());
+        if near < far && near > 0.01 {
+            prop_assert!(hit.is_some());
+            let hit = hit.unwrap();
+            prop_assert!(approx_eq(hit.ray_t, near), "t {} expected {}", hit.ray_t, near);
+            prop_assert!(hit.is_front_face);
+        } else if near >= far || far < 0.0 {
+            prop_assert!(hit.is_none());
+        }
+    }
+
+    #[test]
+    fn csg_hit_changes_membership(csg in csg(), origin in point(), jitter in vector()) {
+        // 光线射向包围盒中心附近
+        let direction = ((csg.bounds_min() + csg.bounds_max()) / 2.0 + jitter / 10.0 - origin.coords).normalize();
+        let ray = Ray::new(origin, direction);
+        if let Some(hit) = csg.hit(&ray, &universe()) {
+            // 附近还有别的端点时，两侧的内外状态取决于误差
+            let delta = 1e-3;
+            let isolated = (0..csg.nodes().len())
+                .map(|i| csg.leaf_span(i, &ray))
+                .filter(|span| span.min() <= span.max())
+                .flat_map(|span| [*span.min(), *span.max()])
+                .all(|t| (t - hit.ray_t).abs() < 1e-4 || (t - hit.ray_t).abs() > 4.0 * delta);
+            // 掠射时沿光线移动 delta 离表面太近，内外状态同样取决于误差
+            let grazing = hit.normal.dot(&ray.direction).abs() < 0.1;
+            if isolated && !grazing && hit.ray_t > 2.0 * delta {
+                // 正面是从外部进入整体
+                prop_assert_eq!(csg_contains(&csg, &ray.at(hit.ray_t + delta)), hit.is_front_face);
+                prop_assert_eq!(csg_contains(&csg, &ray.at(hit.ray_t - delta)), !hit.is_front_face);
+            }
+            prop_assert!(approx_eq(hit.normal.norm(), 1.0));
+            prop_assert!(approx_eq(hit.tangent.dot(&hit.normal), 0.0) && approx_eq(hit.tangent.norm(), 1.0));
+        }
+    }
+
+    #[test]
+    fn csg_bounding_box_contains_interior(csg in csg()) {
+        // 在放大一倍的包围盒里取网格点，整体内部的点都要在包围盒中
+        let bounding_box = csg.bounding_box();
+        let (bounds_min, bounds_max) = (csg.bounds_min(), csg.bounds_max());
+        let middle = (bounds_min + bounds_max) / 2.0;
+        for i in 0..8 * 8 * 8 {
+            let grid = Vector3::new(i % 8, i / 8 % 8, i / 64).cast::<f32>() / 7.0 * 2.0 - Vector3::repeat(1.0);
+            let p = Point3::from(middle + (bounds_max - bounds_min).component_mul(&grid));
+            if csg_contains(&csg, &p) {
+                prop_assert!(contains_point(&bounding_box, &p), "{:?} outside {:?}", p, bounding_box);
+            }
+        }
+    }
+}
+
+/*------------------------------------------- Curve ---------------------------------------------*/
+
+// 中心线上离 p 最近的采样点到 p 的距离
+fn curve_distance(curve: &CurveData, p: &Point3<f32>) -> f32 {
+    (0..=1000)
+        .map(|i| (curve.point(i as f32 / 1000.0).xyz() - p.coords).norm())
+        .fold(f32::MAX, f32::min)
+}
+
+proptest! {
+    #[test]
+    fn curve_bounding_box_contains_swept_width(curve in curve(), u in 0.0f32..1.0, direction in unit_vector()) {
+        let center = curve.point(u);
+        let p = Point3::from(center.xyz() + direction * center.w / 2.0);
+        prop_assert!(contains_point(&curve.bounding_box(), &p), "{:?} outside {:?}", p, curve.bounding_box());
+    }
+
+    #[test]
+    fn ray_hits_curve_near_sampled_point(curve in curve(), u in 0.05f32..0.95, origin in point()) {
+        // 光线穿过中心线上的一点，沿曲线方向的光线投影后退化，不测试
+        let center = curve.point(u);
+        let to_center = center.xyz() - origin.coords;
+        let direction = to_center.normalize();
+        prop_assume!(to_center.norm() > 1.0);
+        prop_assume!(curve.derivative(u).xyz().normalize().dot(&direction).abs() < 0.9);
+
+        let hit = curve.hit(&Ray::new(origin, direction), &universe());
+        prop_assert!(hit.is_some());
+        let hit = hit.unwrap();
+        let max_width = curve.control_points().iter().map(|p| p.w).fold(0.0, f32::max);
+        prop_assert!(hit.ray_t <= to_center.norm() + max_width, "t {} beyond {}", hit.ray_t, to_center.norm());
+        prop_assert!(curve_distance(&curve, &hit.position) <= 0.6 * max_width);
+
+        prop_assert!(hit.is_front_face);
+        prop_assert!(approx_eq(hit.normal.norm(), 1.0));
+        prop_assert!(hit.normal.dot(&direction) <= EPSILON);
+        prop_assert!(approx_eq(hit.tangent.dot(&hit.normal), 0.0) && approx_eq(hit.tangent.norm(), 1.0));
+        prop_assert!((0.0..=1.0).contains(&hit.uv.x) && (0.0..=1.0).contains(&hit.uv.y));
+    }
+
+    #[test]
+    fn ray_from_curve_surface_does_not_hit_it_again(curve in curve(), u in 0.05f32..0.95, origin in point(), out in unit_vector()) {
+        // 从交点出发的散射光线不会在同一条曲线的同一处再次相交
+        let center = curve.point(u);
+        let direction = (center.xyz() - origin.coords).normalize();
+        prop_assume!(curve.derivative(u).xyz().normalize().dot(&direction).abs() < 0.9);
+        if let Some(hit) = curve.hit(&Ray::new(origin, direction), &universe()) {
+            if let Some(again) = curve.hit(&Ray::new(hit.position, out), &universe()) {
+                let min_width = curve.control_points().iter().map(|p| p.w).fold(f32::MAX, f32::min);
+                prop_assert!((again.position - hit.position).norm() > 0.5 * min_width, "hit again at {}", again.ray_t);
+            }
+        }
+    }
+}
+
+#[test]
+fn curves_are_parsed_from_text() {
+    let source = "
+        # 两条曲线，一条两段，一条一段
+        tube
+        0 0 0 0.1
+        0 1 0 0.1
+        0 2 0 0.1
+        0 3 0 0.1  # 第一段的终点
+        1 3 0 0.1
+        2 3 0 0.1
+        3 3 0 0.05
+        ribbon
+        0 0 1 0.2
+        0 1 1 nan?
+        0 1 1 0.2
+        0 2 1 0.2
+        0 3 1 0.2
+        tube
+        0 0 2 0.1
+        0 1 2 0.1
+    ";
+    let mut curves = parse_curves(source, HANDLE);
+    assert_eq!(curves.len(), 2);
+    assert_eq!(curves[0].segment_count(), 2);
+    assert_eq!(curves[1].segment_count(), 1);
+
+    let segments = curve_data(&mut curves[0]);
+    assert_eq!(segments.len(), 2);
+    assert_eq!((segments[0].u_min(), segments[0].u_max()), (0.0, 0.5));
+    assert_eq!((segments[1].u_min(), segments[1].u_max()), (0.5, 1.0));
+    assert_eq!(segments[0].kind(), CurveKind::TUBE);
+    assert_eq!(segments[1].point(0.0), segments[0].point(1.0));
+    assert_eq!(segments[1].point(1.0).w, 0.05);
+    // 直的一段只需要一段折线
+    assert_eq!(segments[0].pieces(), 1);
+
+    // 缩放时宽度按几何平均缩放
+    curves[1].scale(Scale3::new(2.0, 4.0, 1.0));
+    let segment = curve_data(&mut curves[1])[0];
+    assert_eq!(segment.kind(), CurveKind::RIBBON);
+    assert!(approx_eq(segment.point(0.0).w, 0.4));
+    assert!(approx_eq(segment.point(1.0).y, 12.0));
+}
+
+/*----------------------------------------- Heightfield -----------------------------------------*/
+
+// 网格坐标 (x, z) 处的表面点，高度在所在格子的三角形上线性插值，同时返回它所在的块和三角形的斜率
+fn heightfield_surface(tiles: &[HeightfieldData], x: f32, z: f32) -> (HeightfieldData, Point3<f32>, Vector2<f32>) {
+    let tile = *tiles
+        .iter()
+        .find(|tile| {
+            (tile.bounds_min().x..=tile.bounds_max().x).contains(&x)
+                && (tile.bounds_min().z..=tile.bounds_max().z).contains(&z)
+        })
+        .unwrap();
+    let cell_x = x.floor().clamp(tile.bounds_min().x, tile.bounds_max().x - 1.0) as i32;
+    let cell_z = z.floor().clamp(tile.bounds_min().z, tile.bounds_max().z - 1.0) as i32;
+    let (fx, fz) = (x - cell_x as f32, z - cell_z as f32);
+
+    let height = |dx: i32, dz: i32| tile.height(cell_x + dx, cell_z + dz);
+    let slope = if fx >= fz {
+        Vector2::new(height(1, 0) - height(0, 0), height(1, 1) - height(1, 0))
+    } else {
+        Vector2::new(height(1, 1) - height(0, 1), height(0, 1) - height(0, 0))
+    };
+    let y = height(0, 0) + slope.dot(&Vector2::new(fx, fz));
+    (tile, Point3::new(x, y, z), slope)
+}
+
+fn heightfield_world(tile: &HeightfieldData, local: &Point3<f32>) -> Point3<f32> {
+    tile.origin() + tile.transform() * local.coords
+}
+
+fn heightfield_scene(tiles: &[HeightfieldData]) -> SceneData {
+    let primitives: Vec<_> = tiles.iter().map(|tile| PrimitiveData::Heightfield(*tile)).collect();
+    scene_data(&primitives)
+}
+
+// 沿局部坐标的 -y 方向从高处射向网格坐标 (x, z)
+fn heightfield_vertical_hit(scene_data: &SceneData, tiles: &[HeightfieldData], x: f32, z: f32) -> Option<HitRecord> {
+    let tile = &tiles[0];
+    let origin = heightfield_world(tile, &Point3::new(x, 2.0, z));
+    let direction = (tile.transform() * -Vector3::y()).normalize();
+    scene_data.hit(&Ray::new(origin, direction), &universe())
+}
+
+proptest! {
+    #[test]
+    fn heightfield_bounding_box_contains_surface(tiles in heightfield(), u in 0.0f32..1.0, v in 0.0f32..1.0) {
+        let grid_size = tiles[0].grid_size();
+        let (tile, local, _) = heightfield_surface(&tiles, u * grid_size.x, v * grid_size.y);
+        let p = heightfield_world(&tile, &local);
+        prop_assert!(contains_point(&tile.bounding_box(), &p), "{:?} outside {:?}", p, tile.bounding_box());
+    }
+
+    #[test]
+    fn vertical_ray_hits_heightfield_at_interpolated_height(tiles in heightfield(), u in 0.0f32..1.0, v in 0.0f32..1.0) {
+        let grid_size = tiles[0].grid_size();
+        let (tile, local, _) = heightfield_surface(&tiles, u * grid_size.x, v * grid_size.y);
+
+        let hit = heightfield_vertical_hit(&heightfield_scene(&tiles), &tiles, local.x, local.z);
+        prop_assert!(hit.is_some());
+        let hit = hit.unwrap();
+        let expected = heightfield_world(&tile, &local);
+        prop_assert!(vector_approx_eq(&hit.position.coords, &expected.coords), "hit {:?} expected {:?}", hit.position, expected);
+        prop_assert!(hit.is_front_face);
+        prop_assert!(approx_eq(hit.uv.x, u) && approx_eq(hit.uv.y, v));
+        prop_assert!(hit.geometric_normal.dot(&hit.in_direction) < 0.0);
+        prop_assert!(approx_eq(hit.normal.norm(), 1.0));
+        prop_assert!(approx_eq(hit.tangent.dot(&hit.geometric_normal), 0.0) && approx_eq(hit.tangent.norm(), 1.0));
+    }
+
+    #[test]
+    fn ray_hits_heightfield_on_its_surface(
+        tiles in heightfield(),
+        u in 0.0f32..1.0,
+        v in 0.0f32..1.0,
+        direction in unit_vector(),
+        distance in 0.5f32..20.0,
+    ) {
+        // 光线射向表面上的一点，可能先被别处挡住，但交点一定在表面上
+        let grid_size = tiles[0].grid_size();
+        let (tile, local, slope) = heightfield_surface(&tiles, u * grid_size.x, v * grid_size.y);
+        let target = heightfield_world(&tile, &local);
+        let normal = (tile.inverse_transform().transpose() * Vector3::new(-slope.x, 1.0, -slope.y)).normalize();
+        let direction = steep_direction(direction, &normal);
+
+        let hit = heightfield_scene(&tiles).hit(&Ray::new(target - direction * distance, direction), &universe());
+        prop_assert!(hit.is_some());
+        let hit = hit.unwrap();
+        prop_assert!(hit.ray_t <= distance + EPSILON * distance.max(1.0), "t {} beyond {}", hit.ray_t, distance);
+
+        let hit_local = Point3::from(tile.inverse_transform() * (hit.position - tile.origin()));
+        let (_, surface, _) = heightfield_surface(
+            &tiles,
+            hit_local.x.clamp(0.0, grid_size.x),
+            hit_local.z.clamp(0.0, grid_size.y),
+        );
+        prop_assert!(approx_eq(hit_local.y, surface.y), "hit {:?} surface {:?}", hit_local, surface);
+    }
+
+    #[test]
+    fn planar_heightfield_has_flat_normals(
+        (width, depth) in (2usize..30, 2usize..30),
+        (a, b, c) in (0.0f32..0.5, -0.5f32..0.0, 0.0f32..0.5),
+        (u, v) in (0.0f32..1.0, 0.0f32..1.0),
+    ) {
+        // 块边缘的法线用到网格外线性外推的采样点，平面上着色法线仍与几何法线一致
+        let heights = (0..depth)
+            .flat_map(|z| {
+                (0..width).map(move |x| c + a * x as f32 / (width - 1) as f32 + b * z as f32 / (depth - 1) as f32)
+            })
+            .collect();
+        let mut heightfield = Heightfield::new(width, depth, heights, Vector2::new(4.0, 3.0), 2.0, HANDLE);
+        let tiles = heightfield_data(&mut heightfield);
+        let grid_size = tiles[0].grid_size();
+
+        let hit = heightfield_vertical_hit(&heightfield_scene(&tiles), &tiles, u * grid_size.x, v * grid_size.y);
+        prop_assert!(hit.is_some());
+        let hit = hit.unwrap();
+        let expected = Vector3::new(-2.0 * a / 4.0, 1.0, -2.0 * b / 3.0).normalize();
+        prop_assert!(vector_approx_eq(&hit.geometric_normal, &expected), "{:?} expected {:?}", hit.geometric_normal, expected);
+        prop_assert!(vector_approx_eq(&hit.normal, &expected), "{:?} expected {:?}", hit.normal, expected);
+    }
+}
+
+#[test]
+fn heightfield_normals_are_continuous_across_tiles() {
+    let (width, depth) = (21, 6);
+    let heights = (0..depth)
+        .flat_map(|z| (0..width).map(move |x| 0.5 + 0.3 * (x as f32 * 0.7).sin() * (z as f32 * 0.9).cos()))
+        .collect();
+    let mut heightfield = Heightfield::new(width, depth, heights, Vector2::new(20.0, 5.0), 1.0, HANDLE);
+    let tiles = heightfield_data(&mut heightfield);
+    assert_eq!(tiles.len(), 3);
+
+    let scene_data = heightfield_scene(&tiles);
+    for boundary in [8.0, 16.0] {
+        let before = heightfield_vertical_hit(&scene_data, &tiles, boundary - 1e-3, 2.5).unwrap();
+        let after = heightfield_vertical_hit(&scene_data, &tiles, boundary + 1e-3, 2.5).unwrap();
+        assert!(
+            (before.normal - after.normal).norm() < 0.01,
+            "{:?} != {:?}",
+            before.normal,
+            after.normal
+        );
+    }
+}
+
+#[test]
+fn heightfield_is_loaded_from_16_bit_image() {
+    let image = ImageBuffer::from_fn(17, 9, |x, y| Luma([(x * 3000 + y * 1000) as u16]));
+    let path = std::env::temp_dir().join(format!("heightfield-{}.png", std::process::id()));
+    image.save(&path).unwrap();
+    let loaded = Heightfield::load(&path, Vector2::new(16.0, 8.0), 2.0, HANDLE);
+    std::fs::remove_file(&path).unwrap();
+
+    let mut heightfield = loaded.unwrap();
+    assert_eq!(heightfield.tile_count(), 2);
+    let tiles = heightfield_data(&mut heightfield);
+    assert_eq!(tiles[0].grid_size(), Vector2::new(16.0, 8.0));
+    assert_eq!(tiles[1].height(12, 5), (12 * 3000 + 5 * 1000) as f32 / u16::MAX as f32);
+
+    // 格子边长为 1，中心在原点
+    let hit = heightfield_vertical_hit(&heightfield_scene(&tiles), &tiles, 12.0, 5.0).unwrap();
+    let expected = 2.0 * (12 * 3000 + 5 * 1000) as f32 / u16::MAX as f32;
+    assert!(vector_approx_eq(
+        &hit.position.coords,
+        &Vector3::new(4.0, expected, 1.0)
+    ));
+
+    assert!(Heightfield::load(
+        std::env::temp_dir().join("missing-heightfield.png"),
+        Vector2::new(1.0, 1.0),
+        1.0,
+        HANDLE
+    )
+    .is_err());
+}
+
+/*------------------------------------------- BVH -----------------------------------------------*/
+
+proptest! {
+    #[test]
+    fn bvh_reaches_every_primitive_once(primitives in prop::collection::vec(primitive(), 1..40)) {
+        let scene_data = scene_data(&primitives);
+        let tree = scene_data.bvh_tree();
+
+        let mut reached = Vec::new();
+        let mut stack = vec![0usize];
+        while let Some(id) = stack.pop() {
+            let node = &tree[id];
+            if node.is_leaf == 1 {
+                reached.push((node.left_or_primitive_type, node.right_or_primitive_id));
+                continue;
+            }
+
+            for child in [node.left_or_primitive_type, node.right_or_primitive_id] {
+                let child = child as usize;
+                prop_assert_eq!(tree[child].parent as usize, id, "parent link of node {}", child);
+                prop_assert!(contains_box(&node.bounding_box, &tree[child].bounding_box), "child {} escapes parent {}", child, id);
+                stack.push(child);
+            }
+            prop_assert_ne!(node.left_or_primitive_type, node.right_or_primitive_id);
+        }
+
+        let mut expected: Vec<(u32, u32)> = (0..scene_data.quads().len() as u32).map(|id| (0, id))
+            .chain((0..scene_data.spheres().len() as u32).map(|id| (1, id)))
+            .chain((0..scene_data.shapes().len() as u32).map(|id| (2, id)))
+            .chain((0..scene_data.sdfs().len() as u32).map(|id| (3, id)))
+            .chain((0..scene_data.csgs().len() as u32).map(|id| (4, id)))
+            .chain((0..scene_data.curves().len() as u32).map(|id| (5, id)))
+            .chain((0..scene_data.heightfields().len() as u32).map(|id| (6, id)))
+            .collect();
+        reached.sort_unstable();
+        expected.sort_unstable();
+        prop_assert_eq!(reached, expected);
+    }
+
+    #[test]
+    fn bvh_leaf_boxes_contain_their_primitive(primitives in prop::collection::vec(primitive(), 1..40)) {
+        let scene_data = scene_data(&primitives);
+        for node in scene_data.bvh_tree().iter().filter(|node| node.is_leaf == 1) {
+            let primitive_box = match node.left_or_primitive_type {
+                0 => scene_data.quads()[node.right_or_primitive_id as usize].bounding_box(),
+                1 => scene_data.spheres()[node.right_or_primitive_id as usize].bounding_box(),
+                2 => scene_data.shapes()[node.right_or_primitive_id as usize].bounding_box(),
+                3 => scene_data.sdfs()[node.right_or_primitive_id as usize].bounding_box(),
+                4 => scene_data.csgs()[node.right_or_primitive_id as usize].bounding_box(),
+                5 => scene_data.curves()[node.right_or_primitive_id as usize].bounding_box(),
+                _ => scene_data.heightfields()[node.right_or_primitive_id as usize].bounding_box(),
+            };
+            prop_assert!(contains_box(&node.bounding_box, &primitive_box));
+        }
+    }
+}
+
+fn same_box(a: &BoundingBox, b: &BoundingBox) -> bool {
+    (0..3).all(|axis| a.axis(axis).min() == b.axis(axis).min() && a.axis(axis).max() == b.axis(axis).max())
+}
+
+// 叶节点是图元的包围盒，内部节点恰好是两个子节点的并
+fn assert_bvh_fitted(tree: &[BvhNode], leaf_box: impl Fn(u32, u32) -> BoundingBox) {
+    for (id, node) in tree.iter().enumerate() {
+        let expected = if node.is_leaf == 1 {
+            leaf_box(node.left_or_primitive_type, node.right_or_primitive_id)
+        } else {
+            BoundingBox::new_from_boxes(
+                &tree[node.left_or_primitive_type as usize].bounding_box,
+                &tree[node.right_or_primitive_id as usize].bounding_box,
+            )
+        };
+        assert!(same_box(&node.bounding_box, &expected), "node {id} is not fitted");
+    }
+}
+
+fn sphere_row(centers: impl Iterator<Item = f32>) -> Vec<Rc<PrimitiveData>> {
+    centers
+        .map(|x| {
+            Rc::new(PrimitiveData::Sphere(SphereData::new(
+                Point3::new(x, 0.0, 0.0),
+                0.4,
+                0,
+                0,
+            )))
+        })
+        .collect()
+}
+
+proptest! {
+    #[test]
+    fn bvh_refit_fits_moved_primitives(spheres in prop::collection::vec((sphere(), sphere()), 1..40)) {
+        let (before, after): (Vec<_>, Vec<_>) = spheres.into_iter().unzip();
+        let before: Vec<_> = before.into_iter().map(PrimitiveData::Sphere).collect();
+        let mut tree = scene_data(&before).bvh_tree().clone();
+
+        refit_bvh_tree(&mut tree, |primitive_type, primitive_id| {
+            assert_eq!(primitive_type, 1);
+            after[primitive_id as usize].bounding_box()
+        });
+        assert_bvh_fitted(&tree, |_, primitive_id| after[primitive_id as usize].bounding_box());
+    }
+}
+
+#[test]
+fn bvh_refit_keeps_an_unchanged_tree() {
+    let primitives = sphere_row((0..9).map(|i| i as f32));
+    let scene_data = SceneData::new(&primitives, &[], &MaterialRegistry::default());
+    let mut tree = scene_data.bvh_tree().clone();
+    refit_bvh_tree(&mut tree, |_, primitive_id| {
+        scene_data.spheres()[primitive_id as usize].bounding_box()
+    });
+    assert_eq!(
+        bytemuck::cast_slice::<_, u8>(&tree),
+        bytemuck::cast_slice::<_, u8>(scene_data.bvh_tree())
+    );
+}
+
+#[test]
+fn scene_data_update_refits_or_rebuilds() {
+    let materials = MaterialRegistry::default();
+    let mut scene_data = SceneData::new(&sphere_row((0..64).map(|i| i as f32)), &[], &materials);
+    let built_cost = scene_data.bvh_cost();
+    assert_eq!(built_cost, bvh_sah_cost(scene_data.bvh_tree()));
+
+    // 一个球稍微移动，拓扑不变
+    let moved = sphere_row((0..64).map(|i| if i == 10 { 10.3 } else { i as f32 }));
+    assert_eq!(
+        scene_data.update(&moved, &[], &materials, BvhRefitMode::Cpu),
+        BvhUpdate::Refit
+    );
+    assert_eq!(scene_data.bvh_cost(), built_cost);
+    assert!(bvh_sah_cost(scene_data.bvh_tree()) <= built_cost * BVH_REFIT_MAX_COST_RATIO);
+    assert_bvh_fitted(scene_data.bvh_tree(), |_, id| {
+        scene_data.spheres()[id as usize].bounding_box()
+    });
+
+    // 打乱所有球的位置，每个子树都横跨整行，重新拟合后代价太高
+    let shuffled = sphere_row((0..64).map(|i| (i * 37 % 64) as f32));
+    assert_eq!(
+        scene_data.update(&shuffled, &[], &materials, BvhRefitMode::Cpu),
+        BvhUpdate::Rebuild
+    );
+    assert_eq!(scene_data.bvh_cost(), bvh_sah_cost(scene_data.bvh_tree()));
+    assert!(scene_data.bvh_cost() <= built_cost * 1.01);
+
+    // 图元数量改变
+    let added = sphere_row((0..65).map(|i| i as f32));
+    assert_eq!(
+        scene_data.update(&added, &[], &materials, BvhRefitMode::Cpu),
+        BvhUpdate::Rebuild
+    );
+    assert_eq!(scene_data.spheres().len(), 65);
+    assert_bvh_fitted(scene_data.bvh_tree(), |_, id| {
+        scene_data.spheres()[id as usize].bounding_box()
+    });
+}
+
+// 每个图元的数据都在 primitives 头部给出的起始下标处，与 SceneData 中按类型分开的数组一致
+fn assert_packed<T: bytemuck::Pod>(packed: &[u32], primitive_type: usize, primitives: &[T]) {
+    let start = packed[primitive_type * 2] as usize;
+    let words = packed[primitive_type * 2 + 1] as usize;
+    assert_eq!(words * size_of::<u32>(), size_of::<T>());
+    for (i, primitive) in primitives.iter().enumerate() {
+        let offset = start + i * words;
+        assert_eq!(
+            &packed[offset..offset + words],
+            bytemuck::cast_slice::<T, u32>(&[*primitive])
+        );
+    }
+}
+
+proptest! {
+    #[test]
+    fn primitives_are_packed_by_type(primitives in prop::collection::vec(primitive(), 1..12)) {
+        let scene_data = scene_data(&primitives);
+        let packed = scene_data.primitives();
+        assert_packed(packed, 0, scene_data.quads());
+        assert_packed(packed, 1, scene_data.spheres());
+        assert_packed(packed, 2, scene_data.shapes());
+        assert_packed(packed, 3, scene_data.sdfs());
+        assert_packed(packed, 4, scene_data.csgs());
+        assert_packed(packed, 5, scene_data.curves());
+        assert_packed(packed, 6, scene_data.heightfields());
+    }
+}
+
+#[test]
+fn gpu_refit_mode_checks_the_cost_periodically() {
+    let materials = MaterialRegistry::default();
+    let mut scene_data = SceneData::new(&sphere_row((0..64).map(|i| i as f32)), &[], &materials);
+    let built_tree = scene_data.bvh_tree().clone();
+
+    // 只更新叶节点，内部节点保持原样，即使打乱后的代价已经太高
+    let shuffled = sphere_row((0..64).map(|i| (i * 37 % 64) as f32));
+    for _ in 1..BVH_GPU_REFIT_CHECK_INTERVAL {
+        assert_eq!(
+            scene_data.update(&shuffled, &[], &materials, BvhRefitMode::Gpu),
+            BvhUpdate::RefitLeaves
+        );
+    }
+    for (node, built) in scene_data.bvh_tree().iter().zip(&built_tree) {
+        let expected = if node.is_leaf == 1 {
+            scene_data.spheres()[node.right_or_primitive_id as usize].bounding_box()
+        } else {
+            built.bounding_box
+        };
+        assert_eq!(bytemuck::bytes_of(&node.bounding_box), bytemuck::bytes_of(&expected));
+    }
+
+    assert_eq!(
+        scene_data.update(&shuffled, &[], &materials, BvhRefitMode::Gpu),
+        BvhUpdate::Rebuild
+    );
+    let moved = sphere_row((0..64).map(|i| (i * 37 % 64) as f32 + 0.1));
+    assert_eq!(
+        scene_data.update(&moved, &[], &materials, BvhRefitMode::Gpu),
+        BvhUpdate::RefitLeaves
+    );
+}
+
+// 与 bvh_refit.wgsl 中的 BvhRefit_key 和 BvhRefit_value 逐行对应
+fn refit_key(value: f32) -> u32 {
+    let bits = value.to_bits();
+    if bits & 0x80000000 != 0 {
+        !bits
+    } else {
+        bits | 0x80000000
+    }
+}
+
+fn refit_value(key: u32) -> f32 {
+    f32::from_bits(if key & 0x80000000 != 0 { key & 0x7fffffff } else { !key })
+}
+
+// 在 CPU 上模拟 BvhRefit_propagate 和 BvhRefit_write。每个叶节点一个线程，每一步由 schedule 选择
+// 一个线程合并到一个节点，用完后依次执行剩下的线程，覆盖线程之间不同的执行顺序
+fn gpu_refit(tree: &mut [BvhNode], schedule: &[usize]) {
+    let mut bounds = vec![0u32; tree.len() * 6];
+    let mut threads: Vec<_> = bvh_refit_leaves(tree)
+        .into_iter()
+        .map(|leaf| (leaf.node, leaf.bounding_box))
+        .collect();
+    let mut schedule = schedule.iter();
+    while !threads.is_empty() {
+        let thread = schedule.next().map_or(0, |choice| choice % threads.len());
+        let (node, leaf_box) = threads[thread];
+
+        let mut grown = false;
+        for axis in 0..3 {
+            let base = node as usize * 6 + axis as usize * 2;
+            let min_key = !refit_key(*leaf_box.axis(axis).min());
+            let max_key = refit_key(*leaf_box.axis(axis).max());
+            grown |= bounds[base] < min_key;
+            bounds[base] = bounds[base].max(min_key);
+            grown |= bounds[base + 1] < max_key;
+            bounds[base + 1] = bounds[base + 1].max(max_key);
+        }
+        if !grown || node == 0 {
+            threads.swap_remove(thread);
+        } else {
+            threads[thread].0 = tree[node as usize].parent;
+        }
+    }
+
+    for (node, bounds) in tree.iter_mut().zip(bounds.chunks(6)) {
+        for axis in 0..3 {
+            let base = axis as usize * 2;
+            *node.bounding_box.axis_mut(axis) =
+                Interval::new(refit_value(!bounds[base]), refit_value(bounds[base + 1]));
+        }
+    }
+}
+
+proptest! {
+    #[test]
+    fn refit_key_preserves_order(a in any::<f32>(), b in any::<f32>()) {
+        prop_assume!(a.is_finite() && b.is_finite());
+        prop_assert_eq!(refit_value(refit_key(a)).to_bits(), a.to_bits());
+        if a < b {
+            prop_assert!(refit_key(a) < refit_key(b));
+        }
+    }
+
+    #[test]
+    fn gpu_refit_matches_cpu_refit(
+        spheres in prop::collection::vec((sphere(), sphere()), 1..40),
+        schedule in prop::collection::vec(any::<usize>(), 0..200),
+    ) {
+        let (before, after): (Vec<_>, Vec<_>) = spheres.into_iter().unzip();
+        let before: Vec<_> = before.into_iter().map(PrimitiveData::Sphere).collect();
+        let mut cpu_tree = scene_data(&before).bvh_tree().clone();
+        let leaf_box = |_, primitive_id: u32| after[primitive_id as usize].bounding_box();
+
+        let mut gpu_tree = cpu_tree.clone();
+        for node in gpu_tree.iter_mut().filter(|node| node.is_leaf == 1) {
+            node.bounding_box = leaf_box(node.left_or_primitive_type, node.right_or_primitive_id);
+        }
+        gpu_refit(&mut gpu_tree, &schedule);
+        refit_bvh_tree(&mut cpu_tree, leaf_box);
+        for (gpu, cpu) in gpu_tree.iter().zip(&cpu_tree) {
+            for axis in 0..3 {
+                prop_assert_eq!(gpu.bounding_box.axis(axis).min(), cpu.bounding_box.axis(axis).min());
+                prop_assert_eq!(gpu.bounding_box.axis(axis).max(), cpu.bounding_box.axis(axis).max());
+            }
+        }
+    }
+}
+
+/*--------------------------------------- Intersection ------------------------------------------*/
+
+// 保证光线不会几乎平行于平面，否则 t 对误差过于敏感
+fn steep_direction(direction: Vector3<f32>, normal: &Vector3<f32>) -> Vector3<f32> {
+    (direction + normal * 0.5f32.copysign(direction.dot(normal))).normalize()
+}
+
+fn universe() -> Interval {
+    Interval::new(0.001, f32::MAX)
+}
+
+proptest! {
+    #[test]
+    fn ray_hits_quad_at_planar_coordinates(
+        quad in quad(),
+        alpha in 0.01f32..0.99,
+        beta in 0.01f32..0.99,
+        direction in unit_vector(),
+        distance in 0.5f32..20.0,
+    ) {
+        let target = quad.bottom_left() + alpha * quad.right() + beta * quad.up();
+        let direction = steep_direction(direction, &quad.normal());
+
+        let ray = Ray::new(target - direction * distance, direction);
+        let hit = quad.hit(&ray, &universe());
+        prop_assert!(hit.is_some());
+
+        let hit = hit.unwrap();
+        prop_assert!(approx_eq(hit.ray_t, distance));
+        prop_assert!(approx_eq(hit.uv.x, alpha) && approx_eq(hit.uv.y, beta));
+        prop_assert!(hit.normal.dot(&ray.direction) <= 0.0);
+        prop_assert_eq!(hit.is_front_face, ray.direction.dot(&quad.normal()) < 0.0);
+    }
+
+    #[test]
+    fn ray_misses_quad_outside_its_edges(
+        quad in quad(),
+        planar in (1.05f32..3.0, -1.0f32..2.0),
+        swap in any::<bool>(),
+        direction in unit_vector(),
+        distance in 0.5f32..20.0,
+    ) {
+        let (alpha, beta) = if swap { (planar.1, planar.0) } else { planar };
+        let target = quad.bottom_left() + alpha * quad.right() + beta * quad.up();
+        let direction = steep_direction(direction, &quad.normal());
+
+        let ray = Ray::new(target - direction * distance, direction);
+        prop_assert!(quad.hit(&ray, &universe()).is_none());
+    }
+
+    #[test]
+    fn ray_hits_sphere_at_analytic_root(sphere in sphere(), origin in point(), direction in unit_vector()) {
+        let oc = (origin - sphere.center()).cast::<f64>();
+        let d = direction.cast::<f64>();
+        let r = sphere.radius() as f64;
+        prop_assume!(oc.norm() > r * 1.01);
+
+        // |o + t d - c|^2 = r^2
+        let b = oc.dot(&d);
+        let c = oc.norm_squared() - r * r;
+        let discriminant = b * b - c;
+
+        let hit = sphere.hit(&Ray::new(origin, direction), &universe());
+        let t = -b - discriminant.max(0.0).sqrt();
+        if discriminant > 1e-3 && t > 0.01 {
+            prop_assert!(hit.is_some());
+            let hit = hit.unwrap();
+            prop_assert!(approx_eq(hit.ray_t, t as f32));
+            prop_assert!(hit.is_front_face);
+            prop_assert!(approx_eq(hit.normal.norm(), 1.0));
+            prop_assert!(vector_approx_eq(&(hit.position - sphere.center()), &(hit.normal * sphere.radius())));
+        } else if discriminant < -1e-3 || -b + discriminant.max(0.0).sqrt() < -0.01 {
+            prop_assert!(hit.is_none());
+        }
+    }
+
+    #[test]
+    fn ray_from_inside_sphere_hits_back_face(sphere in sphere(), offset in 0.0f32..0.9, direction in unit_vector(), toward in unit_vector()) {
+        let origin = sphere.center() + toward * offset * sphere.radius();
+        let hit = sphere.hit(&Ray::new(origin, direction), &universe());
+
+        prop_assert!(hit.is_some());
+        let hit = hit.unwrap();
+        prop_assert!(!hit.is_front_face);
+        prop_assert!(approx_eq((hit.position - sphere.center()).norm(), sphere.radius()));
+    }
+
+    #[test]
+    fn ray_hits_ellipsoid_at_surface_point(
+        sphere in ellipsoid(),
+        direction in unit_vector(),
+        distance in 0.5f32..10.0,
+        tilt in unit_vector(),
+    ) {
+        // 距离相对椭球的大小太远时 f32 的误差会淹没法线
+        let distance = distance * sphere.bounding_radius();
+        let inverse = sphere.inverse_transform();
+        let target = sphere.center() + sphere.transform() * (direction * sphere.radius());
+        let normal = (inverse.transpose() * direction).normalize();
+
+        // 从切平面外侧射向 target，凸体上第一个交点就是 target
+        let toward = steep_direction(tilt, &normal);
+        let toward = if toward.dot(&normal) > 0.0 { -toward } else { toward };
+        // 在局部坐标中擦边时两个根几乎重合，误差过大
+        prop_assume!((inverse * toward).normalize().dot(&direction) < -0.2);
+
+        let ray = Ray::new(target - toward * distance, toward);
+        let hit = sphere.hit(&ray, &universe());
+
+        prop_assert!(hit.is_some());
+        let hit = hit.unwrap();
+        prop_assert!(approx_eq(hit.ray_t, distance), "t {} expected {}", hit.ray_t, distance);
+        prop_assert!(hit.is_front_face);
+        // 远处的光线求根有抵消误差，用交点处的梯度方向比较
+        let gradient = inverse.transpose() * inverse * (hit.position - sphere.center());
+        prop_assert!(vector_approx_eq(&hit.normal, &gradient.normalize()));
+        prop_assert!(approx_eq(hit.tangent.dot(&hit.normal), 0.0) && approx_eq(hit.tangent.norm(), 1.0));
+        prop_assert!(approx_eq((inverse * (hit.position - sphere.center())).norm(), sphere.radius()));
+    }
+
+    #[test]
+    fn bvh_hit_matches_brute_force(
+        primitives in prop::collection::vec(primitive(), 1..30),
+        origin in point(),
+        direction in unit_vector(),
+    ) {
+        let scene_data = scene_data(&primitives);
+        let ray = Ray::new(origin, direction);
+
+        let brute_force = scene_data.quads().iter().filter_map(|quad| quad.hit(&ray, &universe()))
+            .chain(scene_data.spheres().iter().filter_map(|sphere| sphere.hit(&ray, &universe())))
+            .chain(scene_data.shapes().iter().filter_map(|shape| shape.hit(&ray, &universe())))
+            .chain(scene_data.sdfs().iter().filter_map(|sdf| sdf.hit(&ray, &universe())))
+            .chain(scene_data.csgs().iter().filter_map(|csg| csg.hit(&ray, &universe())))
+            .chain(scene_data.curves().iter().filter_map(|curve| curve.hit(&ray, &universe())))
+            .chain(scene_data.heightfields().iter().filter_map(|heightfield| heightfield.hit(&ray, &universe())))
+            .map(|hit| hit.ray_t)
+            .min_by(f32::total_cmp);
+        let bvh = scene_data.hit(&ray, &universe()).map(|hit| hit.ray_t);
+
+        match (bvh, brute_force) {
+            (Some(a), Some(b)) => prop_assert!(approx_eq(a, b), "bvh {} brute force {}", a, b),
+            (a, b) => prop_assert_eq!(a, b),
+        }
+    }
+}
+
+/*------------------------------------------ Motion ---------------------------------------------*/
+
+proptest! {
+    #[test]
+    fn moving_quad_bounding_box_contains_every_time(quad in quad(), motion in motion(), time in 0.0f32..1.0) {
+        let mut moving = quad;
+        moving.set_motion(motion);
+        let bounding_box = moving.bounding_box();
+
+        let transform = motion.at(time);
+        for corner in [Vector3::zeros(), quad.right(), quad.up(), quad.right() + quad.up()] {
+            prop_assert!(contains_point(&bounding_box, &(transform * (quad.bottom_left() + corner))));
+        }
+    }
+
+    #[test]
+    fn moving_sphere_bounding_box_contains_every_time(
+        sphere in sphere(),
+        motion in motion(),
+        time in 0.0f32..1.0,
+        direction in unit_vector(),
+    ) {
+        let mut moving = sphere;
+        moving.set_motion(motion);
+
+        let surface_point = motion.at(time) * (sphere.center() + direction * sphere.radius());
+        prop_assert!(contains_point(&moving.bounding_box(), &surface_point));
+    }
+
+    #[test]
+    fn ray_hits_moving_sphere_where_it_is_at_ray_time(
+        sphere in sphere(),
+        motion in motion(),
+        time in 0.0f32..1.0,
+        origin in point(),
+        direction in unit_vector(),
+    ) {
+        let mut moving = sphere;
+        moving.set_motion(motion);
+        let scene_data = scene_data(&[PrimitiveData::Sphere(moving)]);
+
+        // 与 time 时刻所在位置的静止球的交点一致
+        let center = motion.at(time) * sphere.center();
+        let still = SphereData::new(center, sphere.radius(), 0, 0);
+        prop_assume!((origin - center).norm() > sphere.radius() * 1.01);
+
+        let expected = still.hit(&Ray::new(origin, direction), &universe());
+        let actual = scene_data.hit(&Ray::with_time(origin, direction, time), &universe());
+        match (actual, expected) {
+            (Some(actual), Some(expected)) => {
+                prop_assert!(approx_eq(actual.ray_t, expected.ray_t), "moving {} still {}", actual.ray_t, expected.ray_t);
+                prop_assert!(vector_approx_eq(&actual.normal, &expected.normal));
+                prop_assert!(vector_approx_eq(&actual.position.coords, &expected.position.coords));
+            }
+            (None, None) => {}
+            // 擦边的光线可能因为误差只有一方命中
+            (actual, expected) => {
+                let grazing = |t: f32| {
+                    let closest = origin + direction * direction.dot(&(center - origin)).max(0.0);
+                    ((closest - center).norm() - sphere.radius()).abs() < 1e-2 * t.max(1.0)
+                };
+                prop_assert!(grazing(actual.or(expected).map_or(0.0, |hit| hit.ray_t)));
+            }
+        }
+    }
+}
+
+#[test]
+fn static_motion_keeps_bounding_box() {
+    let sphere = SphereData::new(Point3::new(1.0, 2.0, 3.0), 0.5, 0, 0);
+    let mut moving = sphere;
+    moving.set_motion(MotionData::from(&Motion::default()));
+
+    assert!(!moving.motion().is_moving());
+    assert!(contains_box(&sphere.bounding_box(), &moving.bounding_box()));
+    assert!(contains_box(&moving.bounding_box(), &sphere.bounding_box()));
+}
+
+#[test]
+fn sphere_uv_matches_documented_values() {
+    use renderer_core::rendering::cpu::sphere_uv;
+
+    let cases = [
+        (Vector3::new(1.0, 0.0, 0.0), Vector2::new(0.5, 0.5)),
+        (Vector3::new(-1.0, 0.0, 0.0), Vector2::new(0.0, 0.5)),
+        (Vector3::new(0.0, 1.0, 0.0), Vector2::new(0.5, 1.0)),
+        (Vector3::new(0.0, -1.0, 0.0), Vector2::new(0.5, 0.0)),
+        (Vector3::new(0.0, 0.0, 1.0), Vector2::new(0.25, 0.5)),
+        (Vector3::new(0.0, 0.0, -1.0), Vector2::new(0.75, 0.5)),
+    ];
+    for (position, expected) in cases {
+        let uv = sphere_uv(&position);
+        // (-1, 0, 0) 处 atan2 的结果在 -PI 和 PI 之间跳变，u 可能是 0 或 1
+        assert!(
+            (approx_eq(uv.x, expected.x) || approx_eq(uv.x.fract(), expected.x)) && approx_eq(uv.y, expected.y),
+            "{position:?} -> {uv:?}, expected {expected:?}"
+        );
+    }
+}
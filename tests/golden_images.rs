@@ -0,0 +1,408 @@
+// 用 CPU 路径追踪器以固定的种子和采样数渲染每个内置场景，与 tests/reference 中的参考图比较。
+// 更新参考图：GOLDEN_IMAGES_BLESS=1 cargo test --test golden_images
+// 比较失败时会把渲染结果和误差图写到 target/tmp/golden_images 中
+
+use image::{Rgb, RgbImage, RgbaImage};
+use renderer_core::app::scene::Scene;
+use renderer_core::rendering::SamplerType;
+use std::path::PathBuf;
+
+const WIDTH: u32 = 64;
+const HEIGHT: u32 = 64;
+const SAMPLES_PER_PIXEL: u32 = 64;
+const MAX_RAY_BOUNCES: u32 = 8;
+const SEED: u32 = 0;
+
+// 逐像素的误差由蒙特卡洛噪声主导，所有指标都在 4x4 的块平均之后计算。
+// 块平均会抵消大部分噪声，而亮度或颜色的系统性变化不会
+const BLOCK_SIZE: u32 = 4;
+const FLIP_BLUR_SIGMA: f32 = 2.0;
+
+#[derive(Copy, Clone, Debug)]
+struct Tolerance {
+    rmse: f32,
+    relative_mse: f32,
+    flip: f32,
+}
+
+// 64 spp 时不同种子之间 flip 约为 0.025，整体亮度降低 10% 时约为 0.06。
+// rmse 和 relative_mse 对这种程度的变化不敏感，只用来发现明显的错误
+const TOLERANCE: Tolerance = Tolerance {
+    rmse: 0.03,
+    relative_mse: 0.01,
+    flip: 0.04,
+};
+
+#[derive(Copy, Clone, Debug)]
+struct ImageMetrics {
+    rmse: f32,         // sRGB 空间的均方根误差
+    relative_mse: f32, // 线性空间的相对均方误差
+    flip: f32,         // 类似 FLIP 的感知色差的均值，范围 [0, 1]
+}
+
+impl ImageMetrics {
+    fn within(&self, tolerance: &Tolerance) -> bool {
+        self.rmse <= tolerance.rmse && self.relative_mse <= tolerance.relative_mse && self.flip <= tolerance.flip
+    }
+}
+
+struct LinearImage {
+    width: u32,
+    height: u32,
+    pixels: Vec<[f32; 3]>,
+}
+
+impl LinearImage {
+    fn from_srgb8(image: &RgbaImage) -> Self {
+        let pixels = image
+            .pixels()
+            .map(|p| [0, 1, 2].map(|c| srgb_to_linear(p[c] as f32 / 255.0)))
+            .collect();
+        Self {
+            width: image.width(),
+            height: image.height(),
+            pixels,
+        }
+    }
+
+    fn pixel(&self, x: u32, y: u32) -> [f32; 3] {
+        self.pixels[(x + y * self.width) as usize]
+    }
+
+    fn block_average(&self, block_size: u32) -> Self {
+        let width = self.width / block_size;
+        let height = self.height / block_size;
+        let mut pixels = Vec::with_capacity((width * height) as usize);
+        for by in 0..height {
+            for bx in 0..width {
+                let mut sum = [0.0; 3];
+                for y in by * block_size..(by + 1) * block_size {
+                    for x in bx * block_size..(bx + 1) * block_size {
+                        let p = self.pixel(x, y);
+                        (0..3).for_each(|c| sum[c] += p[c]);
+                    }
+                }
+                pixels.push(sum.map(|s| s / (block_size * block_size) as f32));
+            }
+        }
+        Self { width, height, pixels }
+    }
+
+    // 可分离的高斯模糊，模拟人眼对高频噪声不敏感
+    fn gaussian_blur(&self, sigma: f32) -> Self {
+        let radius = (3.0 * sigma).ceil() as i32;
+        let weights: Vec<f32> = (-radius..=radius)
+            .map(|i| (-(i * i) as f32 / (2.0 * sigma * sigma)).exp())
+            .collect();
+        let total: f32 = weights.iter().sum();
+
+        let blur = |image: &LinearImage, dx: i32, dy: i32| {
+            let mut pixels = Vec::with_capacity(image.pixels.len());
+            for y in 0..image.height as i32 {
+                for x in 0..image.width as i32 {
+                    let mut sum = [0.0; 3];
+                    for (i, w) in (-radius..=radius).zip(&weights) {
+                        let sx = (x + i * dx).clamp(0, image.width as i32 - 1) as u32;
+                        let sy = (y + i * dy).clamp(0, image.height as i32 - 1) as u32;
+                        let p = image.pixel(sx, sy);
+                        (0..3).for_each(|c| sum[c] += p[c] * w);
+                    }
+                    pixels.push(sum.map(|s| s / total));
+                }
+            }
+            LinearImage {
+                width: image.width,
+                height: image.height,
+                pixels,
+            }
+        };
+
+        blur(&blur(self, 1, 0), 0, 1)
+    }
+}
+
+// 返回指标和块分辨率的误差图
+fn compare(actual: &RgbaImage, reference: &RgbaImage) -> (ImageMetrics, LinearImage) {
+    let actual = LinearImage::from_srgb8(actual).block_average(BLOCK_SIZE);
+    let reference = LinearImage::from_srgb8(reference).block_average(BLOCK_SIZE);
+    let channels = (actual.pixels.len() * 3) as f32;
+
+    let channel_pairs = || {
+        actual
+            .pixels
+            .iter()
+            .zip(&reference.pixels)
+            .flat_map(|(a, r)| (0..3).map(move |c| (a[c], r[c])))
+    };
+
+    let rmse = (channel_pairs()
+        .map(|(a, r)| (linear_to_srgb(a) - linear_to_srgb(r)).powi(2))
+        .sum::<f32>()
+        / channels)
+        .sqrt();
+    let relative_mse = channel_pairs()
+        .map(|(a, r)| (a - r).powi(2) / (r * r + 0.01))
+        .sum::<f32>()
+        / channels;
+
+    let flip_errors = flip_error_map(&actual, &reference);
+    let flip = flip_errors.iter().sum::<f32>() / flip_errors.len() as f32;
+
+    let error_map = LinearImage {
+        width: actual.width,
+        height: actual.height,
+        pixels: flip_errors.iter().map(|e| [*e; 3]).collect(),
+    };
+
+    (
+        ImageMetrics {
+            rmse,
+            relative_mse,
+            flip,
+        },
+        error_map,
+    )
+}
+
+// FLIP（Andersson et al. 2020）颜色部分的简化版：先模糊，再在 CIELAB 中计算 HyAB 距离并归一化。
+// 省略了边缘和点特征项
+fn flip_error_map(actual: &LinearImage, reference: &LinearImage) -> Vec<f32> {
+    const MAX_HYAB: f32 = 100.0;
+
+    let actual = actual.gaussian_blur(FLIP_BLUR_SIGMA);
+    let reference = reference.gaussian_blur(FLIP_BLUR_SIGMA);
+    actual
+        .pixels
+        .iter()
+        .zip(&reference.pixels)
+        .map(|(a, r)| {
+            let a = linear_to_lab(a);
+            let r = linear_to_lab(r);
+            let hyab = (a[0] - r[0]).abs() + ((a[1] - r[1]).powi(2) + (a[2] - r[2]).powi(2)).sqrt();
+            (hyab / MAX_HYAB).min(1.0).powf(0.7)
+        })
+        .collect()
+}
+
+fn linear_to_lab(rgb: &[f32; 3]) -> [f32; 3] {
+    let [r, g, b] = *rgb;
+    // sRGB D65
+    let x = (0.4124 * r + 0.3576 * g + 0.1805 * b) / 0.9505;
+    let y = 0.2126 * r + 0.7152 * g + 0.0722 * b;
+    let z = (0.0193 * r + 0.1192 * g + 0.9505 * b) / 1.089;
+
+    let f = |t: f32| {
+        if t > 0.008856 {
+            t.cbrt()
+        } else {
+            7.787 * t + 16.0 / 116.0
+        }
+    };
+    let (fx, fy, fz) = (f(x), f(y), f(z));
+    [116.0 * fy - 16.0, 500.0 * (fx - fy), 200.0 * (fy - fz)]
+}
+
+fn linear_to_srgb(c: f32) -> f32 {
+    if c < 0.0031308 {
+        c * 12.92
+    } else {
+        1.055 * c.powf(1.0 / 2.4) - 0.055
+    }
+}
+
+fn srgb_to_linear(c: f32) -> f32 {
+    if c < 0.04045 {
+        c / 12.92
+    } else {
+        ((c + 0.055) / 1.055).powf(2.4)
+    }
+}
+
+// 0 -> 蓝，0.5 -> 绿，1 -> 红，与 ray_tracing.wgsl 中的 heatmap 相同。误差图按块放大到原图大小
+fn heatmap(width: u32, height: u32, errors: &LinearImage) -> RgbImage {
+    RgbImage::from_fn(width, height, |x, y| {
+        let t = errors.pixel(x / BLOCK_SIZE, y / BLOCK_SIZE)[0].clamp(0.0, 1.0);
+        let color = [2.0 * t - 1.0, 1.0 - (2.0 * t - 1.0).abs(), 1.0 - 2.0 * t];
+        Rgb(color.map(|c| (c.clamp(0.0, 1.0) * 255.0).round() as u8))
+    })
+}
+
+fn reference_path(name: &str) -> PathBuf {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+        .join("tests/reference")
+        .join(format!("{name}.png"))
+}
+
+fn output_path(name: &str, suffix: &str) -> PathBuf {
+    let directory = PathBuf::from(env!("CARGO_TARGET_TMPDIR")).join("golden_images");
+    std::fs::create_dir_all(&directory).unwrap();
+    directory.join(format!("{name}-{suffix}.png"))
+}
+
+fn render(name: &str) -> RgbaImage {
+    let (_, scene) = Scene::BUILT_IN
+        .iter()
+        .find(|(scene_name, _)| *scene_name == name)
+        .unwrap_or_else(|| panic!("unknown scene {name}"));
+
+    scene()
+        .render_cpu(
+            WIDTH,
+            HEIGHT,
+            SAMPLES_PER_PIXEL,
+            MAX_RAY_BOUNCES,
+            SamplerType::Independent,
+            SEED,
+        )
+        .to_rgba8()
+}
+
+fn check_scene(name: &str) {
+    let actual = render(name);
+    let reference_path = reference_path(name);
+
+    if std::env::var_os("GOLDEN_IMAGES_BLESS").is_some() {
+        std::fs::create_dir_all(reference_path.parent().unwrap()).unwrap();
+        actual.save(&reference_path).unwrap();
+        return;
+    }
+
+    let reference = image::open(&reference_path)
+        .unwrap_or_else(|e| panic!("failed to open {}: {e}", reference_path.display()))
+        .to_rgba8();
+    assert_eq!(
+        actual.dimensions(),
+        reference.dimensions(),
+        "{name}: image size changed"
+    );
+
+    let (metrics, errors) = compare(&actual, &reference);
+    if !metrics.within(&TOLERANCE) {
+        let actual_path = output_path(name, "actual");
+        let diff_path = output_path(name, "diff");
+        actual.save(&actual_path).unwrap();
+        heatmap(WIDTH, HEIGHT, &errors).save(&diff_path).unwrap();
+
+        panic!(
+            "{name}: {metrics:?} exceeds {TOLERANCE:?}\n  reference: {}\n  actual: {}\n  diff: {}",
+            reference_path.display(),
+            actual_path.display(),
+            diff_path.display(),
+        );
+    }
+}
+
+#[test]
+fn quad() {
+    check_scene("quad");
+}
+
+#[test]
+fn primitives() {
+    check_scene("primitives");
+}
+
+#[test]
+fn light() {
+    check_scene("light");
+}
+
+#[test]
+fn light_huge() {
+    check_scene("light_huge");
+}
+
+#[test]
+fn cornell_box() {
+    check_scene("cornell_box");
+}
+
+#[test]
+fn principled() {
+    check_scene("principled");
+}
+
+#[test]
+fn normal_map() {
+    check_scene("normal_map");
+}
+
+#[test]
+fn alpha_mask() {
+    check_scene("alpha_mask");
+}
+
+#[test]
+fn bouncing_spheres() {
+    check_scene("bouncing_spheres");
+}
+
+#[test]
+fn shapes() {
+    check_scene("shapes");
+}
+
+#[test]
+fn sdf() {
+    check_scene("sdf");
+}
+
+#[test]
+fn csg() {
+    check_scene("csg");
+}
+
+#[test]
+fn curves() {
+    check_scene("curves");
+}
+
+#[test]
+fn terrain() {
+    check_scene("terrain");
+}
+
+fn render_cornell_box(sampler_type: SamplerType, seed: u32) -> RgbaImage {
+    Scene::scene_cornell_box()
+        .render_cpu(WIDTH, HEIGHT, SAMPLES_PER_PIXEL, MAX_RAY_BOUNCES, sampler_type, seed)
+        .to_rgba8()
+}
+
+// 不同种子之间只有噪声的差别，应当在容差之内
+#[test]
+fn tolerance_accepts_monte_carlo_noise() {
+    let (metrics, _) = compare(
+        &render_cornell_box(SamplerType::Independent, 1),
+        &render_cornell_box(SamplerType::Independent, 2),
+    );
+    assert!(metrics.within(&TOLERANCE), "{metrics:?} exceeds {TOLERANCE:?}");
+}
+
+// 每种采样器都是无偏的，与另一个种子的独立采样只有噪声的差别。
+// 维度之间相关的采样器会让路径空间塌缩，图像整体偏暗
+#[test]
+fn samplers_converge_to_independent() {
+    let reference = render_cornell_box(SamplerType::Independent, 1);
+    for sampler_type in SamplerType::ALL {
+        let (metrics, _) = compare(&render_cornell_box(sampler_type, 2), &reference);
+        assert!(
+            metrics.within(&TOLERANCE),
+            "{sampler_type:?}: {metrics:?} exceeds {TOLERANCE:?}"
+        );
+    }
+}
+
+#[test]
+fn tolerance_rejects_brightness_change() {
+    let reference = render_cornell_box(SamplerType::Independent, SEED);
+    let mut darker = reference.clone();
+    for pixel in darker.pixels_mut() {
+        for c in 0..3 {
+            let linear = srgb_to_linear(pixel[c] as f32 / 255.0) * 0.9;
+            pixel[c] = (linear_to_srgb(linear) * 255.0).round() as u8;
+        }
+    }
+
+    let (metrics, _) = compare(&darker, &reference);
+    assert!(!metrics.within(&TOLERANCE), "{metrics:?} is within {TOLERANCE:?}");
+}
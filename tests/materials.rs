@@ -0,0 +1,462 @@
+// 材质参数的打包，从 glTF 和 MTL 到 Principled 的转换，光源的颜色和强度，以及透明度贴图对求交的影响
+
+use image::{Rgba, RgbaImage};
+use nalgebra::{Point3, Vector3};
+use renderer_core::rendering::cpu::Ray;
+use renderer_core::rendering::interval::Interval;
+use renderer_core::rendering::material::import::{parse_mtl, GltfMaterial};
+use renderer_core::rendering::material::{
+    angular_profile, blackbody, luminance, texture_sample, AlphaMask, AlphaMode, DiffuseLight, GpuMaterial, Lambertian,
+    LightIntensity, MaterialParameter, MaterialRegistry, NormalMap, Principled, SurfaceMaps, Terrain, TerrainLayer,
+    Texture, LUMINOUS_EFFICACY,
+};
+use renderer_core::rendering::primitive::sphere::SphereData;
+use renderer_core::rendering::primitive::{PrimitiveData, QuadData};
+use renderer_core::rendering::scene_data::SceneData;
+use std::f32::consts::PI;
+use std::rc::Rc;
+
+#[test]
+fn principled_pack_round_trip() {
+    let principled = Principled {
+        specular_tint: 0.25,
+        sheen: 0.5,
+        clearcoat: 0.75,
+        transmission: 0.125,
+        ior: 1.33,
+        ..Principled::new(Point3::new(0.1, 0.2, 0.3), 0.4, 0.6)
+    };
+    let mut data = Vec::new();
+    principled.pack(&mut data);
+    assert_eq!(data.len(), 13);
+
+    let unpacked = Principled::unpack(&data);
+    assert_eq!(unpacked.base_color, principled.base_color);
+    assert_eq!(unpacked.metallic, 0.4);
+    assert_eq!(unpacked.roughness, 0.6);
+    assert_eq!(unpacked.specular_tint, 0.25);
+    assert_eq!(unpacked.sheen, 0.5);
+    assert_eq!(unpacked.clearcoat, 0.75);
+    assert_eq!(unpacked.transmission, 0.125);
+    assert_eq!(unpacked.ior, 1.33);
+}
+
+#[test]
+fn editing_parameters_keeps_packed_layout() {
+    let mut materials = MaterialRegistry::default();
+    materials.add(Box::new(Principled::default()));
+    let second = materials.add(Box::new(Principled::default()));
+    let len = materials.pack().len();
+
+    for (_, material) in materials.iter_mut() {
+        for parameter in material.parameters() {
+            match parameter {
+                MaterialParameter::Scalar {
+                    name: "Metallic",
+                    value,
+                    ..
+                } => *value = 1.0,
+                MaterialParameter::Color { value, .. } => *value = Point3::new(1.0, 0.0, 0.0),
+                _ => {}
+            }
+        }
+    }
+
+    let data = materials.pack();
+    assert_eq!(data.len(), len);
+    let principled = Principled::unpack(&data[second.material_id as usize..]);
+    assert_eq!(principled.metallic, 1.0);
+    assert_eq!(principled.base_color, Point3::new(1.0, 0.0, 0.0));
+}
+
+#[test]
+fn gltf_defaults_convert_to_rough_metal() {
+    let principled = Principled::from(&GltfMaterial::default());
+    assert_eq!(principled.base_color, Point3::new(1.0, 1.0, 1.0));
+    assert_eq!(principled.metallic, 1.0);
+    assert_eq!(principled.roughness, 1.0);
+    assert_eq!(principled.specular, 0.5);
+    assert_eq!(principled.ior, 1.5);
+}
+
+#[test]
+fn gltf_extensions_convert() {
+    let gltf = GltfMaterial {
+        base_color_factor: [0.2, 0.4, 0.6, 1.0],
+        metallic_factor: 0.0,
+        roughness_factor: 0.1,
+        sheen_color_factor: [0.3, 0.6, 0.0],
+        clearcoat_factor: 1.0,
+        clearcoat_roughness_factor: 0.2,
+        transmission_factor: 0.9,
+        ior: 1.45,
+        ..Default::default()
+    };
+    let principled = Principled::from(&gltf);
+    assert_eq!(principled.base_color, Point3::new(0.2, 0.4, 0.6));
+    assert_eq!(principled.sheen, 0.6);
+    assert_eq!(principled.sheen_tint, 1.0);
+    assert_eq!(principled.clearcoat, 1.0);
+    assert_eq!(principled.clearcoat_roughness, 0.2);
+    assert_eq!(principled.transmission, 0.9);
+    assert_eq!(principled.ior, 1.45);
+}
+
+const MTL: &str = "
+# exported
+newmtl red plastic
+Kd 0.8 0.1 0.1
+Ks 0.04 0.04 0.04
+Ns 98.0
+Ni 1.45
+
+newmtl chrome
+Kd 0.05 0.05 0.05
+Ks 0.9
+Ns 1000
+
+newmtl glass
+Kd 1 1 1
+Tr 0.75 # 透明度
+Pr 0.0
+
+newmtl pbr
+Kd 0.5 0.5 0.5
+Pr 0.35
+Pm 0.5
+Ps 0.2
+Pc 1
+Pcr 0.1
+d not-a-number
+";
+
+#[test]
+fn mtl_is_parsed() {
+    let materials = parse_mtl(MTL);
+    let names: Vec<_> = materials.iter().map(|material| material.name.as_str()).collect();
+    assert_eq!(names, ["red plastic", "chrome", "glass", "pbr"]);
+
+    assert_eq!(materials[0].diffuse, Some(Point3::new(0.8, 0.1, 0.1)));
+    assert_eq!(materials[0].shininess, Some(98.0));
+    assert_eq!(materials[1].specular, Some(Point3::new(0.9, 0.9, 0.9)));
+    assert_eq!(materials[2].dissolve, Some(0.25));
+    assert_eq!(materials[3].clearcoat_roughness, Some(0.1));
+    // 格式错误的行被忽略
+    assert_eq!(materials[3].dissolve, None);
+}
+
+#[test]
+fn mtl_converts_to_principled() {
+    let materials: Vec<_> = parse_mtl(MTL).iter().map(Principled::from).collect();
+
+    // Ns = 98 时 α = sqrt(2 / 100)
+    let plastic = &materials[0];
+    assert_eq!(plastic.metallic, 0.0);
+    assert!((plastic.roughness - 0.02f32.sqrt().sqrt()).abs() < 1e-6);
+    assert_eq!(plastic.ior, 1.45);
+    assert_eq!(plastic.transmission, 0.0);
+
+    // 镜面反射远亮于漫反射，视为金属并取镜面反射颜色
+    let chrome = &materials[1];
+    assert_eq!(chrome.metallic, 1.0);
+    assert_eq!(chrome.base_color, Point3::new(0.9, 0.9, 0.9));
+
+    // d 只影响透明度贴图，不会变成透射
+    let glass = &materials[2];
+    assert_eq!(glass.transmission, 0.0);
+    assert_eq!(glass.roughness, 0.0);
+
+    let pbr = &materials[3];
+    assert_eq!(pbr.roughness, 0.35);
+    assert_eq!(pbr.metallic, 0.5);
+    assert_eq!(pbr.sheen, 0.2);
+    assert_eq!(pbr.clearcoat, 1.0);
+}
+
+// d 是不透明度，半透明的材质得到以 d 为概率不透明的透明度贴图
+#[test]
+fn mtl_dissolve_becomes_an_alpha_mask() {
+    let materials = parse_mtl(MTL);
+    assert!(materials[0].alpha_mask().is_none());
+    assert!(materials[3].alpha_mask().is_none());
+
+    let mask = materials[2].alpha_mask().expect("Tr 0.75 is translucent");
+    assert!(matches!(mask.mode, AlphaMode::Stochastic));
+    let mut data = Vec::new();
+    mask.texture.pack(&mut data);
+    assert!((texture_sample(&data, 0.3, 0.7)[3] - 0.25).abs() < 1.0 / 255.0);
+}
+
+#[test]
+fn blackbody_has_unit_luminance_and_shifts_from_red_to_blue() {
+    for kelvin in [1000.0, 1900.0, 2700.0, 4000.0, 6500.0, 10000.0, 30000.0] {
+        let color = blackbody(kelvin);
+        assert!((luminance(&color) - 1.0).abs() < 1e-4, "{} K: {:?}", kelvin, color);
+        assert!(color.iter().all(|c| *c >= 0.0));
+    }
+
+    let candle = blackbody(1900.0);
+    assert!(candle.x > candle.y && candle.y > candle.z);
+    let sky = blackbody(12000.0);
+    assert!(sky.z > sky.x);
+
+    // D65 附近接近白色
+    let daylight = blackbody(6504.0);
+    assert!(daylight.iter().all(|c| (c - 1.0).abs() < 0.05), "{:?}", daylight);
+}
+
+#[test]
+fn new_diffuse_light_keeps_radiance() {
+    let mut light = DiffuseLight::new(Point3::new(15.0, 10.0, 5.0));
+    // 界面上的颜色只有色度，分量在 [0, 1] 内
+    let color = match light.parameters().remove(0) {
+        MaterialParameter::Color { value, .. } => *value,
+        _ => unreachable!(),
+    };
+    assert!((color - Point3::new(1.0, 10.0 / 15.0, 5.0 / 15.0)).norm() < 1e-4);
+
+    let mut data = Vec::new();
+    light.pack(&mut data);
+    let radiance = Point3::new(
+        f32::from_bits(data[0]),
+        f32::from_bits(data[1]),
+        f32::from_bits(data[2]),
+    );
+    assert!((radiance - Point3::new(15.0, 10.0, 5.0)).norm() < 1e-4);
+}
+
+#[test]
+fn watts_convert_to_nits() {
+    // 各向均匀的单面朗伯光源：Φ = π L A
+    let watts = 60.0;
+    let area = 0.5;
+    let light = DiffuseLight::blackbody(2700.0, LightIntensity::Watts { watts, area });
+    let expected = watts * LUMINOUS_EFFICACY / (PI * area);
+    assert!((light.nits() / expected - 1.0).abs() < 1e-3);
+
+    // 双面发光时每个面分到一半
+    let two_sided = DiffuseLight {
+        two_sided: true,
+        ..light.clone()
+    };
+    assert!((two_sided.nits() / expected - 0.5).abs() < 1e-3);
+
+    // 角度分布越集中，同样的功率下峰值亮度越高
+    let spot = DiffuseLight {
+        angular_profile: vec![1.0, 0.5, 0.0],
+        ..light.clone()
+    };
+    assert!(spot.nits() > light.nits());
+}
+
+#[test]
+fn angular_profile_interpolates_between_samples() {
+    let profile = [1.0, 0.5, 0.0];
+    assert_eq!(angular_profile(&[], 0.3), 1.0);
+    assert_eq!(angular_profile(&[0.25], 0.3), 0.25);
+    assert_eq!(angular_profile(&profile, 1.0), 1.0);
+    assert!((angular_profile(&profile, (PI / 4.0).cos()) - 0.5).abs() < 1e-5);
+    assert!((angular_profile(&profile, (PI / 8.0).cos()) - 0.75).abs() < 1e-5);
+    assert!(angular_profile(&profile, 0.0).abs() < 1e-5);
+}
+
+#[test]
+fn diffuse_light_pack_round_trip() {
+    let image = RgbaImage::from_fn(3, 2, |x, y| Rgba([x as u8 * 100, y as u8 * 200, 7, 255]));
+    let light = DiffuseLight {
+        two_sided: true,
+        angular_profile: vec![1.0, 0.8, 0.1],
+        texture: Some(Texture::from_image(&image)),
+        ..DiffuseLight::new(Point3::new(2.0, 2.0, 2.0))
+    };
+
+    let mut data = Vec::new();
+    light.pack(&mut data);
+    assert_eq!(data.len(), DiffuseLight::PROFILE + 3 + 2 + 6);
+
+    let unpacked = DiffuseLight::unpack(&data);
+    assert!(unpacked.two_sided);
+    assert_eq!(unpacked.angular_profile, light.angular_profile);
+    let texture = unpacked.texture.as_ref().unwrap();
+    assert_eq!((texture.width, texture.height), (3, 2));
+    assert_eq!(texture.texels[4].to_le_bytes(), [100, 200, 7, 255]);
+
+    let mut repacked = Vec::new();
+    unpacked.pack(&mut repacked);
+    assert_eq!(repacked.len(), data.len());
+}
+
+#[test]
+fn terrain_pack_round_trip() {
+    let grass = RgbaImage::from_fn(2, 2, |x, y| Rgba([x as u8 * 50, y as u8 * 100, 3, 255]));
+    let snow = RgbaImage::from_fn(1, 3, |_, y| Rgba([200, 200, 200 + y as u8, 255]));
+    let terrain = Terrain {
+        height_blend: 0.2,
+        ..Terrain::new(vec![
+            TerrainLayer::new(Point3::new(0.3, 0.3, 0.3)),
+            TerrainLayer::new(Point3::new(0.1, 0.5, 0.1))
+                .heights(-1.0, 0.5)
+                .slopes(0.0, 30.0)
+                .texture(Texture::from_image(&grass), 8.0),
+            TerrainLayer::new(Point3::new(0.9, 0.9, 0.9)).heights(1.0, 2.0),
+            TerrainLayer::new(Point3::new(1.0, 1.0, 1.0)).texture(Texture::from_image(&snow), 2.0),
+        ])
+    };
+
+    let mut data = Vec::new();
+    terrain.pack(&mut data);
+    let layers = Terrain::LAYERS + 4 * Terrain::LAYER_SIZE;
+    assert_eq!(data.len(), layers + 2 + 4 + 2 + 3);
+
+    // 纹理按层的顺序排在所有层之后，没有纹理的层记为 0
+    let texture = |layer: usize| data[Terrain::LAYERS + layer * Terrain::LAYER_SIZE + Terrain::LAYER_TEXTURE] as usize;
+    assert_eq!(texture(0), 0);
+    assert_eq!(texture(1), layers);
+    assert_eq!(texture(2), 0);
+    assert_eq!(texture(3), layers + 2 + 4);
+    assert_eq!(texture_sample(&data[texture(1)..], 0.75, 0.75)[1], 100.0 / 255.0);
+
+    let unpacked = Terrain::unpack(&data);
+    assert_eq!(unpacked.layers.len(), 4);
+    assert_eq!(unpacked.height_blend, 0.2);
+    assert_eq!(unpacked.slope_blend, terrain.slope_blend);
+    assert_eq!(unpacked.layers[1].max_height, 0.5);
+    assert_eq!(unpacked.layers[1].max_slope, 30.0);
+    assert_eq!(unpacked.layers[1].texture_scale, 8.0);
+    assert_eq!(unpacked.layers[2].min_slope, f32::MIN);
+    assert!(unpacked.layers[2].texture.is_none());
+    let snow_texture = unpacked.layers[3].texture.as_ref().unwrap();
+    assert_eq!((snow_texture.width, snow_texture.height), (1, 3));
+    assert_eq!(snow_texture.texels[2].to_le_bytes(), [200, 200, 202, 255]);
+
+    let mut repacked = Vec::new();
+    unpacked.pack(&mut repacked);
+    assert_eq!(repacked, data);
+}
+
+#[test]
+fn normal_maps_are_packed_after_their_material() {
+    let image = RgbaImage::from_fn(2, 2, |x, y| Rgba([128, 128, 255, (x + 2 * y) as u8]));
+    let mut materials = MaterialRegistry::default();
+    let plain = materials.add(Box::new(Lambertian::new(Point3::new(0.1, 0.2, 0.3))));
+    let mapped = materials.add_with_normal_map(
+        Box::new(Lambertian::new(Point3::new(0.4, 0.5, 0.6))),
+        NormalMap::Bump {
+            texture: Texture::from_image(&image),
+            scale: 0.25,
+        },
+    );
+    let after = materials.add(Box::new(Lambertian::new(Point3::new(0.7, 0.8, 0.9))));
+    let data = materials.pack();
+
+    assert_eq!(plain.material_id, 2);
+    assert_eq!(data[plain.material_id as usize - 1], 0);
+    assert_eq!(data[after.material_id as usize - 1], 0);
+
+    let normal_map = data[mapped.material_id as usize - 1] as usize;
+    assert_eq!(normal_map, mapped.material_id as usize + 3);
+    assert_eq!(data[normal_map], NormalMap::KIND_BUMP);
+    assert_eq!(f32::from_bits(data[normal_map + 1]), 0.25);
+
+    let texture = &data[normal_map + 2..];
+    assert_eq!(texture_sample(texture, 0.75, 0.75)[3], 3.0 / 255.0);
+    // uv 按 1 循环
+    assert_eq!(texture_sample(texture, 1.75, -0.25)[3], 3.0 / 255.0);
+
+    assert_eq!(after.material_id as usize, normal_map + 2 + 2 + 4 + 2);
+    assert_eq!(data.len(), after.material_id as usize + 3);
+    assert_eq!(Lambertian::unpack(&data[after.material_id as usize..]).albedo.x, 0.7);
+}
+
+fn alpha_masked(materials: &mut MaterialRegistry, image: &RgbaImage, mode: AlphaMode) -> (u32, u32) {
+    let handle = materials.add_with_maps(
+        Box::new(Lambertian::new(Point3::new(0.5, 0.5, 0.5))),
+        SurfaceMaps {
+            alpha_mask: Some(AlphaMask {
+                texture: Texture::from_image(image),
+                mode,
+            }),
+            ..Default::default()
+        },
+    );
+    (handle.material_type, handle.material_id)
+}
+
+#[test]
+fn alpha_mask_skips_transparent_hits() {
+    // 左半边透明、右半边不透明的镂空方块，后面是一个普通的方块
+    let half = RgbaImage::from_fn(2, 1, |x, _| Rgba([255, 255, 255, if x == 0 { 0 } else { 255 }]));
+    let mut materials = MaterialRegistry::default();
+    let (cutout_type, cutout_id) = alpha_masked(&mut materials, &half, AlphaMode::Cutoff(0.5));
+    let plain = materials.add(Box::new(Lambertian::new(Point3::new(0.5, 0.5, 0.5))));
+
+    let data = materials.pack();
+    let alpha_mask = data[cutout_id as usize - 2] as usize;
+    assert_eq!(alpha_mask, cutout_id as usize + 3);
+    assert_eq!(data[alpha_mask], AlphaMask::MODE_CUTOFF);
+    assert_eq!(data[cutout_id as usize - 1], 0);
+    assert_eq!(data[plain.material_id as usize - 2], 0);
+
+    let quad = |z: f32, material_type: u32, material_id: u32| {
+        Rc::new(PrimitiveData::Quad(QuadData::new(
+            Point3::new(0.0, 0.0, z),
+            Vector3::new(2.0, 0.0, 0.0),
+            Vector3::new(0.0, 2.0, 0.0),
+            material_type,
+            material_id,
+        )))
+    };
+    let primitives = vec![
+        quad(0.0, cutout_type, cutout_id),
+        quad(-1.0, plain.material_type, plain.material_id),
+    ];
+    let scene_data = SceneData::new(&primitives, &[], &materials);
+    let interval = Interval::new(0.001, f32::MAX);
+
+    let through_hole = Ray::new(Point3::new(-0.5, 0.0, 1.0), -Vector3::z());
+    let hit_record = scene_data.hit(&through_hole, &interval).unwrap();
+    assert_eq!(hit_record.ray_t, 2.0);
+    assert_eq!(hit_record.material_id, plain.material_id);
+
+    let on_surface = Ray::new(Point3::new(0.5, 0.0, 1.0), -Vector3::z());
+    let hit_record = scene_data.hit(&on_surface, &interval).unwrap();
+    assert_eq!(hit_record.ray_t, 1.0);
+    assert_eq!(hit_record.material_id, cutout_id);
+}
+
+#[test]
+fn stochastic_alpha_mask_follows_alpha() {
+    let alpha = |a: u8| RgbaImage::from_pixel(1, 1, Rgba([255, 255, 255, a]));
+    let mut materials = MaterialRegistry::default();
+    let transparent = alpha_masked(&mut materials, &alpha(0), AlphaMode::Stochastic);
+    let opaque = alpha_masked(&mut materials, &alpha(255), AlphaMode::Stochastic);
+    let half = alpha_masked(&mut materials, &alpha(128), AlphaMode::Stochastic);
+
+    let sphere = |(material_type, material_id): (u32, u32)| {
+        let primitives = vec![Rc::new(PrimitiveData::Sphere(SphereData::new(
+            Point3::origin(),
+            1.0,
+            material_type,
+            material_id,
+        )))];
+        SceneData::new(&primitives, &[], &materials)
+    };
+    let interval = Interval::new(0.001, f32::MAX);
+    let ray = |y: f32| Ray::new(Point3::new(0.0, y, 5.0), -Vector3::z());
+
+    // 完全透明时正面和背面都被穿过
+    assert!(sphere(transparent).hit(&ray(0.0), &interval).is_none());
+    assert_eq!(sphere(opaque).hit(&ray(0.0), &interval).unwrap().ray_t, 4.0);
+
+    // alpha 为 0.5 时大约一半的光线停在正面，其余的一半停在背面
+    let scene_data = sphere(half);
+    let rays = 1000;
+    let front = (0..rays)
+        .filter_map(|i| scene_data.hit(&ray(i as f32 / rays as f32 * 0.9), &interval))
+        .filter(|hit_record| hit_record.is_front_face)
+        .count();
+    assert!(
+        (400..600).contains(&front),
+        "{front} of {rays} rays stopped at the front face"
+    );
+}
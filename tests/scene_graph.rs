@@ -0,0 +1,295 @@
+// 场景图的路径查找、世界变换的累积，以及节点变换和材质改变后重新生成的图元
+
+use nalgebra::{Point3, Scale3, Translation3, UnitQuaternion, Vector3};
+use renderer_core::rendering::material::MaterialHandle;
+use renderer_core::rendering::mesh::mesh_list::TransformableMeshList;
+use renderer_core::rendering::mesh::scene_graph::{SceneGraph, Transform};
+use renderer_core::rendering::mesh::Mesh;
+use renderer_core::rendering::primitive::sphere::Sphere;
+use renderer_core::rendering::primitive::{PrimitiveData, Quad, QuadData, Transformable};
+use std::f32::consts::FRAC_PI_2;
+use std::rc::Rc;
+
+const EPSILON: f32 = 1e-5;
+
+const HANDLE: MaterialHandle = MaterialHandle {
+    material_type: 0,
+    material_id: 0,
+};
+
+fn unit_quad(important: bool) -> Quad {
+    Quad::new(
+        Point3::new(0.5, 0.5, 0.0),
+        Vector3::new(1.0, 0.0, 0.0),
+        Vector3::new(0.0, 1.0, 0.0),
+        HANDLE,
+        important,
+    )
+}
+
+fn primitives(mesh: &mut impl Mesh) -> (Vec<Rc<PrimitiveData>>, Vec<u32>) {
+    let mut primitives = Vec::new();
+    let mut important_indices = Vec::new();
+    mesh.primitives(&mut primitives, &mut important_indices);
+    (primitives, important_indices)
+}
+
+fn quad_data(primitive: &PrimitiveData) -> QuadData {
+    match primitive {
+        PrimitiveData::Quad(quad_data) => *quad_data,
+        _ => panic!("expected a quad, got {:?}", primitive),
+    }
+}
+
+fn points_approx_eq(a: &Point3<f32>, b: &Point3<f32>) -> bool {
+    (a - b).norm() <= EPSILON * a.coords.norm().max(1.0)
+}
+
+#[test]
+fn nodes_are_found_by_path() {
+    let mut graph = SceneGraph::new();
+    let cornell = graph.add_node(None, "cornell", Transform::identity());
+    let tall_box = graph.add_node(Some(cornell), "tall_box", Transform::identity());
+    let lid = graph.add_mesh(Some(tall_box), "lid", unit_quad(false));
+    let other = graph.add_node(None, "other", Transform::identity());
+    let other_box = graph.add_node(Some(other), "tall_box", Transform::identity());
+
+    assert_eq!(graph.len(), 5);
+    assert_eq!(graph.roots(), &[cornell, other]);
+    assert_eq!(graph.find("cornell"), Some(cornell));
+    assert_eq!(graph.find("cornell/tall_box"), Some(tall_box));
+    assert_eq!(graph.find("cornell/tall_box/lid"), Some(lid));
+    assert_eq!(graph.find("other/tall_box"), Some(other_box));
+    assert_eq!(graph.find("tall_box"), None);
+    assert_eq!(graph.find("cornell/lid"), None);
+    assert_eq!(graph.find(""), None);
+
+    assert_eq!(graph.path(lid), "cornell/tall_box/lid");
+    assert_eq!(graph.node(lid).parent(), Some(tall_box));
+    assert_eq!(graph.node(cornell).children(), &[tall_box]);
+    assert!(graph.node(lid).has_mesh() && !graph.node(tall_box).has_mesh());
+    assert_eq!(graph.depth_first(), vec![cornell, tall_box, lid, other, other_box]);
+}
+
+#[test]
+#[should_panic(expected = "already exists")]
+fn sibling_names_are_unique() {
+    let mut graph = SceneGraph::new();
+    let parent = graph.add_node(None, "parent", Transform::identity());
+    graph.add_node(Some(parent), "child", Transform::identity());
+    graph.add_node(Some(parent), "child", Transform::identity());
+}
+
+#[test]
+#[should_panic(expected = "invalid scene node name")]
+fn names_cannot_contain_the_separator() {
+    SceneGraph::new().add_node(None, "a/b", Transform::identity());
+}
+
+#[test]
+fn world_transform_accumulates_from_the_root() {
+    // 父节点非均匀缩放、子节点旋转，世界变换中带有剪切
+    let mut graph = SceneGraph::new();
+    let parent = graph.add_node(
+        None,
+        "parent",
+        Transform {
+            translation: Translation3::new(1.0, 2.0, 3.0),
+            rotation: UnitQuaternion::from_axis_angle(&Vector3::x_axis(), 0.3),
+            scale: Scale3::new(2.0, 0.5, 1.0),
+        },
+    );
+    let child = graph.add_node(
+        Some(parent),
+        "child",
+        Transform {
+            translation: Translation3::new(0.0, 1.0, 0.0),
+            rotation: UnitQuaternion::from_axis_angle(&Vector3::z_axis(), 0.7),
+            ..Transform::identity()
+        },
+    );
+    graph.set_mesh(child, unit_quad(false));
+
+    let world = graph.world_matrix(child);
+    assert_eq!(
+        world,
+        graph.node(parent).transform().matrix() * graph.node(child).transform().matrix()
+    );
+
+    let (primitives, _) = primitives(&mut graph);
+    let quad = quad_data(&primitives[0]);
+    for (local, actual) in [
+        (Point3::origin(), quad.bottom_left()),
+        (Point3::new(1.0, 0.0, 0.0), quad.bottom_left() + quad.right()),
+        (Point3::new(0.0, 1.0, 0.0), quad.bottom_left() + quad.up()),
+    ] {
+        let expected = world.transform_point(&local);
+        assert!(points_approx_eq(&actual, &expected), "{actual:?} != {expected:?}");
+    }
+}
+
+#[test]
+fn primitives_match_eagerly_transformed_meshes() {
+    let transform = Transform {
+        translation: Translation3::new(2.650, 0.0, 2.950),
+        rotation: UnitQuaternion::from_axis_angle(&Vector3::y_axis(), 0.26),
+        scale: Scale3::new(1.0, 1.5, 1.0),
+    };
+    let cube = || TransformableMeshList::cube(Point3::new(0.5, 0.5, 0.5), 1.0, 1.0, 1.0, HANDLE, false);
+
+    let mut graph = SceneGraph::new();
+    let node = graph.add_node(None, "cube", transform);
+    graph.set_mesh(node, cube());
+
+    let mut eager = cube();
+    eager.scale(transform.scale);
+    eager.rotate(transform.rotation);
+    eager.translate(transform.translation);
+
+    let (actual, _) = primitives(&mut graph);
+    let (expected, _) = primitives(&mut eager);
+    assert_eq!(actual.len(), 6);
+    for (actual, expected) in actual.iter().zip(&expected) {
+        let (actual, expected) = (quad_data(actual), quad_data(expected));
+        assert_eq!(actual.bottom_left(), expected.bottom_left());
+        assert_eq!(actual.right(), expected.right());
+        assert_eq!(actual.up(), expected.up());
+    }
+}
+
+#[test]
+fn moving_a_parent_moves_its_children() {
+    let mut graph = SceneGraph::new();
+    let parent = graph.add_node(None, "parent", Transform::identity());
+    let child = graph.add_mesh(
+        Some(parent),
+        "ball",
+        Sphere::new(Point3::new(0.0, 1.0, 0.0), 0.5, HANDLE, false),
+    );
+    graph.add_mesh(None, "floor", unit_quad(false));
+
+    let (before, _) = primitives(&mut graph);
+    let (again, _) = primitives(&mut graph);
+    // 没有改变时重复使用同一份图元
+    assert!(before.iter().zip(&again).all(|(a, b)| Rc::ptr_eq(a, b)));
+
+    graph.set_transform(
+        parent,
+        Transform {
+            rotation: UnitQuaternion::from_axis_angle(&Vector3::z_axis(), FRAC_PI_2),
+            ..Transform::from_translation(Translation3::new(0.0, 0.0, 5.0))
+        },
+    );
+    let (after, _) = primitives(&mut graph);
+    match *after[0] {
+        PrimitiveData::Sphere(sphere) => {
+            assert!(points_approx_eq(&sphere.center(), &Point3::new(-1.0, 0.0, 5.0)));
+        }
+        _ => panic!("expected the sphere first"),
+    }
+    assert!(points_approx_eq(
+        &graph.world_matrix(child).transform_point(&Point3::new(0.0, 1.0, 0.0)),
+        &Point3::new(-1.0, 0.0, 5.0)
+    ));
+    // 不在这个子树中的节点不受影响
+    assert!(Rc::ptr_eq(&before[1], &after[1]));
+}
+
+#[test]
+fn nearest_material_overrides_meshes() {
+    let red = MaterialHandle {
+        material_type: 1,
+        material_id: 10,
+    };
+    let blue = MaterialHandle {
+        material_type: 1,
+        material_id: 20,
+    };
+
+    let mut graph = SceneGraph::new();
+    let group = graph.add_node(None, "group", Transform::identity());
+    let plain = graph.add_mesh(Some(group), "plain", unit_quad(false));
+    let painted = graph.add_mesh(Some(group), "painted", unit_quad(false));
+    graph.add_mesh(None, "outside", unit_quad(false));
+
+    graph.set_material(group, Some(red));
+    graph.set_material(painted, Some(blue));
+    let (quads, _) = primitives(&mut graph);
+    let material_ids: Vec<_> = quads
+        .iter()
+        .map(|primitive| quad_data(primitive).material_id())
+        .collect();
+    assert_eq!(material_ids, vec![10, 20, 0]);
+    assert!(graph.node(plain).material().is_none());
+
+    graph.set_material(group, None);
+    let (quads, _) = primitives(&mut graph);
+    assert_eq!(quad_data(&quads[0]).material_id(), 0);
+    assert_eq!(quad_data(&quads[1]).material_id(), 20);
+}
+
+#[test]
+fn important_indices_are_offset_by_earlier_nodes() {
+    let mut graph = SceneGraph::new();
+    let room = graph.add_node(None, "room", Transform::identity());
+    graph.add_mesh(
+        Some(room),
+        "walls",
+        TransformableMeshList::cube(Point3::origin(), 4.0, 4.0, 4.0, HANDLE, false),
+    );
+    graph.add_mesh(Some(room), "light", unit_quad(true));
+    graph.add_mesh(None, "lamp", unit_quad(true));
+
+    let (primitives, important_indices) = primitives(&mut graph);
+    assert_eq!(primitives.len(), 8);
+    assert_eq!(important_indices, vec![6, 7]);
+}
+
+#[test]
+fn removing_a_node_removes_its_subtree() {
+    let mut graph = SceneGraph::new();
+    let room = graph.add_node(None, "room", Transform::identity());
+    let table = graph.add_mesh(Some(room), "table", unit_quad(false));
+    let cup = graph.add_mesh(Some(table), "cup", unit_quad(true));
+    let lamp = graph.add_mesh(Some(room), "lamp", unit_quad(true));
+
+    graph.remove(table);
+    assert_eq!(graph.len(), 2);
+    assert!(!graph.contains(table) && !graph.contains(cup));
+    assert_eq!(graph.node(room).children(), &[lamp]);
+    assert_eq!(graph.find("room/table"), None);
+    assert_eq!(graph.depth_first(), vec![room, lamp]);
+
+    let (primitives, important_indices) = primitives(&mut graph);
+    assert_eq!(primitives.len(), 1);
+    assert_eq!(important_indices, vec![0]);
+
+    // 删除后可以重新使用同一个名字
+    let table = graph.add_node(Some(room), "table", Transform::identity());
+    assert_eq!(graph.find("room/table"), Some(table));
+
+    graph.remove(room);
+    assert!(graph.is_empty());
+}
+
+#[test]
+fn mutations_mark_the_graph_changed() {
+    let mut graph = SceneGraph::new();
+    let node = graph.add_mesh(None, "quad", unit_quad(false));
+    assert!(graph.take_changed());
+    assert!(!graph.take_changed());
+
+    // 生成图元不算修改
+    primitives(&mut graph);
+    assert!(!graph.take_changed());
+
+    graph.set_transform(node, Transform::from_translation(Translation3::new(1.0, 0.0, 0.0)));
+    assert!(graph.take_changed());
+    graph.set_material(node, Some(HANDLE));
+    assert!(graph.take_changed());
+    graph.set_mesh(node, unit_quad(true));
+    assert!(graph.take_changed());
+    graph.remove(node);
+    assert!(graph.take_changed());
+    assert!(!graph.take_changed());
+}
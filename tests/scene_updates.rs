@@ -0,0 +1,139 @@
+// 运行时修改场景：材质的修改标记，以及只上传有变化的字节范围
+
+use nalgebra::Point3;
+use proptest::prelude::*;
+use renderer_core::rendering::material::{
+    Dielectric, GpuMaterial, Lambertian, MaterialFunction, MaterialParameter, MaterialRegistry,
+};
+use renderer_core::rendering::wgpu::changed_range;
+
+// 打包的长度随参数改变的材质，违反 GpuMaterial::parameters 的约定
+struct Resizable {
+    extended: bool,
+}
+
+impl GpuMaterial for Resizable {
+    fn wgsl_name(&self) -> &'static str {
+        "Resizable"
+    }
+
+    fn wgsl_source(&self) -> &'static str {
+        ""
+    }
+
+    fn wgsl_functions(&self) -> &'static [MaterialFunction] {
+        &[]
+    }
+
+    fn pack(&self, data: &mut Vec<u32>) {
+        data.push(0);
+        if self.extended {
+            data.push(0);
+        }
+    }
+
+    fn parameters(&mut self) -> Vec<MaterialParameter<'_>> {
+        vec![MaterialParameter::Toggle {
+            name: "Extended",
+            value: &mut self.extended,
+        }]
+    }
+}
+
+fn registry() -> MaterialRegistry {
+    let mut materials = MaterialRegistry::default();
+    materials.add(Box::new(Lambertian::new(Point3::new(0.1, 0.2, 0.3))));
+    materials.add(Box::new(Dielectric::new(1.5)));
+    materials.add(Box::new(Lambertian::new(Point3::new(0.4, 0.5, 0.6))));
+    materials
+}
+
+#[test]
+fn editing_a_material_rewrites_only_its_data() {
+    let mut materials = registry();
+    assert!(materials.take_changed());
+    assert!(!materials.take_changed());
+
+    let before = materials.pack();
+    let handle = materials.materials()[2].0;
+    let material = materials.get_mut(handle).expect("registered material");
+    for parameter in material.parameters() {
+        if let MaterialParameter::Color { value, .. } = parameter {
+            *value = Point3::new(0.9, 0.9, 0.9);
+        }
+    }
+    assert!(materials.take_changed());
+
+    let after = materials.pack();
+    assert_eq!(before.len(), after.len());
+    let changed = changed_range(bytemuck::cast_slice(&before), bytemuck::cast_slice(&after));
+    assert!(!changed.is_empty());
+    assert!(changed.start >= handle.material_id as usize * 4);
+    assert_eq!(Lambertian::unpack(&after[handle.material_id as usize..]).albedo.x, 0.9);
+}
+
+#[test]
+fn adding_a_material_marks_the_registry_changed() {
+    let mut materials = registry();
+    materials.take_changed();
+    let before = materials.wgsl();
+
+    materials.add(Box::new(Lambertian::new(Point3::new(1.0, 1.0, 1.0))));
+    assert!(materials.take_changed());
+    // 同一种材质不需要重新编译着色器
+    assert_eq!(materials.wgsl(), before);
+}
+
+// 长度改变会让之后所有材质的数据错位，打包时拒绝这次修改，保留之前的数据
+#[test]
+fn changing_the_packed_length_is_rejected_and_keeps_old_data() {
+    let mut materials = registry();
+    let handle = materials.add(Box::new(Resizable { extended: false }));
+    let lambertian = materials.add(Box::new(Lambertian::new(Point3::new(0.7, 0.7, 0.7))));
+    let before = materials.pack();
+
+    for parameter in materials.get_mut(handle).unwrap().parameters() {
+        if let MaterialParameter::Toggle { value, .. } = parameter {
+            *value = true;
+        }
+    }
+    assert!(materials.take_changed());
+    let after = materials.pack();
+    assert_eq!(after, before);
+    assert_eq!(
+        Lambertian::unpack(&after[lambertian.material_id as usize..]).albedo.x,
+        0.7
+    );
+}
+
+#[test]
+fn identical_contents_upload_nothing() {
+    let data = [1u8, 2, 3, 4, 5, 6, 7, 8];
+    assert!(changed_range(&data, &data).is_empty());
+    assert!(changed_range(&data, &data[..4]).is_empty());
+    assert!(changed_range(&[], &[]).is_empty());
+}
+
+proptest! {
+    #[test]
+    fn changed_range_covers_every_difference(
+        old in prop::collection::vec(0u8..4, 0..64).prop_map(|mut v| { v.truncate(v.len() / 4 * 4); v }),
+        new in prop::collection::vec(0u8..4, 0..64).prop_map(|mut v| { v.truncate(v.len() / 4 * 4); v }),
+    ) {
+        let range = changed_range(&old, &new);
+        prop_assert_eq!(range.start % 4, 0);
+        prop_assert_eq!(range.end % 4, 0);
+        prop_assert!(range.end <= new.len());
+
+        // 把范围内的字节写进旧内容之后，与新内容一致
+        let mut uploaded = old.clone();
+        uploaded.resize(new.len().max(old.len()), 0);
+        uploaded[range.clone()].copy_from_slice(&new[range.clone()]);
+        for (i, byte) in new.iter().enumerate() {
+            if i < old.len() || range.contains(&i) {
+                prop_assert_eq!(uploaded[i], *byte);
+            }
+        }
+        prop_assert!(new.len() <= old.len() || range.end == new.len());
+    }
+}
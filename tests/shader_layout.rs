@@ -0,0 +1,358 @@
+// 用 naga 离线解析并验证 ray_tracing.wgsl、bvh_refit.wgsl 和材质生成的代码，并检查手写填充的 Rust 结构体与 WGSL 结构体的内存布局和绑定顺序是否一致
+
+use naga::proc::Layouter;
+use naga::valid::{Capabilities, ValidationFlags, Validator};
+use naga::{AddressSpace, Expression, Handle, Literal, Module, ResourceBinding, Type, TypeInner};
+use nalgebra::Point3;
+use renderer_core::rendering::bounding_box::BoundingBox;
+use renderer_core::rendering::bvh::{
+    BvhNode, BvhRefitContext, BvhRefitLeaf, BVH_REFIT_BINDINGS, BVH_REFIT_WORKGROUP_SIZE,
+};
+use renderer_core::rendering::interval::Interval;
+use renderer_core::rendering::layout::{WgslLayout, RAY_TRACING_BINDINGS};
+use renderer_core::rendering::material::{
+    pack_point3, DebugNormal, Dielectric, DiffuseLight, GpuMaterial, Hair, Lambertian, MaterialFunction,
+    MaterialRegistry, Principled, Terrain, TerrainLayer,
+};
+use renderer_core::rendering::primitive::sphere::SphereData;
+use renderer_core::rendering::primitive::{
+    CsgData, CsgNodeData, CurveData, HeightfieldData, MotionData, PrimitiveIndex, QuadData, SdfData, SdfNodeData,
+    ShapeData,
+};
+use renderer_core::rendering::{AdaptiveSamplingCounters, RenderContext};
+
+const RAY_TRACING_SHADER: &str = include_str!("../src/shader/ray_tracing.wgsl");
+const BVH_REFIT_SHADER: &str = include_str!("../src/shader/bvh_refit.wgsl");
+
+// 测试材质，模拟在下游 crate 中定义、不修改渲染器就能注册的材质
+struct Tinted {
+    color: Point3<f32>,
+}
+
+impl GpuMaterial for Tinted {
+    fn wgsl_name(&self) -> &'static str {
+        "Tinted"
+    }
+
+    fn wgsl_source(&self) -> &'static str {
+        "
+struct Tinted {
+    color: vec3f
+}
+
+fn Tinted_emit(ray_in: ptr<function, Ray>, hit_record: ptr<function, HitRecord>) -> vec3f {
+    return material_vec3f((*hit_record).material_id) * abs((*hit_record).normal);
+}
+"
+    }
+
+    fn wgsl_functions(&self) -> &'static [MaterialFunction] {
+        &[MaterialFunction::Emit]
+    }
+
+    fn pack(&self, data: &mut Vec<u32>) {
+        pack_point3(data, &self.color);
+    }
+}
+
+fn built_in_materials() -> MaterialRegistry {
+    let mut materials = MaterialRegistry::default();
+    materials.add(Box::new(DebugNormal {}));
+    materials.add(Box::new(Lambertian::new(Point3::new(0.5, 0.5, 0.5))));
+    materials.add(Box::new(DiffuseLight::new(Point3::new(1.0, 1.0, 1.0))));
+    materials.add(Box::new(Dielectric::new(1.5)));
+    materials.add(Box::new(Principled::default()));
+    materials.add(Box::new(Hair::default()));
+    materials.add(Box::new(Terrain::new(vec![TerrainLayer::new(Point3::new(
+        0.5, 0.5, 0.5,
+    ))])));
+    materials
+}
+
+struct Shader {
+    source: String,
+    module: Module,
+    layouter: Layouter,
+}
+
+impl Shader {
+    fn parse() -> Self {
+        Self::parse_with_materials(&built_in_materials())
+    }
+
+    // 与 Renderer 一样，把材质生成的代码拼接在 ray_tracing.wgsl 之后
+    fn parse_with_materials(materials: &MaterialRegistry) -> Self {
+        Self::parse_source(format!("{}\n{}", RAY_TRACING_SHADER, materials.wgsl()))
+    }
+
+    fn parse_source(source: String) -> Self {
+        let module =
+            naga::front::wgsl::parse_str(&source).unwrap_or_else(|error| panic!("{}", error.emit_to_string(&source)));
+        let mut layouter = Layouter::default();
+        layouter.update(module.to_ctx()).unwrap();
+        Self {
+            source,
+            module,
+            layouter,
+        }
+    }
+
+    fn validate(&self) {
+        Validator::new(ValidationFlags::all(), Capabilities::default())
+            .validate(&self.module)
+            .unwrap_or_else(|error| panic!("{}", error.emit_to_string(&self.source)));
+    }
+
+    fn find_type(&self, name: &str) -> Handle<Type> {
+        self.module
+            .types
+            .iter()
+            .find(|(_, ty)| ty.name.as_deref() == Some(name))
+            .map(|(handle, _)| handle)
+            .unwrap_or_else(|| panic!("struct {} not found in the shader", name))
+    }
+
+    // 存储缓冲区的数组取元素类型，其余取变量本身的类型
+    fn binding_element_type(&self, name: &str) -> Handle<Type> {
+        let (_, variable) = self
+            .module
+            .global_variables
+            .iter()
+            .find(|(_, variable)| variable.name.as_deref() == Some(name))
+            .unwrap_or_else(|| panic!("binding {} not found in the shader", name));
+        match self.module.types[variable.ty].inner {
+            TypeInner::Array { base, .. } => base,
+            _ => variable.ty,
+        }
+    }
+
+    fn assert_layout<T: WgslLayout>(&self) {
+        let handle = self.find_type(T::WGSL_NAME);
+        let TypeInner::Struct { ref members, .. } = self.module.types[handle].inner else {
+            panic!("{} is not a struct", T::WGSL_NAME);
+        };
+        let layout = self.layouter[handle];
+
+        // 数组的步长是按对齐取整后的大小，Rust 结构体需要手动填充到同样的大小
+        assert_eq!(
+            T::WGSL_SIZE as u32,
+            layout.to_stride(),
+            "size of {} (WGSL alignment {})",
+            T::WGSL_NAME,
+            layout.alignment
+        );
+
+        let wgsl_members: Vec<_> = members
+            .iter()
+            .map(|member| (member.name.as_deref().unwrap_or(""), member.offset as usize))
+            .collect();
+        assert_eq!(T::wgsl_members(), wgsl_members, "members of {}", T::WGSL_NAME);
+    }
+
+    fn constant_u32(&self, name: &str) -> u32 {
+        let (_, constant) = self
+            .module
+            .constants
+            .iter()
+            .find(|(_, constant)| constant.name.as_deref() == Some(name))
+            .unwrap_or_else(|| panic!("constant {} not found in the shader", name));
+        match self.module.global_expressions[constant.init] {
+            Expression::Literal(Literal::U32(value)) => value,
+            ref init => panic!("constant {} is not a u32 literal: {:?}", name, init),
+        }
+    }
+}
+
+#[test]
+fn ray_tracing_shader_is_valid() {
+    Shader::parse().validate();
+}
+
+#[test]
+fn ray_tracing_shader_without_materials_is_valid() {
+    Shader::parse_with_materials(&MaterialRegistry::default()).validate();
+}
+
+#[test]
+fn custom_material_can_be_registered() {
+    let mut materials = built_in_materials();
+    let lambertian = materials.add(Box::new(Lambertian::new(Point3::new(0.1, 0.2, 0.3))));
+    let tinted = materials.add(Box::new(Tinted {
+        color: Point3::new(1.0, 0.5, 0.25),
+    }));
+    Shader::parse_with_materials(&materials).validate();
+
+    // 同名材质共用一个标签，新材质得到新的标签，数据紧接着前一个材质和两个字的透明度贴图、法线贴图下标
+    assert_eq!(lambertian.material_type, 1);
+    assert_eq!(tinted.material_type, 7);
+    assert_eq!(tinted.material_id, lambertian.material_id + 3 + 2);
+    assert_eq!(materials.kind_names()[tinted.material_type as usize], "Tinted");
+
+    let data = materials.pack();
+    assert_eq!(data.len(), tinted.material_id as usize + 3);
+    assert_eq!(f32::from_bits(data[tinted.material_id as usize + 1]), 0.5);
+    assert_eq!(
+        Lambertian::unpack(&data[lambertian.material_id as usize..]).albedo.y,
+        0.2
+    );
+}
+
+#[test]
+fn struct_layouts_match() {
+    let shader = Shader::parse();
+    shader.assert_layout::<RenderContext>();
+    shader.assert_layout::<AdaptiveSamplingCounters>();
+    shader.assert_layout::<Interval>();
+    shader.assert_layout::<BoundingBox>();
+    shader.assert_layout::<BvhNode>();
+    shader.assert_layout::<PrimitiveIndex>();
+    shader.assert_layout::<MotionData>();
+    shader.assert_layout::<QuadData>();
+    shader.assert_layout::<SphereData>();
+    shader.assert_layout::<ShapeData>();
+    shader.assert_layout::<SdfNodeData>();
+    shader.assert_layout::<SdfData>();
+    shader.assert_layout::<CsgNodeData>();
+    shader.assert_layout::<CsgData>();
+    shader.assert_layout::<CurveData>();
+    shader.assert_layout::<HeightfieldData>();
+}
+
+// Renderer 按名字列表的顺序创建绑定组，下标就是 @binding 的值
+fn assert_bindings(shader: &Shader, names: &[&str]) {
+    let mut bindings: Vec<_> = shader
+        .module
+        .global_variables
+        .iter()
+        .filter_map(|(_, variable)| Some((variable.binding.clone()?, variable.name.clone()?)))
+        .collect();
+    bindings.sort_by_key(|(binding, _)| (binding.group, binding.binding));
+
+    let expected: Vec<_> = names
+        .iter()
+        .enumerate()
+        .map(|(i, name)| {
+            let binding = ResourceBinding {
+                group: 0,
+                binding: i as u32,
+            };
+            (binding, name.to_string())
+        })
+        .collect();
+    assert_eq!(bindings, expected);
+}
+
+#[test]
+fn binding_indices_match() {
+    let shader = Shader::parse();
+    assert_bindings(&shader, &RAY_TRACING_BINDINGS);
+
+    let (_, context) = shader
+        .module
+        .global_variables
+        .iter()
+        .find(|(_, variable)| variable.name.as_deref() == Some(RAY_TRACING_BINDINGS[0]))
+        .unwrap();
+    assert_eq!(context.space, AddressSpace::Uniform);
+
+    // 不提高设备的默认限制，所有适配器都能创建管线
+    let storage_buffers = shader
+        .module
+        .global_variables
+        .iter()
+        .filter(|(_, variable)| matches!(variable.space, AddressSpace::Storage { .. }))
+        .count();
+    assert!(storage_buffers as u32 <= wgpu::Limits::default().max_storage_buffers_per_shader_stage);
+}
+
+#[test]
+fn binding_element_types_match() {
+    fn element<T: WgslLayout>(binding: &'static str) -> (&'static str, &'static str) {
+        (binding, T::WGSL_NAME)
+    }
+
+    let shader = Shader::parse();
+    for (binding, wgsl_name) in [
+        element::<RenderContext>("context"),
+        element::<AdaptiveSamplingCounters>("adaptive_counters"),
+        element::<BvhNode>("bvh_tree"),
+        element::<PrimitiveIndex>("importance"),
+    ] {
+        let ty = shader.binding_element_type(binding);
+        assert_eq!(
+            shader.module.types[ty].name.as_deref(),
+            Some(wgsl_name),
+            "element of {}",
+            binding
+        );
+    }
+
+    // Renderer 中按每像素 3 个 f32 和 4 个 u32 分配的缓冲区，以及按字打包的图元和材质
+    for (binding, stride) in [
+        ("pixel_color", 12),
+        ("pixel_statistics", 16),
+        ("primitives", 4),
+        ("materials", 4),
+    ] {
+        let ty = shader.binding_element_type(binding);
+        assert_eq!(shader.layouter[ty].to_stride(), stride, "element stride of {}", binding);
+    }
+}
+
+// primitives 中 Sdf、Csg、Heightfield 之后紧跟的数组按字数定位，与 Rust 结构体中数组的偏移和元素大小一致
+#[test]
+fn trailing_arrays_match() {
+    let shader = Shader::parse();
+    for (constant, bytes) in [
+        ("SDF_HEADER_WORDS", SdfData::WGSL_SIZE),
+        ("SDF_NODE_WORDS", size_of::<SdfNodeData>()),
+        ("CSG_HEADER_WORDS", CsgData::WGSL_SIZE),
+        ("CSG_NODE_WORDS", size_of::<CsgNodeData>()),
+        ("HEIGHTFIELD_HEADER_WORDS", HeightfieldData::WGSL_SIZE),
+    ] {
+        assert_eq!(shader.constant_u32(constant) as usize * 4, bytes, "{}", constant);
+    }
+}
+
+#[test]
+fn bvh_refit_shader_matches() {
+    let shader = Shader::parse_source(BVH_REFIT_SHADER.to_string());
+    shader.validate();
+
+    shader.assert_layout::<Interval>();
+    shader.assert_layout::<BoundingBox>();
+    shader.assert_layout::<BvhNode>();
+    shader.assert_layout::<BvhRefitContext>();
+    shader.assert_layout::<BvhRefitLeaf>();
+
+    assert_bindings(&shader, &BVH_REFIT_BINDINGS);
+    for (binding, wgsl_name) in [
+        ("refit", BvhRefitContext::WGSL_NAME),
+        ("refit_leaves", BvhRefitLeaf::WGSL_NAME),
+        ("bvh_tree", BvhNode::WGSL_NAME),
+    ] {
+        let ty = shader.binding_element_type(binding);
+        assert_eq!(
+            shader.module.types[ty].name.as_deref(),
+            Some(wgsl_name),
+            "element of {}",
+            binding
+        );
+    }
+
+    // Renderer 按 BVH_REFIT_WORKGROUP_SIZE 计算调度的线程组数量
+    let mut entry_points: Vec<_> = shader
+        .module
+        .entry_points
+        .iter()
+        .map(|entry_point| (entry_point.name.as_str(), entry_point.workgroup_size))
+        .collect();
+    entry_points.sort();
+    assert_eq!(
+        entry_points,
+        [
+            ("BvhRefit_propagate", [BVH_REFIT_WORKGROUP_SIZE, 1, 1]),
+            ("BvhRefit_write", [BVH_REFIT_WORKGROUP_SIZE, 1, 1]),
+        ]
+    );
+}